@@ -0,0 +1,195 @@
+//! generates the instruction enum, its discriminators, the instruction arg structs and the
+//! account discriminators from `idls/marinade-idl.json`, into `$OUT_DIR/marinade_idl.rs` for
+//! `src/accounts/idl.rs` to include. replacing the idl file and running `cargo build` is all a
+//! regeneration takes
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const IDL: &str = "idls/marinade-idl.json";
+
+/// idl types the crate defines by hand, with where; generated code refers to these instead
+const HANDWRITTEN: &[(&str, &str)] =
+    &[("Fee", "crate::accounts::marinade::Fee"), ("FeeCents", "crate::accounts::marinade::FeeCents")];
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", IDL);
+    println!("cargo:rerun-if-changed=build.rs");
+    let idl: Value = serde_json::from_str(&std::fs::read_to_string(IDL).expect("idl readable")).expect("idl is json");
+    let out = Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR set by cargo")).join("marinade_idl.rs");
+    std::fs::write(out, generate(&idl)).expect("generated code written");
+}
+
+fn generate(idl: &Value) -> String {
+    let instructions = idl["instructions"].as_array().expect("idl instructions");
+    let types = idl["types"].as_array().map_or(&[][..], |types| types.as_slice());
+    let mut out = String::new();
+    for (_, path) in HANDWRITTEN {
+        writeln!(out, "#[allow(unused_imports)]\nuse {};", path).unwrap();
+    }
+
+    let names: Vec<String> =
+        instructions.iter().map(|ix| snake(ix["name"].as_str().expect("instruction name"))).collect();
+    out += "\n/// every instruction of the program, in idl order\n";
+    out += "#[derive(AnchorDeserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]\n";
+    out += "pub enum MarinadeFinanceInstruction {\n";
+    for name in &names {
+        writeln!(out, "    {},", pascal(name)).unwrap();
+    }
+    out += "}\n\nimpl MarinadeFinanceInstruction {\n";
+    writeln!(out, "    pub const ALL: [MarinadeFinanceInstruction; {}] = [", names.len()).unwrap();
+    for name in &names {
+        writeln!(out, "        Self::{},", pascal(name)).unwrap();
+    }
+    out += "    ];\n\n    /// the program's handler name, which is what the discriminator is derived from\n";
+    out += "    pub fn name(&self) -> &'static str {\n        match self {\n";
+    for name in &names {
+        writeln!(out, "            Self::{} => \"{}\",", pascal(name), name).unwrap();
+    }
+    out += "        }\n    }\n}\n\n";
+
+    let mut table: Vec<(u64, &String)> =
+        names.iter().map(|name| (u64::from_le_bytes(sighash("global", name)), name)).collect();
+    table.sort_unstable();
+    out += "/// instruction discriminators as little-endian u64s, sorted for binary search\n";
+    writeln!(out, "pub(crate) const DISCRIMINATORS: [(u64, MarinadeFinanceInstruction); {}] = [", table.len()).unwrap();
+    for (discriminator, name) in &table {
+        writeln!(out, "    ({:#018x}, MarinadeFinanceInstruction::{}),", discriminator, pascal(name)).unwrap();
+    }
+    out += "];\n";
+
+    for ix in instructions {
+        let args = ix["args"].as_array().expect("instruction args");
+        if args.is_empty() {
+            continue;
+        }
+        let name = snake(ix["name"].as_str().unwrap());
+        let doc = format!("`{}`'s args", name);
+        out += &structure(&format!("{}Args", pascal(&name)), &doc, args, types);
+    }
+
+    // the idl types the args use, and the ones those use in turn
+    let mut generated = BTreeSet::new();
+    let mut pending: Vec<String> = instructions
+        .iter()
+        .flat_map(|ix| ix["args"].as_array().unwrap())
+        .flat_map(|arg| defined(&arg["type"]))
+        .collect();
+    while let Some(name) = pending.pop() {
+        if HANDWRITTEN.iter().any(|(handwritten, _)| *handwritten == name) || !generated.insert(name.clone()) {
+            continue;
+        }
+        let ty = find_type(types, &name);
+        let fields = ty["type"]["fields"].as_array().unwrap_or_else(|| panic!("idl type {} isn't a struct", name));
+        pending.extend(fields.iter().flat_map(|field| defined(&field["type"])));
+        out += &structure(&name, &format!("the idl's `{}`", name), fields, types);
+    }
+
+    let accounts = idl["accounts"].as_array().map_or(&[][..], |accounts| accounts.as_slice());
+    for account in accounts {
+        let name = account["name"].as_str().expect("account name");
+        let constant = snake(name).to_uppercase();
+        let doc = format!("the anchor discriminator of a `{}` account, `sha256(\"account:{}\")[..8]`", name, name);
+        writeln!(out, "\n/// {}", doc).unwrap();
+        writeln!(out, "pub const {}_DISCRIMINATOR: [u8; 8] = {:?};", constant, sighash("account", name)).unwrap();
+    }
+    out
+}
+
+fn structure(name: &str, doc: &str, fields: &[Value], types: &[Value]) -> String {
+    let plain = fields.iter().all(|field| is_plain(&field["type"], types));
+    let derives = if plain { ", Copy, PartialEq, Eq" } else { ", PartialEq" };
+    let mut out = format!("\n/// {}\n#[derive(AnchorDeserialize, AnchorSerialize, Debug, Clone{})]\n", doc, derives);
+    writeln!(out, "pub struct {} {{", name).unwrap();
+    for field in fields {
+        let field_name = snake(field["name"].as_str().expect("field name"));
+        writeln!(out, "    pub {}: {},", field_name, rust_type(&field["type"])).unwrap();
+    }
+    out + "}\n"
+}
+
+fn rust_type(ty: &Value) -> String {
+    match ty {
+        Value::String(name) => match name.as_str() {
+            "publicKey" => "Pubkey".to_string(),
+            "string" => "String".to_string(),
+            "bytes" => "Vec<u8>".to_string(),
+            primitive => primitive.to_string(),
+        },
+        Value::Object(map) => match map.iter().next() {
+            Some((kind, inner)) if kind == "option" => format!("Option<{}>", rust_type(inner)),
+            Some((kind, inner)) if kind == "vec" => format!("Vec<{}>", rust_type(inner)),
+            Some((kind, Value::Array(array))) if kind == "array" => format!("[{}; {}]", rust_type(&array[0]), array[1]),
+            Some((kind, Value::String(name))) if kind == "defined" => name.clone(),
+            _ => panic!("unsupported idl type {}", ty),
+        },
+        _ => panic!("unsupported idl type {}", ty),
+    }
+}
+
+/// copyable and comparable for equality all the way down
+fn is_plain(ty: &Value, types: &[Value]) -> bool {
+    match ty {
+        Value::String(name) => !matches!(name.as_str(), "string" | "bytes" | "f32" | "f64"),
+        Value::Object(map) => match map.iter().next() {
+            Some((kind, inner)) if kind == "option" => is_plain(inner, types),
+            Some((kind, Value::Array(array))) if kind == "array" => is_plain(&array[0], types),
+            Some((kind, Value::String(name))) if kind == "defined" => {
+                !HANDWRITTEN.iter().any(|(handwritten, _)| handwritten == name)
+                    && find_type(types, name)["type"]["fields"]
+                        .as_array()
+                        .is_some_and(|fields| fields.iter().all(|field| is_plain(&field["type"], types)))
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// the `defined` type names inside `ty`
+fn defined(ty: &Value) -> Vec<String> {
+    match ty {
+        Value::Object(map) => match map.iter().next() {
+            Some((kind, Value::String(name))) if kind == "defined" => vec![name.clone()],
+            Some((kind, Value::Array(array))) if kind == "array" => defined(&array[0]),
+            Some((_, inner)) => defined(inner),
+            None => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+fn find_type<'a>(types: &'a [Value], name: &str) -> &'a Value {
+    types.iter().find(|ty| ty["name"] == name).unwrap_or_else(|| panic!("idl type {} not defined", name))
+}
+
+/// the first 8 bytes of sha256("<namespace>:<name>"), anchor's discriminator
+fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let digest = Sha256::digest(format!("{}:{}", namespace, name).as_bytes());
+    digest[..8].try_into().unwrap()
+}
+
+/// `camelCase` or `PascalCase` to `snake_case`
+fn snake(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `snake_case` to `PascalCase`
+fn pascal(name: &str) -> String {
+    name.split('_').map(|word| word[..1].to_uppercase() + &word[1..]).collect()
+}
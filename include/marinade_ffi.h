@@ -0,0 +1,92 @@
+#ifndef MARINADE_FFI_H
+#define MARINADE_FFI_H
+
+/* generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * what went wrong in a call, `MARINADE_STATUS_OK` when nothing did
+ */
+typedef enum MarinadeStatus {
+  MARINADE_STATUS_OK = 0,
+  /**
+   * a required pointer argument was null
+   */
+  MARINADE_STATUS_NULL_POINTER = 1,
+  /**
+   * the state buffer is shorter than a state account
+   */
+  MARINADE_STATUS_SHORT_BUFFER = 2,
+  /**
+   * the buffer isn't a marinade state account, e.g. another account's discriminator,
+   * trailing bytes or balances that overflow the price math
+   */
+  MARINADE_STATUS_INVALID_DATA = 3,
+} MarinadeStatus;
+
+/**
+ * the price components of a marinade state account
+ */
+typedef struct MarinadeStatePrice {
+  /**
+   * lamports per whole mSOL
+   */
+  uint64_t msol_price_lamports;
+  /**
+   * the SOL backing the whole supply
+   */
+  uint64_t total_virtual_staked_lamports;
+  uint64_t msol_supply;
+} MarinadeStatePrice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * parse the price components out of `len` bytes of state account data. returns null on
+ * failure, with the reason in `status` when it isn't null. release the result with
+ * `marinade_price_free`
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes and `status` must be null or writable
+ */
+MarinadeStatePrice *marinade_parse_state(const uint8_t *data, uintptr_t len, MarinadeStatus *status);
+
+/**
+ * mSOL base units `lamports` buy at `price`, written to `msol`
+ *
+ * # Safety
+ *
+ * `price` must be null or point to a `MarinadeStatePrice`, `msol` must be null or writable
+ */
+MarinadeStatus marinade_sol_to_msol(const MarinadeStatePrice *price, uint64_t lamports, uint64_t *msol);
+
+/**
+ * lamports `msol` base units are worth at `price`, written to `lamports`
+ *
+ * # Safety
+ *
+ * `price` must be null or point to a `MarinadeStatePrice`, `lamports` must be null or writable
+ */
+MarinadeStatus marinade_msol_to_sol(const MarinadeStatePrice *price, uint64_t msol, uint64_t *lamports);
+
+/**
+ * release a price returned by `marinade_parse_state`. null is ignored
+ *
+ * # Safety
+ *
+ * `price` must be null or come from `marinade_parse_state`, and not be used afterwards
+ */
+void marinade_price_free(MarinadeStatePrice *price);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MARINADE_FFI_H */
@@ -0,0 +1,12 @@
+#!/bin/sh
+# builds and tests every transport combination on its own, so a feature can't lean on code only
+# another one brings in. run from the crate root
+set -e
+
+cargo clippy --no-default-features --all-targets -- -D warnings
+for features in rpc rpc,blocking rpc,nonblocking rpc,blocking,nonblocking; do
+    echo "== $features"
+    cargo clippy --no-default-features --features "$features" --all-targets -- -D warnings
+    cargo test --no-default-features --features "$features" --no-run
+done
+cargo clippy --all-features --all-targets -- -D warnings
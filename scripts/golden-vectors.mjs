@@ -0,0 +1,187 @@
+// writes tests/golden/<name>.json: the state inputs below, each with the price math marinade's
+// typescript sdk does on them, for `tests/golden.rs` to hold the crate to. run from the crate root:
+//
+//     node scripts/golden-vectors.mjs          # the sdk's formulas, ported to BigInt
+//     node scripts/golden-vectors.mjs --sdk    # the same, checked against the sdk itself
+//
+// `--sdk` needs `npm install @marinade.finance/marinade-ts-sdk bn.js` somewhere node resolves
+// from, and fails on the first value the port and `MarinadeUtils.proportionalBN` disagree on.
+// each vector's `source` says which of the two made it, with the sdk version when it was checked.
+// the sdk's amounts are all `proportionalBN(amount, numerator, denominator)`, amount * numerator
+// / denominator rounded down, or amount itself for a zero denominator:
+//
+//     sol_to_msol(lamports)  = proportionalBN(lamports, msol_supply, total_virtual_staked)
+//     msol_to_sol(msol)      = proportionalBN(msol, total_virtual_staked, msol_supply)
+//     msol_price_lamports    = msol_to_sol(1 mSOL)
+//
+// with total_virtual_staked = total_active_balance + both cooling downs + the available reserve
+// - circulating tickets. a vector spells out each intermediate, so a formula change that breaks
+// one shows up in the diff as the component that moved. states with more tickets than lamports
+// under control are left out: the sdk's BN goes negative where the crate saturates at 0.
+//
+// the sdk's BN has no upper limit, the crate's u64s do: a state whose balances add up past one
+// gets its exact totals and, in `overflow`, the first of them the crate can't hold. no amounts,
+// the crate refuses the state (the `checked_*` math is None) rather than valuing it
+
+import { existsSync, mkdirSync, readFileSync, writeFileSync } from 'node:fs'
+import { createRequire } from 'node:module'
+import { dirname, join } from 'node:path'
+
+const SOL = 1_000_000_000n
+
+const STATES = {
+  // `test_utils::sample_state`: 7.201M SOL behind 6M mSOL
+  fixture: {
+    total_active_balance: 7_000_000n * SOL,
+    delayed_unstake_cooling_down: 1_000n * SOL,
+    emergency_cooling_down: 0n,
+    available_reserve_balance: 400_000n * SOL,
+    circulating_ticket_balance: 200_000n * SOL,
+    msol_supply: 6_000_000n * SOL,
+  },
+  // mainnet's order of magnitude, with amounts that don't divide evenly
+  mainnet_scale: {
+    total_active_balance: 7_384_112_908_451_667n,
+    delayed_unstake_cooling_down: 41_277_990_122_004n,
+    emergency_cooling_down: 3_001_442_871n,
+    available_reserve_balance: 95_114_876_553_210n,
+    circulating_ticket_balance: 38_906_112_774_519n,
+    msol_supply: 6_051_297_509_772_839n,
+  },
+  // mSOL worth less than SOL, e.g. after slashing
+  below_par: {
+    total_active_balance: 999_999_999_999n,
+    delayed_unstake_cooling_down: 0n,
+    emergency_cooling_down: 7n,
+    available_reserve_balance: 13n,
+    circulating_ticket_balance: 0n,
+    msol_supply: 1_000_000_000_003n,
+  },
+  // `u64` products only fit in 128 bits
+  near_u64_max: {
+    total_active_balance: 18_000_000_000_000_000_000n,
+    delayed_unstake_cooling_down: 0n,
+    emergency_cooling_down: 0n,
+    available_reserve_balance: 446_744_073_709_551_615n,
+    circulating_ticket_balance: 1n,
+    msol_supply: 17_999_999_999_999_999_989n,
+  },
+  // `near_u64_max` with one more lamport in the reserve: under control is 2^64
+  past_u64_max: {
+    total_active_balance: 18_000_000_000_000_000_000n,
+    delayed_unstake_cooling_down: 0n,
+    emergency_cooling_down: 0n,
+    available_reserve_balance: 446_744_073_709_551_616n,
+    circulating_ticket_balance: 1n,
+    msol_supply: 17_999_999_999_999_999_989n,
+  },
+  // a fresh deployment: everything 1:1
+  empty: {
+    total_active_balance: 0n,
+    delayed_unstake_cooling_down: 0n,
+    emergency_cooling_down: 0n,
+    available_reserve_balance: 0n,
+    circulating_ticket_balance: 0n,
+    msol_supply: 0n,
+  },
+  // supply but nothing behind it, the zero denominator of `sol_to_msol`
+  unbacked: {
+    total_active_balance: 0n,
+    delayed_unstake_cooling_down: 0n,
+    emergency_cooling_down: 0n,
+    available_reserve_balance: 5n,
+    circulating_ticket_balance: 5n,
+    msol_supply: 1_000n * SOL,
+  },
+}
+
+const U64_MAX = (1n << 64n) - 1n
+
+const AMOUNTS = [0n, 1n, 999n, SOL, 1_234_567_891n, 1_000_000n * SOL, 18_446_744_073_709_551_615n]
+
+function proportional(amount, numerator, denominator) {
+  return denominator === 0n ? amount : (amount * numerator) / denominator
+}
+
+// the version of the sdk node resolves, from the package.json above its entry point
+function sdkVersion(name) {
+  let dir = dirname(createRequire(import.meta.url).resolve(name))
+  while (!existsSync(join(dir, 'package.json')) || JSON.parse(readFileSync(join(dir, 'package.json'))).name !== name) {
+    if (dirname(dir) === dir) throw new Error(`no package.json for ${name}`)
+    dir = dirname(dir)
+  }
+  return JSON.parse(readFileSync(join(dir, 'package.json'))).version
+}
+
+let source = 'scripts/golden-vectors.mjs: the sdk formulas ported to BigInt, not checked against the sdk'
+let sdkProportional = null
+if (process.argv.includes('--sdk')) {
+  const sdk = '@marinade.finance/marinade-ts-sdk'
+  source = `scripts/golden-vectors.mjs --sdk: checked against ${sdk}@${sdkVersion(sdk)}`
+  const { MarinadeUtils } = await import(sdk)
+  const { default: BN } = await import('bn.js')
+  const bn = (value) => new BN(value.toString())
+  sdkProportional = (amount, numerator, denominator) =>
+    BigInt(MarinadeUtils.proportionalBN(bn(amount), bn(numerator), bn(denominator)).toString())
+}
+
+function amount(name, value, numerator, denominator) {
+  const ported = proportional(value, numerator, denominator)
+  if (sdkProportional !== null) {
+    const sdk = sdkProportional(value, numerator, denominator)
+    if (sdk !== ported) {
+      throw new Error(`${name}(${value}): the sdk gives ${sdk}, the port ${ported}`)
+    }
+  }
+  return ported
+}
+
+function vector(name, state) {
+  const underControl =
+    state.total_active_balance +
+    state.delayed_unstake_cooling_down +
+    state.emergency_cooling_down +
+    state.available_reserve_balance
+  const virtualStaked = underControl - state.circulating_ticket_balance
+  const supply = state.msol_supply
+  const price = amount(`${name} msol_price_lamports`, SOL, virtualStaked, supply)
+  const totals = {
+    total_lamports_under_control: underControl,
+    total_virtual_staked_lamports: virtualStaked,
+    msol_price_lamports: price,
+  }
+  const overflow = Object.keys(totals).find((key) => totals[key] > U64_MAX)
+  const pairs = (label, numerator, denominator) =>
+    overflow !== undefined
+      ? []
+      : AMOUNTS.map((value) => [value, amount(`${name} ${label}`, value, numerator, denominator)])
+          // the crate's results are u64, so an amount whose result isn't doesn't make a vector
+          .filter(([, result]) => result <= U64_MAX)
+  return {
+    source,
+    state,
+    ...totals,
+    ...(overflow === undefined ? {} : { overflow }),
+    sol_to_msol: pairs('sol_to_msol', supply, virtualStaked),
+    msol_to_sol: pairs('msol_to_sol', virtualStaked, supply),
+  }
+}
+
+// json by hand: BigInts don't stringify, and one pair per line keeps the diffs readable
+function render(value, indent = '') {
+  const inner = indent + '  '
+  if (typeof value === 'bigint') return value.toString()
+  if (typeof value === 'string') return JSON.stringify(value)
+  if (Array.isArray(value)) {
+    if (value.every((item) => typeof item === 'bigint')) return `[${value.join(', ')}]`
+    return `[\n${value.map((item) => inner + render(item, inner)).join(',\n')}\n${indent}]`
+  }
+  const entries = Object.entries(value).map(([key, item]) => `${inner}${JSON.stringify(key)}: ${render(item, inner)}`)
+  return `{\n${entries.join(',\n')}\n${indent}}`
+}
+
+mkdirSync('tests/golden', { recursive: true })
+for (const [name, state] of Object.entries(STATES)) {
+  writeFileSync(`tests/golden/${name}.json`, render(vector(name, state)) + '\n')
+  console.log(`tests/golden/${name}.json`)
+}
@@ -0,0 +1,222 @@
+//! the raw bytes of two state accounts compared, for layout investigations: where `diff` needs
+//! both to parse, this works on any two buffers, e.g. a snapshot from before marinade appended
+//! fields and one from after. each run of differing bytes is named by the fields of the known
+//! layout it overlaps, and `render_byte_diff` prints the rows around it hexdump style
+
+use serde::Serialize;
+
+use crate::accounts::marinade::STATE_LEN;
+
+/// the leaf fields of the borsh layout of `MarinadeState` with their sizes, in layout order.
+/// named by their path, like `diff` names them
+const STATE_FIELDS: &[(&str, usize)] = &[
+    ("msol_mint", 32),
+    ("admin_authority", 32),
+    ("operational_sol_account", 32),
+    ("treasury_msol_account", 32),
+    ("reserve_bump_seed", 1),
+    ("msol_mint_authority_bump_seed", 1),
+    ("rent_exempt_for_token_acc", 8),
+    ("reward_fee", 4),
+    ("stake_system.stake_list.account", 32),
+    ("stake_system.stake_list.item_size", 4),
+    ("stake_system.stake_list.count", 4),
+    ("stake_system.stake_list.reserved1", 32),
+    ("stake_system.stake_list.reserved2", 4),
+    ("stake_system.delayed_unstake_cooling_down", 8),
+    ("stake_system.stake_deposit_bump_seed", 1),
+    ("stake_system.stake_withdraw_bump_seed", 1),
+    ("stake_system.slots_for_stake_delta", 8),
+    ("stake_system.last_stake_delta_epoch", 8),
+    ("stake_system.min_stake", 8),
+    ("stake_system.extra_stake_delta_runs", 4),
+    ("validator_system.validator_list.account", 32),
+    ("validator_system.validator_list.item_size", 4),
+    ("validator_system.validator_list.count", 4),
+    ("validator_system.validator_list.reserved1", 32),
+    ("validator_system.validator_list.reserved2", 4),
+    ("validator_system.manager_authority", 32),
+    ("validator_system.total_validator_score", 4),
+    ("validator_system.total_active_balance", 8),
+    ("validator_system.auto_add_validator_enabled", 1),
+    ("liq_pool.lp_mint", 32),
+    ("liq_pool.lp_mint_authority_bump_seed", 1),
+    ("liq_pool.sol_leg_bump_seed", 1),
+    ("liq_pool.msol_leg_authority_bump_seed", 1),
+    ("liq_pool.msol_leg", 32),
+    ("liq_pool.lp_liquidity_target", 8),
+    ("liq_pool.lp_max_fee", 4),
+    ("liq_pool.lp_min_fee", 4),
+    ("liq_pool.treasury_cut", 4),
+    ("liq_pool.lp_supply", 8),
+    ("liq_pool.lent_from_sol_leg", 8),
+    ("liq_pool.liquidity_sol_cap", 8),
+    ("available_reserve_balance", 8),
+    ("msol_supply", 8),
+    ("msol_price", 8),
+    ("circulating_ticket_count", 8),
+    ("circulating_ticket_balance", 8),
+    ("lent_from_reserve", 8),
+    ("min_deposit", 8),
+    ("min_withdraw", 8),
+    ("staking_sol_cap", 8),
+    ("emergency_cooling_down", 8),
+    ("pause_authority", 32),
+    ("paused", 1),
+    ("delayed_unstake_fee", 4),
+    ("withdraw_stake_account_fee", 4),
+    ("withdraw_stake_account_enabled", 1),
+    ("last_stake_move_epoch", 8),
+    ("stake_moved", 8),
+    ("max_stake_moved_per_epoch", 4),
+];
+
+const ROW: usize = 16;
+
+/// where a field of the layout sits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FieldSpan {
+    pub field: &'static str,
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// every field of the layout, in order. they end at `STATE_LEN`
+pub fn state_layout() -> Vec<FieldSpan> {
+    let mut offset = 0;
+    STATE_FIELDS
+        .iter()
+        .map(|&(field, len)| {
+            offset += len;
+            FieldSpan { field, offset: offset - len, len }
+        })
+        .collect()
+}
+
+/// bytes `start..end` differ, or are only in one of the buffers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ByteRange {
+    pub start: usize,
+    pub end: usize,
+    /// the fields of the layout the range overlaps, in order. empty past `STATE_LEN`, where no
+    /// field is known yet
+    pub fields: Vec<&'static str>,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// whether the range runs past the known layout, e.g. into fields marinade appended
+    pub fn past_layout(&self) -> bool {
+        self.end > STATE_LEN
+    }
+}
+
+/// the runs of bytes `a` and `b` differ in, a length difference being one more run at the end
+pub fn diff_state_bytes(a: &[u8], b: &[u8]) -> Vec<ByteRange> {
+    let layout = state_layout();
+    let differs = |i: usize| a.get(i) != b.get(i);
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < a.len().max(b.len()) {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < a.len().max(b.len()) && differs(i) {
+            i += 1;
+        }
+        let overlaps = |span: &&FieldSpan| span.offset < i && start < span.offset + span.len;
+        let fields = layout.iter().filter(overlaps).map(|span| span.field).collect();
+        ranges.push(ByteRange { start, end: i, fields });
+    }
+    ranges
+}
+
+/// each of `ranges` with the rows of `a` and `b` it falls in, 16 bytes to a row, its bytes
+/// marked underneath. a byte only one buffer has shows as `--` in the other
+pub fn render_byte_diff(a: &[u8], b: &[u8], ranges: &[ByteRange]) -> String {
+    let len = a.len().max(b.len());
+    let mut out = String::new();
+    for range in ranges {
+        let fields = match range.fields.is_empty() {
+            true => "past the known layout".to_string(),
+            false => range.fields.join(", "),
+        };
+        out.push_str(&format!("{}..{} ({} bytes): {}\n", range.start, range.end, range.len(), fields));
+        for row in (range.start / ROW * ROW..range.end).step_by(ROW) {
+            let columns = row..(row + ROW).min(len);
+            let hex = |data: &[u8]| {
+                let byte = |i: usize| data.get(i).map_or("--".to_string(), |byte| format!("{:02x}", byte));
+                columns.clone().map(byte).collect::<Vec<_>>().join(" ")
+            };
+            let marks = columns.clone().map(|i| if (range.start..range.end).contains(&i) { "^^" } else { "  " });
+            let marks = marks.collect::<Vec<_>>().join(" ");
+            out.push_str(&format!("{:08x}  a {}\n          b {}\n", row, hex(a), hex(b)));
+            out.push_str(&format!("            {}\n", marks.trim_end()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::{
+        CIRCULATING_TICKET_BALANCE_OFFSET, DELAYED_UNSTAKE_COOLING_DOWN_OFFSET, EMERGENCY_COOLING_DOWN_OFFSET,
+        MSOL_SUPPLY_OFFSET, TOTAL_ACTIVE_BALANCE_OFFSET,
+    };
+    use crate::test_utils::{sample_state, state_data};
+
+    #[test]
+    fn test_layout_matches_the_known_offsets() {
+        let layout = state_layout();
+        let offset = |field: &str| layout.iter().find(|span| span.field == field).unwrap().offset;
+        assert_eq!(offset("stake_system.delayed_unstake_cooling_down"), DELAYED_UNSTAKE_COOLING_DOWN_OFFSET);
+        assert_eq!(offset("validator_system.total_active_balance"), TOTAL_ACTIVE_BALANCE_OFFSET);
+        assert_eq!(offset("msol_supply"), MSOL_SUPPLY_OFFSET);
+        assert_eq!(offset("circulating_ticket_balance"), CIRCULATING_TICKET_BALANCE_OFFSET);
+        assert_eq!(offset("emergency_cooling_down"), EMERGENCY_COOLING_DOWN_OFFSET);
+        let last = layout.last().unwrap();
+        assert_eq!((last.offset + last.len, state_data(&sample_state()).len()), (STATE_LEN, STATE_LEN));
+    }
+
+    #[test]
+    fn test_ranges_name_the_fields_they_fall_in() {
+        let a = state_data(&sample_state());
+        let mut b = a.clone();
+        // the supply's second byte, the last of msol_supply and the first of msol_price, and 4
+        // appended bytes
+        b[MSOL_SUPPLY_OFFSET + 1] ^= 0xff;
+        b[MSOL_SUPPLY_OFFSET + 7] ^= 0x01;
+        b[MSOL_SUPPLY_OFFSET + 8] ^= 0x01;
+        b.extend_from_slice(&[1, 2, 3, 4]);
+
+        let ranges = diff_state_bytes(&a, &b);
+        let expected = [
+            ByteRange { start: 497, end: 498, fields: vec!["msol_supply"] },
+            ByteRange { start: 503, end: 505, fields: vec!["msol_supply", "msol_price"] },
+            ByteRange { start: STATE_LEN, end: STATE_LEN + 4, fields: vec![] },
+        ];
+        assert_eq!(ranges, expected);
+        assert!(!ranges[1].past_layout() && ranges[2].past_layout());
+        assert!(diff_state_bytes(&a, &a).is_empty());
+
+        let rendered = render_byte_diff(&a, &b, &ranges);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "497..498 (1 bytes): msol_supply");
+        assert!(lines[1].starts_with("000001f0  a "), "{}", lines[1]);
+        assert_eq!(lines[3], format!("{}{}", " ".repeat(15), "^^"));
+        assert_eq!(lines[4], "503..505 (2 bytes): msol_supply, msol_price");
+        let appended = &lines[lines.len() - 4..];
+        assert_eq!(appended[0], "630..634 (4 bytes): past the known layout");
+        assert!(appended[1].ends_with(" -- -- -- --") && appended[2].ends_with(" 01 02 03 04"), "{:?}", appended);
+    }
+}
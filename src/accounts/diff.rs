@@ -0,0 +1,298 @@
+//! what changed in the marinade state between two reads of it, for post-mortems.
+//!
+//! integer fields (balances, supplies, counters, epochs) are reported with a signed delta,
+//! everything else (fees, flags, bump seeds, authorities) with its old and new value. fields are
+//! named by their path in `MarinadeState`, e.g. `validator_system.total_active_balance`
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::accounts::marinade::{Fee, FeeCents, List, MarinadeState};
+
+/// an integer field that changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDelta {
+    pub field: String,
+    pub old: u64,
+    pub new: u64,
+    /// `new - old`
+    pub delta: i128,
+}
+
+/// any other field that changed, both values as displayed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// the mSOL price implied by each state, see `MarinadeState::msol_price_lamports`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PriceChange {
+    pub old_lamports: u64,
+    pub new_lamports: u64,
+    pub delta_lamports: i128,
+    /// the delta relative to the old price, in basis points. 0 when the old price is 0
+    pub delta_bps: f64,
+}
+
+/// see `MarinadeState::diff`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarinadeStateDiff {
+    /// the context slots of the older and newer state, when they were read from somewhere that
+    /// tells, see `MarinadeClient::diff_between_slots`
+    pub slots: Option<(u64, u64)>,
+    pub deltas: Vec<FieldDelta>,
+    pub changes: Vec<FieldChange>,
+    pub price: PriceChange,
+}
+
+impl MarinadeStateDiff {
+    /// no field changed
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty() && self.changes.is_empty()
+    }
+
+    /// the delta of `field`, None when it didn't change or isn't an integer field
+    pub fn delta(&self, field: &str) -> Option<i128> {
+        self.deltas.iter().find(|delta| delta.field == field).map(|delta| delta.delta)
+    }
+}
+
+/// one line for the price, then one per changed field
+impl fmt::Display for MarinadeStateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((older, newer)) = self.slots {
+            write!(f, "slot {} -> {}: ", older, newer)?;
+        }
+        let price = &self.price;
+        write!(
+            f,
+            "msol price {} -> {} lamports ({:+}, {:+.2} bp)",
+            price.old_lamports, price.new_lamports, price.delta_lamports, price.delta_bps
+        )?;
+        if self.is_empty() {
+            return write!(f, ", no field changed");
+        }
+        for delta in &self.deltas {
+            write!(f, "\n  {}: {} -> {} ({:+})", delta.field, delta.old, delta.new, delta.delta)?;
+        }
+        for change in &self.changes {
+            write!(f, "\n  {}: {} -> {}", change.field, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Fee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bp", self.basis_points)
+    }
+}
+
+/// hundredths of a basis point
+impl fmt::Display for FeeCents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02} bp", self.bp_cents / 100, self.bp_cents % 100)
+    }
+}
+
+impl MarinadeState {
+    /// every field that differs from `older` to `newer`, in layout order, and the price change.
+    /// the reserved fields of the lists are left out
+    pub fn diff(older: &MarinadeState, newer: &MarinadeState) -> MarinadeStateDiff {
+        let mut diff = Differ::default();
+        diff.value("msol_mint", &older.msol_mint, &newer.msol_mint);
+        diff.value("admin_authority", &older.admin_authority, &newer.admin_authority);
+        diff.value("operational_sol_account", &older.operational_sol_account, &newer.operational_sol_account);
+        diff.value("treasury_msol_account", &older.treasury_msol_account, &newer.treasury_msol_account);
+        diff.value("reserve_bump_seed", &older.reserve_bump_seed, &newer.reserve_bump_seed);
+        let (old, new) = (older.msol_mint_authority_bump_seed, newer.msol_mint_authority_bump_seed);
+        diff.value("msol_mint_authority_bump_seed", &old, &new);
+        diff.amount("rent_exempt_for_token_acc", older.rent_exempt_for_token_acc, newer.rent_exempt_for_token_acc);
+        diff.value("reward_fee", &older.reward_fee, &newer.reward_fee);
+
+        let (old, new) = (&older.stake_system, &newer.stake_system);
+        diff.list("stake_system.stake_list", &old.stake_list, &new.stake_list);
+        let field = "stake_system.delayed_unstake_cooling_down";
+        diff.amount(field, old.delayed_unstake_cooling_down, new.delayed_unstake_cooling_down);
+        diff.value("stake_system.stake_deposit_bump_seed", &old.stake_deposit_bump_seed, &new.stake_deposit_bump_seed);
+        let field = "stake_system.stake_withdraw_bump_seed";
+        diff.value(field, &old.stake_withdraw_bump_seed, &new.stake_withdraw_bump_seed);
+        diff.amount("stake_system.slots_for_stake_delta", old.slots_for_stake_delta, new.slots_for_stake_delta);
+        diff.amount("stake_system.last_stake_delta_epoch", old.last_stake_delta_epoch, new.last_stake_delta_epoch);
+        diff.amount("stake_system.min_stake", old.min_stake, new.min_stake);
+        let field = "stake_system.extra_stake_delta_runs";
+        diff.amount(field, old.extra_stake_delta_runs.into(), new.extra_stake_delta_runs.into());
+
+        let (old, new) = (&older.validator_system, &newer.validator_system);
+        diff.list("validator_system.validator_list", &old.validator_list, &new.validator_list);
+        diff.value("validator_system.manager_authority", &old.manager_authority, &new.manager_authority);
+        let field = "validator_system.total_validator_score";
+        diff.amount(field, old.total_validator_score.into(), new.total_validator_score.into());
+        diff.amount("validator_system.total_active_balance", old.total_active_balance, new.total_active_balance);
+        let field = "validator_system.auto_add_validator_enabled";
+        diff.value(field, &old.auto_add_validator_enabled, &new.auto_add_validator_enabled);
+
+        let (old, new) = (&older.liq_pool, &newer.liq_pool);
+        diff.value("liq_pool.lp_mint", &old.lp_mint, &new.lp_mint);
+        let field = "liq_pool.lp_mint_authority_bump_seed";
+        diff.value(field, &old.lp_mint_authority_bump_seed, &new.lp_mint_authority_bump_seed);
+        diff.value("liq_pool.sol_leg_bump_seed", &old.sol_leg_bump_seed, &new.sol_leg_bump_seed);
+        let field = "liq_pool.msol_leg_authority_bump_seed";
+        diff.value(field, &old.msol_leg_authority_bump_seed, &new.msol_leg_authority_bump_seed);
+        diff.value("liq_pool.msol_leg", &old.msol_leg, &new.msol_leg);
+        diff.amount("liq_pool.lp_liquidity_target", old.lp_liquidity_target, new.lp_liquidity_target);
+        diff.value("liq_pool.lp_max_fee", &old.lp_max_fee, &new.lp_max_fee);
+        diff.value("liq_pool.lp_min_fee", &old.lp_min_fee, &new.lp_min_fee);
+        diff.value("liq_pool.treasury_cut", &old.treasury_cut, &new.treasury_cut);
+        diff.amount("liq_pool.lp_supply", old.lp_supply, new.lp_supply);
+        diff.amount("liq_pool.lent_from_sol_leg", old.lent_from_sol_leg, new.lent_from_sol_leg);
+        diff.amount("liq_pool.liquidity_sol_cap", old.liquidity_sol_cap, new.liquidity_sol_cap);
+
+        diff.amount("available_reserve_balance", older.available_reserve_balance, newer.available_reserve_balance);
+        diff.amount("msol_supply", older.msol_supply, newer.msol_supply);
+        diff.amount("msol_price", older.msol_price, newer.msol_price);
+        diff.amount("circulating_ticket_count", older.circulating_ticket_count, newer.circulating_ticket_count);
+        diff.amount("circulating_ticket_balance", older.circulating_ticket_balance, newer.circulating_ticket_balance);
+        diff.amount("lent_from_reserve", older.lent_from_reserve, newer.lent_from_reserve);
+        diff.amount("min_deposit", older.min_deposit, newer.min_deposit);
+        diff.amount("min_withdraw", older.min_withdraw, newer.min_withdraw);
+        diff.amount("staking_sol_cap", older.staking_sol_cap, newer.staking_sol_cap);
+        diff.amount("emergency_cooling_down", older.emergency_cooling_down, newer.emergency_cooling_down);
+        diff.value("pause_authority", &older.pause_authority, &newer.pause_authority);
+        diff.value("paused", &older.paused, &newer.paused);
+        diff.value("delayed_unstake_fee", &older.delayed_unstake_fee, &newer.delayed_unstake_fee);
+        diff.value("withdraw_stake_account_fee", &older.withdraw_stake_account_fee, &newer.withdraw_stake_account_fee);
+        let (old, new) = (older.withdraw_stake_account_enabled, newer.withdraw_stake_account_enabled);
+        diff.value("withdraw_stake_account_enabled", &old, &new);
+        diff.amount("last_stake_move_epoch", older.last_stake_move_epoch, newer.last_stake_move_epoch);
+        diff.amount("stake_moved", older.stake_moved, newer.stake_moved);
+        diff.value("max_stake_moved_per_epoch", &older.max_stake_moved_per_epoch, &newer.max_stake_moved_per_epoch);
+
+        let (old_lamports, new_lamports) = (older.msol_price_lamports(), newer.msol_price_lamports());
+        let delta_lamports = new_lamports as i128 - old_lamports as i128;
+        let delta_bps = match old_lamports {
+            0 => 0.0,
+            old => delta_lamports as f64 * 10_000.0 / old as f64,
+        };
+        MarinadeStateDiff {
+            slots: None,
+            deltas: diff.deltas,
+            changes: diff.changes,
+            price: PriceChange { old_lamports, new_lamports, delta_lamports, delta_bps },
+        }
+    }
+}
+
+#[derive(Default)]
+struct Differ {
+    deltas: Vec<FieldDelta>,
+    changes: Vec<FieldChange>,
+}
+
+impl Differ {
+    fn amount(&mut self, field: &str, old: u64, new: u64) {
+        if old != new {
+            self.deltas.push(FieldDelta { field: field.to_string(), old, new, delta: new as i128 - old as i128 });
+        }
+    }
+
+    fn value<T: PartialEq + fmt::Display>(&mut self, field: &str, old: &T, new: &T) {
+        if old != new {
+            self.changes.push(FieldChange { field: field.to_string(), old: old.to_string(), new: new.to_string() });
+        }
+    }
+
+    fn list(&mut self, field: &str, old: &List, new: &List) {
+        self.value(&format!("{}.account", field), &old.account, &new.account);
+        self.amount(&format!("{}.item_size", field), old.item_size.into(), new.item_size.into());
+        self.amount(&format!("{}.count", field), old.count.into(), new.count.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::PRICE_DENOMINATOR;
+    use crate::test_utils::sample_state;
+
+    /// the state a slot before and a slot after the first crank of an epoch: rewards land in
+    /// the active balance, the reward fee is minted to the treasury and the stake delta runs
+    fn straddling_a_crank() -> (MarinadeState, MarinadeState) {
+        let before = sample_state();
+        let mut after = before.clone();
+        after.validator_system.total_active_balance += 1_400_000_000_000;
+        after.msol_supply += 70_000_000_000;
+        after.stake_system.last_stake_delta_epoch = 580;
+        after.stake_system.delayed_unstake_cooling_down -= 200_000_000_000;
+        after.available_reserve_balance += 200_000_000_000;
+        after.msol_price = (after.msol_price_lamports() as u128 * PRICE_DENOMINATOR as u128 / 1_000_000_000) as u64;
+        after.reward_fee = Fee { basis_points: 700 };
+        after.delayed_unstake_fee = FeeCents { bp_cents: 150 };
+        (before, after)
+    }
+
+    #[test]
+    fn test_diff_across_an_epoch_crank() {
+        let (before, after) = straddling_a_crank();
+        let diff = MarinadeState::diff(&before, &after);
+
+        assert_eq!(diff.delta("validator_system.total_active_balance"), Some(1_400_000_000_000));
+        assert_eq!(diff.delta("stake_system.delayed_unstake_cooling_down"), Some(-200_000_000_000));
+        assert_eq!(diff.delta("stake_system.last_stake_delta_epoch"), Some(580));
+        assert_eq!(diff.delta("msol_supply"), Some(70_000_000_000));
+        assert_eq!(diff.deltas.len(), 6);
+        let changes: Vec<_> = diff.changes.iter().map(|c| (c.field.as_str(), c.old.as_str(), c.new.as_str())).collect();
+        assert_eq!(changes, [("reward_fee", "600 bp", "700 bp"), ("delayed_unstake_fee", "0.00 bp", "1.50 bp")]);
+
+        let price = diff.price;
+        assert_eq!((price.old_lamports, price.new_lamports), (before.msol_price_lamports(), after.msol_price_lamports()));
+        assert!(price.delta_lamports > 0 && price.delta_bps > 0.0 && price.delta_bps < 100.0, "{:?}", price);
+        let text = diff.to_string();
+        assert!(text.starts_with(&format!("msol price {} -> {}", price.old_lamports, price.new_lamports)), "{}", text);
+        assert!(text.contains("\n  msol_supply: 6000000000000000 -> 6000070000000000 (+70000000000)"), "{}", text);
+        assert!(text.contains("\n  reward_fee: 600 bp -> 700 bp"), "{}", text);
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["changes"][0]["field"], "reward_fee");
+
+        // the other way round negates every delta
+        let back = MarinadeState::diff(&after, &before);
+        assert_eq!(back.delta("msol_supply"), Some(-70_000_000_000));
+        assert_eq!(back.price.delta_lamports, -price.delta_lamports);
+        let same = MarinadeState::diff(&before, &before);
+        assert!(same.is_empty());
+        assert!(same.to_string().ends_with("(+0, +0.00 bp), no field changed"), "{}", same);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_diff_between_cached_slots() {
+        use crate::cache::SnapshotCache;
+        use crate::client::MarinadeClient;
+        use crate::cluster::Cluster;
+        use crate::constants::MARINADE_STATE_PUBKEY;
+        use crate::snapshot::SnapshotMetadata;
+        use crate::test_utils::{MockFetcher, FIXTURE_SLOT};
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("marinade-diff-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (before, after) = straddling_a_crank();
+        let cache = SnapshotCache::new(&dir, Cluster::default());
+        cache.put(&before, &SnapshotMetadata::new(&Cluster::default(), MARINADE_STATE_PUBKEY, FIXTURE_SLOT)).unwrap();
+        cache.put(&after, &SnapshotMetadata::new(&Cluster::default(), MARINADE_STATE_PUBKEY, FIXTURE_SLOT + 2)).unwrap();
+
+        let rpc = Arc::new(MockFetcher::new());
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).snapshot_cache(&dir).offline(true).build();
+        let diff = client.diff_between_slots(FIXTURE_SLOT - 5, FIXTURE_SLOT + 1).unwrap();
+        assert_eq!(diff.slots, Some((FIXTURE_SLOT, FIXTURE_SLOT + 2)));
+        assert_eq!(MarinadeStateDiff { slots: None, ..diff.clone() }, MarinadeState::diff(&before, &after));
+        assert!(diff.to_string().starts_with(&format!("slot {} -> {}: msol price", FIXTURE_SLOT, FIXTURE_SLOT + 2)));
+        assert!(client.diff_between_slots(FIXTURE_SLOT, FIXTURE_SLOT + 3).is_err());
+        assert!(rpc.calls().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
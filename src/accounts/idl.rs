@@ -0,0 +1,33 @@
+//! the instruction enum, instruction args and account discriminators, generated by `build.rs`
+//! from `idls/marinade-idl.json`. edit the idl, not the output; `cargo build` regenerates it
+
+use anchor_lang::prelude::*;
+
+include!(concat!(env!("OUT_DIR"), "/marinade_idl.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_discriminators_match_on_chain() {
+        // sighashes of the deployed program's handlers and accounts, as seen in mainnet data
+        let known: [(MarinadeFinanceInstruction, [u8; 8]); 4] = [
+            (MarinadeFinanceInstruction::Deposit, [242, 35, 198, 137, 82, 225, 242, 182]),
+            (MarinadeFinanceInstruction::LiquidUnstake, [30, 30, 119, 240, 191, 227, 12, 16]),
+            (MarinadeFinanceInstruction::OrderUnstake, [97, 167, 144, 107, 117, 190, 128, 36]),
+            (MarinadeFinanceInstruction::Claim, [62, 198, 214, 193, 213, 159, 108, 210]),
+        ];
+        for (ix, discriminator) in known {
+            assert!(DISCRIMINATORS.contains(&(u64::from_le_bytes(discriminator), ix)), "{}", ix.name());
+        }
+        assert_eq!(DISCRIMINATORS.len(), MarinadeFinanceInstruction::ALL.len());
+        assert!(DISCRIMINATORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(STATE_DISCRIMINATOR, [0xd8, 0x92, 0x6b, 0x5e, 0x68, 0x4b, 0xb6, 0xb1]);
+        assert_eq!(TICKET_ACCOUNT_DATA_DISCRIMINATOR, [0x85, 0x4d, 0x12, 0x62, 0xd3, 0x01, 0xe7, 0x03]);
+
+        let args = MergeStakesArgs { destination_stake_index: 1, source_stake_index: 2, validator_index: 3 };
+        let data = [&MarinadeFinanceInstruction::MergeStakes.discriminator()[..], &args.try_to_vec().unwrap()].concat();
+        assert_eq!(crate::accounts::instructions::decode_args::<MergeStakesArgs>(&data), Some(args));
+    }
+}
@@ -1,33 +1,345 @@
+//! the instruction enum and arg structs are generated from the idl, see `super::idl`; this adds
+//! what the idl doesn't say
+
+use std::fmt;
+use std::str::FromStr;
+
 use anchor_lang::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub use super::idl::*;
+
+/// anchor prefixes instruction data with the first 8 bytes of sha256("global:<name>")
+pub const DISCRIMINATOR_LEN: usize = 8;
+
+impl MarinadeFinanceInstruction {
+    /// every variant, in idl order
+    pub fn iter_all() -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter()
+    }
+
+    pub fn discriminator(&self) -> [u8; DISCRIMINATOR_LEN] {
+        DISCRIMINATORS
+            .iter()
+            .find(|(_, ix)| ix == self)
+            .map(|(d, _)| d.to_le_bytes())
+            .expect("every variant has a discriminator")
+    }
+
+    /// identify an instruction from its raw data; args after the discriminator are ignored
+    pub fn try_from_data(data: &[u8]) -> Option<Self> {
+        let prefix: [u8; DISCRIMINATOR_LEN] = data.get(..DISCRIMINATOR_LEN)?.try_into().ok()?;
+        let key = u64::from_le_bytes(prefix);
+        DISCRIMINATORS.binary_search_by_key(&key, |(d, _)| *d).ok().map(|i| DISCRIMINATORS[i].1)
+    }
+
+    /// instructions that move supply and underlying together, leaving the mSOL/SOL rate untouched
+    pub fn is_price_neutral(&self) -> bool {
+        matches!(self, Self::Deposit | Self::LiquidUnstake | Self::OrderUnstake | Self::Claim)
+    }
+
+    /// the epoch cranks, which extract rewards and mint the reward fee to the treasury
+    pub fn is_crank(&self) -> bool {
+        matches!(self, Self::UpdateActive | Self::UpdateDeactivated)
+    }
+
+    /// the user instructions that pay the treasury a fee: the treasury's cut of the liquid
+    /// unstake fee and `withdraw_stake_account_fee`. `deposit_stake_account` charges none, and the
+    /// delayed unstake fee stays behind for mSOL holders
+    pub fn pays_treasury(&self) -> bool {
+        matches!(self, Self::LiquidUnstake | Self::WithdrawStakeAccount)
+    }
+
+    /// the accounts the handler takes, in order, by their idl names, with nested account
+    /// structs like `common` flattened. empty for the instructions nothing here reads accounts of
+    /// yet
+    pub fn account_roles(&self) -> &'static [&'static str] {
+        match self {
+            Self::EmergencyUnstake => &[
+                "state",
+                "validator_manager_authority",
+                "validator_list",
+                "stake_list",
+                "stake_account",
+                "stake_deposit_authority",
+                "clock",
+                "stake_program",
+            ],
+            Self::ChangeAuthority => &["state", "admin_authority"],
+            Self::Pause | Self::Resume => &["state", "pause_authority"],
+            Self::Deposit => &[
+                "state",
+                "msol_mint",
+                "liq_pool_sol_leg_pda",
+                "liq_pool_msol_leg",
+                "liq_pool_msol_leg_authority",
+                "reserve_pda",
+                "transfer_from",
+                "mint_to",
+                "msol_mint_authority",
+                "system_program",
+                "token_program",
+            ],
+            Self::LiquidUnstake => &[
+                "state",
+                "msol_mint",
+                "liq_pool_sol_leg_pda",
+                "liq_pool_msol_leg",
+                "treasury_msol_account",
+                "get_msol_from",
+                "get_msol_from_authority",
+                "transfer_sol_to",
+                "system_program",
+                "token_program",
+            ],
+            Self::AddLiquidity => &[
+                "state",
+                "lp_mint",
+                "lp_mint_authority",
+                "liq_pool_msol_leg",
+                "liq_pool_sol_leg_pda",
+                "transfer_from",
+                "mint_to",
+                "system_program",
+                "token_program",
+            ],
+            Self::RemoveLiquidity => &[
+                "state",
+                "lp_mint",
+                "burn_from",
+                "burn_from_authority",
+                "transfer_sol_to",
+                "transfer_msol_to",
+                "liq_pool_sol_leg_pda",
+                "liq_pool_msol_leg",
+                "liq_pool_msol_leg_authority",
+                "system_program",
+                "token_program",
+            ],
+            Self::OrderUnstake => &[
+                "state",
+                "msol_mint",
+                "burn_msol_from",
+                "burn_msol_authority",
+                "new_ticket_account",
+                "clock",
+                "rent",
+                "token_program",
+            ],
+            Self::Claim => &["state", "reserve_pda", "ticket_account", "transfer_sol_to", "clock", "system_program"],
+            Self::StakeReserve => &[
+                "state",
+                "validator_list",
+                "stake_list",
+                "validator_vote",
+                "reserve_pda",
+                "stake_account",
+                "stake_deposit_authority",
+                "rent_payer",
+                "clock",
+                "epoch_schedule",
+                "rent",
+                "stake_history",
+                "stake_config",
+                "system_program",
+                "stake_program",
+            ],
+            Self::UpdateDeactivated => &[
+                "state",
+                "stake_list",
+                "stake_account",
+                "stake_withdraw_authority",
+                "reserve_pda",
+                "msol_mint",
+                "msol_mint_authority",
+                "treasury_msol_account",
+                "clock",
+                "stake_history",
+                "stake_program",
+                "token_program",
+                "operational_sol_account",
+                "system_program",
+            ],
+            Self::DeactivateStake => &[
+                "state",
+                "reserve_pda",
+                "validator_list",
+                "stake_list",
+                "stake_account",
+                "stake_deposit_authority",
+                "split_stake_account",
+                "split_stake_rent_payer",
+                "clock",
+                "rent",
+                "epoch_schedule",
+                "stake_history",
+                "system_program",
+                "stake_program",
+            ],
+            Self::MergeStakes => &[
+                "state",
+                "stake_list",
+                "validator_list",
+                "destination_stake",
+                "source_stake",
+                "stake_deposit_authority",
+                "stake_withdraw_authority",
+                "operational_sol_account",
+                "clock",
+                "stake_history",
+                "stake_program",
+            ],
+            Self::PartialUnstake => &[
+                "state",
+                "validator_manager_authority",
+                "validator_list",
+                "stake_list",
+                "stake_account",
+                "stake_deposit_authority",
+                "reserve_pda",
+                "split_stake_account",
+                "split_stake_rent_payer",
+                "clock",
+                "rent",
+                "stake_history",
+                "system_program",
+                "stake_program",
+            ],
+            Self::Redelegate => &[
+                "state",
+                "validator_list",
+                "stake_list",
+                "stake_account",
+                "stake_deposit_authority",
+                "reserve_pda",
+                "split_stake_account",
+                "split_stake_rent_payer",
+                "dest_validator_account",
+                "redelegate_stake_account",
+                "clock",
+                "stake_history",
+                "stake_config",
+                "system_program",
+                "stake_program",
+            ],
+            Self::WithdrawStakeAccount => &[
+                "state",
+                "msol_mint",
+                "burn_msol_from",
+                "burn_msol_authority",
+                "treasury_msol_account",
+                "validator_list",
+                "stake_list",
+                "stake_withdraw_authority",
+                "stake_deposit_authority",
+                "stake_account",
+                "split_stake_account",
+                "split_stake_rent_payer",
+                "clock",
+                "system_program",
+                "token_program",
+                "stake_program",
+            ],
+            _ => &[],
+        }
+    }
+}
+
+/// the handler name, e.g. `liquid_unstake`
+impl fmt::Display for MarinadeFinanceInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// a name no instruction has
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseInstructionError(String);
+
+impl fmt::Display for ParseInstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no marinade instruction is named {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseInstructionError {}
+
+impl FromStr for MarinadeFinanceInstruction {
+    type Err = ParseInstructionError;
+
+    /// the handler name, as `name` returns it
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::iter_all().find(|ix| ix.name() == s).ok_or_else(|| ParseInstructionError(s.to_string()))
+    }
+}
+
+impl Serialize for MarinadeFinanceInstruction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for MarinadeFinanceInstruction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        <String as Deserialize>::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// an instruction's args, from the whole instruction data, discriminator included
+pub fn decode_args<T: AnchorDeserialize>(data: &[u8]) -> Option<T> {
+    T::deserialize(&mut data.get(DISCRIMINATOR_LEN..)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discriminator_round_trip() {
+        for ix in MarinadeFinanceInstruction::ALL {
+            let mut data = ix.discriminator().to_vec();
+            data.extend_from_slice(&42u64.to_le_bytes());
+            assert_eq!(MarinadeFinanceInstruction::try_from_data(&data), Some(ix));
+        }
+        // sha256("global:deposit")[..8]
+        assert_eq!(MarinadeFinanceInstruction::Deposit.discriminator(), [242, 35, 198, 137, 82, 225, 242, 182]);
+        assert_eq!(MarinadeFinanceInstruction::try_from_data(&[0u8; 8]), None);
+        assert_eq!(MarinadeFinanceInstruction::try_from_data(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        assert_eq!(MarinadeFinanceInstruction::iter_all().count(), MarinadeFinanceInstruction::ALL.len());
+        for ix in MarinadeFinanceInstruction::iter_all() {
+            assert_eq!(ix.to_string().parse(), Ok(ix));
+            let json = serde_json::to_string(&ix).unwrap();
+            assert_eq!(json, format!("\"{}\"", ix.name()));
+            assert_eq!(serde_json::from_str::<MarinadeFinanceInstruction>(&json).unwrap(), ix);
+        }
+        assert_eq!(MarinadeFinanceInstruction::LiquidUnstake.to_string(), "liquid_unstake");
+        assert!("LiquidUnstake".parse::<MarinadeFinanceInstruction>().is_err());
+        assert!(serde_json::from_str::<MarinadeFinanceInstruction>("\"unstake\"").is_err());
+    }
 
-#[derive(AnchorDeserialize)]
-pub enum MarinadeFinanceInstruction {
-    Initialize,
-    ChangeAuthority,
-    AddValidator,
-    RemoveValidator,
-    SetValidatorScore,
-    ConfigValidatorSystem,
-    Deposit,
-    DepositStakeAccount,
-    LiquidUnstake,
-    AddLiquidity,
-    RemoveLiquidity,
-    ConfigLp,
-    ConfigMarinade,
-    OrderUnstake,
-    Claim,
-    StakeReserve,
-    UpdateActive,
-    UpdateDeactivated,
-    DeactivateStake,
-    EmergencyUnstake,
-    PartialUnstake,
-    MergeStakes,
-    Redelegate,
-    Pause,
-    Resume,
-    WithdrawStakeAccount,
-    ReallocValidatorList,
-    ReallocStakeList,
-}
\ No newline at end of file
+    #[test]
+    fn test_garbage_instruction_data() {
+        let with_amount = |ix: &MarinadeFinanceInstruction| [&ix.discriminator()[..], &42u64.to_le_bytes()].concat();
+        let mut corpus: Vec<Vec<u8>> = MarinadeFinanceInstruction::ALL.iter().map(with_amount).collect();
+        // every option set, for the args that are mostly options
+        for ix in [MarinadeFinanceInstruction::ConfigMarinade, MarinadeFinanceInstruction::ChangeAuthority] {
+            corpus.push([&ix.discriminator()[..], &[1; 200]].concat());
+        }
+        for data in crate::test_utils::adversarial_inputs(&corpus, 5_000, 64) {
+            if let Some(ix) = MarinadeFinanceInstruction::try_from_data(&data) {
+                assert_eq!(data[..DISCRIMINATOR_LEN], ix.discriminator());
+            }
+            if let Some(args) = decode_args::<DepositArgs>(&data) {
+                assert_eq!(args.try_to_vec().unwrap(), data[DISCRIMINATOR_LEN..DISCRIMINATOR_LEN + 8]);
+            }
+            let _ = decode_args::<ConfigMarinadeArgs>(&data);
+            let _ = decode_args::<ChangeAuthorityArgs>(&data);
+            let _ = decode_args::<InitializeArgs>(&data);
+            let _ = decode_args::<WithdrawStakeAccountArgs>(&data);
+        }
+    }
+}
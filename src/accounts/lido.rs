@@ -0,0 +1,72 @@
+//! the leading fields of the lido (v2) state account, up to and including the exchange rate.
+//! everything after it - fees, metrics, list pointers - isn't needed for valuation and is skipped
+
+use anchor_lang::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+
+/// `AccountType::Lido` in the solido program
+pub const LIDO_ACCOUNT_TYPE: u8 = 1;
+
+/// deserialize the valuation prefix of a lido state account; works the same on a live
+/// account and on an archived snapshot of one
+pub fn parse_lido_state(account_data: &[u8]) -> std::io::Result<LidoState> {
+    let state = LidoState::deserialize(&mut &account_data[..])?;
+    if state.account_type != LIDO_ACCOUNT_TYPE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("expected a lido account (type {}), got type {}", LIDO_ACCOUNT_TYPE, state.account_type),
+        ));
+    }
+    Ok(state)
+}
+
+/// stSOL supply and the SOL behind it, as of the last epoch the rate was updated in
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ExchangeRate {
+    pub computed_in_epoch: u64,
+    pub st_sol_supply: u64,
+    pub sol_balance: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct LidoState {
+    pub account_type: u8,
+    pub lido_version: u8,
+    pub manager: Pubkey,
+    pub st_sol_mint: Pubkey,
+    pub exchange_rate: ExchangeRate,
+}
+
+impl LidoState {
+    /// lamports per stSOL at the stored exchange rate, 1:1 before the first update
+    pub fn price_lamports(&self) -> u64 {
+        let rate = &self.exchange_rate;
+        if rate.st_sol_supply == 0 {
+            return LAMPORTS_PER_MSOL;
+        }
+        (LAMPORTS_PER_MSOL as u128 * rate.sol_balance as u128 / rate.st_sol_supply as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{lido_data, sample_lido_state};
+
+    #[test]
+    fn test_parse_lido_state() {
+        let state = sample_lido_state();
+        let data = lido_data(&state);
+        let parsed = parse_lido_state(&data).unwrap();
+        assert_eq!(parsed, state);
+        // 10.5M SOL behind 9M stSOL
+        assert_eq!(parsed.price_lamports(), 1_166_666_666);
+
+        let validator = LidoState { account_type: 2, ..state };
+        assert!(parse_lido_state(&validator.try_to_vec().unwrap()).is_err());
+        assert!(parse_lido_state(&data[..40]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! items of the state's stake and validator lists. each list is its own account: an 8-byte
+//! discriminator, then `count` items of `item_size` bytes, as the state's `List` describes it.
+//! `realloc_stake_list` and `realloc_validator_list` grow the accounts over time, so capacity is
+//! whatever the data length holds rather than anything fixed, and past `count` it's padding
+
+use std::io;
+
+use anchor_lang::prelude::*;
+use serde::Serialize;
+
+use crate::accounts::marinade::List;
+
+/// bytes before the first item of a list account
+pub const LIST_HEADER_LEN: usize = 8;
+
+/// an item of the stake list, one per stake account the program controls
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct StakeRecord {
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    /// the delegation as of the last `update_active`
+    pub last_update_delegated_lamports: u64,
+    pub last_update_epoch: u64,
+    /// set by `emergency_unstake` and `partial_unstake` while the stake cools down
+    pub is_emergency_unstaking: u8,
+}
+
+/// an item of the validator list
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ValidatorRecord {
+    /// the vote account
+    #[serde(with = "crate::serde_pubkey")]
+    pub validator_account: Pubkey,
+    pub active_balance: u64,
+    pub score: u32,
+    pub last_stake_delta_epoch: u64,
+    pub duplication_flag_bump_seed: u8,
+}
+
+/// item `index` of `list`, read from the list account's data. fails past `list.count` or the
+/// end of the data, including an end a 32-bit `usize` can't reach
+pub fn list_item<T: AnchorDeserialize>(list: &List, account_data: &[u8], index: u32) -> io::Result<T> {
+    if index >= list.count {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("index {} of {} items", index, list.count)));
+    }
+    let item_size = list.item_size as usize;
+    let start = (index as usize).checked_mul(item_size).and_then(|offset| offset.checked_add(LIST_HEADER_LEN));
+    let item = start.and_then(|start| account_data.get(start..start.checked_add(item_size)?));
+    let Some(item) = item else {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("list account ends before item {}", index)));
+    };
+    T::deserialize(&mut &item[..])
+}
+
+/// a whole list account, parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedList<T> {
+    /// the items the account has room for
+    pub capacity: u32,
+    /// the items in use, `items.len()`
+    pub count: u32,
+    pub items: Vec<T>,
+}
+
+/// how many items of `list.item_size` fit in `account_data_len` bytes of list account. a
+/// trailing partial item doesn't count
+pub fn list_capacity(list: &List, account_data_len: usize) -> u32 {
+    match list.item_size {
+        0 => 0,
+        item_size => {
+            let capacity = account_data_len.saturating_sub(LIST_HEADER_LEN) / item_size as usize;
+            capacity.min(u32::MAX as usize) as u32
+        }
+    }
+}
+
+/// every item of `list` in the list account's data, ignoring the padding after the last. fails
+/// when the data can't hold `list.count` items
+pub fn parse_list<T: AnchorDeserialize>(list: &List, account_data: &[u8]) -> io::Result<ParsedList<T>> {
+    let capacity = list_capacity(list, account_data.len());
+    if list.count > capacity {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} items in a list account with room for {}", list.count, capacity),
+        ));
+    }
+    let items = (0..list.count).map(|index| list_item(list, account_data, index)).collect::<io::Result<_>>()?;
+    Ok(ParsedList { capacity, count: list.count, items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items_are_read_at_their_stride() {
+        let records: Vec<StakeRecord> = (0..3)
+            .map(|i| StakeRecord {
+                stake_account: Pubkey::new_unique(),
+                last_update_delegated_lamports: i,
+                ..StakeRecord::default()
+            })
+            .collect();
+        // items padded past their serialized size, as the program allocates them
+        let item_size = 56;
+        let mut data = vec![0u8; LIST_HEADER_LEN];
+        for record in &records {
+            let mut item = record.try_to_vec().unwrap();
+            item.resize(item_size, 0);
+            data.extend(item);
+        }
+        let list = List { count: 3, item_size: item_size as u32, ..List::default() };
+
+        assert_eq!(list_item::<StakeRecord>(&list, &data, 2).unwrap(), records[2]);
+        assert_eq!(list_item::<StakeRecord>(&list, &data, 3).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let truncated = &data[..data.len() - 1];
+        assert_eq!(list_item::<StakeRecord>(&list, truncated, 2).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+    #[test]
+    fn test_capacity_before_and_after_realloc() {
+        let records: Vec<ValidatorRecord> = (0..3)
+            .map(|score| ValidatorRecord { validator_account: Pubkey::new_unique(), score, ..ValidatorRecord::default() })
+            .collect();
+        let item_size = 61;
+        let mut before = vec![0u8; LIST_HEADER_LEN];
+        for record in &records {
+            let mut item = record.try_to_vec().unwrap();
+            item.resize(item_size, 0);
+            before.extend(item);
+        }
+        // room for one more, zeroed
+        before.resize(LIST_HEADER_LEN + 4 * item_size, 0);
+        let list = List { count: 3, item_size: item_size as u32, ..List::default() };
+
+        let parsed = parse_list::<ValidatorRecord>(&list, &before).unwrap();
+        assert_eq!((parsed.capacity, parsed.count), (4, 3));
+        assert_eq!(parsed.items, records);
+
+        // the realloc grew the account, with a partial item's worth of slack at the end
+        let mut after = before.clone();
+        after.resize(LIST_HEADER_LEN + 10 * item_size + 7, 0);
+        let parsed = parse_list::<ValidatorRecord>(&list, &after).unwrap();
+        assert_eq!((parsed.capacity, parsed.count, parsed.items), (10, 3, records));
+
+        let overfull = List { count: 5, ..list.clone() };
+        let err = parse_list::<ValidatorRecord>(&overfull, &before).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(list_capacity(&List::default(), after.len()), 0);
+    }
+
+    #[test]
+    fn test_garbage_lists_fail_cleanly() {
+        let mut account = vec![0u8; LIST_HEADER_LEN];
+        for i in 0..4 {
+            let record = ValidatorRecord { validator_account: Pubkey::new_unique(), score: i, ..Default::default() };
+            let mut item = record.try_to_vec().unwrap();
+            item.resize(61, 0);
+            account.extend(item);
+        }
+        let corpus = [account.clone(), account[..LIST_HEADER_LEN].to_vec()];
+        // descriptors as a broken state could hold them, up to ones no account could fit
+        let lists = [(4, 61), (3, 56), (1, 0), (200, 1), (u32::MAX, 1), (2, u32::MAX), (u32::MAX, u32::MAX)];
+        for data in crate::test_utils::adversarial_inputs(&corpus, 2_000, 300) {
+            for (count, item_size) in lists {
+                let list = List { count, item_size, ..List::default() };
+                if let Ok(parsed) = parse_list::<ValidatorRecord>(&list, &data) {
+                    assert!(parsed.count <= parsed.capacity && parsed.items.len() == count as usize);
+                    assert!(LIST_HEADER_LEN + count as usize * item_size as usize <= data.len());
+                }
+                let _ = parse_list::<StakeRecord>(&list, &data);
+                let _ = list_item::<StakeRecord>(&list, &data, count.saturating_sub(1));
+            }
+        }
+    }
+}
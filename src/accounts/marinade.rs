@@ -1,16 +1,436 @@
 use anchor_lang::prelude::*;
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_sdk::pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
 
-pub fn parse_marinade_state(account_data: &[u8]) -> Result<MarinadeState> {
-    Ok(MarinadeState::try_from_slice(account_data)?)
+use super::byte_diff::state_layout;
+use crate::sol::Sol;
+
+/// deserialize a state account. equivalent to `MarinadeState::try_from_slice` (the whole
+/// buffer must be consumed), but since every field is fixed-size the length is checked once
+/// up front and fields are copied straight out of the slice, which is several times faster
+/// than going through borsh's `io::Read` machinery. errors carry a `StateParseError` saying
+/// where in the layout the data went wrong
+pub fn parse_marinade_state(account_data: &[u8]) -> std::io::Result<MarinadeState> {
+    let len = account_data.len();
+    match len {
+        len if len < STATE_LEN => return Err(StateParseError::truncated(len).into()),
+        len if len > STATE_LEN => {
+            return Err(StateParseError::at(StateParseProblem::TrailingBytes, STATE_LEN, len).into())
+        }
+        _ => {}
+    }
+    let mut reader = StateReader { data: account_data };
+    let state = reader.state();
+    for offset in [PAUSED_OFFSET, WITHDRAW_STAKE_ACCOUNT_ENABLED_OFFSET] {
+        if account_data[offset] > 1 {
+            return Err(StateParseError::at(StateParseProblem::InvalidBool(account_data[offset]), offset, len).into());
+        }
+    }
+    Ok(state)
+}
+
+/// the sections of the layout a `StateParseError` names, each with the offset it ends at
+const STATE_SECTIONS: &[(&str, usize)] = &[
+    ("mint and authorities", 128),
+    ("bump seeds and rent", 138),
+    ("reward fee", 142),
+    ("stake system", 256),
+    ("validator system", 377),
+    ("liq pool", AVAILABLE_RESERVE_BALANCE_OFFSET),
+    ("balances", MINIMAL_LAYOUT_LEN),
+    ("trailing fields", STATE_LEN),
+];
+
+/// what stopped a parse, see `StateParseError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateParseProblem {
+    /// the data ended inside the field at the offset
+    Truncated,
+    /// bytes were left once the layout was read
+    TrailingBytes,
+    /// the bool at the offset is neither 0 nor 1
+    InvalidBool(u8),
+    /// the data doesn't start with `STATE_DISCRIMINATOR`
+    UnknownDiscriminator([u8; 8]),
+}
+
+/// where a parse of the state stopped: the section and field of the layout it had reached, that
+/// field's offset into the account data, the bytes left from there and the length of the data.
+/// the `io::Error`s of `parse_marinade_state` and `parse_marinade_state_with` carry one, see `of`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateParseError {
+    pub problem: StateParseProblem,
+    pub section: &'static str,
+    /// path of the field, as `byte_diff` names them. None for the discriminator and past the layout
+    pub field: Option<&'static str>,
+    pub offset: usize,
+    pub remaining: usize,
+    pub len: usize,
+}
+
+impl StateParseError {
+    /// the one behind `error`, if a parse of the state raised it
+    pub fn of(error: &std::io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    fn at(problem: StateParseProblem, offset: usize, len: usize) -> Self {
+        let field = state_layout().into_iter().find(|span| offset < span.offset + span.len).map(|span| span.field);
+        let section = STATE_SECTIONS.iter().find(|&&(_, end)| offset < end);
+        let section = section.map_or("past the layout", |&(name, _)| name);
+        Self { problem, section, field, offset, remaining: len - offset, len }
+    }
+
+    /// `len` bytes of data end in the first field they don't hold all of
+    fn truncated(len: usize) -> Self {
+        let span = state_layout().into_iter().find(|span| span.offset + span.len > len);
+        let span = span.expect("shorter than the layout");
+        Self::at(StateParseProblem::Truncated, span.offset, len)
+    }
+
+    fn unknown_discriminator(discriminator: [u8; 8], len: usize) -> Self {
+        let problem = StateParseProblem::UnknownDiscriminator(discriminator);
+        Self { problem, section: "discriminator", field: None, offset: 0, remaining: len, len }
+    }
+
+    /// data too short to hold the discriminator
+    fn short_discriminator(len: usize) -> Self {
+        let problem = StateParseProblem::Truncated;
+        Self { problem, section: "discriminator", field: None, offset: 0, remaining: len, len }
+    }
+
+    /// the same place in data read from behind a `prefix` byte discriminator
+    fn behind(self, prefix: usize) -> Self {
+        Self { offset: self.offset + prefix, len: self.len + prefix, ..self }
+    }
+}
+
+impl std::fmt::Display for StateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.problem {
+            StateParseProblem::Truncated => write!(f, "unexpected length of input")?,
+            StateParseProblem::TrailingBytes => write!(f, "not all bytes read")?,
+            StateParseProblem::InvalidBool(value) => write!(f, "invalid bool representation {}", value)?,
+            StateParseProblem::UnknownDiscriminator(discriminator) => {
+                write!(f, "unknown discriminator {:02x?}", discriminator)?
+            }
+        }
+        write!(f, " in {}", self.section)?;
+        if let Some(field) = self.field {
+            write!(f, " ({})", field)?;
+        }
+        write!(f, " at offset {}: {} of {} bytes remaining", self.offset, self.remaining, self.len)
+    }
+}
+
+impl std::error::Error for StateParseError {}
+
+impl From<StateParseError> for std::io::Error {
+    fn from(error: StateParseError) -> Self {
+        let kind = match error.problem {
+            StateParseProblem::Truncated => std::io::ErrorKind::UnexpectedEof,
+            StateParseProblem::TrailingBytes | StateParseProblem::UnknownDiscriminator(_) => {
+                std::io::ErrorKind::InvalidData
+            }
+            StateParseProblem::InvalidBool(_) => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+/// how `parse_marinade_state_with` treats data that isn't exactly a current state account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// exactly `STATE_LEN` bytes behind `STATE_DISCRIMINATOR`, with every invariant holding
+    #[default]
+    Strict,
+    /// trailing bytes, data cut short after the price fields and failed invariants are taken as
+    /// they come, each recorded as a `ParseWarning`. the discriminator is skipped when it's there
+    Lenient,
+}
+
+pub use super::idl::STATE_DISCRIMINATOR;
+
+/// what a lenient parse let through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// bytes past the end of the layout, e.g. space left by a realloc
+    TrailingBytes { len: usize },
+    /// the data ended `missing` bytes short of the layout, e.g. an older one. the fields past
+    /// the end are defaults
+    Truncated { missing: usize },
+    /// a value the program never writes, kept as read
+    Invariant(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedState {
+    pub state: MarinadeState,
+    /// empty for a strict parse
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// parse the data of a state account as the node serves it, `STATE_DISCRIMINATOR` first, in
+/// `mode`. lenient also takes the bare layout, which `parse_marinade_state` reads
+pub fn parse_marinade_state_with(account_data: &[u8], mode: ParseMode) -> std::io::Result<ParsedState> {
+    let invalid = |kind, message: String| Err(std::io::Error::new(kind, message));
+    let data = match account_data.strip_prefix(&STATE_DISCRIMINATOR[..]) {
+        Some(rest) => rest,
+        None if mode == ParseMode::Lenient => account_data,
+        None => {
+            return Err(match account_data.first_chunk::<8>() {
+                Some(discriminator) => StateParseError::unknown_discriminator(*discriminator, account_data.len()),
+                None => StateParseError::short_discriminator(account_data.len()),
+            }
+            .into())
+        }
+    };
+    let prefix = account_data.len() - data.len();
+    let mut warnings = Vec::new();
+    let state = match mode {
+        ParseMode::Strict => parse_marinade_state(data).map_err(|e| match StateParseError::of(&e) {
+            Some(error) if prefix > 0 => error.clone().behind(prefix).into(),
+            _ => e,
+        })?,
+        ParseMode::Lenient if data.len() < MINIMAL_LAYOUT_LEN => {
+            let message = format!("state layout is {} bytes, need at least {}", data.len(), MINIMAL_LAYOUT_LEN);
+            return invalid(std::io::ErrorKind::UnexpectedEof, message);
+        }
+        ParseMode::Lenient => {
+            let mut padded = [0u8; STATE_LEN];
+            let len = data.len().min(STATE_LEN);
+            padded[..len].copy_from_slice(&data[..len]);
+            match data.len() {
+                len if len > STATE_LEN => warnings.push(ParseWarning::TrailingBytes { len: len - STATE_LEN }),
+                len if len < STATE_LEN => warnings.push(ParseWarning::Truncated { missing: STATE_LEN - len }),
+                _ => {}
+            }
+            for offset in [PAUSED_OFFSET, WITHDRAW_STAKE_ACCOUNT_ENABLED_OFFSET] {
+                if padded[offset] > 1 {
+                    warnings.push(ParseWarning::Invariant(format!("Invalid bool representation: {}", padded[offset])));
+                }
+            }
+            StateReader { data: &padded }.state()
+        }
+    };
+    let violations = state.invariant_violations();
+    match (mode, violations.first()) {
+        (ParseMode::Strict, Some(violation)) => invalid(std::io::ErrorKind::InvalidData, violation.clone()),
+        _ => {
+            warnings.extend(violations.into_iter().map(ParseWarning::Invariant));
+            Ok(ParsedState { state, warnings })
+        }
+    }
+}
+
+/// serialized size of `MarinadeState`
+pub const STATE_LEN: usize = 630;
+const PAUSED_OFFSET: usize = 600;
+const WITHDRAW_STAKE_ACCOUNT_ENABLED_OFFSET: usize = 609;
+
+/// cursor over the borsh layout of `MarinadeState`; callers check the length first
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, rest) = self.data.split_first_chunk::<N>().expect("length checked by the caller");
+        self.data = rest;
+        *head
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn bool(&mut self) -> bool {
+        self.u8() != 0
+    }
+
+    fn pubkey(&mut self) -> Pubkey {
+        Pubkey::new_from_array(self.take())
+    }
+
+    fn fee(&mut self) -> Fee {
+        Fee { basis_points: self.u32() }
+    }
+
+    fn fee_cents(&mut self) -> FeeCents {
+        FeeCents { bp_cents: self.u32() }
+    }
+
+    fn list(&mut self) -> List {
+        List {
+            account: self.pubkey(),
+            item_size: self.u32(),
+            count: self.u32(),
+            reserved1: self.pubkey(),
+            reserved2: self.u32(),
+        }
+    }
+
+    fn stake_system(&mut self) -> StakeSystem {
+        StakeSystem {
+            stake_list: self.list(),
+            delayed_unstake_cooling_down: self.u64(),
+            stake_deposit_bump_seed: self.u8(),
+            stake_withdraw_bump_seed: self.u8(),
+            slots_for_stake_delta: self.u64(),
+            last_stake_delta_epoch: self.u64(),
+            min_stake: self.u64(),
+            extra_stake_delta_runs: self.u32(),
+        }
+    }
+
+    fn validator_system(&mut self) -> ValidatorSystem {
+        ValidatorSystem {
+            validator_list: self.list(),
+            manager_authority: self.pubkey(),
+            total_validator_score: self.u32(),
+            total_active_balance: self.u64(),
+            auto_add_validator_enabled: self.u8(),
+        }
+    }
+
+    fn liq_pool(&mut self) -> LiqPool {
+        LiqPool {
+            lp_mint: self.pubkey(),
+            lp_mint_authority_bump_seed: self.u8(),
+            sol_leg_bump_seed: self.u8(),
+            msol_leg_authority_bump_seed: self.u8(),
+            msol_leg: self.pubkey(),
+            lp_liquidity_target: self.u64(),
+            lp_max_fee: self.fee(),
+            lp_min_fee: self.fee(),
+            treasury_cut: self.fee(),
+            lp_supply: self.u64(),
+            lent_from_sol_leg: self.u64(),
+            liquidity_sol_cap: self.u64(),
+        }
+    }
+
+    // struct literal fields evaluate in source order, which is what keeps this in layout order
+    fn state(&mut self) -> MarinadeState {
+        MarinadeState {
+            msol_mint: self.pubkey(),
+            admin_authority: self.pubkey(),
+            operational_sol_account: self.pubkey(),
+            treasury_msol_account: self.pubkey(),
+            reserve_bump_seed: self.u8(),
+            msol_mint_authority_bump_seed: self.u8(),
+            rent_exempt_for_token_acc: self.u64(),
+            reward_fee: self.fee(),
+            stake_system: self.stake_system(),
+            validator_system: self.validator_system(),
+            liq_pool: self.liq_pool(),
+            available_reserve_balance: self.u64(),
+            msol_supply: self.u64(),
+            msol_price: self.u64(),
+            circulating_ticket_count: self.u64(),
+            circulating_ticket_balance: self.u64(),
+            lent_from_reserve: self.u64(),
+            min_deposit: self.u64(),
+            min_withdraw: self.u64(),
+            staking_sol_cap: self.u64(),
+            emergency_cooling_down: self.u64(),
+            pause_authority: self.pubkey(),
+            paused: self.bool(),
+            delayed_unstake_fee: self.fee_cents(),
+            withdraw_stake_account_fee: self.fee_cents(),
+            withdraw_stake_account_enabled: self.bool(),
+            last_stake_move_epoch: self.u64(),
+            stake_moved: self.u64(),
+            max_stake_moved_per_epoch: self.fee(),
+        }
+    }
+}
+
+// byte offsets of the fields the price formula reads, in the borsh layout of `MarinadeState`. an
+// account holds the layout after `STATE_DISCRIMINATOR`
+pub const MSOL_MINT_OFFSET: usize = 0;
+pub const DELAYED_UNSTAKE_COOLING_DOWN_OFFSET: usize = 218;
+pub const TOTAL_ACTIVE_BALANCE_OFFSET: usize = 368;
+pub const AVAILABLE_RESERVE_BALANCE_OFFSET: usize = 488;
+pub const MSOL_SUPPLY_OFFSET: usize = 496;
+pub const CIRCULATING_TICKET_BALANCE_OFFSET: usize = 520;
+pub const EMERGENCY_COOLING_DOWN_OFFSET: usize = 560;
+
+/// bytes of the account needed by `parse_marinade_state_minimal`, the discriminator included
+pub const MINIMAL_STATE_LEN: usize = STATE_DISCRIMINATOR.len() + MINIMAL_LAYOUT_LEN;
+/// bytes of the layout up to the last price field, what a lenient parse needs at least
+const MINIMAL_LAYOUT_LEN: usize = EMERGENCY_COOLING_DOWN_OFFSET + 8;
+
+/// the handful of state fields the mSOL price depends on, and the mint being priced
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct MinimalState {
+    pub msol_mint: Pubkey,
+    pub total_active_balance: u64,
+    pub delayed_unstake_cooling_down: u64,
+    pub emergency_cooling_down: u64,
+    pub available_reserve_balance: u64,
+    pub circulating_ticket_balance: u64,
+    pub msol_supply: u64,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Default, Debug, PartialEq)]
+fn read_u64(account_data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&account_data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// read only the price fields at their fixed offsets, skipping full deserialization. takes the
+/// account data as the node serves it, `STATE_DISCRIMINATOR` first, the offsets counting from
+/// after it
+pub fn parse_marinade_state_minimal(account_data: &[u8]) -> std::io::Result<MinimalState> {
+    if account_data.len() < MINIMAL_STATE_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("state account is {} bytes, need at least {}", account_data.len(), MINIMAL_STATE_LEN),
+        ));
+    }
+    let (discriminator, layout) = account_data.split_at(STATE_DISCRIMINATOR.len());
+    if discriminator != STATE_DISCRIMINATOR {
+        let discriminator = discriminator.try_into().unwrap();
+        return Err(StateParseError::unknown_discriminator(discriminator, account_data.len()).into());
+    }
+    let mut msol_mint = [0u8; 32];
+    msol_mint.copy_from_slice(&layout[MSOL_MINT_OFFSET..MSOL_MINT_OFFSET + 32]);
+    Ok(MinimalState {
+        msol_mint: Pubkey::new_from_array(msol_mint),
+        total_active_balance: read_u64(layout, TOTAL_ACTIVE_BALANCE_OFFSET),
+        delayed_unstake_cooling_down: read_u64(layout, DELAYED_UNSTAKE_COOLING_DOWN_OFFSET),
+        emergency_cooling_down: read_u64(layout, EMERGENCY_COOLING_DOWN_OFFSET),
+        available_reserve_balance: read_u64(layout, AVAILABLE_RESERVE_BALANCE_OFFSET),
+        circulating_ticket_balance: read_u64(layout, CIRCULATING_TICKET_BALANCE_OFFSET),
+        msol_supply: read_u64(layout, MSOL_SUPPLY_OFFSET),
+    })
+}
+
+/// denominator of the fixed-point `msol_price` field
+pub const PRICE_DENOMINATOR: u64 = 0x1_0000_0000;
+
+/// base units in one SOL / one mSOL
+pub const LAMPORTS_PER_MSOL: u64 = 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct MarinadeState {
+    #[serde(with = "crate::serde_pubkey")]
     pub msol_mint: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
     pub admin_authority: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
     pub operational_sol_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
     pub treasury_msol_account: Pubkey,
     pub reserve_bump_seed: u8,
     pub msol_mint_authority_bump_seed: u8,
@@ -29,6 +449,7 @@ pub struct MarinadeState {
     pub min_withdraw: u64,
     pub staking_sol_cap: u64,
     pub emergency_cooling_down: u64,
+    #[serde(with = "crate::serde_pubkey")]
     pub pause_authority: Pubkey,
     pub paused: bool,
     pub delayed_unstake_fee: FeeCents,
@@ -39,30 +460,410 @@ pub struct MarinadeState {
     pub max_stake_moved_per_epoch: Fee,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+impl MarinadeState {
+    /// the fee and bound invariants the program enforces on every update, as messages
+    fn invariant_violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        let liq_pool = &self.liq_pool;
+        let fees = [
+            ("reward_fee", &self.reward_fee),
+            ("liq_pool.lp_max_fee", &liq_pool.lp_max_fee),
+            ("liq_pool.lp_min_fee", &liq_pool.lp_min_fee),
+            ("liq_pool.treasury_cut", &liq_pool.treasury_cut),
+            ("max_stake_moved_per_epoch", &self.max_stake_moved_per_epoch),
+        ];
+        for (field, fee) in fees {
+            if fee.basis_points > 10_000 {
+                violations.push(format!("{} is {} bp, over 100%", field, fee.basis_points));
+            }
+        }
+        for (field, fee) in [
+            ("delayed_unstake_fee", &self.delayed_unstake_fee),
+            ("withdraw_stake_account_fee", &self.withdraw_stake_account_fee),
+        ] {
+            if fee.bp_cents > 1_000_000 {
+                violations.push(format!("{} is {}, over 100%", field, fee));
+            }
+        }
+        let (min_fee, max_fee) = (&liq_pool.lp_min_fee, &liq_pool.lp_max_fee);
+        if min_fee.basis_points > max_fee.basis_points {
+            violations.push(format!("liq_pool.lp_min_fee {} is over lp_max_fee {}", min_fee, max_fee));
+        }
+        violations
+    }
+}
+
+impl MinimalState {
+    /// stake being deactivated, both for delayed unstakes and emergency unstakes
+    pub fn total_cooling_down(&self) -> u64 {
+        self.delayed_unstake_cooling_down.saturating_add(self.emergency_cooling_down)
+    }
+
+    /// saturating at `u64::MAX`, see `checked_total_lamports_under_control`
+    pub fn total_lamports_under_control(&self) -> u64 {
+        self.checked_total_lamports_under_control().unwrap_or(u64::MAX)
+    }
+
+    /// None when the balances add up past a u64, which no real state does: the account data is
+    /// corrupt or crafted
+    pub fn checked_total_lamports_under_control(&self) -> Option<u64> {
+        let balances = [self.delayed_unstake_cooling_down, self.emergency_cooling_down, self.available_reserve_balance];
+        balances.into_iter().try_fold(self.total_active_balance, u64::checked_add)
+    }
+
+    /// the SOL backing mSOL: everything under control minus what is already owed to ticket holders
+    pub fn total_virtual_staked_lamports(&self) -> u64 {
+        self.total_lamports_under_control().saturating_sub(self.circulating_ticket_balance)
+    }
+
+    /// `total_virtual_staked_lamports`, None when the balances overflow
+    pub fn checked_total_virtual_staked_lamports(&self) -> Option<u64> {
+        Some(self.checked_total_lamports_under_control()?.saturating_sub(self.circulating_ticket_balance))
+    }
+
+    /// lamports one whole mSOL is worth, using the same proportional math as the program
+    /// (1:1 while there is no supply). saturates at `u64::MAX`, see `checked_msol_price_lamports`
+    pub fn msol_price_lamports(&self) -> u64 {
+        if self.msol_supply == 0 {
+            return LAMPORTS_PER_MSOL;
+        }
+        saturate(LAMPORTS_PER_MSOL as u128 * self.total_virtual_staked_lamports() as u128 / self.msol_supply as u128)
+    }
+
+    /// `msol_price_lamports`, None when the balances overflow or the price doesn't fit a u64
+    pub fn checked_msol_price_lamports(&self) -> Option<u64> {
+        let total_virtual_staked_lamports = self.checked_total_virtual_staked_lamports()?;
+        if self.msol_supply == 0 {
+            return Some(LAMPORTS_PER_MSOL);
+        }
+        u64::try_from(LAMPORTS_PER_MSOL as u128 * total_virtual_staked_lamports as u128 / self.msol_supply as u128).ok()
+    }
+
+    pub fn sol_to_msol(&self, lamports: u64) -> u64 {
+        sol_to_msol(lamports, self.total_virtual_staked_lamports(), self.msol_supply)
+    }
+
+    pub fn msol_to_sol(&self, msol: u64) -> u64 {
+        msol_to_sol(msol, self.total_virtual_staked_lamports(), self.msol_supply)
+    }
+
+    /// lamports the next stake-delta crank delegates (positive) or deactivates (negative),
+    /// the program's `stake_delta` of the available reserve. the reserve, less what emergency
+    /// unstakes will still owe it, plus the delayed unstakes cooling down has to cover the
+    /// tickets; a shortfall the emergency unstakes will make up once they land is left alone
+    pub fn pending_stake_delta(&self) -> i128 {
+        let raw = self.available_reserve_balance.saturating_sub(self.emergency_cooling_down) as i128
+            + self.delayed_unstake_cooling_down as i128
+            - self.circulating_ticket_balance as i128;
+        if raw >= 0 {
+            return raw;
+        }
+        (raw + self.emergency_cooling_down as i128).min(0)
+    }
+}
+
+/// mSOL base units `lamports` buy at the state's price, rounded down like the program's
+/// `calc_msol_from_lamports` (1:1 while there is no supply). saturates at `u64::MAX`, where the
+/// supply dwarfs the stake
+pub fn sol_to_msol(lamports: u64, total_virtual_staked_lamports: u64, msol_supply: u64) -> u64 {
+    if msol_supply == 0 || total_virtual_staked_lamports == 0 {
+        return lamports;
+    }
+    saturate(lamports as u128 * msol_supply as u128 / total_virtual_staked_lamports as u128)
+}
+
+/// lamports `msol` base units are worth at the state's price, rounded down like the program's
+/// `calc_lamports_from_msol_amount` (1:1 while there is no supply). saturates at `u64::MAX`,
+/// where the stake dwarfs the supply
+pub fn msol_to_sol(msol: u64, total_virtual_staked_lamports: u64, msol_supply: u64) -> u64 {
+    if msol_supply == 0 {
+        return msol;
+    }
+    saturate(msol as u128 * total_virtual_staked_lamports as u128 / msol_supply as u128)
+}
+
+fn saturate(value: u128) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
+
+/// a crank's effect on the price, see `project_price_after_rewards`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RewardProjection {
+    pub gross_reward_lamports: u64,
+    /// `reward_fee` of the rewards, paid to the treasury
+    pub protocol_fee_lamports: u64,
+    /// the fee in mSOL, minted at the price before the rewards land
+    pub treasury_msol_minted: u64,
+    pub price_lamports_before: u64,
+    pub price_lamports_after: u64,
+    /// what the price would have been had no fee been minted
+    pub price_lamports_without_fee: u64,
+}
+
+/// project the price after a crank extracts `gross_reward_lamports` of staking rewards. like
+/// `update_active`, the rewards add to the active balance and `reward_fee` of them is minted to
+/// the treasury as mSOL at the pre-reward price, which dilutes the increase
+pub fn project_price_after_rewards(state: &MarinadeState, gross_reward_lamports: u64) -> RewardProjection {
+    let before = state.minimal();
+    let protocol_fee_lamports = state.reward_fee.apply(gross_reward_lamports);
+    let treasury_msol_minted = before.sol_to_msol(protocol_fee_lamports);
+    let total_active_balance = before.total_active_balance.saturating_add(gross_reward_lamports);
+    let rewarded = MinimalState { total_active_balance, ..before };
+    let after = MinimalState { msol_supply: before.msol_supply.saturating_add(treasury_msol_minted), ..rewarded };
+    RewardProjection {
+        gross_reward_lamports,
+        protocol_fee_lamports,
+        treasury_msol_minted,
+        price_lamports_before: before.msol_price_lamports(),
+        price_lamports_after: after.msol_price_lamports(),
+        price_lamports_without_fee: rewarded.msol_price_lamports(),
+    }
+}
+
+impl From<&MarinadeState> for MinimalState {
+    fn from(state: &MarinadeState) -> Self {
+        Self {
+            msol_mint: state.msol_mint,
+            total_active_balance: state.validator_system.total_active_balance,
+            delayed_unstake_cooling_down: state.stake_system.delayed_unstake_cooling_down,
+            emergency_cooling_down: state.emergency_cooling_down,
+            available_reserve_balance: state.available_reserve_balance,
+            circulating_ticket_balance: state.circulating_ticket_balance,
+            msol_supply: state.msol_supply,
+        }
+    }
+}
+
+impl MarinadeState {
+    pub fn minimal(&self) -> MinimalState {
+        MinimalState::from(self)
+    }
+
+    pub fn total_cooling_down(&self) -> u64 {
+        self.minimal().total_cooling_down()
+    }
+
+    pub fn total_lamports_under_control(&self) -> u64 {
+        self.minimal().total_lamports_under_control()
+    }
+
+    pub fn total_virtual_staked_lamports(&self) -> u64 {
+        self.minimal().total_virtual_staked_lamports()
+    }
+
+    pub fn msol_price_lamports(&self) -> u64 {
+        self.minimal().msol_price_lamports()
+    }
+
+    pub fn pending_stake_delta(&self) -> i128 {
+        self.minimal().pending_stake_delta()
+    }
+
+    pub fn operational(&self) -> OperationalConfig {
+        OperationalConfig {
+            operational_sol_account: self.operational_sol_account,
+            treasury_msol_account: self.treasury_msol_account,
+            admin_authority: self.admin_authority,
+            pause_authority: self.pause_authority,
+            validator_manager_authority: self.validator_system.manager_authority,
+            rent_exempt_for_token_acc: self.rent_exempt_for_token_acc,
+        }
+    }
+
+    /// the epoch the stake-delta crank last ran in, see `crank::crank_window_status`
+    pub fn last_stake_delta_epoch(&self) -> u64 {
+        self.stake_system.last_stake_delta_epoch
+    }
+
+    /// how many slots before an epoch's end the stake-delta crank may run
+    pub fn slots_for_stake_delta(&self) -> u64 {
+        self.stake_system.slots_for_stake_delta
+    }
+
+    /// the smallest deposit the program accepts
+    pub fn min_deposit_lamports(&self) -> u64 {
+        self.min_deposit
+    }
+
+    /// the smallest unstake the program accepts, by the lamports the mSOL is worth
+    pub fn min_withdraw_lamports(&self) -> u64 {
+        self.min_withdraw
+    }
+
+    /// check a deposit of `lamports` against the minimum and, as if all of it went to the
+    /// reserve, the staking cap. a deposit the liq pool fills can get past the cap, see
+    /// `deposit::simulate_deposit`
+    pub fn validate_deposit(&self, lamports: u64) -> std::result::Result<(), AmountError> {
+        self.check_min_deposit(lamports)?;
+        self.check_staking_cap(lamports)
+    }
+
+    /// check an unstake of `msol` against the minimum withdraw. `BelowMinimum` is in lamports,
+    /// what `msol` is worth at the state's price
+    pub fn validate_unstake(&self, msol: u64) -> std::result::Result<(), AmountError> {
+        let lamports = self.minimal().msol_to_sol(msol);
+        match lamports < self.min_withdraw {
+            true => Err(AmountError::BelowMinimum { min: self.min_withdraw, got: lamports }),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_min_deposit(&self, lamports: u64) -> std::result::Result<(), AmountError> {
+        match lamports < self.min_deposit {
+            true => Err(AmountError::BelowMinimum { min: self.min_deposit, got: lamports }),
+            false => Ok(()),
+        }
+    }
+
+    /// whether staking `lamports` more keeps the lamports under control within `staking_sol_cap`
+    pub(crate) fn check_staking_cap(&self, lamports: u64) -> std::result::Result<(), AmountError> {
+        let current = self.total_lamports_under_control();
+        match lamports > crate::deposit::deposit_headroom(self) {
+            true => Err(AmountError::ExceedsCap { cap: self.staking_sol_cap, current, got: lamports }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// SOL views of the lamport fields, which stay the source of truth
+impl MarinadeState {
+    /// stake delegated to validators, `validator_system.total_active_balance`
+    pub fn total_active_sol(&self) -> Sol {
+        Sol(self.validator_system.total_active_balance)
+    }
+
+    /// `available_reserve_balance`
+    pub fn reserve_sol(&self) -> Sol {
+        Sol(self.available_reserve_balance)
+    }
+
+    /// everything under the program's control, `total_lamports_under_control`
+    pub fn tvl_sol(&self) -> Sol {
+        Sol(self.total_lamports_under_control())
+    }
+
+    /// the headline numbers together
+    pub fn summary(&self) -> StateSummary {
+        StateSummary {
+            tvl_sol: self.tvl_sol(),
+            total_active_sol: self.total_active_sol(),
+            reserve_sol: self.reserve_sol(),
+            cooling_down_sol: Sol(self.total_cooling_down()),
+            ticket_sol: Sol(self.circulating_ticket_balance),
+            backing_sol: Sol(self.total_virtual_staked_lamports()),
+            msol_supply: self.msol_supply,
+            msol_price_sol: Sol(self.msol_price_lamports()),
+            paused: self.paused,
+            staking_sol_cap: Sol(self.staking_sol_cap),
+            liquidity_sol_cap: Sol(self.liq_pool.liquidity_sol_cap),
+        }
+    }
+}
+
+/// see `MarinadeState::summary`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateSummary {
+    pub tvl_sol: Sol,
+    pub total_active_sol: Sol,
+    pub reserve_sol: Sol,
+    /// delayed and emergency unstakes deactivating
+    pub cooling_down_sol: Sol,
+    /// owed to unclaimed tickets
+    pub ticket_sol: Sol,
+    /// what backs the mSOL supply, `total_virtual_staked_lamports`
+    pub backing_sol: Sol,
+    /// in mSOL base units
+    pub msol_supply: u64,
+    pub msol_price_sol: Sol,
+    pub paused: bool,
+    /// the most the lamports under the program's control can reach through deposits
+    pub staking_sol_cap: Sol,
+    /// the most the liq pool's sol leg can hold through `add_liquidity`
+    pub liquidity_sol_cap: Sol,
+}
+
+/// the accounts and authorities running the program day to day, out of a `MarinadeState`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OperationalConfig {
+    /// funds the program's housekeeping, and is refunded the rent of the stake accounts it closes
+    #[serde(with = "crate::serde_pubkey")]
+    pub operational_sol_account: Pubkey,
+    /// where the reward fee is minted
+    #[serde(with = "crate::serde_pubkey")]
+    pub treasury_msol_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub admin_authority: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub pause_authority: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub validator_manager_authority: Pubkey,
+    /// lamports the program keeps in token accounts it creates
+    pub rent_exempt_for_token_acc: u64,
+}
+
+/// an amount outside what the program accepts, see `MarinadeState::validate_deposit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountError {
+    BelowMinimum { min: u64, got: u64 },
+    /// `got` more on top of `current` would take the lamports under control past `cap`
+    ExceedsCap { cap: u64, current: u64, got: u64 },
+}
+
+impl std::fmt::Display for AmountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BelowMinimum { min, got } => write!(f, "{} lamports is below the minimum of {}", got, min),
+            Self::ExceedsCap { cap, current, got } => {
+                write!(f, "{} more lamports would exceed the cap of {} ({} under control)", got, cap, current)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct Fee {
     pub basis_points: u32,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+impl Fee {
+    /// the fee's share of `amount`, rounded down like the program's `Fee::apply`
+    pub fn apply(&self, amount: u64) -> u64 {
+        (amount as u128 * self.basis_points as u128 / 10_000) as u64
+    }
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct FeeCents {
     pub bp_cents: u32,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+impl FeeCents {
+    /// the fee's share of `amount`, rounded down like the program's `FeeCents::apply`
+    pub fn apply(&self, amount: u64) -> u64 {
+        (amount as u128 * self.bp_cents as u128 / 1_000_000) as u64
+    }
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct List {
+    #[serde(with = "crate::serde_pubkey")]
     pub account: Pubkey,
     pub item_size: u32,
     pub count: u32,
+    #[serde(with = "crate::serde_pubkey")]
     pub reserved1: Pubkey,
     pub reserved2: u32,
 }
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct LiqPool {
+    #[serde(with = "crate::serde_pubkey")]
     pub lp_mint: Pubkey,
     pub lp_mint_authority_bump_seed: u8,
     pub sol_leg_bump_seed: u8,
     pub msol_leg_authority_bump_seed: u8,
+    #[serde(with = "crate::serde_pubkey")]
     pub msol_leg: Pubkey,
     pub lp_liquidity_target: u64,
     pub lp_max_fee: Fee,
@@ -73,7 +874,20 @@ pub struct LiqPool {
     pub liquidity_sol_cap: u64,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+impl LiqPool {
+    /// the liquid unstake fee with `lamports` left in the sol leg: `lp_min_fee` at or above
+    /// `lp_liquidity_target`, rising linearly to `lp_max_fee` as the leg empties
+    pub fn linear_fee(&self, lamports: u64) -> Fee {
+        if lamports >= self.lp_liquidity_target {
+            return self.lp_min_fee.clone();
+        }
+        let range = self.lp_max_fee.basis_points.saturating_sub(self.lp_min_fee.basis_points) as u128;
+        let discount = range * lamports as u128 / self.lp_liquidity_target as u128;
+        Fee { basis_points: self.lp_max_fee.basis_points - discount as u32 }
+    }
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct StakeSystem {
     pub stake_list: List,
     pub delayed_unstake_cooling_down: u64,
@@ -85,11 +899,337 @@ pub struct StakeSystem {
     pub extra_stake_delta_runs: u32,
 }
 
-#[derive(AnchorDeserialize, AnchorSerialize, Clone, Default, Debug, PartialEq)]
+#[derive(AnchorDeserialize, AnchorSerialize, Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
 pub struct ValidatorSystem {
     pub validator_list: List,
+    #[serde(with = "crate::serde_pubkey")]
     pub manager_authority: Pubkey,
     pub total_validator_score: u32,
     pub total_active_balance: u64,
     pub auto_add_validator_enabled: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{fixture_states, state_account_data, state_data};
+
+    #[test]
+    fn test_minimal_parse_matches_full_parse() {
+        for state in fixture_states() {
+            let full = parse_marinade_state(&state_data(&state)).unwrap();
+            let minimal = parse_marinade_state_minimal(&state_account_data(&state)).unwrap();
+            assert_eq!(minimal, full.minimal());
+            assert_eq!(minimal.msol_price_lamports(), full.msol_price_lamports());
+        }
+    }
+
+    #[test]
+    fn test_reward_fee_dampens_the_projected_price() {
+        let before = crate::test_utils::sample_state();
+        // the same state after a crank extracting 10_000 SOL at its 600 bp fee
+        let mut after = before.clone();
+        after.validator_system.total_active_balance = 7_010_000_000_000_000;
+        after.msol_supply = 6_000_499_930_565_199;
+
+        let projection = project_price_after_rewards(&before, 10_000_000_000_000);
+        assert_eq!(projection.protocol_fee_lamports, 600_000_000_000);
+        assert_eq!(projection.treasury_msol_minted, after.msol_supply - before.msol_supply);
+        assert_eq!(projection.price_lamports_before, 1_200_166_666);
+        assert_eq!(projection.price_lamports_after, after.msol_price_lamports());
+        assert_eq!(projection.price_lamports_after, 1_201_733_202);
+        assert_eq!(projection.price_lamports_without_fee, 1_201_833_333);
+    }
+
+    #[test]
+    fn test_pending_stake_delta() {
+        // the sample state: 400k SOL reserve and 1k SOL cooling down against 200k SOL of tickets
+        let mut state = crate::test_utils::sample_state().minimal();
+        assert_eq!(state.pending_stake_delta(), 201_000_000_000_000);
+
+        // tickets beyond the reserve deactivate the difference
+        state.circulating_ticket_balance = 500_000_000_000_000;
+        assert_eq!(state.pending_stake_delta(), -99_000_000_000_000);
+        // emergency unstakes come back to the reserve, they don't change the shortfall
+        state.emergency_cooling_down = 50_000_000_000_000;
+        assert_eq!(state.pending_stake_delta(), -99_000_000_000_000);
+        // nor stake what's still on its way back
+        state.circulating_ticket_balance = 300_000_000_000_000;
+        state.emergency_cooling_down = 200_000_000_000_000;
+        assert_eq!(state.pending_stake_delta(), 0);
+    }
+
+    #[test]
+    fn test_amount_thresholds() {
+        let mut state = crate::test_utils::sample_state();
+        state.min_deposit = 1_000;
+        state.staking_sol_cap = state.total_lamports_under_control() + 5_000;
+        let current = state.total_lamports_under_control();
+
+        assert_eq!(state.min_deposit_lamports(), 1_000);
+        assert_eq!(state.validate_deposit(999), Err(AmountError::BelowMinimum { min: 1_000, got: 999 }));
+        assert_eq!(state.validate_deposit(1_000), Ok(()));
+        assert_eq!(state.validate_deposit(5_000), Ok(()));
+        assert_eq!(
+            state.validate_deposit(5_001),
+            Err(AmountError::ExceedsCap { cap: state.staking_sol_cap, current, got: 5_001 })
+        );
+        assert!(matches!(state.validate_deposit(u64::MAX), Err(AmountError::ExceedsCap { .. })));
+
+        // 1_000 mSOL base units are worth 1_200 lamports at the sample price
+        state.min_withdraw = 1_200;
+        assert_eq!(state.min_withdraw_lamports(), 1_200);
+        assert_eq!(state.validate_unstake(999), Err(AmountError::BelowMinimum { min: 1_200, got: 1_198 }));
+        assert_eq!(state.validate_unstake(1_000), Ok(()));
+        assert_eq!(state.validate_unstake(1_001), Ok(()));
+    }
+
+    #[test]
+    fn test_state_len_and_bool_offsets() {
+        let state = MarinadeState { paused: true, withdraw_stake_account_enabled: true, ..MarinadeState::default() };
+        let data = state_data(&state);
+        assert_eq!(data.len(), STATE_LEN);
+        assert_eq!(data[PAUSED_OFFSET], 1);
+        assert_eq!(data[WITHDRAW_STAKE_ACCOUNT_ENABLED_OFFSET], 1);
+    }
+
+    #[test]
+    fn test_parse_matches_borsh() {
+        for state in fixture_states() {
+            let data = state_data(&state);
+            assert_eq!(parse_marinade_state(&data).unwrap(), MarinadeState::try_from_slice(&data).unwrap());
+            assert_eq!(parse_marinade_state(&data).unwrap(), state);
+
+            // both reject truncated input, trailing bytes and invalid bools
+            let mut trailing = data.clone();
+            trailing.push(0);
+            let mut bad_bool = data.clone();
+            bad_bool[PAUSED_OFFSET] = 2;
+            for bad in [&data[..data.len() - 1], &trailing[..], &bad_bool[..]] {
+                assert!(parse_marinade_state(bad).is_err());
+                assert!(MarinadeState::try_from_slice(bad).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_strict_and_lenient_parse() {
+        let state = crate::test_utils::sample_state();
+        let data = state_data(&state);
+        let parse = |data: &[u8], mode| parse_marinade_state_with(data, mode);
+        let lenient = |data: &[u8]| parse(data, ParseMode::Lenient).unwrap();
+        let clean = ParsedState { state: state.clone(), warnings: vec![] };
+
+        let with_discriminator = [&STATE_DISCRIMINATOR[..], &data].concat();
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            assert_eq!(parse(&with_discriminator, mode).unwrap(), clean);
+        }
+        // only lenient takes the bare layout
+        let err = parse(&data, ParseMode::Strict).unwrap_err();
+        assert!(matches!(StateParseError::of(&err).unwrap().problem, StateParseProblem::UnknownDiscriminator(_)));
+        assert_eq!(lenient(&data), clean);
+        let mut wrong_discriminator = with_discriminator.clone();
+        wrong_discriminator[0] ^= 1;
+        let err = parse(&wrong_discriminator, ParseMode::Strict).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = parse(&with_discriminator[..5], ParseMode::Strict).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(StateParseError::of(&err).unwrap().section, "discriminator");
+
+        // space left by a realloc
+        let reallocated = [&with_discriminator[..], &[0u8; 100][..]].concat();
+        assert!(parse(&reallocated, ParseMode::Strict).is_err());
+        let parsed = lenient(&reallocated);
+        assert_eq!((parsed.state, parsed.warnings), (state.clone(), vec![ParseWarning::TrailingBytes { len: 100 }]));
+
+        // an older, shorter layout: the price fields are there, the rest defaults
+        let older = &data[..MINIMAL_LAYOUT_LEN];
+        assert!(parse(&with_discriminator[..8 + MINIMAL_LAYOUT_LEN], ParseMode::Strict).is_err());
+        let parsed = lenient(older);
+        assert_eq!(parsed.state.minimal(), state.minimal());
+        assert_eq!((parsed.state.pause_authority, parsed.state.paused), (Pubkey::default(), false));
+        assert_eq!(parsed.warnings, vec![ParseWarning::Truncated { missing: STATE_LEN - MINIMAL_LAYOUT_LEN }]);
+        assert!(parse(&data[..MINIMAL_LAYOUT_LEN - 1], ParseMode::Lenient).is_err());
+
+        // values the program never writes
+        let broken = MarinadeState { reward_fee: Fee { basis_points: 20_000 }, ..state.clone() };
+        let mut broken = state_account_data(&broken);
+        let paused = STATE_DISCRIMINATOR.len() + PAUSED_OFFSET;
+        broken[paused] = 2;
+        assert!(parse(&broken, ParseMode::Strict).is_err());
+        broken[paused] = 1;
+        let err = parse(&broken, ParseMode::Strict).unwrap_err();
+        assert_eq!(err.to_string(), "reward_fee is 20000 bp, over 100%");
+        broken[paused] = 2;
+        let parsed = lenient(&broken);
+        assert!(parsed.state.paused);
+        assert_eq!(
+            parsed.warnings,
+            vec![
+                ParseWarning::Invariant("Invalid bool representation: 2".to_string()),
+                ParseWarning::Invariant("reward_fee is 20000 bp, over 100%".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_parse_says_where() {
+        let data = state_data(&crate::test_utils::sample_state());
+        let fails = |data: &[u8]| StateParseError::of(&parse_marinade_state(data).unwrap_err()).unwrap().clone();
+        let cut = [
+            (0, "mint and authorities", "msol_mint", 0),
+            (129, "bump seeds and rent", "msol_mint_authority_bump_seed", 129),
+            (140, "reward fee", "reward_fee", 138),
+            (200, "stake system", "stake_system.stake_list.reserved1", 182),
+            (300, "validator system", "validator_system.validator_list.reserved1", 296),
+            (470, "liq pool", "liq_pool.lp_supply", 464),
+            (500, "balances", "msol_supply", 496),
+            (629, "trailing fields", "max_stake_moved_per_epoch", 626),
+        ];
+        for (len, section, field, offset) in cut {
+            let error = fails(&data[..len]);
+            let expected = (StateParseProblem::Truncated, section, Some(field), offset, len - offset, len);
+            assert_eq!((error.problem, error.section, error.field, error.offset, error.remaining, error.len), expected);
+        }
+        let error = parse_marinade_state(&data[..470]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            error.to_string(),
+            "unexpected length of input in liq pool (liq_pool.lp_supply) at offset 464: 6 of 470 bytes remaining"
+        );
+
+        let longer = [&data[..], &[0; 10]].concat();
+        let error = fails(&longer);
+        let trailing = (StateParseProblem::TrailingBytes, "past the layout", None, STATE_LEN, 10);
+        assert_eq!((error.problem, error.section, error.field, error.offset, error.remaining), trailing);
+
+        // behind a discriminator the offsets are the account's
+        let mut prefixed = [&STATE_DISCRIMINATOR[..], &data].concat();
+        prefixed[8 + PAUSED_OFFSET] = 2;
+        let error = parse_marinade_state_with(&prefixed, ParseMode::Strict).unwrap_err();
+        let error = StateParseError::of(&error).unwrap();
+        assert_eq!((error.problem, error.field), (StateParseProblem::InvalidBool(2), Some("paused")));
+        assert_eq!((error.offset, error.remaining, error.len), (8 + PAUSED_OFFSET, 30, 8 + STATE_LEN));
+        let error = parse_marinade_state_with(&prefixed[..8 + 300], ParseMode::Strict).unwrap_err();
+        let error = StateParseError::of(&error).unwrap();
+        assert_eq!((error.section, error.offset, error.len), ("validator system", 8 + 296, 8 + 300));
+        prefixed[0] ^= 1;
+        let error = parse_marinade_state_with(&prefixed, ParseMode::Strict).unwrap_err();
+        assert_eq!(StateParseError::of(&error).unwrap().section, "discriminator");
+    }
+
+    /// every derived number of `state`, checked against its checked form where there is one
+    fn price_math(state: &MinimalState) {
+        let checked = state.checked_msol_price_lamports();
+        assert!(checked.is_none() || checked == Some(state.msol_price_lamports()));
+        let total = state.checked_total_virtual_staked_lamports();
+        assert!(total.is_none() || total == Some(state.total_virtual_staked_lamports()));
+        let _ = (state.total_cooling_down(), state.pending_stake_delta());
+        for amount in [0, 1, LAMPORTS_PER_MSOL, u64::MAX] {
+            let _ = (state.sol_to_msol(amount), state.msol_to_sol(amount));
+        }
+    }
+
+    #[test]
+    fn test_garbage_state_data() {
+        let mut corpus: Vec<Vec<u8>> = fixture_states().iter().map(state_data).collect();
+        // balances at the top of the range, for the mutations to push past it
+        let mut extreme = crate::test_utils::sample_state();
+        extreme.validator_system.total_active_balance = u64::MAX;
+        (extreme.available_reserve_balance, extreme.msol_supply) = (1, 1);
+        corpus.push(state_data(&extreme));
+        corpus.extend([0, 3].map(|i| [&STATE_DISCRIMINATOR[..], &corpus[i]].concat()));
+        for data in crate::test_utils::adversarial_inputs(&corpus, 5_000, STATE_LEN + 16) {
+            match parse_marinade_state(&data) {
+                // the layout has no slack, so anything it parses serializes back as it was
+                Ok(state) => assert_eq!(state_data(&state), data),
+                Err(e) => {
+                    let error = StateParseError::of(&e).unwrap();
+                    assert_eq!((error.offset + error.remaining, error.len), (data.len(), data.len()));
+                }
+            }
+            let strict = parse_marinade_state_with(&data, ParseMode::Strict);
+            assert!(strict.is_err() || data.starts_with(&STATE_DISCRIMINATOR));
+            let lenient = parse_marinade_state_with(&data, ParseMode::Lenient);
+            let minimal = parse_marinade_state_minimal(&data);
+            let minimal_ok = data.len() >= MINIMAL_STATE_LEN && data.starts_with(&STATE_DISCRIMINATOR);
+            assert_eq!(minimal.is_ok(), minimal_ok);
+            // whatever parses, the price math runs on without panicking
+            let parsed = [strict, lenient].into_iter().flatten().map(|parsed| parsed.state);
+            for state in parsed.map(|state| state.minimal()).chain(minimal) {
+                price_math(&state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimal_parse_rejects_short_data() {
+        let state = &fixture_states()[0];
+        let data = state_account_data(state);
+        assert!(parse_marinade_state_minimal(&data[..MINIMAL_STATE_LEN - 1]).is_err());
+        assert_eq!(parse_marinade_state_minimal(&data[..MINIMAL_STATE_LEN]).unwrap(), state.minimal());
+        // the bare layout reads wrong at every offset, so it's refused rather than misread
+        let bare = state_data(state);
+        let error = parse_marinade_state_minimal(&bare).unwrap_err();
+        let problem = StateParseProblem::UnknownDiscriminator(bare[..8].try_into().unwrap());
+        assert_eq!(StateParseError::of(&error).unwrap().problem, problem);
+        let mut wrong = data.clone();
+        wrong[0] ^= 1;
+        assert_eq!(parse_marinade_state_minimal(&wrong).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sol_views_and_summary() {
+        let state = crate::test_utils::sample_state();
+        assert_eq!(state.total_active_sol().to_string(), "7000000.000000000");
+        assert_eq!(state.reserve_sol(), Sol(state.available_reserve_balance));
+        assert_eq!(state.tvl_sol().to_string(), "7401000.000000000");
+
+        let summary = state.summary();
+        assert_eq!((summary.cooling_down_sol.whole(), summary.ticket_sol.whole()), (1_000, 200_000));
+        assert_eq!(summary.backing_sol.to_string(), "7201000.000000000");
+        assert_eq!(summary.msol_price_sol.to_string(), "1.200166666");
+        assert_eq!((summary.msol_supply, summary.paused), (state.msol_supply, false));
+        let json = serde_json::to_value(summary).unwrap();
+        assert_eq!(json["tvl_sol"], "7401000.000000000");
+        assert_eq!(json["staking_sol_cap"], Sol(u64::MAX).to_string());
+        assert_eq!(serde_json::from_value::<StateSummary>(json).unwrap(), summary);
+    }
+
+    #[test]
+    fn test_sol_msol_conversions() {
+        // 7.201M SOL behind 6M mSOL
+        let state = crate::test_utils::sample_state().minimal();
+        assert_eq!(state.sol_to_msol(7_201), 6_000);
+        assert_eq!(state.msol_to_sol(6_000), 7_201);
+        // rounded down both ways
+        assert_eq!(state.sol_to_msol(1), 0);
+        assert_eq!(state.msol_to_sol(1), 1);
+        assert_eq!(state.msol_to_sol(LAMPORTS_PER_MSOL), state.msol_price_lamports());
+
+        let empty = MinimalState::default();
+        assert_eq!(empty.sol_to_msol(LAMPORTS_PER_MSOL), LAMPORTS_PER_MSOL);
+        assert_eq!(empty.msol_to_sol(LAMPORTS_PER_MSOL), LAMPORTS_PER_MSOL);
+    }
+
+    #[test]
+    fn test_overflowing_balances() {
+        let state = crate::test_utils::sample_state().minimal();
+        assert_eq!(state.checked_msol_price_lamports(), Some(state.msol_price_lamports()));
+        assert_eq!(state.checked_total_virtual_staked_lamports(), Some(state.total_virtual_staked_lamports()));
+
+        let crafted = MinimalState { total_active_balance: u64::MAX, available_reserve_balance: 1, ..state };
+        assert_eq!(crafted.checked_total_lamports_under_control(), None);
+        assert_eq!(crafted.checked_msol_price_lamports(), None);
+        assert_eq!(crafted.total_lamports_under_control(), u64::MAX);
+        let exact = MinimalState { total_active_balance: u64::MAX, ..MinimalState::default() };
+        assert_eq!(exact.checked_total_lamports_under_control(), Some(u64::MAX));
+
+        // a price or conversion past u64 saturates rather than truncating
+        let tiny_supply = MinimalState { msol_supply: 1, ..state };
+        assert_eq!((tiny_supply.checked_msol_price_lamports(), tiny_supply.msol_price_lamports()), (None, u64::MAX));
+        assert_eq!(tiny_supply.msol_to_sol(2), 2 * state.total_virtual_staked_lamports());
+        assert_eq!(tiny_supply.msol_to_sol(u64::MAX), u64::MAX);
+        assert_eq!(sol_to_msol(u64::MAX, 1, u64::MAX), u64::MAX);
+    }
+}
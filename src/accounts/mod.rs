@@ -1,2 +1,9 @@
+pub mod byte_diff;
+pub mod diff;
+pub mod idl;
+pub mod lido;
+pub mod lists;
 pub mod marinade;
+pub mod spl_stake_pool;
+pub mod stake;
 pub mod instructions;
\ No newline at end of file
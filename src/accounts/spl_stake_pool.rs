@@ -0,0 +1,118 @@
+//! the `StakePool` account of the spl stake pool program, which backs jitoSOL, bSOL and most
+//! other pool-based lsts
+
+use anchor_lang::prelude::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+
+/// deserialize a stake pool account. accounts are allocated with room to grow, so unlike
+/// `parse_marinade_state` trailing bytes are expected and ignored
+pub fn parse_stake_pool(account_data: &[u8]) -> std::io::Result<StakePool> {
+    let pool = StakePool::deserialize(&mut &account_data[..])?;
+    if pool.account_type != AccountType::StakePool {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("expected a stake pool account, got {:?}", pool.account_type),
+        ));
+    }
+    Ok(pool)
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum AccountType {
+    #[default]
+    Uninitialized,
+    StakePool,
+    ValidatorList,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Lockup {
+    pub unix_timestamp: i64,
+    pub epoch: u64,
+    pub custodian: Pubkey,
+}
+
+/// `numerator / denominator` of the amount
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Fee {
+    pub denominator: u64,
+    pub numerator: u64,
+}
+
+/// a fee change scheduled for a later epoch
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum FutureEpochFee {
+    #[default]
+    None,
+    One(Fee),
+    Two(Fee),
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Default, Debug, PartialEq)]
+pub struct StakePool {
+    pub account_type: AccountType,
+    pub manager: Pubkey,
+    pub staker: Pubkey,
+    pub stake_deposit_authority: Pubkey,
+    pub stake_withdraw_bump_seed: u8,
+    pub validator_list: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub pool_mint: Pubkey,
+    pub manager_fee_account: Pubkey,
+    pub token_program_id: Pubkey,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub last_update_epoch: u64,
+    pub lockup: Lockup,
+    pub epoch_fee: Fee,
+    pub next_epoch_fee: FutureEpochFee,
+    pub preferred_deposit_validator_vote_address: Option<Pubkey>,
+    pub preferred_withdraw_validator_vote_address: Option<Pubkey>,
+    pub stake_deposit_fee: Fee,
+    pub stake_withdrawal_fee: Fee,
+    pub next_stake_withdrawal_fee: FutureEpochFee,
+    pub stake_referral_fee: u8,
+    pub sol_deposit_authority: Option<Pubkey>,
+    pub sol_deposit_fee: Fee,
+    pub sol_referral_fee: u8,
+    pub sol_withdraw_authority: Option<Pubkey>,
+    pub sol_withdrawal_fee: Fee,
+    pub next_sol_withdrawal_fee: FutureEpochFee,
+    pub last_epoch_pool_token_supply: u64,
+    pub last_epoch_total_lamports: u64,
+}
+
+impl StakePool {
+    /// lamports per whole pool token (`total_lamports / pool_token_supply`), 1:1 for an empty pool
+    pub fn price_lamports(&self) -> u64 {
+        if self.pool_token_supply == 0 {
+            return LAMPORTS_PER_MSOL;
+        }
+        (LAMPORTS_PER_MSOL as u128 * self.total_lamports as u128 / self.pool_token_supply as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sample_stake_pool, stake_pool_data};
+
+    #[test]
+    fn test_parse_stake_pool_with_padding() {
+        let pool = sample_stake_pool(Pubkey::new_unique());
+        let data = stake_pool_data(&pool);
+        assert!(data.len() > pool.try_to_vec().unwrap().len());
+
+        let parsed = parse_stake_pool(&data).unwrap();
+        assert_eq!(parsed, pool);
+        // 7.7M SOL backing 7M tokens
+        assert_eq!(parsed.price_lamports(), 1_100_000_000);
+
+        let not_a_pool = StakePool { account_type: AccountType::ValidatorList, ..pool };
+        assert!(parse_stake_pool(&not_a_pool.try_to_vec().unwrap()).is_err());
+        assert!(parse_stake_pool(&data[..100]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! native stake accounts, as far as valuing them needs
+
+use anchor_lang::prelude::borsh::BorshDeserialize;
+use solana_program::clock::Epoch;
+use solana_program::stake::state::StakeState;
+use solana_program::stake_history::StakeHistory;
+
+/// size of a stake account
+pub const STAKE_ACCOUNT_LEN: usize = 200;
+/// `Meta::authorized.staker`, after the enum tag and `rent_exempt_reserve`
+pub const STAKER_OFFSET: usize = 12;
+/// `Meta::authorized.withdrawer`
+pub const WITHDRAWER_OFFSET: usize = 44;
+
+pub fn parse_stake_account(account_data: &[u8]) -> std::io::Result<StakeState> {
+    StakeState::deserialize(&mut &account_data[..])
+}
+
+/// how the delegated part of a stake account splits at an epoch; the three never overlap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StakeBalances {
+    pub active: u64,
+    pub activating: u64,
+    pub deactivating: u64,
+}
+
+impl StakeBalances {
+    pub fn total(&self) -> u64 {
+        self.active + self.activating + self.deactivating
+    }
+}
+
+/// split a stake account's delegation at `epoch`. undelegated accounts have nothing staked.
+/// warmup/cooldown uses the original 25% rate; it only matters for stake still warming up
+/// after its activation epoch, which is rare outside network-wide stake shifts
+pub fn stake_balances(state: &StakeState, epoch: Epoch, history: &StakeHistory) -> StakeBalances {
+    match state {
+        StakeState::Stake(_, stake) => {
+            let status = stake.delegation.stake_activating_and_deactivating(epoch, Some(history), None);
+            // `effective` still counts stake that is cooling down; keep the buckets disjoint
+            StakeBalances {
+                active: status.effective - status.deactivating,
+                activating: status.activating,
+                deactivating: status.deactivating,
+            }
+        }
+        _ => StakeBalances::default(),
+    }
+}
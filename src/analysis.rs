@@ -0,0 +1,932 @@
+//! the mSOL analysis behind the crate root's free functions, everything that needs a node
+
+#[cfg(feature = "blocking")]
+use solana_client::rpc_client::RpcClient;
+use solana_transaction_status::UiTransactionEncoding;
+use solana_client::rpc_response::Response;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcTransactionConfig};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use tracing::{info_span, instrument};
+use crate::accounts::marinade::{
+    MarinadeState, MinimalState, MINIMAL_STATE_LEN, parse_marinade_state_minimal, parse_marinade_state_with,
+};
+use crate::cache::SnapshotCache;
+use crate::deployment::DeploymentConfig;
+use crate::constants::SOL_MINT_PUBKEY;
+use crate::error::{Error, ErrorKind, Result};
+use crate::observer::{CacheKind, Observed};
+use crate::parsers::marinade::MarinadeParser;
+use crate::parsers::{LstValueParser, ParserContext};
+use crate::raw::{RawAccount, RawState};
+use crate::rpc::{at_least_confirmed, wait_for_min_context_slot, RpcFetcher};
+use crate::snapshot::SnapshotMetadata;
+#[cfg(feature = "blocking")]
+use crate::cluster::Cluster;
+use crate::transaction::{
+    check_version, max_supported_version, transaction_version, unsupported_version, IntoSignature, TransactionInput,
+};
+use crate::{AnalyzeOptions, BlockTimeSource, MintUnderlying};
+use crate::verbosity::{debug, error, trace, warn};
+
+/// fetch account data for given a public key, with the context slot of the response.
+/// `role` names the account in errors. read at `options.commitment`, waiting up to
+/// `options.wait_for_slot` for a node that isn't at `slot` yet
+#[instrument(level = "debug", skip(rpc_client, options), fields(pubkey = %pubkey, slot = ?slot))]
+pub(crate) fn fetch_account_data(
+    rpc_client: &dyn RpcFetcher,
+    pubkey: &Pubkey,
+    role: &'static str,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, Vec<u8>)> {
+    let (context_slot, account) = fetch_account_slice(rpc_client, pubkey, role, slot, options, None)?;
+    Ok((context_slot, account.data))
+}
+
+/// `fetch_account_data` for only the `data_slice` bytes of the account, when the node honours it,
+/// and the rest of the account with them.
+/// with `options.prefer_zstd` the data is asked for compressed first, and again as plain base64
+/// when the node refuses that or the answer has no account, which is also what a payload that
+/// doesn't decompress comes back as
+fn fetch_account_slice(
+    rpc_client: &dyn RpcFetcher,
+    pubkey: &Pubkey,
+    role: &'static str,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<(u64, Account)> {
+    let _verbosity = options.verbosity.enter();
+    let config = account_info_config(slot, options, data_slice);
+    if options.prefer_zstd {
+        let zstd = RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::Base64Zstd), ..config.clone() };
+        match read_account(rpc_client, pubkey, slot, options, zstd) {
+            Ok(response) if response.value.is_some() => {
+                options.observe(|o| o.on_account_read(UiAccountEncoding::Base64Zstd));
+                return account_data(response, pubkey, role, slot);
+            }
+            Ok(_) => debug!("no account in the zstd read, reading it as base64"),
+            Err(e) if matches!(e.kind(), ErrorKind::Rpc(_)) => {
+                debug!(error = %e, "zstd read failed, reading the account as base64")
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let response = read_account(rpc_client, pubkey, slot, options, config)?;
+    if response.value.is_some() {
+        options.observe(|o| o.on_account_read(UiAccountEncoding::Base64));
+    }
+    account_data(response, pubkey, role, slot)
+}
+
+// the closure hands `RpcClient`'s error through as it is
+#[allow(clippy::result_large_err)]
+fn read_account(
+    rpc_client: &dyn RpcFetcher,
+    pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+    config: RpcAccountInfoConfig,
+) -> Result<Response<Option<Account>>> {
+    wait_for_min_context_slot(rpc_client, slot, options.commitment, options.wait_for_slot, || {
+        rpc_client.get_account_with_config(pubkey, config.clone())
+    })
+    .map_err(|e| Error::from(e).with_pubkey(*pubkey).with_slot(slot))
+}
+
+/// what an account read asks the node for, whichever transport it goes over. always plain
+/// base64; `fetch_account_data` swaps in zstd itself
+pub(crate) fn account_info_config(
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+    data_slice: Option<UiDataSliceConfig>,
+) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(options.commitment),
+        data_slice,
+        min_context_slot: slot,
+    }
+}
+
+/// an account read for `slot` with its context slot, `AccountNotFound` as `role` when there is
+/// none
+pub(crate) fn account_data(
+    response: Response<Option<Account>>,
+    pubkey: &Pubkey,
+    role: &'static str,
+    slot: Option<u64>,
+) -> Result<(u64, Account)> {
+    match response.value {
+        Some(account) => {
+            debug!(length = account.data.len(), context_slot = response.context.slot, "account data fetched");
+            Ok((response.context.slot, account))
+        },
+        None => {
+            debug!("account not found");
+            Err(Error::new(ErrorKind::AccountNotFound { role }).with_pubkey(*pubkey).with_slot(slot))
+        }
+    }
+}
+
+/// fetch the marinade state account and deserialize it
+#[instrument(level = "debug", skip(rpc_client), fields(pubkey = %pubkey, slot = ?slot))]
+pub(crate) fn find_and_parse_marinade_state(
+    rpc_client: &dyn RpcFetcher,
+    pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MarinadeState)> {
+    let _verbosity = options.verbosity.enter();
+    // Fetch account data, passing the optional slot
+    let (context_slot, account_data) = fetch_account_data(rpc_client, pubkey, "marinade state", slot, options)?;
+    Ok((context_slot, parse_fetched_state(&account_data, pubkey, slot, options)?))
+}
+
+/// `find_and_parse_marinade_state`, keeping the account the state was parsed from
+pub(crate) fn find_and_parse_marinade_state_raw(
+    rpc_client: &dyn RpcFetcher,
+    pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<RawState> {
+    let _verbosity = options.verbosity.enter();
+    let (context_slot, account) = fetch_account_slice(rpc_client, pubkey, "marinade state", slot, options, None)?;
+    let state = parse_fetched_state(&account.data, pubkey, slot, options)?;
+    Ok(RawState { state, raw: RawAccount::new(*pubkey, context_slot, account) })
+}
+
+/// a state account's data as fetched for `slot`, fully parsed
+pub(crate) fn parse_fetched_state(
+    account_data: &[u8],
+    pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<MarinadeState> {
+    // Log the first few bytes of the account data
+    trace!(prefix = ?account_data.get(..16).unwrap_or(&[]), "account data prefix");
+
+    parse_state(account_data, options).map_err(|e| {
+        debug!(error = %e, length = account_data.len(), "failed to parse Marinade state");
+        invalid_state(e, pubkey, slot)
+    })
+}
+
+/// a full parse in `options.parse_mode`, logging what a lenient one let through
+pub(crate) fn parse_state(account_data: &[u8], options: &AnalyzeOptions) -> std::io::Result<MarinadeState> {
+    let parsed = parse_marinade_state_with(account_data, options.parse_mode)?;
+    for warning in &parsed.warnings {
+        warn!(?warning, length = account_data.len(), "marinade state parsed leniently");
+    }
+    Ok(parsed.state)
+}
+
+fn invalid_state(source: std::io::Error, pubkey: &Pubkey, slot: Option<u64>) -> Error {
+    Error::new(ErrorKind::InvalidAccountData { role: "marinade state", source }).with_pubkey(*pubkey).with_slot(slot)
+}
+
+/// resolve the block time for a tx, falling back to the node when the tx doesn't carry one
+pub(crate) fn resolve_block_time(
+    rpc_client: &dyn RpcFetcher,
+    tx: TransactionInput<'_>,
+    options: &AnalyzeOptions,
+) -> Result<(i64, BlockTimeSource)> {
+    if let Some(time) = tx.block_time() {
+        return Ok((time, BlockTimeSource::Transaction));
+    }
+
+    let slot = tx.slot();
+    if !options.fallback_block_time {
+        debug!("tx block time is None and the fallback is disabled");
+        return Err(Error::new(ErrorKind::MissingBlockTime).with_slot(Some(slot)));
+    }
+
+    debug!(slot, "tx block time is None, fetching block time");
+    match rpc_client.get_block_time(slot) {
+        Ok(time) => Ok((time, BlockTimeSource::Rpc)),
+        Err(e) => {
+            debug!(error = %e, "get_block_time failed");
+            Err(Error::from(e).with_slot(Some(slot)))
+        }
+    }
+}
+
+/// analyze a tx to check if it affects the Marinade state and if so, convert the data into MintUnderlying and return.
+///
+/// failures are returned, not logged (beyond debug level); see `AnalyzeOptions::verbose`
+pub fn analyze_transaction(rpc_client: &dyn RpcFetcher, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying> {
+    analyze_transaction_with_options(rpc_client, tx, &AnalyzeOptions::default())
+}
+
+/// same as `analyze_transaction`, with explicit options
+pub fn analyze_transaction_with_options(
+    rpc_client: &dyn RpcFetcher,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    options: &AnalyzeOptions,
+) -> Result<MintUnderlying> {
+    match &options.observer {
+        Some(observer) => {
+            let observed = Observed::new(rpc_client, observer.clone());
+            MarinadeParser::default().analyze(&ParserContext::new(&observed, options), tx)
+        }
+        None => MarinadeParser::default().analyze(&ParserContext::new(rpc_client, options), tx),
+    }
+}
+
+/// analyze against a given deployment; the free functions use mainnet, `MarinadeClient` its cluster's
+pub(crate) fn analyze_with(
+    rpc_client: &dyn RpcFetcher,
+    tx: TransactionInput<'_>,
+    deployment: &DeploymentConfig,
+    options: &AnalyzeOptions,
+) -> Result<MintUnderlying> {
+    let _verbosity = options.verbosity.enter();
+    let span = analyze_span(tx);
+    let _guard = span.enter();
+    let version = tx.version().map_or(Ok(()), |version| check_version(&version, &options.max_transaction_version));
+    version
+        .and_then(|()| fetch_post_state(rpc_client, &deployment.state, tx.slot(), options))
+        .and_then(|(state_slot, post_state)| {
+            mint_underlying_from_state(rpc_client, tx, state_slot, &post_state, deployment, options, false)
+        })
+        .map_err(|e| e.with_signature(tx.signature()))
+        .inspect(|_| options.observe(|o| o.on_analysis_complete(tx.slot())))
+        .inspect_err(|e| report_failure(options, e))
+}
+
+/// the old chatty behaviour, for callers that opt into it
+pub(crate) fn report_failure(options: &AnalyzeOptions, e: &Error) {
+    if options.verbose {
+        error!(error = %e, "analysis failed");
+    }
+}
+
+/// span covering the analysis of one transaction
+pub(crate) fn analyze_span(tx: TransactionInput<'_>) -> tracing::Span {
+    let signature = tx.signature().map(|sig| sig.to_string()).unwrap_or_default();
+    info_span!("analyze_transaction", signature = %signature, slot = tx.slot())
+}
+
+/// fetch the price fields of the marinade state as of (at least) `slot`, with the context slot
+/// they were read at
+pub(crate) fn fetch_post_state(
+    rpc_client: &dyn RpcFetcher,
+    state_pubkey: &Pubkey,
+    slot: u64,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MinimalState)> {
+    fetch_state(rpc_client, state_pubkey, Some(slot), options)
+}
+
+/// fetch the price fields of the marinade state, the latest one when `slot` is None,
+/// with the context slot it was read at
+pub(crate) fn fetch_state(
+    rpc_client: &dyn RpcFetcher,
+    state_pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MinimalState)> {
+    let _verbosity = options.verbosity.enter();
+    if options.offline {
+        return observed_cached_state(state_pubkey, slot, options).map(|(slot, state)| (slot, state.minimal()));
+    }
+    if options.sliced_fetch && options.snapshot_cache.is_none() {
+        if let Some(state) = fetch_sliced_state(rpc_client, state_pubkey, slot, options)? {
+            return Ok(state);
+        }
+    }
+    let state = if options.minimal_parse {
+        let (context_slot, account_data) =
+            fetch_account_data(rpc_client, state_pubkey, "marinade state", slot, options)?;
+        let state = parse_marinade_state_minimal(&account_data).map_err(|e| {
+            debug!(error = %e, "failed to parse minimal Marinade state");
+            invalid_state(e, state_pubkey, slot)
+        })?;
+        if let Some(cache) = &options.snapshot_cache {
+            // the cache keeps full states, which the minimal parse skipped
+            match parse_state(&account_data, options) {
+                Ok(full) => write_through(cache, state_pubkey, context_slot, &full),
+                Err(e) => debug!(error = %e, "state not cached, full parse failed"),
+            }
+        }
+        (context_slot, state)
+    } else {
+        let (context_slot, state) = find_and_parse_marinade_state(rpc_client, state_pubkey, slot, options)?;
+        if let Some(cache) = &options.snapshot_cache {
+            write_through(cache, state_pubkey, context_slot, &state);
+        }
+        (context_slot, state.minimal())
+    };
+    debug!(minimal = options.minimal_parse, context_slot = state.0, "marinade state fetched");
+    Ok(state)
+}
+
+/// the price fields from just the leading bytes of the state account. None when the node
+/// returned something else than the slice asked for, or bytes that can't be a state, for the
+/// caller to fetch the whole account instead
+fn fetch_sliced_state(
+    rpc_client: &dyn RpcFetcher,
+    state_pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<Option<(u64, MinimalState)>> {
+    let slice = UiDataSliceConfig { offset: 0, length: MINIMAL_STATE_LEN };
+    let fetched = fetch_account_slice(rpc_client, state_pubkey, "marinade state", slot, options, Some(slice));
+    let (context_slot, account_data) = match fetched {
+        Ok((context_slot, account)) => (context_slot, account.data),
+        // some providers reject `dataSlice` outright
+        Err(e) if matches!(e.kind(), ErrorKind::Rpc(_)) => {
+            debug!(error = %e, "sliced state fetch failed, fetching the whole account");
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    if account_data.len() != MINIMAL_STATE_LEN {
+        debug!(length = account_data.len(), "state slice not honoured, fetching the whole account");
+        return Ok(None);
+    }
+    // the slice starts at the discriminator, which the reader checks before taking the offsets
+    match parse_marinade_state_minimal(&account_data) {
+        // an all-zero mint means the fields aren't where the offsets say
+        Ok(state) if state.msol_mint != Pubkey::default() => {
+            debug!(context_slot, "marinade state fetched sliced");
+            Ok(Some((context_slot, state)))
+        }
+        Ok(_) => {
+            warn!(context_slot, "state slice doesn't look like a marinade state, fetching the whole account");
+            Ok(None)
+        }
+        Err(e) => {
+            warn!(context_slot, error = %e, "state slice isn't a marinade state, fetching the whole account");
+            Ok(None)
+        }
+    }
+}
+
+/// a failed write only costs the offline copy, so it's logged rather than failing the fetch
+fn write_through(cache: &SnapshotCache, state_pubkey: &Pubkey, context_slot: u64, state: &MarinadeState) {
+    let metadata = SnapshotMetadata::new(cache.cluster(), *state_pubkey, context_slot);
+    if let Err(e) = cache.put(state, &metadata) {
+        warn!(error = %e, dir = %cache.dir().display(), context_slot, "failed to cache marinade state");
+    }
+}
+
+/// the whole state, unlike `fetch_state`, honouring `options.offline` and the snapshot cache
+/// the same way
+pub(crate) fn fetch_full_state(
+    rpc_client: &dyn RpcFetcher,
+    state_pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MarinadeState)> {
+    let _verbosity = options.verbosity.enter();
+    if options.offline {
+        return observed_cached_state(state_pubkey, slot, options);
+    }
+    let (context_slot, state) = find_and_parse_marinade_state(rpc_client, state_pubkey, slot, options)?;
+    if let Some(cache) = &options.snapshot_cache {
+        write_through(cache, state_pubkey, context_slot, &state);
+    }
+    Ok((context_slot, state))
+}
+
+fn observed_cached_state(
+    state_pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MarinadeState)> {
+    let cached = cached_state(options.snapshot_cache.as_deref(), state_pubkey, slot);
+    options.observe(|o| match cached {
+        Ok(_) => o.on_cache_hit(CacheKind::Snapshot),
+        Err(_) => o.on_cache_miss(CacheKind::Snapshot),
+    });
+    cached
+}
+
+/// the state as of at least `slot` from the snapshot cache, with the slot it was read at
+fn cached_state(cache: Option<&SnapshotCache>, state_pubkey: &Pubkey, slot: Option<u64>) -> Result<(u64, MarinadeState)> {
+    let with_context = |e: Error| e.with_pubkey(*state_pubkey).with_slot(slot);
+    let miss = || with_context(ErrorKind::OfflineMiss { slot }.into());
+    let Some(cache) = cache else {
+        return Err(miss());
+    };
+    let cached = cache.get(slot).map_err(|source| {
+        with_context(ErrorKind::InvalidAccountData { role: "cached marinade state", source }.into())
+    })?;
+    let Some((state, metadata)) = cached else {
+        return Err(miss());
+    };
+    if !metadata.matches(cache.cluster(), state_pubkey) {
+        return Err(with_context(
+            ErrorKind::ClusterMismatch {
+                expected: format!("{} state {}", cache.cluster().name(), state_pubkey),
+                found: format!("{} state {}", metadata.cluster, metadata.state),
+            }
+            .into(),
+        ));
+    }
+    debug!(context_slot = metadata.slot, "marinade state read from the snapshot cache");
+    Ok((metadata.slot, state))
+}
+
+/// value a tx against an already fetched post-tx state, read at `state_slot`
+pub(crate) fn mint_underlying_from_state(
+    rpc_client: &dyn RpcFetcher,
+    tx: TransactionInput<'_>,
+    state_slot: u64,
+    post_state: &MinimalState,
+    deployment: &DeploymentConfig,
+    options: &AnalyzeOptions,
+    state_reused: bool,
+) -> Result<MintUnderlying> {
+    let _verbosity = options.verbosity.enter();
+    check_slot_skew(tx.slot(), state_slot, options)?;
+    let (block_time, block_time_source) = resolve_block_time(rpc_client, tx, options)?;
+    let mu = value_at(post_state, deployment, block_time, block_time_source, state_reused)
+        .map_err(|e| e.with_slot(Some(tx.slot())).with_signature(tx.signature()))?;
+    Ok(MintUnderlying { state_slot: Some(state_slot), ..with_supply_change(mu, tx, deployment, post_state) })
+}
+
+/// `ErrorKind::SlotSkew` when the state is further from the tx than `AnalyzeOptions::max_slot_skew`
+fn check_slot_skew(tx_slot: u64, state_slot: u64, options: &AnalyzeOptions) -> Result<()> {
+    match options.max_slot_skew {
+        Some(max) if state_slot.abs_diff(tx_slot) > max => {
+            Err(Error::new(ErrorKind::SlotSkew { tx_slot, state_slot, max }).with_slot(Some(tx_slot)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// value a tx against a state the caller already has, e.g. from a snapshot, without any rpc.
+/// the tx must carry its block time
+pub fn analyze_transaction_with_state<'t>(
+    tx: impl Into<TransactionInput<'t>>,
+    post_state: &MinimalState,
+) -> Result<MintUnderlying> {
+    let tx = tx.into();
+    let span = analyze_span(tx);
+    let _guard = span.enter();
+    let block_time = tx
+        .block_time()
+        .ok_or_else(|| Error::new(ErrorKind::MissingBlockTime).with_slot(Some(tx.slot())).with_signature(tx.signature()))?;
+    let deployment = &DeploymentConfig::MAINNET;
+    let mu = value_at(post_state, deployment, block_time, BlockTimeSource::Transaction, false)
+        .map_err(|e| e.with_slot(Some(tx.slot())).with_signature(tx.signature()))?;
+    Ok(with_supply_change(mu, tx, deployment, post_state))
+}
+
+fn with_supply_change(
+    mu: MintUnderlying,
+    tx: TransactionInput<'_>,
+    deployment: &DeploymentConfig,
+    post_state: &MinimalState,
+) -> MintUnderlying {
+    let change = crate::supply::msol_supply_change(tx, deployment, post_state);
+    let (msol_minted, msol_burned, msol_discrepancy) = (change.minted, change.burned, change.discrepancy);
+    MintUnderlying { msol_minted, msol_burned, msol_discrepancy, ..mu }
+}
+
+fn value_at(
+    post_state: &MinimalState,
+    deployment: &DeploymentConfig,
+    block_time: i64,
+    block_time_source: BlockTimeSource,
+    state_reused: bool,
+) -> Result<MintUnderlying> {
+    let sol_amount = post_state.total_virtual_staked_lamports();
+    let msol_value = post_state.msol_price_lamports();
+
+    // the state is the source of truth for the mint; the constant only catches a wrong state account
+    if post_state.msol_mint != deployment.msol_mint {
+        warn!(state_mint = %post_state.msol_mint, expected = %deployment.msol_mint, "state msol_mint differs from the known mSOL mint");
+    }
+
+    let mu = MintUnderlying::builder()
+        .block_time(block_time, block_time_source)
+        .msol_value(msol_value)
+        .mint(post_state.msol_mint)
+        .platform_program(deployment.state)
+        .underlying(SOL_MINT_PUBKEY, sol_amount)
+        .supply(post_state.msol_supply)
+        .state_reused(state_reused)
+        .build()
+        .map_err(ErrorKind::InvalidMintUnderlying)?;
+    debug!(sol_amount, msol_value, block_time, ?block_time_source, state_reused, "analysis complete");
+    Ok(mu)
+}
+
+
+/// fetch a transaction through `rpc_client`, at `options.commitment` or `confirmed` if that is
+/// `processed`, which the node refuses for transactions. one newer than
+/// `options.max_transaction_version` fails with `ErrorKind::UnsupportedTransactionVersion`
+pub fn fetch_transaction_with_options(
+    rpc_client: &dyn RpcFetcher,
+    signature: impl IntoSignature,
+    options: &AnalyzeOptions,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let _verbosity = options.verbosity.enter();
+    let signature = &signature.into_signature()?;
+    let max = &options.max_transaction_version;
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(at_least_confirmed(options.commitment)),
+        max_supported_transaction_version: max_supported_version(max),
+    };
+    let tx = rpc_client
+        .get_transaction_with_config(signature, config)
+        .map_err(|e| unsupported_version(e, max).with_signature(Some(*signature)))?;
+    // in case the node didn't hold to the setting
+    if let Some(version) = transaction_version(&tx) {
+        check_version(&version, max).map_err(|e| e.with_signature(Some(*signature)))?;
+    }
+    Ok(tx)
+}
+
+/// fetch a transaction from the public mainnet endpoint, by `Signature` or its base58 string,
+/// with the default `AnalyzeOptions`
+#[cfg(feature = "blocking")]
+#[deprecated(note = "use `MarinadeClient::fetch_transaction`, or `fetch_transaction_with_options` with your own node")]
+pub fn fetch_transaction(signature: impl IntoSignature) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let rpc_client = RpcClient::new(Cluster::MainnetBeta.rpc_url().to_string());
+    fetch_transaction_with_options(&rpc_client, signature, &AnalyzeOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY};
+    use crate::{accounts, test_utils};
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    #[ignore = "reads a transaction off mainnet, run with --ignored when online"]
+    fn test_deposit_transaction() {
+        use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+        env_logger::init();  // Initialize logger
+
+        debug!("starting test_deposit_transaction");
+        let rpc_client = RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
+        let deposit_signature = "4uL95njGxnL7oPRBv6qb9ZKeWbTfKifbJgKe5zJ98FFyh7TJofUghQ2tcp4gR9fUHsX5exHayzcK9Zt1SR1Cwy7k";
+        // what the depositor paid and got back; the record values the pool, not the deposit
+        let (deposit_lamports, msol_received) = (20_890_732_u128, 17_192_933_u128);
+        let deposit_price = (deposit_lamports * LAMPORTS_PER_MSOL as u128 / msol_received) as u64;
+
+        debug!("fetching transaction with signature: {}", deposit_signature);
+        let tx = fetch_transaction_with_options(&rpc_client, deposit_signature, &AnalyzeOptions::default())
+            .expect("failed to fetch deposit transaction");
+        debug!("transaction fetched successfully");
+
+        debug!("analyzing transaction");
+        let result = analyze_transaction(&rpc_client, &tx);
+        debug!("analysis result: {:?}", result);
+        let mint_underlying = result.expect("deposit transaction should produce a result");
+        debug!("MintUnderlying: {:?}", mint_underlying);
+
+        assert_eq!(mint_underlying.mint_pubkey, MSOL_MINT_PUBKEY.to_string());
+        assert_eq!(mint_underlying.platform_program_pubkey, MARINADE_STATE_PUBKEY.to_string());
+        let underlyings: Vec<_> = mint_underlying.underlyings().unwrap();
+        assert_eq!(underlyings.iter().map(|(mint, _)| *mint).collect::<Vec<_>>(), [SOL_MINT_PUBKEY]);
+
+        // a public node serves the current state, not the one at the tx's slot, so the pool's
+        // totals can only be bounded: its SOL dwarfs the deposit, and the price hasn't fallen
+        // below what the deposit paid per mSOL since
+        let total_underlying_sol = underlyings[0].1;
+        debug!("total underlying SOL: {}", total_underlying_sol);
+        assert!(total_underlying_sol >= 100_000 * LAMPORTS_PER_MSOL, "the pool's SOL, not the deposit's");
+
+        let msol_value = mint_underlying.msol_value;
+        debug!("msol value: {}, deposit paid {} per mSOL", msol_value, deposit_price);
+        assert!(
+            msol_value + 1 >= deposit_price && msol_value < 2 * LAMPORTS_PER_MSOL,
+            "msol value is outside the expected range"
+        );
+
+        debug!("test_deposit_transaction completed successfully");
+    }
+
+    fn mock_with_state() -> test_utils::MockFetcher {
+        let state = test_utils::sample_state();
+        test_utils::MockFetcher::new().with_account(
+            MARINADE_STATE_PUBKEY,
+            test_utils::state_account(&state),
+        )
+    }
+
+    #[test]
+    fn test_block_time_taken_from_transaction() {
+        let rpc = mock_with_state();
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+
+        let mu = analyze_transaction(&rpc, &tx).expect("analysis should succeed");
+        assert_eq!(mu.block_time, test_utils::FIXTURE_BLOCK_TIME);
+        assert_eq!(mu.block_time_source, BlockTimeSource::Transaction);
+        assert_eq!(rpc.call_count("getBlockTime"), 0);
+    }
+
+    #[test]
+    fn test_block_time_falls_back_to_rpc() {
+        let rpc = mock_with_state().with_block_time(test_utils::FIXTURE_SLOT, test_utils::FIXTURE_BLOCK_TIME);
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, None);
+
+        let mu = analyze_transaction(&rpc, &tx).expect("fallback should recover the block time");
+        assert_eq!(mu.block_time, test_utils::FIXTURE_BLOCK_TIME);
+        assert_eq!(mu.block_time_source, BlockTimeSource::Rpc);
+        assert_eq!(rpc.call_count("getBlockTime"), 1);
+    }
+
+    #[test]
+    fn test_block_time_fallback_failure_and_opt_out() {
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, None);
+
+        // node doesn't know the slot either
+        let rpc = mock_with_state();
+        assert!(analyze_transaction(&rpc, &tx).is_err());
+
+        // fallback disabled: no extra call is made even though the node could answer
+        let rpc = mock_with_state().with_block_time(test_utils::FIXTURE_SLOT, test_utils::FIXTURE_BLOCK_TIME);
+        let options = AnalyzeOptions { fallback_block_time: false, ..AnalyzeOptions::default() };
+        assert!(analyze_transaction_with_options(&rpc, &tx, &options).is_err());
+        assert_eq!(rpc.call_count("getBlockTime"), 0);
+    }
+
+    #[test]
+    fn test_minimal_parse_option_gives_same_result() {
+        let state = test_utils::sample_state();
+        let rpc = mock_with_state();
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+
+        let full = analyze_transaction(&rpc, &tx).unwrap();
+        let options = AnalyzeOptions { minimal_parse: true, ..AnalyzeOptions::default() };
+        let minimal = analyze_transaction_with_options(&rpc, &tx, &options).unwrap();
+        assert_eq!(minimal.msol_value, full.msol_value);
+        assert_eq!(minimal.underlyings().unwrap(), full.underlyings().unwrap());
+
+        // the offsets count from after the discriminator, so the bare layout is refused
+        let bare = Account { data: test_utils::state_data(&state), ..test_utils::state_account(&state) };
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, bare);
+        let err = analyze_transaction_with_options(&rpc, &tx, &options).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAccountData { role: "marinade state", .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_mode_option() {
+        let mut account = test_utils::state_account(&test_utils::sample_state());
+        account.data.extend_from_slice(&[0; 64]);
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, account);
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+
+        let err = analyze_transaction(&rpc, &tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAccountData { role: "marinade state", .. }));
+        let options = AnalyzeOptions { parse_mode: accounts::marinade::ParseMode::Lenient, ..AnalyzeOptions::default() };
+        let lenient = analyze_transaction_with_options(&rpc, &tx, &options).unwrap();
+        assert_eq!(lenient.msol_value, analyze_transaction(&mock_with_state(), &tx).unwrap().msol_value);
+
+        // the layout without its discriminator only parses leniently
+        let state = test_utils::sample_state();
+        let bare = Account { data: test_utils::state_data(&state), ..test_utils::state_account(&state) };
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, bare);
+        let err = analyze_transaction(&rpc, &tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAccountData { role: "marinade state", .. }));
+        assert_eq!(analyze_transaction_with_options(&rpc, &tx, &options).unwrap().msol_value, lenient.msol_value);
+    }
+
+    #[test]
+    fn test_sliced_fetch_matches_full_parse() {
+        let sliced = AnalyzeOptions { sliced_fetch: true, ..AnalyzeOptions::default() };
+        for state in test_utils::fixture_states() {
+            let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, test_utils::state_account(&state));
+            let (_, fetched) = fetch_state(&rpc, &MARINADE_STATE_PUBKEY, None, &sliced).unwrap();
+            assert_eq!(fetched, state.minimal());
+            assert_eq!(fetched.msol_price_lamports(), state.msol_price_lamports());
+            // the default fixture has no mint where the offsets look, so it's fetched again in full
+            let calls = 1 + (state.msol_mint == Pubkey::default()) as usize;
+            assert_eq!(rpc.call_count("getAccountInfo"), calls);
+        }
+
+        // the layout without its discriminator isn't read at the offsets, it's fetched again in full
+        let state = test_utils::sample_state();
+        let bare = Account { data: test_utils::state_data(&state), ..test_utils::state_account(&state) };
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, bare);
+        let lenient = AnalyzeOptions { parse_mode: accounts::marinade::ParseMode::Lenient, ..sliced.clone() };
+        let (_, fetched) = fetch_state(&rpc, &MARINADE_STATE_PUBKEY, None, &lenient).unwrap();
+        assert_eq!(fetched, state.minimal());
+        assert_eq!(rpc.call_count("getAccountInfo"), 2);
+
+        // so is the state when the node rejects the slice
+        let state = test_utils::sample_state();
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, test_utils::state_account(&state));
+        rpc.fail_next("getAccountInfo", 1);
+        let (_, fetched) = fetch_state(&rpc, &MARINADE_STATE_PUBKEY, None, &sliced).unwrap();
+        assert_eq!(fetched, state.minimal());
+        assert_eq!(rpc.call_count("getAccountInfo"), 2);
+    }
+
+    #[test]
+    fn test_zstd_reads_fall_back_to_base64() {
+        use crate::observer::StatsObserver;
+        use std::sync::Arc;
+        use UiAccountEncoding::{Base64, Base64Zstd};
+
+        let state = test_utils::sample_state();
+        let account = test_utils::state_account(&state);
+        let mock = || test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, account.clone());
+        let observer = Arc::new(StatsObserver::new());
+        let options = AnalyzeOptions { prefer_zstd: true, observer: Some(observer.clone()), ..Default::default() };
+
+        // the state round-trips through the compressed payload
+        let rpc = mock();
+        let (_, fetched) = fetch_full_state(&rpc, &MARINADE_STATE_PUBKEY, None, &options).unwrap();
+        assert_eq!(fetched, state);
+        assert_eq!(rpc.encodings(), [Some(Base64Zstd)]);
+        assert_eq!((observer.stats().zstd_account_reads, observer.stats().base64_account_reads), (1, 0));
+
+        // a node without zstd, and a payload that doesn't decompress, are read again as base64
+        for rpc in [mock().without_zstd(), mock().with_corrupt_zstd()] {
+            let (_, fetched) = fetch_full_state(&rpc, &MARINADE_STATE_PUBKEY, None, &options).unwrap();
+            assert_eq!(fetched, state);
+            assert_eq!(rpc.encodings(), [Some(Base64Zstd), Some(Base64)]);
+        }
+        assert_eq!((observer.stats().zstd_account_reads, observer.stats().base64_account_reads), (1, 2));
+
+        // off by default
+        let rpc = mock();
+        fetch_full_state(&rpc, &MARINADE_STATE_PUBKEY, None, &AnalyzeOptions::default()).unwrap();
+        assert_eq!(rpc.encodings(), [Some(Base64)]);
+    }
+
+    #[test]
+    fn test_waits_for_a_lagging_node() {
+        use std::time::Duration;
+        use test_utils::FIXTURE_SLOT;
+        let tx = test_utils::sample_transaction(FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+        let lagging = || mock_with_state().with_node_slots([FIXTURE_SLOT - 2, FIXTURE_SLOT - 1, FIXTURE_SLOT]);
+
+        // off by default: the node's error is passed on
+        let rpc = lagging();
+        let err = analyze_transaction(&rpc, &tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Rpc(_)));
+        assert_eq!(rpc.call_count("getSlot"), 0);
+
+        // caught up after two polls, then fetched again
+        let rpc = lagging();
+        let options = AnalyzeOptions { wait_for_slot: Some(Duration::from_secs(5)), ..AnalyzeOptions::default() };
+        assert!(analyze_transaction_with_options(&rpc, &tx, &options).is_ok());
+        assert_eq!(rpc.calls(), ["getAccountInfo", "getSlot", "getSlot", "getAccountInfo"]);
+
+        // a node that doesn't get there in time fails the fetch once the deadline is up
+        let rpc = mock_with_state().with_node_slots([FIXTURE_SLOT - 1]);
+        let options = AnalyzeOptions { wait_for_slot: Some(Duration::from_millis(120)), ..AnalyzeOptions::default() };
+        assert!(analyze_transaction_with_options(&rpc, &tx, &options).is_err());
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+        assert!(rpc.call_count("getSlot") >= 2);
+    }
+
+    #[test]
+    fn test_fetch_transaction_takes_the_options() {
+        use solana_sdk::commitment_config::CommitmentConfig;
+        use solana_sdk::signature::Signature;
+        use solana_sdk::transaction::TransactionVersion;
+        use test_utils::{FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+        let signature = Signature::new_unique();
+        let v0 = test_utils::v0_marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[]);
+        let rpc = test_utils::MockFetcher::new().with_transaction(signature, v0);
+
+        let finalized = AnalyzeOptions { commitment: CommitmentConfig::finalized(), ..AnalyzeOptions::default() };
+        assert_eq!(fetch_transaction_with_options(&rpc, signature, &finalized).unwrap().slot, FIXTURE_SLOT);
+        // the node refuses `processed` for transactions
+        let processed = AnalyzeOptions { commitment: CommitmentConfig::processed(), ..AnalyzeOptions::default() };
+        assert!(fetch_transaction_with_options(&rpc, signature.to_string().as_str(), &processed).is_ok());
+        let asked = [Some(CommitmentConfig::finalized()), Some(CommitmentConfig::confirmed())];
+        assert_eq!(rpc.commitments(), asked.map(|commitment| ("getTransaction", commitment)));
+
+        let legacy_only =
+            AnalyzeOptions { max_transaction_version: TransactionVersion::LEGACY, ..AnalyzeOptions::default() };
+        let err = fetch_transaction_with_options(&rpc, signature, &legacy_only).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsupportedTransactionVersion { version: 0, .. }));
+        assert_eq!(err.signature(), Some(&signature));
+    }
+
+    #[test]
+    fn test_state_slot_and_max_skew() {
+        use test_utils::FIXTURE_SLOT;
+        let tx = test_utils::sample_transaction(FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+        let at = |node_slot: u64| mock_with_state().with_node_slots([node_slot]);
+
+        // the node answers at its own slot, whatever the transaction's
+        for node_slot in [FIXTURE_SLOT, FIXTURE_SLOT + 50] {
+            assert_eq!(analyze_transaction(&at(node_slot), &tx).unwrap().state_slot, Some(node_slot));
+        }
+
+        let options = |max| AnalyzeOptions { max_slot_skew: Some(max), ..AnalyzeOptions::default() };
+        let mu = analyze_transaction_with_options(&at(FIXTURE_SLOT + 50), &tx, &options(50)).unwrap();
+        assert_eq!(mu.state_slot, Some(FIXTURE_SLOT + 50));
+        let e = analyze_transaction_with_options(&at(FIXTURE_SLOT + 51), &tx, &options(50)).unwrap_err();
+        let expected = ErrorKind::SlotSkew { tx_slot: FIXTURE_SLOT, state_slot: FIXTURE_SLOT + 51, max: 50 };
+        assert_eq!(format!("{:?}", e.kind()), format!("{:?}", expected));
+        assert_eq!(e.slot(), Some(FIXTURE_SLOT));
+
+        // a state the caller supplies has no slot to check
+        let state = test_utils::sample_state().minimal();
+        assert_eq!(analyze_transaction_with_state(&tx, &state).unwrap().state_slot, None);
+    }
+
+    #[test]
+    fn test_analysis_emits_structured_spans() {
+        let recorder = test_utils::TraceRecorder::default();
+        let rpc = mock_with_state();
+        let tx = test_utils::marinade_transaction(
+            test_utils::FIXTURE_SLOT,
+            Some(test_utils::FIXTURE_BLOCK_TIME),
+            &[accounts::instructions::MarinadeFinanceInstruction::Deposit],
+        );
+
+        let mu = recorder.capture(|| analyze_transaction(&rpc, &tx)).unwrap();
+
+        let analyze = recorder.span("analyze_transaction").expect("analyze_transaction span");
+        assert_eq!(analyze.fields["slot"], test_utils::FIXTURE_SLOT.to_string());
+        assert_eq!(analyze.fields["signature"], crate::transaction::transaction_signature(&tx).unwrap().to_string());
+
+        let fetch = recorder.span("fetch_account_data").expect("fetch_account_data span");
+        assert_eq!(fetch.fields["pubkey"], MARINADE_STATE_PUBKEY.to_string());
+
+        let done = recorder.event("analysis complete").expect("outcome event");
+        assert_eq!(done.fields["msol_value"], mu.msol_value.to_string());
+        assert_eq!(done.fields["state_reused"], "false");
+    }
+
+    #[test]
+    fn test_errors_carry_context() {
+        let state_pubkey = MARINADE_STATE_PUBKEY;
+        let tx = test_utils::marinade_transaction(test_utils::FIXTURE_SLOT, None, &[accounts::instructions::MarinadeFinanceInstruction::Deposit]);
+        let signature = crate::transaction::transaction_signature(&tx).unwrap();
+
+        // state account missing
+        let err = analyze_transaction(&test_utils::MockFetcher::new(), &tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::AccountNotFound { .. }));
+        assert_eq!(err.pubkey(), Some(&state_pubkey));
+        assert_eq!(err.slot(), Some(test_utils::FIXTURE_SLOT));
+        assert_eq!(err.signature(), Some(&signature));
+
+        // state account holds garbage: the borsh error is kept
+        let mut garbage = test_utils::state_account(&test_utils::sample_state());
+        garbage.data.truncate(100);
+        let rpc = test_utils::MockFetcher::new().with_account(state_pubkey, garbage);
+        let err = analyze_transaction(&rpc, &tx).unwrap_err();
+        assert_eq!(err.parse_error().map(|e| e.kind()), Some(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(err.pubkey(), Some(&state_pubkey));
+        assert_eq!(err.signature(), Some(&signature));
+
+        // block time lookup fails: the rpc error is kept, no account involved
+        let err = analyze_transaction(&mock_with_state(), &tx).unwrap_err();
+        assert!(err.rpc_error().is_some());
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(err.pubkey(), None);
+        assert_eq!(err.slot(), Some(test_utils::FIXTURE_SLOT));
+        assert_eq!(err.signature(), Some(&signature));
+        assert!(err.to_string().contains(&signature.to_string()));
+    }
+
+    #[test]
+    fn test_failures_are_only_logged_when_verbose() {
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+        let rpc = test_utils::MockFetcher::new();
+        let logged_errors = |recorder: &test_utils::TraceRecorder| {
+            recorder.records().into_iter().filter(|r| r.kind == "event" && r.level == tracing::Level::ERROR).count()
+        };
+
+        let quiet = test_utils::TraceRecorder::default();
+        assert!(quiet.capture(|| analyze_transaction(&rpc, &tx)).is_err());
+        assert_eq!(logged_errors(&quiet), 0);
+
+        let verbose = test_utils::TraceRecorder::default();
+        let options = AnalyzeOptions { verbose: true, ..AnalyzeOptions::default() };
+        assert!(verbose.capture(|| analyze_transaction_with_options(&rpc, &tx, &options)).is_err());
+        assert_eq!(logged_errors(&verbose), 1);
+        assert!(verbose.event("analysis failed").unwrap().fields["error"].contains("not found"));
+    }
+
+    #[test]
+    fn test_mint_pubkey_comes_from_state() {
+        let state = test_utils::sample_state();
+        let tx = test_utils::sample_transaction(test_utils::FIXTURE_SLOT, Some(test_utils::FIXTURE_BLOCK_TIME));
+        let recorder = test_utils::TraceRecorder::default();
+
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, test_utils::state_account(&state));
+        let mu = recorder.capture(|| analyze_transaction(&rpc, &tx)).unwrap();
+        assert_eq!(mu.mint_pubkey, state.msol_mint.to_string());
+        // the fixture's mint isn't the mainnet one, which is worth a warning
+        let mismatch = recorder.event("state msol_mint differs from the known mSOL mint").unwrap();
+        assert_eq!(mismatch.level, tracing::Level::WARN);
+
+        let mut mainnet = state.clone();
+        mainnet.msol_mint = MSOL_MINT_PUBKEY;
+        let rpc = test_utils::MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, test_utils::state_account(&mainnet));
+        let recorder = test_utils::TraceRecorder::default();
+        let mu = recorder.capture(|| analyze_transaction(&rpc, &tx)).unwrap();
+        assert_eq!(mu.mint_pubkey, MSOL_MINT_PUBKEY.to_string());
+        assert!(recorder.event("state msol_mint differs from the known mSOL mint").is_none());
+    }
+}
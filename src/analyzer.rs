@@ -0,0 +1,141 @@
+use solana_sdk::epoch_schedule::{Epoch, EpochSchedule};
+
+use crate::accounts::marinade::MinimalState;
+use crate::deployment::DeploymentConfig;
+use crate::error::Result;
+use crate::observer::CacheKind;
+use crate::rpc::RpcFetcher;
+use crate::transaction::TransactionInput;
+use crate::{fetch_post_state, mint_underlying_from_state, AnalyzeOptions, MintUnderlying};
+use crate::verbosity::debug;
+
+/// stateful counterpart to `analyze_transaction` for runs of transactions.
+///
+/// remembers the last fetched state and reuses it for transactions in the same epoch whose
+/// marinade instructions are all price neutral (see `MarinadeFinanceInstruction::is_price_neutral`).
+/// anything else - cranks, config changes, undecodable txs - triggers a fresh fetch
+pub struct Analyzer<'a> {
+    rpc_client: &'a dyn RpcFetcher,
+    options: AnalyzeOptions,
+    epoch_schedule: EpochSchedule,
+    deployment: DeploymentConfig,
+    last_state: Option<(Epoch, u64, MinimalState)>,
+}
+
+impl<'a> Analyzer<'a> {
+    pub fn new(rpc_client: &'a dyn RpcFetcher, options: AnalyzeOptions) -> Self {
+        Self {
+            rpc_client,
+            options,
+            // mainnet-beta: fixed 432k slot epochs, no warmup
+            epoch_schedule: EpochSchedule::without_warmup(),
+            deployment: DeploymentConfig::MAINNET,
+            last_state: None,
+        }
+    }
+
+    pub fn with_epoch_schedule(mut self, epoch_schedule: EpochSchedule) -> Self {
+        self.epoch_schedule = epoch_schedule;
+        self
+    }
+
+    /// the deployment to analyze against, mainnet by default
+    pub fn with_deployment(mut self, deployment: DeploymentConfig) -> Self {
+        self.deployment = deployment;
+        self.last_state = None;
+        self
+    }
+
+    /// takes a fetched transaction or, through `TransactionInput`, one in the native types
+    pub fn analyze<'t>(&mut self, tx: impl Into<TransactionInput<'t>>) -> Result<MintUnderlying> {
+        let tx = tx.into();
+        let span = crate::analyze_span(tx);
+        let _guard = span.enter();
+        self.analyze_inner(tx)
+            .map_err(|e| e.with_signature(tx.signature()))
+            .inspect(|_| self.options.observe(|o| o.on_analysis_complete(tx.slot())))
+            .inspect_err(|e| crate::report_failure(&self.options, e))
+    }
+
+    fn analyze_inner(&mut self, tx: TransactionInput<'_>) -> Result<MintUnderlying> {
+        let epoch = self.epoch_schedule.get_epoch(tx.slot());
+
+        if let Some((cached_epoch, state_slot, state)) = &self.last_state {
+            if *cached_epoch == epoch && self.is_price_neutral(tx) {
+                debug!(epoch, "reusing cached state");
+                self.options.observe(|o| o.on_cache_hit(CacheKind::State));
+                let (deployment, options) = (&self.deployment, &self.options);
+                return mint_underlying_from_state(self.rpc_client, tx, *state_slot, state, deployment, options, true);
+            }
+        }
+
+        self.options.observe(|o| o.on_cache_miss(CacheKind::State));
+        let crossed_epoch_boundary = self.last_state.as_ref().is_some_and(|(cached_epoch, ..)| *cached_epoch < epoch);
+        let (state_slot, state) = fetch_post_state(self.rpc_client, &self.deployment.state, tx.slot(), &self.options)?;
+        let (deployment, options) = (&self.deployment, &self.options);
+        let result = mint_underlying_from_state(self.rpc_client, tx, state_slot, &state, deployment, options, false);
+        self.last_state = Some((epoch, state_slot, state));
+        result.map(|mu| MintUnderlying { crossed_epoch_boundary, ..mu })
+    }
+
+    fn is_price_neutral(&self, tx: TransactionInput<'_>) -> bool {
+        match tx.marinade_instructions(&self.deployment.program_id) {
+            Some(ixs) => !ixs.is_empty() && ixs.iter().all(|ix| ix.is_price_neutral()),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+    fn mock() -> MockFetcher {
+        MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()))
+    }
+
+    fn tx(slot: u64, ixs: &[MarinadeFinanceInstruction]) -> EncodedConfirmedTransactionWithStatusMeta {
+        marinade_transaction(slot, Some(FIXTURE_BLOCK_TIME), ixs)
+    }
+
+    #[test]
+    fn test_price_neutral_run_reuses_state() {
+        use MarinadeFinanceInstruction::*;
+        let rpc = mock();
+        let mut analyzer = Analyzer::new(&rpc, AnalyzeOptions::default());
+
+        let first = analyzer.analyze(&tx(FIXTURE_SLOT, &[Deposit])).unwrap();
+        assert!(!first.state_reused);
+        for (i, ix) in [Deposit, LiquidUnstake, OrderUnstake, Claim].into_iter().enumerate() {
+            let mu = analyzer.analyze(&tx(FIXTURE_SLOT + 1 + i as u64, &[ix])).unwrap();
+            assert!(mu.state_reused);
+        }
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+    }
+
+    #[test]
+    fn test_cranks_config_and_new_epochs_refetch() {
+        use MarinadeFinanceInstruction::*;
+        let rpc = mock();
+        let mut analyzer = Analyzer::new(&rpc, AnalyzeOptions::default());
+
+        analyzer.analyze(&tx(FIXTURE_SLOT, &[Deposit])).unwrap();
+        assert!(!analyzer.analyze(&tx(FIXTURE_SLOT + 1, &[UpdateActive])).unwrap().state_reused);
+        assert!(!analyzer.analyze(&tx(FIXTURE_SLOT + 2, &[Deposit, ConfigMarinade])).unwrap().state_reused);
+        // no marinade instruction at all: nothing guarantees the price held
+        assert!(!analyzer.analyze(&tx(FIXTURE_SLOT + 3, &[])).unwrap().state_reused);
+        assert_eq!(rpc.call_count("getAccountInfo"), 4);
+
+        // a deposit in the next epoch can't reuse the previous epoch's state
+        let next_epoch = analyzer.epoch_schedule.get_first_slot_in_epoch(analyzer.epoch_schedule.get_epoch(FIXTURE_SLOT) + 1);
+        let crossing = analyzer.analyze(&tx(next_epoch, &[Deposit])).unwrap();
+        assert!(!crossing.state_reused && crossing.crossed_epoch_boundary);
+        let reused = analyzer.analyze(&tx(next_epoch + 1, &[Claim])).unwrap();
+        assert!(reused.state_reused && !reused.crossed_epoch_boundary);
+        assert_eq!(rpc.call_count("getAccountInfo"), 5);
+    }
+}
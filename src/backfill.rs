@@ -0,0 +1,708 @@
+//! historical `MintUnderlying` records, from the signatures of transactions that touched the
+//! marinade state account.
+//!
+//! long runs can persist their progress through a `Checkpoint` and pick up where they died.
+//! delivery is at least once: a resumed run repeats up to `checkpoint_every` records, which
+//! it flags as `possible_duplicate`.
+//!
+//! with `BackfillOptions::workers` above 1 a pool of threads fetches and analyzes ahead of the
+//! iterator, and the records are put back in chain order before they're handed out, within a
+//! window of `reorder_window` signatures.
+//!
+//! a backfill given a `ShutdownHandle` stops handing out records once it's triggered, after
+//! the ones the workers finish within the grace period, and saves its checkpoint
+
+use std::collections::VecDeque;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::signature::Signature;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::instructions::MarinadeFinanceInstruction;
+use crate::analyzer::Analyzer;
+use crate::batch::{analyze_fetched, SlotStates};
+use crate::client::MarinadeClient;
+use crate::error::{Error, ErrorKind, Result};
+use crate::flows::{transaction_flows, Flow};
+use crate::shutdown::{ShutdownHandle, ShutdownSummary};
+use crate::treasury::ProtocolRevenue;
+use crate::transaction::marinade_instructions;
+use crate::signatures::{
+    check_signature_statuses, signature_stream, SignatureRange, SkipReason, StatusFilter, MAX_PAGE_SIZE,
+};
+use crate::MintUnderlying;
+use crate::verbosity::{debug, warn};
+
+#[derive(Debug, Clone)]
+pub struct BackfillOptions {
+    /// resume after this signature, exclusive. from the account's first transaction when None
+    pub start: Option<Signature>,
+    /// stop at the first transaction with an earlier block time
+    pub start_time: Option<UnixTimestamp>,
+    /// ignore transactions with a later block time
+    pub end_time: Option<UnixTimestamp>,
+    /// signatures per page, capped at `MAX_PAGE_SIZE`
+    pub page_size: usize,
+    /// only transactions carrying at least one of these marinade instructions, all when None
+    pub instructions: Option<Vec<MarinadeFinanceInstruction>>,
+    /// wait between signature pages, to stay under public rpc rate limits
+    pub page_delay: Duration,
+    /// signatures handled between two `Checkpoint::save`s
+    pub checkpoint_every: usize,
+    /// what `backfill_signatures` checks its candidates for. the account's own listing already
+    /// leaves out failed transactions, and only lists what's at the client's commitment
+    pub status_filter: StatusFilter,
+    /// threads fetching and analyzing, 1 by default: each signature is fetched when its record
+    /// is asked for. with more, records still come out oldest first
+    pub workers: usize,
+    /// with more than one worker, the most signatures handed to them ahead of the oldest record
+    /// not yet out, at least `workers`. a slow one holds the rest back instead of the finished
+    /// records piling up behind it
+    pub reorder_window: usize,
+    /// stop early once this is triggered, see the module docs. a trigger while the account's
+    /// signatures are still being listed leaves nothing to backfill
+    pub shutdown: Option<ShutdownHandle>,
+}
+
+impl Default for BackfillOptions {
+    fn default() -> Self {
+        Self {
+            start: None,
+            start_time: None,
+            end_time: None,
+            page_size: MAX_PAGE_SIZE,
+            instructions: None,
+            page_delay: Duration::from_millis(200),
+            checkpoint_every: 100,
+            status_filter: StatusFilter::default(),
+            workers: 1,
+            reorder_window: 64,
+            shutdown: None,
+        }
+    }
+}
+
+/// list every signature on `client`'s state account within `options`, then analyze them oldest
+/// first as the returned iterator is advanced. transactions that failed on chain are skipped
+/// while listing; ones that can't be fetched or analyzed come out as errors and the iteration
+/// carries on past them
+pub fn backfill_for_state_account(client: &MarinadeClient, options: BackfillOptions) -> Result<Backfill<'_>> {
+    let range = SignatureRange {
+        start_time: options.start_time,
+        end_time: options.end_time,
+        until: options.start,
+        page_size: options.page_size,
+        page_delay: options.page_delay,
+    };
+    let mut signatures = Vec::new();
+    for info in signature_stream(client, range) {
+        if options.shutdown.as_ref().is_some_and(ShutdownHandle::is_triggered) {
+            debug!(listed = signatures.len(), "shut down while listing signatures");
+            signatures.clear();
+            break;
+        }
+        let info = info?;
+        if info.err.is_none() {
+            signatures.push((info.signature, info.slot));
+        }
+    }
+    signatures.reverse();
+    Ok(Backfill::new(client, signatures, options, Vec::new()))
+}
+
+/// backfill a list of candidate signatures instead of the state account's history. their
+/// statuses are checked first, `options.status_filter` deciding which are skipped, and the rest
+/// analyzed oldest first by the slot of their status; one without a status is skipped whatever
+/// the filter, having no slot to go by. the bounds of the account listing in `options` don't
+/// apply, nor does a checkpoint
+pub fn backfill_signatures<'a>(
+    client: &'a MarinadeClient,
+    signatures: &[Signature],
+    options: BackfillOptions,
+) -> Result<Backfill<'a>> {
+    let check = check_signature_statuses(client, signatures, options.status_filter)?;
+    let mut skipped = check.skipped;
+    let mut kept = Vec::new();
+    for checked in check.kept {
+        match checked.slot {
+            Some(slot) => kept.push((checked.signature, slot)),
+            None => skipped.push((checked.signature, SkipReason::Unknown)),
+        }
+    }
+    kept.sort_by_key(|(_, slot)| *slot);
+    debug!(kept = kept.len(), skipped = skipped.len(), "candidate signatures checked");
+    Ok(Backfill::new(client, kept, options, skipped))
+}
+
+/// `backfill_for_state_account`, starting after the cursor in `checkpoint` when it holds one
+/// (instead of `options.start`) and saving the cursor there as the records are consumed
+pub fn backfill_with_checkpoint<'a>(
+    client: &'a MarinadeClient,
+    mut options: BackfillOptions,
+    checkpoint: impl Checkpoint + 'a,
+) -> Result<Backfill<'a>> {
+    let stored = checkpoint.load().unwrap_or_else(|e| {
+        warn!(error = %e, "unreadable backfill checkpoint, starting over");
+        None
+    });
+    if let Some(cursor) = &stored {
+        debug!(signature = %cursor.signature, slot = cursor.slot, "resuming backfill");
+        options.start = Some(cursor.signature);
+    }
+    let mut backfill = backfill_for_state_account(client, options)?;
+    if stored.is_some() {
+        backfill.replaying = backfill.checkpoint_every;
+    }
+    backfill.checkpoint = Some(Box::new(checkpoint));
+    Ok(backfill)
+}
+
+/// the last signature a backfill got through, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillCursor {
+    pub signature: Signature,
+    pub slot: Slot,
+}
+
+/// where a backfill keeps its `BackfillCursor` between runs
+pub trait Checkpoint {
+    fn load(&self) -> io::Result<Option<BackfillCursor>>;
+
+    fn save(&self, cursor: &BackfillCursor) -> io::Result<()>;
+}
+
+/// the cursor as `{"signature": "<base58>", "slot": n}` in one file
+#[derive(Debug, Clone)]
+pub struct JsonFileCheckpoint {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredCursor {
+    signature: String,
+    slot: Slot,
+}
+
+impl JsonFileCheckpoint {
+    /// the file need not exist yet
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Checkpoint for JsonFileCheckpoint {
+    fn load(&self) -> io::Result<Option<BackfillCursor>> {
+        let json = match std::fs::read(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let stored: StoredCursor = serde_json::from_slice(&json)?;
+        let signature =
+            Signature::from_str(&stored.signature).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some(BackfillCursor { signature, slot: stored.slot }))
+    }
+
+    /// written aside and renamed into place, so a crash mid-write keeps the previous cursor
+    fn save(&self, cursor: &BackfillCursor) -> io::Result<()> {
+        let stored = StoredCursor { signature: cursor.signature.to_string(), slot: cursor.slot };
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".partial");
+        std::fs::write(&partial, serde_json::to_vec(&stored)?)?;
+        std::fs::rename(partial, &self.path)
+    }
+}
+
+/// one analyzed transaction of a backfill
+#[derive(Debug, Clone)]
+pub struct BackfillRecord {
+    pub signature: Signature,
+    pub slot: Slot,
+    /// handed out after a resume, before the point the interrupted run is known not to have
+    /// reached. it may have been handed out then too
+    pub possible_duplicate: bool,
+    pub underlying: MintUnderlying,
+    pub flows: Vec<Flow>,
+    /// see `TreasuryAnalyzer::revenue`
+    pub protocol_revenue: Option<ProtocolRevenue>,
+}
+
+/// see `backfill_for_state_account`. with a checkpoint, a record counts as handled once the
+/// next one is asked for
+pub struct Backfill<'a> {
+    client: &'a MarinadeClient,
+    analyzer: Analyzer<'a>,
+    signatures: VecDeque<(Signature, Slot)>,
+    instructions: Option<Vec<MarinadeFinanceInstruction>>,
+    checkpoint: Option<Box<dyn Checkpoint + 'a>>,
+    checkpoint_every: usize,
+    /// handed out or skipped since the last save
+    uncommitted: usize,
+    last_handled: Option<BackfillCursor>,
+    /// signatures left that an interrupted run may already have handed out
+    replaying: usize,
+    skipped: Vec<(Signature, SkipReason)>,
+    pool: Option<Pool>,
+    shutdown: Option<ShutdownHandle>,
+    /// signatures handled since the start, skipped by the instruction filter included
+    handled: usize,
+}
+
+impl<'a> Backfill<'a> {
+    fn new(
+        client: &'a MarinadeClient,
+        signatures: Vec<(Signature, Slot)>,
+        options: BackfillOptions,
+        skipped: Vec<(Signature, SkipReason)>,
+    ) -> Self {
+        let pool = (options.workers > 1).then(|| {
+            Pool::spawn(client, options.instructions.clone(), options.workers, options.reorder_window)
+        });
+        Self {
+            client,
+            analyzer: client.analyzer(),
+            signatures: signatures.into(),
+            instructions: options.instructions,
+            checkpoint: None,
+            checkpoint_every: options.checkpoint_every.max(1),
+            uncommitted: 0,
+            last_handled: None,
+            replaying: 0,
+            skipped,
+            pool,
+            shutdown: options.shutdown,
+            handled: 0,
+        }
+    }
+}
+
+impl Backfill<'_> {
+    /// the candidates `backfill_signatures` didn't fetch, and why
+    pub fn skipped(&self) -> &[(Signature, SkipReason)] {
+        &self.skipped
+    }
+
+    /// signatures whose records aren't out yet, oldest first, those the workers are on included
+    pub fn remaining(&self) -> impl Iterator<Item = &Signature> {
+        let pending = self.pool.iter().flat_map(|pool| pool.pending.iter().map(|(signature, ..)| signature));
+        pending.chain(self.signatures.iter().map(|(signature, _)| signature))
+    }
+
+    /// the signatures handled so far as completed, and the rest as abandoned. for a backfill
+    /// that was shut down, once the iterator is done
+    pub fn summary(&self) -> ShutdownSummary {
+        ShutdownSummary { completed: self.handled, abandoned: self.remaining().count() }
+    }
+
+    /// the oldest signature not handled yet, with what handling it came to. None once
+    /// everything is handled, or when shut down
+    fn next_outcome(&mut self) -> Option<(Signature, Slot, Outcome)> {
+        if let Some(pool) = &mut self.pool {
+            return pool.next(&mut self.signatures, self.shutdown.as_ref());
+        }
+        if self.shutdown.as_ref().is_some_and(ShutdownHandle::is_triggered) {
+            return None;
+        }
+        let (signature, slot) = self.signatures.pop_front()?;
+        let instructions = self.instructions.as_deref();
+        let outcome = handle(self.client, instructions, signature, slot, |tx| self.analyzer.analyze(tx));
+        Some((signature, slot, outcome))
+    }
+
+    /// save the cursor if `every` signatures went by since the last save
+    fn commit(&mut self, every: usize) {
+        let (Some(checkpoint), Some(cursor)) = (&self.checkpoint, &self.last_handled) else {
+            return;
+        };
+        if self.uncommitted < every {
+            return;
+        }
+        match checkpoint.save(cursor) {
+            Ok(()) => self.uncommitted = 0,
+            Err(e) => warn!(error = %e, signature = %cursor.signature, "failed to save backfill checkpoint"),
+        }
+    }
+}
+
+impl Iterator for Backfill<'_> {
+    type Item = Result<BackfillRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.commit(self.checkpoint_every);
+        while let Some((signature, slot, outcome)) = self.next_outcome() {
+            self.last_handled = Some(BackfillCursor { signature, slot });
+            self.uncommitted += 1;
+            self.handled += 1;
+            let possible_duplicate = self.replaying > 0;
+            self.replaying = self.replaying.saturating_sub(1);
+            if let Some(result) = outcome {
+                return Some(result.map(|record| BackfillRecord { possible_duplicate, ..record }));
+            }
+        }
+        self.commit(1);
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining().count()))
+    }
+}
+
+/// a signature's record, None when the transaction has none of the wanted instructions
+type Outcome = Option<Result<BackfillRecord>>;
+
+/// fetch and analyze one signature, `possible_duplicate` left for the caller to set
+fn handle(
+    client: &MarinadeClient,
+    instructions: Option<&[MarinadeFinanceInstruction]>,
+    signature: Signature,
+    slot: Slot,
+    analyze: impl FnOnce(&EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying>,
+) -> Outcome {
+    let tx = match client.fetch_transaction(signature) {
+        Ok(tx) => tx,
+        Err(e) => return Some(Err(e)),
+    };
+    let program_id = client.deployment().program_id;
+    if let Some(wanted) = instructions {
+        let found = marinade_instructions(&tx, &program_id).unwrap_or_default();
+        if !found.iter().any(|ix| wanted.contains(ix)) {
+            debug!(%signature, "no wanted instructions, skipping");
+            return None;
+        }
+    }
+    let record = analyze(&tx);
+    let flows = transaction_flows(&tx, &program_id);
+    Some(record.and_then(|underlying| {
+        let protocol_revenue = client.treasury_analyzer().revenue(&tx)?;
+        Ok(BackfillRecord { signature, slot, possible_duplicate: false, underlying, flows, protocol_revenue })
+    }))
+}
+
+// distinct slots whose state the workers share, like `Pipeline`'s default
+const STATE_SLOTS: usize = 1024;
+
+/// the workers of a backfill with more than one. signatures go to them in chain order, at most
+/// `window` ahead of the oldest record not handed out, and their outcomes wait in `pending`
+/// until everything before them is out. dropping it stops each worker after its current item
+struct Pool {
+    jobs: Sender<(usize, Signature, Slot)>,
+    results: Receiver<(usize, Outcome)>,
+    /// handed to the workers and not out yet, oldest first, with the outcome once it's in
+    pending: VecDeque<(Signature, Slot, Option<Outcome>)>,
+    /// the index of `pending`'s front, counted from the first signature handed out
+    first: usize,
+    window: usize,
+}
+
+impl Pool {
+    fn spawn(
+        client: &MarinadeClient,
+        instructions: Option<Vec<MarinadeFinanceInstruction>>,
+        workers: usize,
+        window: usize,
+    ) -> Self {
+        let (jobs, queue) = mpsc::channel::<(usize, Signature, Slot)>();
+        let (done, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let states = Arc::new(SlotStates::new(STATE_SLOTS));
+        for _ in 0..workers {
+            let (client, queue, done) = (client.clone(), queue.clone(), done.clone());
+            let (states, instructions) = (states.clone(), instructions.clone());
+            std::thread::spawn(move || loop {
+                // the lock is only held while waiting for the next signature
+                let job = queue.lock().unwrap().recv();
+                let Ok((index, signature, slot)) = job else {
+                    break;
+                };
+                let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    let analyze = |tx: &_| analyze_fetched(&client, &states, tx);
+                    handle(&client, instructions.as_deref(), signature, slot, analyze)
+                }));
+                let outcome = outcome.unwrap_or_else(|panic| Some(Err(panicked(panic.as_ref(), signature))));
+                if done.send((index, outcome)).is_err() {
+                    debug!("backfill dropped, worker exiting");
+                    break;
+                }
+            });
+        }
+        Self { jobs, results, pending: VecDeque::new(), first: 0, window: window.max(workers) }
+    }
+
+    /// the oldest outcome, once it's in, after topping the workers up from `signatures`. once
+    /// `shutdown` is triggered nothing more goes to the workers, and None comes instead of an
+    /// outcome still out at the deadline
+    fn next(
+        &mut self,
+        signatures: &mut VecDeque<(Signature, Slot)>,
+        shutdown: Option<&ShutdownHandle>,
+    ) -> Option<(Signature, Slot, Outcome)> {
+        let deadline = shutdown.and_then(ShutdownHandle::deadline);
+        while deadline.is_none() && self.pending.len() < self.window {
+            let Some((signature, slot)) = signatures.pop_front() else {
+                break;
+            };
+            let index = self.first + self.pending.len();
+            self.jobs.send((index, signature, slot)).expect("backfill workers outlive the pool");
+            self.pending.push_back((signature, slot, None));
+        }
+        while self.pending.front()?.2.is_none() {
+            let (index, outcome) = match deadline {
+                None => self.results.recv().expect("backfill workers outlive the pool"),
+                Some(deadline) => match self.results.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(result) => result,
+                    Err(RecvTimeoutError::Timeout) => {
+                        warn!(in_flight = self.pending.len(), "backfill grace period ran out");
+                        return None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => panic!("backfill workers outlive the pool"),
+                },
+            };
+            self.pending[index - self.first].2 = Some(outcome);
+        }
+        let (signature, slot, outcome) = self.pending.pop_front()?;
+        self.first += 1;
+        Some((signature, slot, outcome.expect("waited for above")))
+    }
+}
+
+/// `ErrorKind::WorkerPanicked` for a worker's panic on `signature`
+fn panicked(panic: &(dyn std::any::Any + Send), signature: Signature) -> Error {
+    let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "no message".to_string(),
+    };
+    warn!(%signature, %message, "backfill worker panicked");
+    Error::new(ErrorKind::WorkerPanicked { message }).with_signature(Some(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::transaction::TransactionError;
+    use std::sync::Arc;
+
+    fn status(signature: Signature, slot: u64) -> RpcConfirmedTransactionStatusWithSignature {
+        RpcConfirmedTransactionStatusWithSignature {
+            signature: signature.to_string(),
+            slot,
+            err: None,
+            memo: None,
+            block_time: Some(FIXTURE_BLOCK_TIME + slot as i64),
+            confirmation_status: None,
+        }
+    }
+
+    /// seven deposits at slots 0..7 of the fixture's epoch, plus one failed tx at slot 7
+    fn history() -> (Arc<MockFetcher>, Vec<Signature>) {
+        let (rpc, signatures) = history_of(8, MockFetcher::new());
+        (Arc::new(rpc), signatures)
+    }
+
+    /// `len - 1` txs at slots from 0, a liquid unstake at 3 and deposits otherwise, then a
+    /// failed one
+    fn history_of(len: usize, rpc: MockFetcher) -> (MockFetcher, Vec<Signature>) {
+        let signatures: Vec<Signature> = (0..len).map(|_| Signature::new_unique()).collect();
+        let mut rpc = rpc.with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let mut statuses = Vec::new();
+        for (slot, signature) in signatures.iter().enumerate() {
+            let slot = slot as u64;
+            let tx = marinade_transaction(
+                FIXTURE_SLOT + slot,
+                Some(FIXTURE_BLOCK_TIME + slot as i64),
+                &[if slot == 3 { MarinadeFinanceInstruction::LiquidUnstake } else { MarinadeFinanceInstruction::Deposit }],
+            );
+            rpc = rpc.with_transaction(*signature, tx);
+            statuses.push(status(*signature, slot));
+        }
+        statuses[len - 1].err = Some(TransactionError::InstructionError(0, InstructionError::Custom(1)));
+        (rpc.with_signatures(MARINADE_STATE_PUBKEY, statuses), signatures)
+    }
+
+    fn options(page_size: usize) -> BackfillOptions {
+        BackfillOptions { page_size, page_delay: Duration::ZERO, ..BackfillOptions::default() }
+    }
+
+    #[test]
+    fn test_pages_oldest_first_without_gaps() {
+        let (rpc, signatures) = history();
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        // page sizes that split evenly, unevenly and not at all
+        for page_size in [1, 2, 3, 4, 8, 100] {
+            let backfill = backfill_for_state_account(&client, options(page_size)).unwrap();
+            assert_eq!(backfill.remaining().copied().collect::<Vec<_>>(), signatures[..7], "page size {}", page_size);
+        }
+        assert_eq!(rpc.call_count("getSignaturesForAddress"), 9 + 5 + 3 + 3 + 2 + 1);
+
+        let backfill = backfill_for_state_account(&client, options(3)).unwrap();
+        let times: Vec<i64> = backfill.map(|r| r.unwrap().underlying.block_time).collect();
+        assert_eq!(times, (0..7).map(|slot| FIXTURE_BLOCK_TIME + slot).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_start_end_time_and_instruction_filter() {
+        let (rpc, signatures) = history();
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let bounded = BackfillOptions { start: Some(signatures[1]), end_time: Some(FIXTURE_BLOCK_TIME + 5), ..options(2) };
+        let backfill = backfill_for_state_account(&client, bounded.clone()).unwrap();
+        assert_eq!(backfill.remaining().copied().collect::<Vec<_>>(), signatures[2..6]);
+
+        let unstakes = BackfillOptions { instructions: Some(vec![MarinadeFinanceInstruction::LiquidUnstake]), ..bounded };
+        let found: Vec<BackfillRecord> = backfill_for_state_account(&client, unstakes).unwrap().map(Result::unwrap).collect();
+        assert_eq!(found.iter().map(|r| (r.signature, r.slot)).collect::<Vec<_>>(), [(signatures[3], 3)]);
+        assert_eq!(found[0].underlying.block_time, FIXTURE_BLOCK_TIME + 3);
+
+        // a signature the node can't serve is reported, and the rest still come through
+        let rpc = MockFetcher::new()
+            .with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()))
+            .with_signatures(MARINADE_STATE_PUBKEY, vec![status(Signature::new_unique(), 0), status(signatures[1], 1)]);
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let rpc = rpc.with_transaction(signatures[1], tx);
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let results: Vec<_> = backfill_for_state_account(&client, options(10)).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].as_ref().unwrap_err().signature().is_some());
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    fn test_candidates_are_status_checked_and_ordered() {
+        use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+        let (rpc, signatures) = history();
+        let mut rpc = Arc::into_inner(rpc).unwrap();
+        for (slot, signature) in signatures.iter().enumerate() {
+            let err = (slot == 7).then_some(TransactionError::InstructionError(0, InstructionError::Custom(1)));
+            let (slot, status) = (FIXTURE_SLOT + slot as u64, err.clone().map_or(Ok(()), Err));
+            let confirmation_status = Some(TransactionConfirmationStatus::Finalized);
+            let status = TransactionStatus { slot, confirmations: None, status, err, confirmation_status };
+            rpc = rpc.with_signature_status(*signature, status);
+        }
+        let rpc = Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let unknown = Signature::new_unique();
+        let mut candidates: Vec<Signature> = signatures.iter().rev().copied().collect();
+        candidates.insert(3, unknown);
+
+        // kept the unknown one, but without a slot there's nowhere to put it
+        let filter = StatusFilter { skip_unknown: false, ..StatusFilter::default() };
+        let options = BackfillOptions { status_filter: filter, ..options(10) };
+        let backfill = backfill_signatures(&client, &candidates, options).unwrap();
+        assert_eq!(backfill.skipped(), [(signatures[7], SkipReason::Failed), (unknown, SkipReason::Unknown)]);
+        assert_eq!(backfill.remaining().copied().collect::<Vec<_>>(), signatures[..7]);
+        let slots: Vec<Slot> = backfill.map(|r| r.unwrap().slot).collect();
+        assert_eq!(slots, (0..7).map(|slot| FIXTURE_SLOT + slot).collect::<Vec<_>>());
+        assert_eq!((rpc.call_count("getSignatureStatuses"), rpc.call_count("getTransaction")), (1, 7));
+    }
+
+    #[test]
+    fn test_killed_backfill_resumes_from_checkpoint() {
+        let (rpc, signatures) = history();
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let path = std::env::temp_dir().join(format!("marinade-backfill-checkpoint-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let options = BackfillOptions { checkpoint_every: 2, ..options(3) };
+
+        // dies holding the fifth record, before it is known to be handled
+        let first: Vec<BackfillRecord> = backfill_with_checkpoint(&client, options.clone(), JsonFileCheckpoint::new(&path))
+            .unwrap()
+            .take(5)
+            .map(Result::unwrap)
+            .collect();
+        assert!(first.iter().all(|r| !r.possible_duplicate));
+        let stored = JsonFileCheckpoint::new(&path).load().unwrap().unwrap();
+        assert_eq!(stored, BackfillCursor { signature: signatures[3], slot: 3 });
+
+        let resumed = backfill_with_checkpoint(&client, options.clone(), JsonFileCheckpoint::new(&path)).unwrap();
+        let resumed: Vec<BackfillRecord> = resumed.map(Result::unwrap).collect();
+        assert_eq!(resumed.iter().map(|r| r.possible_duplicate).collect::<Vec<_>>(), [true, true, false]);
+        // the fifth record comes again, none is missed
+        let mut seen: Vec<Signature> = first.iter().chain(&resumed).map(|r| r.signature).collect();
+        assert_eq!(seen.len(), 8);
+        seen.dedup();
+        assert_eq!(seen, signatures[..7]);
+
+        // a finished run leaves the last signature behind, so a rerun has nothing to do
+        assert_eq!(JsonFileCheckpoint::new(&path).load().unwrap().unwrap().signature, signatures[6]);
+        assert_eq!(backfill_with_checkpoint(&client, options, JsonFileCheckpoint::new(&path)).unwrap().count(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_workers_keep_chain_order() {
+        // up to 4ms per call, different for every item, so workers finish out of order
+        let rpc = MockFetcher::new().with_jitter(Duration::from_millis(4));
+        let (rpc, signatures) = history_of(201, rpc);
+        let rpc = Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let options = BackfillOptions { workers: 6, reorder_window: 12, ..options(50) };
+
+        let mut seen = Vec::new();
+        for record in backfill_for_state_account(&client, options).unwrap() {
+            seen.push(record.unwrap());
+            // never more fetched than the window ahead of what's out
+            assert!(rpc.call_count("getTransaction") <= seen.len() + 12);
+        }
+        assert_eq!(seen.iter().map(|r| r.signature).collect::<Vec<_>>(), signatures[..200]);
+        assert!(seen.iter().enumerate().all(|(i, r)| r.underlying.block_time == FIXTURE_BLOCK_TIME + i as i64));
+        assert_eq!(rpc.call_count("getTransaction"), 200);
+        assert!((2..=6).contains(&rpc.max_in_flight()), "{}", rpc.max_in_flight());
+    }
+
+    #[test]
+    fn test_worker_panic_is_the_items_error() {
+        let (rpc, signatures) = history();
+        let rpc = Arc::into_inner(rpc).unwrap().with_panic_on(signatures[2]);
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let options = BackfillOptions { workers: 3, ..options(10) };
+        let results: Vec<_> = backfill_for_state_account(&client, options).unwrap().collect();
+
+        assert_eq!(results.len(), 7);
+        let e = results[2].as_ref().unwrap_err();
+        let ErrorKind::WorkerPanicked { message } = e.kind() else {
+            panic!("expected a panic, got {}", e);
+        };
+        assert!(message.starts_with("injected panic fetching"), "{}", message);
+        assert_eq!(e.signature(), Some(&signatures[2]));
+        let ok: Vec<Signature> = results.iter().filter_map(|r| r.as_ref().ok()).map(|r| r.signature).collect();
+        assert_eq!(ok, [&signatures[..2], &signatures[3..7]].concat());
+    }
+
+    #[test]
+    fn test_shutdown_drains_the_workers_and_saves_the_checkpoint() {
+        let (rpc, signatures) = history_of(31, MockFetcher::new().with_latency(Duration::from_millis(5)));
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let path = std::env::temp_dir().join(format!("marinade-backfill-shutdown-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let handle = ShutdownHandle::with_grace_period(Duration::from_secs(10));
+        let shutdown = Some(handle.clone());
+        let options = BackfillOptions { workers: 3, reorder_window: 6, checkpoint_every: 100, shutdown, ..options(10) };
+
+        let mut backfill = backfill_with_checkpoint(&client, options, JsonFileCheckpoint::new(&path)).unwrap();
+        let mut seen: Vec<Signature> = backfill.by_ref().take(5).map(|r| r.unwrap().signature).collect();
+        handle.trigger();
+        // what the workers were already on still comes out, in order, and nothing after it
+        seen.extend(backfill.by_ref().map(|r| r.unwrap().signature));
+        assert!((5..=11).contains(&seen.len()), "{}", seen.len());
+        assert_eq!(seen, signatures[..seen.len()]);
+        let summary = backfill.summary();
+        assert_eq!(summary, ShutdownSummary { completed: seen.len(), abandoned: 30 - seen.len() });
+
+        let saved = JsonFileCheckpoint::new(&path).load().unwrap().unwrap();
+        assert_eq!(saved.signature, *seen.last().unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! analyzing many transactions at once, on a bounded number of threads.
+//!
+//! the workers share the client's fetcher, so a `rpc::RateLimited` one caps the whole batch
+//! rather than each worker, and share the states they fetch: transactions landing in the same
+//! slot cost one state fetch between them, however many workers pick them up. once a
+//! transaction lands in a later epoch than every state kept, those states are dropped: the
+//! crank at the boundary changed the price, and the results past it are valued afresh.
+//!
+//! solana-client has no batched json-rpc, so `fetch_transactions` gets its transactions with
+//! one `getTransaction` each, `concurrency` of them in flight
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use solana_sdk::clock::Slot;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::marinade::MinimalState;
+use crate::client::MarinadeClient;
+use crate::error::Result;
+use crate::observer::CacheKind;
+use crate::signatures::{check_signature_statuses, StatusCheck, StatusFilter};
+use crate::transaction::TransactionInput;
+use crate::verbosity::debug;
+use crate::{fetch_post_state, mint_underlying_from_state, MintUnderlying};
+
+/// workers `analyze_signatures` runs when asked for 0
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// the state with the context slot it was read at
+type SlotState = Arc<Mutex<Option<(Slot, MinimalState)>>>;
+
+/// states fetched so far, by the slot of the transaction they were fetched for. the per-slot
+/// lock makes workers that need a slot being fetched wait for it instead of fetching it again
+pub(crate) struct SlotStates {
+    slots: Mutex<BTreeMap<Slot, SlotState>>,
+    capacity: usize,
+    // mainnet-beta's, like `Analyzer`'s default
+    epoch_schedule: EpochSchedule,
+}
+
+impl SlotStates {
+    /// keeping the `capacity` highest slots seen
+    pub(crate) fn new(capacity: usize) -> Self {
+        let epoch_schedule = EpochSchedule::without_warmup();
+        Self { slots: Mutex::new(BTreeMap::new()), capacity: capacity.max(1), epoch_schedule }
+    }
+
+    /// the entry for `slot`, and whether it's the first past an epoch boundary, every state kept
+    /// until then being from an earlier epoch and dropped
+    fn slot(&self, slot: Slot) -> (SlotState, bool) {
+        let mut slots = self.slots.lock().unwrap();
+        let epoch = |slot: Slot| self.epoch_schedule.get_epoch(slot);
+        let crossed = slots.last_key_value().is_some_and(|(latest, _)| epoch(*latest) < epoch(slot));
+        if crossed {
+            debug!(slot, epoch = epoch(slot), dropped = slots.len(), "batch crossed an epoch boundary");
+            slots.clear();
+        }
+        let state = slots.entry(slot).or_default().clone();
+        while slots.len() > self.capacity {
+            slots.pop_first();
+        }
+        (state, crossed)
+    }
+}
+
+/// the transactions of `signatures` with at most `concurrency` fetches in flight, in the order
+/// of `signatures`. a signature the node doesn't know, or a failed fetch, only fails its own entry
+pub fn fetch_transactions(
+    client: &MarinadeClient,
+    signatures: &[Signature],
+    concurrency: usize,
+) -> Vec<Result<EncodedConfirmedTransactionWithStatusMeta>> {
+    bounded(signatures, concurrency, |signature| client.fetch_transaction(signature))
+}
+
+/// `fetch_transactions`, then analyze what was fetched on as many workers, returning the results
+/// in the order of `signatures`. a failure only fails its own entry
+pub fn analyze_signatures(
+    client: &MarinadeClient,
+    signatures: &[Signature],
+    concurrency: usize,
+) -> Vec<Result<MintUnderlying>> {
+    let states = SlotStates::new(usize::MAX);
+    let fetched = fetch_transactions(client, signatures, concurrency);
+    let analyzed = bounded(&fetched, concurrency, |tx| {
+        tx.as_ref().ok().map(|tx| analyze_fetched(client, &states, tx))
+    });
+    let analyzed = fetched.into_iter().zip(analyzed);
+    analyzed.map(|(tx, result)| tx.and_then(|_| result.expect("every fetched transaction is analyzed"))).collect()
+}
+
+/// `f` of every item on at most `concurrency` threads, `DEFAULT_CONCURRENCY` for 0, in the order
+/// of `items`
+fn bounded<T: Sync, R: Send>(items: &[T], concurrency: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let concurrency = match concurrency {
+        0 => DEFAULT_CONCURRENCY,
+        n => n,
+    }
+    .min(items.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().map(|result| result.expect("every index is run")).collect()
+}
+
+/// `analyze_signatures` of what `check_signature_statuses` kept
+#[derive(Debug)]
+pub struct CheckedAnalysis {
+    pub statuses: StatusCheck,
+    /// one per `statuses.kept`, in its order
+    pub results: Vec<Result<MintUnderlying>>,
+}
+
+/// `analyze_signatures` after a status check, so each signature `filter` drops costs a share of
+/// a status call instead of a `get_transaction`. fails only when a status call does
+pub fn analyze_checked_signatures(
+    client: &MarinadeClient,
+    signatures: &[Signature],
+    concurrency: usize,
+    filter: StatusFilter,
+) -> Result<CheckedAnalysis> {
+    let statuses = check_signature_statuses(client, signatures, filter)?;
+    let kept: Vec<Signature> = statuses.kept.iter().map(|checked| checked.signature).collect();
+    let results = analyze_signatures(client, &kept, concurrency);
+    Ok(CheckedAnalysis { statuses, results })
+}
+
+/// `MarinadeClient::analyze_transaction`, taking the post state from `states` when another
+/// transaction of the same slot already fetched it. in a run ordered by slot, the first
+/// transaction of each new epoch comes out with `crossed_epoch_boundary`
+pub(crate) fn analyze_fetched(
+    client: &MarinadeClient,
+    states: &SlotStates,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<MintUnderlying> {
+    let tx = TransactionInput::from(tx);
+    let span = crate::analyze_span(tx);
+    let _guard = span.enter();
+    let (rpc_client, deployment, options) = (client.rpc_client(), client.deployment(), client.options());
+    let (slot, crossed_epoch_boundary) = states.slot(tx.slot());
+    let mut cached = slot.lock().unwrap();
+    let (state, reused) = match *cached {
+        Some(state) => {
+            options.observe(|o| o.on_cache_hit(CacheKind::State));
+            (Ok(state), true)
+        }
+        None => {
+            options.observe(|o| o.on_cache_miss(CacheKind::State));
+            let fetched = fetch_post_state(rpc_client, &deployment.state, tx.slot(), options);
+            (fetched.inspect(|state| *cached = Some(*state)), false)
+        }
+    };
+    drop(cached);
+    state
+        .and_then(|(state_slot, state)| {
+            mint_underlying_from_state(rpc_client, tx, state_slot, &state, deployment, options, reused)
+        })
+        .map(|mu| MintUnderlying { crossed_epoch_boundary, ..mu })
+        .map_err(|e| e.with_signature(tx.signature()))
+        .inspect(|_| options.observe(|o| o.on_analysis_complete(tx.slot())))
+        .inspect_err(|e| crate::report_failure(options, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::rpc::RateLimited;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use std::time::{Duration, Instant};
+
+    /// twelve deposits, four to a slot, with block times telling them apart
+    fn batch(latency: Duration) -> (Arc<MockFetcher>, Vec<Signature>) {
+        let signatures: Vec<Signature> = (0..12).map(|_| Signature::new_unique()).collect();
+        let mut rpc = MockFetcher::new().with_latency(latency).with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        for (i, signature) in signatures.iter().enumerate() {
+            let block_time = Some(FIXTURE_BLOCK_TIME + i as i64);
+            let tx = marinade_transaction(FIXTURE_SLOT + i as u64 % 3, block_time, &[MarinadeFinanceInstruction::Deposit]);
+            rpc = rpc.with_transaction(*signature, tx);
+        }
+        (Arc::new(rpc), signatures)
+    }
+
+    #[test]
+    fn test_concurrency_limit_and_input_order() {
+        let (rpc, signatures) = batch(Duration::from_millis(20));
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let mut with_unknown = signatures.clone();
+        with_unknown.insert(5, Signature::new_unique());
+
+        let results = analyze_signatures(&client, &with_unknown, 3);
+        assert!((2..=3).contains(&rpc.max_in_flight()), "{} in flight", rpc.max_in_flight());
+        assert_eq!(results[5].as_ref().unwrap_err().signature(), Some(&with_unknown[5]));
+        let times: Vec<i64> = results.into_iter().filter_map(|result| result.ok()).map(|mu| mu.block_time).collect();
+        assert_eq!(times, (0..12).map(|i| FIXTURE_BLOCK_TIME + i).collect::<Vec<_>>());
+        // one state fetch per slot, not per transaction or per worker
+        assert_eq!(rpc.call_count("getAccountInfo"), 3);
+        assert_eq!(rpc.call_count("getTransaction"), 13);
+    }
+
+    #[test]
+    fn test_fetches_fail_per_signature() {
+        let (rpc, signatures) = batch(Duration::from_millis(10));
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let block_times = |fetched: Vec<Result<EncodedConfirmedTransactionWithStatusMeta>>| {
+            fetched.into_iter().map(|tx| tx.ok().and_then(|tx| tx.block_time)).collect::<Vec<_>>()
+        };
+
+        // one worker takes the signatures in order, so the injected failure is the first's
+        rpc.fail_next("getTransaction", 1);
+        let fetched = block_times(fetch_transactions(&client, &signatures[..3], 1));
+        assert_eq!(fetched, vec![None, Some(FIXTURE_BLOCK_TIME + 1), Some(FIXTURE_BLOCK_TIME + 2)]);
+        assert_eq!(rpc.max_in_flight(), 1);
+
+        let mut with_unknown = signatures.clone();
+        with_unknown.insert(7, Signature::new_unique());
+        let fetched = fetch_transactions(&client, &with_unknown, 4);
+        assert_eq!(fetched[7].as_ref().unwrap_err().signature(), Some(&with_unknown[7]));
+        let mut expected: Vec<_> = (0..12).map(|i| Some(FIXTURE_BLOCK_TIME + i)).collect();
+        expected.insert(7, None);
+        assert_eq!(block_times(fetched), expected);
+        assert!((2..=4).contains(&rpc.max_in_flight()), "{} in flight", rpc.max_in_flight());
+        assert_eq!(rpc.calls(), vec!["getTransaction"; 16]);
+    }
+
+    #[test]
+    fn test_status_check_spares_the_transaction_fetches() {
+        use crate::signatures::SkipReason;
+        use solana_sdk::instruction::InstructionError;
+        use solana_sdk::transaction::TransactionError;
+        use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+        let (rpc, signatures) = batch(Duration::ZERO);
+        let mut rpc = Arc::into_inner(rpc).unwrap();
+        // the first four landed, the next two failed, then two only processed and the rest unknown
+        for (i, signature) in signatures.iter().enumerate().take(8) {
+            let err = (4..6).contains(&i).then_some(TransactionError::InstructionError(0, InstructionError::Custom(1)));
+            let level = match i {
+                6 | 7 => TransactionConfirmationStatus::Processed,
+                _ => TransactionConfirmationStatus::Confirmed,
+            };
+            let (slot, status, confirmation_status) = (FIXTURE_SLOT, err.clone().map_or(Ok(()), Err), Some(level));
+            let status = TransactionStatus { slot, confirmations: Some(1), status, err, confirmation_status };
+            rpc = rpc.with_signature_status(*signature, status);
+        }
+        let rpc = Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+
+        let checked = analyze_checked_signatures(&client, &signatures, 3, StatusFilter::default()).unwrap();
+        let kept: Vec<Signature> = checked.statuses.kept.iter().map(|checked| checked.signature).collect();
+        assert_eq!(kept, signatures[..4]);
+        assert!(checked.results.iter().all(|result| result.is_ok()));
+        let skipped = |reason| checked.statuses.skipped_for(reason);
+        let counts = (skipped(SkipReason::Failed), skipped(SkipReason::Unconfirmed), skipped(SkipReason::Unknown));
+        assert_eq!(counts, (2, 2, 4));
+        assert_eq!((rpc.call_count("getSignatureStatuses"), rpc.call_count("getTransaction")), (1, 4));
+    }
+
+    #[test]
+    fn test_rate_limit_is_shared_by_the_workers() {
+        let (rpc, signatures) = batch(Duration::ZERO);
+        let client = MarinadeClient::builder().rpc_client(RateLimited::new(rpc.clone(), 200)).build();
+        let started = Instant::now();
+        let results = analyze_signatures(&client, &signatures, 6);
+        assert!(results.iter().all(|result| result.is_ok()));
+        // 15 calls 5ms apart, whatever the number of workers
+        assert_eq!(rpc.calls().len(), 15);
+        assert!(started.elapsed() >= Duration::from_millis(70), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_the_first_transaction_past_a_boundary_gets_the_post_crank_state() {
+        use crate::accounts::marinade::MarinadeState;
+        use crate::cache::SnapshotCache;
+        use crate::cluster::Cluster;
+        use crate::snapshot::SnapshotMetadata;
+
+        let dir = std::env::temp_dir().join(format!("marinade-batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = SnapshotCache::new(&dir, Cluster::MainnetBeta);
+        let boundary = EpochSchedule::without_warmup().get_first_slot_in_epoch(FIXTURE_SLOT / 432_000 + 1);
+        // the crank at the boundary leaves a fifth less supply behind the same underlying
+        let pre_crank = sample_state();
+        let post_crank = MarinadeState { msol_supply: pre_crank.msol_supply / 5 * 4, ..sample_state() };
+        for (slot, state) in [(boundary - 1, &pre_crank), (boundary + 10, &post_crank)] {
+            cache.put(state, &SnapshotMetadata::new(&Cluster::MainnetBeta, MARINADE_STATE_PUBKEY, slot)).unwrap();
+        }
+        let slots = [boundary - 3, boundary - 2, boundary - 2, boundary + 1, boundary + 2];
+        let signatures: Vec<Signature> = slots.iter().map(|_| Signature::new_unique()).collect();
+        let mut rpc = MockFetcher::new();
+        for (slot, signature) in slots.iter().zip(&signatures) {
+            let tx = marinade_transaction(*slot, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+            rpc = rpc.with_transaction(*signature, tx);
+        }
+        let client = MarinadeClient::builder().rpc_client(rpc).snapshot_cache(&dir).offline(true).build();
+
+        let results: Vec<_> = analyze_signatures(&client, &signatures, 1).into_iter().map(|r| r.unwrap()).collect();
+        let prices: Vec<_> = results.iter().map(|mu| mu.msol_value).collect();
+        let (pre, post) = (pre_crank.msol_price_lamports(), post_crank.msol_price_lamports());
+        assert_eq!(prices, vec![pre, pre, pre, post, post]);
+        let crossed: Vec<_> = results.iter().map(|mu| mu.crossed_epoch_boundary).collect();
+        assert_eq!(crossed, vec![false, false, false, true, false]);
+        assert!(results[2].state_reused && !results[3].state_reused);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! a directory of state snapshots, `<slot>.json` per fetched context slot plus `latest.json`,
+//! written through by every state fetch and read back by `AnalyzeOptions::offline`
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::accounts::marinade::MarinadeState;
+use crate::cluster::Cluster;
+use crate::snapshot::SnapshotMetadata;
+
+const LATEST: &str = "latest";
+
+#[derive(Debug, Clone)]
+pub struct SnapshotCache {
+    dir: PathBuf,
+    cluster: Cluster,
+}
+
+impl SnapshotCache {
+    /// snapshots of `cluster`'s state, under `dir`. the directory is created on the first write
+    pub fn new(dir: impl Into<PathBuf>, cluster: Cluster) -> Self {
+        Self { dir: dir.into(), cluster }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// the cluster snapshots are written for and expected to be of
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    /// store `state` under `metadata.slot`, and as the latest one unless a newer slot is cached
+    pub fn put(&self, state: &MarinadeState, metadata: &SnapshotMetadata) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        self.write(&metadata.slot.to_string(), state, metadata)?;
+        let newest = self.load(LATEST)?.map(|(_, latest)| latest.slot);
+        if newest.is_none_or(|newest| metadata.slot >= newest) {
+            self.write(LATEST, state, metadata)?;
+        }
+        Ok(())
+    }
+
+    /// the snapshot at the lowest cached slot not before `slot`, the same "at least" the node
+    /// applies to `min_context_slot`; the latest one when `slot` is None
+    pub fn get(&self, slot: Option<u64>) -> io::Result<Option<(MarinadeState, SnapshotMetadata)>> {
+        let Some(slot) = slot else {
+            return self.load(LATEST);
+        };
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut cached = None;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(cached_slot) = path.file_stem().and_then(|stem| stem.to_str()?.parse::<u64>().ok()) {
+                    if cached_slot >= slot && cached.is_none_or(|best| cached_slot < best) {
+                        cached = Some(cached_slot);
+                    }
+                }
+            }
+        }
+        match cached {
+            Some(cached) => self.load(&cached.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// written aside and renamed into place, so readers never see half a file
+    fn write(&self, name: &str, state: &MarinadeState, metadata: &SnapshotMetadata) -> io::Result<()> {
+        let partial = self.dir.join(format!("{}.json.partial", name));
+        state.save_snapshot(&partial, metadata)?;
+        std::fs::rename(partial, self.path(name))
+    }
+
+    fn load(&self, name: &str) -> io::Result<Option<(MarinadeState, SnapshotMetadata)>> {
+        match MarinadeState::load_snapshot(self.path(name)) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// every path through the cache starts at the client
+#[cfg(all(test, feature = "rpc"))]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::client::MarinadeClient;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::error::ErrorKind;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use std::sync::Arc;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("marinade-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_write_through_then_offline() {
+        let dir = temp_dir("offline");
+        let state = sample_state();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let online = MarinadeClient::builder().rpc_client(rpc).snapshot_cache(&dir).build();
+        assert_eq!(online.price(None).unwrap().slot, FIXTURE_SLOT);
+        online.price(Some(FIXTURE_SLOT + 100)).unwrap();
+        assert!(dir.join(format!("{}.json", FIXTURE_SLOT)).exists());
+        let cache = online.options().snapshot_cache.clone().unwrap();
+        assert_eq!(cache.get(None).unwrap().unwrap().1.slot, FIXTURE_SLOT + 100);
+
+        // nothing may reach the node
+        let rpc = Arc::new(MockFetcher::new());
+        let offline = MarinadeClient::builder().rpc_client(rpc.clone()).snapshot_cache(&dir).offline(true).build();
+        let latest = offline.price(None).unwrap();
+        assert_eq!((latest.slot, latest.price_lamports), (FIXTURE_SLOT + 100, state.msol_price_lamports()));
+        // the earliest snapshot not before the requested slot
+        assert_eq!(offline.price(Some(FIXTURE_SLOT - 10)).unwrap().slot, FIXTURE_SLOT);
+        assert_eq!(offline.price(Some(FIXTURE_SLOT + 1)).unwrap().slot, FIXTURE_SLOT + 100);
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        assert_eq!(offline.analyze_transaction(&tx).unwrap().msol_value, state.msol_price_lamports());
+        assert!(rpc.calls().is_empty());
+
+        let err = offline.price(Some(FIXTURE_SLOT + 101)).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::OfflineMiss { slot: Some(slot) } if *slot == FIXTURE_SLOT + 101));
+        let devnet = MarinadeClient::builder()
+            .cluster(crate::cluster::Cluster::Devnet)
+            .rpc_client(MockFetcher::new())
+            .snapshot_cache(&dir)
+            .offline(true)
+            .build();
+        assert!(matches!(devnet.price(None).unwrap_err().kind(), ErrorKind::ClusterMismatch { .. }));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_offline_miss_without_snapshots() {
+        let dir = temp_dir("empty");
+        let client = MarinadeClient::builder().rpc_client(MockFetcher::new()).snapshot_cache(&dir).offline(true).build();
+        for slot in [None, Some(FIXTURE_SLOT)] {
+            let err = client.price(slot).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::OfflineMiss { slot: missed } if *missed == slot));
+            assert_eq!(err.pubkey(), Some(&MARINADE_STATE_PUBKEY));
+        }
+        // offline without a cache at all misses too
+        let client = MarinadeClient::builder().rpc_client(MockFetcher::new()).offline(true).build();
+        assert!(matches!(client.price(None).unwrap_err().kind(), ErrorKind::OfflineMiss { slot: None }));
+        assert!(!dir.exists());
+    }
+}
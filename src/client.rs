@@ -0,0 +1,658 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+#[cfg(feature = "blocking")]
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::transaction::TransactionVersion;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::diff::MarinadeStateDiff;
+use crate::accounts::marinade::MarinadeState;
+use crate::analyzer::Analyzer;
+use crate::cache::SnapshotCache;
+use crate::cluster::Cluster;
+use crate::config::ClientConfig;
+use crate::deployment::DeploymentConfig;
+use crate::deposit::{self, DepositQuote};
+use crate::epoch::{EpochContext, EpochTracker};
+use crate::error::{Error, ErrorKind, Result};
+use crate::events::EventClassifier;
+use crate::headers::RpcHeaders;
+use crate::liquidity::LiquidityAnalyzer;
+use crate::observer::{Observed, Observer};
+use crate::parsers::marinade::MarinadeParser;
+use crate::parsers::registry::Registry;
+use crate::parsers::{ParserContext, PriceSnapshot};
+use crate::rpc::RpcFetcher;
+use crate::snapshot::SnapshotMetadata;
+use crate::transaction::{IntoSignature, TransactionInput};
+use crate::treasury::TreasuryAnalyzer;
+use crate::unstake::{self, LiquidUnstakeQuote};
+use crate::valuation::{fetch_multiple_accounts, fetch_valuation_accounts, verify_msol_mint, MsolMintReport};
+use crate::verbosity::Verbosity;
+use crate::raw::RawState;
+use crate::{
+    analyze_with, fetch_full_state, fetch_state, fetch_transaction_with_options, find_and_parse_marinade_state,
+    find_and_parse_marinade_state_raw, AnalyzeOptions, MintUnderlying,
+};
+
+/// an rpc connection bound to one cluster's marinade deployment. cheap to clone
+#[derive(Clone)]
+pub struct MarinadeClient {
+    rpc_client: Arc<dyn RpcFetcher>,
+    rpc_url: Option<String>,
+    cluster: Cluster,
+    deployment: DeploymentConfig,
+    options: AnalyzeOptions,
+    registry: Arc<Registry>,
+    /// shared by clones, and only set once the mint passes
+    msol_mint: Arc<OnceLock<MsolMintReport>>,
+    epoch: Arc<EpochTracker>,
+}
+
+impl MarinadeClient {
+    pub fn builder() -> MarinadeClientBuilder {
+        MarinadeClientBuilder::default()
+    }
+
+    /// a client configured by the `MARINADE_PARSER_*` variables, see `ClientConfig::from_env`
+    pub fn from_env() -> Result<MarinadeClient> {
+        Ok(Self::builder().config(ClientConfig::from_env()?).build())
+    }
+
+    /// a client configured by the toml file at `path`, with the environment overriding it
+    pub fn from_config_path(path: impl AsRef<Path>) -> Result<MarinadeClient> {
+        let config = ClientConfig::from_env()?.or(ClientConfig::from_path(path)?);
+        Ok(Self::builder().config(config).build())
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    /// the endpoint the client's own `RpcClient` talks to, None with a fetcher given to the builder
+    pub fn rpc_url(&self) -> Option<&str> {
+        self.rpc_url.as_deref()
+    }
+
+    pub fn deployment(&self) -> &DeploymentConfig {
+        &self.deployment
+    }
+
+    pub fn options(&self) -> &AnalyzeOptions {
+        &self.options
+    }
+
+    pub fn rpc_client(&self) -> &dyn RpcFetcher {
+        self.rpc_client.as_ref()
+    }
+
+    /// the parsers `compare_rates` and other multi-protocol calls look mints up in
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// a copy of the client reading at `commitment`, for the odd call that wants something other
+    /// than the client's setting, e.g. `finalized` prices that can't be rolled back by a fork.
+    /// see `AnalyzeOptions::commitment` for the tradeoff
+    pub fn with_commitment(&self, commitment: CommitmentConfig) -> MarinadeClient {
+        let mut client = self.clone();
+        client.options.commitment = commitment;
+        client
+    }
+
+    /// a copy of the client that waits up to `deadline` for a node lagging behind the slot it is
+    /// asked about, see `AnalyzeOptions::wait_for_slot`
+    pub fn with_slot_wait(&self, deadline: Option<Duration>) -> MarinadeClient {
+        let mut client = self.clone();
+        client.options.wait_for_slot = deadline;
+        client
+    }
+
+    /// at the client's commitment, or `confirmed` if that is `processed`, which the node refuses
+    /// for transactions. one newer than `AnalyzeOptions::max_transaction_version` fails with
+    /// `ErrorKind::UnsupportedTransactionVersion`
+    pub fn fetch_transaction(&self, signature: impl IntoSignature) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+        fetch_transaction_with_options(self.rpc_client(), signature, &self.options)
+    }
+
+    /// takes a fetched transaction or, through `TransactionInput`, one in the native types
+    pub fn analyze_transaction<'t>(&self, tx: impl Into<TransactionInput<'t>>) -> Result<MintUnderlying> {
+        analyze_with(self.rpc_client(), tx.into(), &self.deployment, &self.options)
+    }
+
+    /// `fetch_transaction` then `analyze_transaction`
+    pub fn analyze_signature(&self, signature: impl IntoSignature) -> Result<MintUnderlying> {
+        let signature = signature.into_signature()?;
+        let tx = self.fetch_transaction(signature)?;
+        self.analyze_transaction(&tx).map_err(|e| e.with_signature(Some(signature)))
+    }
+
+    /// the mSOL price as of at least `slot`, or wherever the node is when None
+    pub fn price(&self, slot: Option<u64>) -> Result<PriceSnapshot> {
+        let (context_slot, state) = fetch_state(self.rpc_client(), &self.deployment.state, slot, &self.options)?;
+        Ok(PriceSnapshot::from_marinade_state(&state, context_slot))
+    }
+
+    /// `price`, with the epoch context of the snapshot's slot attached when the node's current
+    /// epoch holds it
+    pub fn price_with_epoch(&self, slot: Option<u64>) -> Result<PriceSnapshot> {
+        let snapshot = self.price(slot)?;
+        Ok(match self.epoch_context(snapshot.slot)? {
+            Some(epoch) => snapshot.with_epoch(epoch),
+            None => snapshot,
+        })
+    }
+
+    /// where `slot` sits in its epoch, asking the node once per epoch, see `EpochTracker`
+    pub fn epoch_context(&self, slot: u64) -> Result<Option<EpochContext>> {
+        let _verbosity = self.options.verbosity.enter();
+        self.epoch.context_at(self.rpc_client(), slot)
+    }
+
+    /// the full current state, with metadata for `MarinadeState::save_snapshot` naming the
+    /// cluster's rpc url as the source
+    pub fn state_snapshot(&self) -> Result<(MarinadeState, SnapshotMetadata)> {
+        let (slot, state) = find_and_parse_marinade_state(self.rpc_client(), &self.deployment.state, None, &self.options)?;
+        Ok((state, SnapshotMetadata::new(&self.cluster, self.deployment.state, slot)))
+    }
+
+    /// the state as of at least `slot`, or the latest when None, with the account bytes it was
+    /// parsed from, for checking the parser against the chain. see `raw`
+    pub fn raw_state(&self, slot: Option<u64>) -> Result<RawState> {
+        find_and_parse_marinade_state_raw(self.rpc_client(), &self.deployment.state, slot, &self.options)
+    }
+
+    /// lamports in the state's `operational_sol_account`, with the context slot they were read at
+    pub fn operational_balance(&self) -> Result<(u64, u64)> {
+        let _verbosity = self.options.verbosity.enter();
+        let rpc = self.rpc_client();
+        let (slot, state) = find_and_parse_marinade_state(rpc, &self.deployment.state, None, &self.options)?;
+        let pubkeys = [state.operational_sol_account];
+        let roles = ["operational sol account"];
+        let (slot, accounts) = fetch_multiple_accounts(rpc, &pubkeys, &roles, Some(slot), &self.options)?;
+        Ok((slot, accounts[0].lamports))
+    }
+
+    /// what changed in the state from the one as of at least `slot_a` to the one as of at least
+    /// `slot_b`. a node only serves its current state, so this is mostly of use offline, against
+    /// snapshots cached over time; the diff's `slots` tell which states were actually read
+    pub fn diff_between_slots(&self, slot_a: u64, slot_b: u64) -> Result<MarinadeStateDiff> {
+        let fetch = |slot| fetch_full_state(self.rpc_client(), &self.deployment.state, Some(slot), &self.options);
+        let ((older_slot, older), (newer_slot, newer)) = (fetch(slot_a)?, fetch(slot_b)?);
+        Ok(MarinadeStateDiff { slots: Some((older_slot, newer_slot)), ..MarinadeState::diff(&older, &newer) })
+    }
+
+    /// `valuation::verify_msol_mint` against the client's deployment, asked of the node the first
+    /// time and remembered after it passes. the quotes call it before doing any token math
+    pub fn verify_msol_mint(&self) -> Result<MsolMintReport> {
+        let _verbosity = self.options.verbosity.enter();
+        if let Some(report) = self.msol_mint.get() {
+            return Ok(report.clone());
+        }
+        let report = verify_msol_mint(self.rpc_client(), &self.deployment, &self.options)?;
+        Ok(self.msol_mint.get_or_init(|| report).clone())
+    }
+
+    /// quote a deposit of `lamports` against the current state and liq pool, see
+    /// `deposit::simulate_deposit`. always asks the node, the snapshot cache has no liq pool
+    pub fn simulate_deposit(&self, lamports: u64) -> Result<DepositQuote> {
+        let _verbosity = self.options.verbosity.enter();
+        self.verify_msol_mint()?;
+        let addresses = self.deployment.valuation_addresses();
+        let accounts = fetch_valuation_accounts(self.rpc_client(), &addresses, None, &self.options)?;
+        let quote = deposit::simulate_deposit(&accounts.state, accounts.liq_pool.msol_leg_amount, lamports)
+            .map_err(|e| Error::new(ErrorKind::Deposit(e)).with_slot(Some(accounts.slot)))?;
+        Ok(DepositQuote { slot: Some(accounts.slot), ..quote })
+    }
+
+    /// quote a liquid unstake of `msol_amount` against the current state and liq pool, see
+    /// `unstake::simulate_liquid_unstake`
+    pub fn simulate_liquid_unstake(&self, msol_amount: u64) -> Result<LiquidUnstakeQuote> {
+        let _verbosity = self.options.verbosity.enter();
+        self.verify_msol_mint()?;
+        let addresses = self.deployment.valuation_addresses();
+        let accounts = fetch_valuation_accounts(self.rpc_client(), &addresses, None, &self.options)?;
+        let pool = accounts.liq_pool;
+        let quote =
+            unstake::simulate_liquid_unstake(&accounts.state, pool.sol_leg_lamports, pool.msol_leg_amount, msol_amount)
+                .map_err(|e| Error::new(ErrorKind::LiquidUnstake(e)).with_slot(Some(accounts.slot)))?;
+        Ok(LiquidUnstakeQuote { slot: Some(accounts.slot), ..quote })
+    }
+
+    pub fn context(&self) -> ParserContext<'_> {
+        ParserContext::new(self.rpc_client(), &self.options)
+    }
+
+    /// the mSOL parser for this client's deployment
+    pub fn parser(&self) -> MarinadeParser {
+        MarinadeParser::new(self.deployment.clone())
+    }
+
+    /// an `Analyzer` over this client's connection and deployment
+    pub fn analyzer(&self) -> Analyzer<'_> {
+        Analyzer::new(self.rpc_client(), self.options.clone()).with_deployment(self.deployment.clone())
+    }
+
+    /// a `TreasuryAnalyzer` over this client's connection and deployment
+    pub fn treasury_analyzer(&self) -> TreasuryAnalyzer<'_> {
+        TreasuryAnalyzer::new(self.rpc_client(), self.options.clone()).with_deployment(self.deployment.clone())
+    }
+
+    /// a `LiquidityAnalyzer` over this client's connection and deployment
+    pub fn liquidity_analyzer(&self) -> LiquidityAnalyzer<'_> {
+        LiquidityAnalyzer::new(self.rpc_client(), self.options.clone()).with_deployment(self.deployment.clone())
+    }
+
+    /// an `EventClassifier` over this client's connection and deployment
+    pub fn event_classifier(&self) -> EventClassifier<'_> {
+        EventClassifier::new(self.rpc_client(), self.options.clone()).with_deployment(self.deployment.clone())
+    }
+}
+
+#[derive(Default)]
+pub struct MarinadeClientBuilder {
+    cluster: Option<Cluster>,
+    deployment: Option<DeploymentConfig>,
+    rpc_client: Option<Arc<dyn RpcFetcher>>,
+    rpc_url: Option<String>,
+    options: AnalyzeOptions,
+    registry: Option<Registry>,
+    snapshot_cache: Option<PathBuf>,
+    offline: bool,
+    observer: Option<Arc<dyn Observer>>,
+    commitment: Option<CommitmentConfig>,
+    verbosity: Option<Verbosity>,
+    max_slot_skew: Option<u64>,
+    max_transaction_version: Option<TransactionVersion>,
+    rpc_headers: Option<RpcHeaders>,
+    prefer_zstd: bool,
+}
+
+impl MarinadeClientBuilder {
+    /// defaults to mainnet-beta
+    pub fn cluster(mut self, cluster: Cluster) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// overrides the cluster's deployment, e.g. for a forked program on a local validator
+    pub fn deployment(mut self, deployment: DeploymentConfig) -> Self {
+        self.deployment = Some(deployment);
+        self
+    }
+
+    /// use this fetcher instead of an `RpcClient` on the cluster's public endpoint
+    pub fn rpc_client(mut self, rpc_client: impl RpcFetcher + 'static) -> Self {
+        self.rpc_client = Some(Arc::new(rpc_client));
+        self
+    }
+
+    /// an `RpcClient` on this endpoint instead of the cluster's public one
+    pub fn rpc_url(mut self, rpc_url: impl Into<String>) -> Self {
+        self.rpc_url = Some(rpc_url.into());
+        self
+    }
+
+    /// send these headers with every request of the default `RpcClient`, e.g. a provider's
+    /// `Authorization`. ignored with an `rpc_client`, which is sent as given
+    pub fn rpc_headers(mut self, headers: RpcHeaders) -> Self {
+        self.rpc_headers = Some(headers);
+        self
+    }
+
+    /// fill in whatever the builder hasn't been given explicitly, before or after, from `config`
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.rpc_url = self.rpc_url.or(config.rpc_url);
+        self.commitment = self.commitment.or(config.commitment);
+        self.cluster = self.cluster.or(config.cluster);
+        self.max_transaction_version = self.max_transaction_version.or(config.max_transaction_version);
+        self
+    }
+
+    pub fn options(mut self, options: AnalyzeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// defaults to the built-in parsers, with mSOL valued against the client's deployment
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// write every fetched state through to a `SnapshotCache` in `dir`, for this cluster
+    pub fn snapshot_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.snapshot_cache = Some(dir.into());
+        self
+    }
+
+    /// read states only from the snapshot cache, see `AnalyzeOptions::offline`
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// ask for account data zstd compressed, see `AnalyzeOptions::prefer_zstd`
+    pub fn prefer_zstd(mut self, prefer_zstd: bool) -> Self {
+        self.prefer_zstd = prefer_zstd;
+        self
+    }
+
+    /// report every rpc call, cache hit and analysis of the client to `observer`
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// what the client's reads ask the node for, `confirmed` by default. see
+    /// `AnalyzeOptions::commitment`, which this overrides
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// how much the crate logs for this client, whatever the host's filter lets through. see
+    /// `AnalyzeOptions::verbosity`, which this overrides
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = Some(verbosity);
+        self
+    }
+
+    /// fail analyses valued against a state read more than `max` slots from the transaction.
+    /// see `AnalyzeOptions::max_slot_skew`, which this overrides
+    pub fn max_slot_skew(mut self, max: u64) -> Self {
+        self.max_slot_skew = Some(max);
+        self
+    }
+
+    /// the newest transaction version the client reads. see `AnalyzeOptions::max_transaction_version`,
+    /// which this overrides
+    pub fn max_transaction_version(mut self, max: TransactionVersion) -> Self {
+        self.max_transaction_version = Some(max);
+        self
+    }
+
+    /// panics without an `rpc_client` when the `blocking` feature is off, there being no
+    /// `RpcClient` to default to
+    pub fn build(self) -> MarinadeClient {
+        let cluster = self.cluster.unwrap_or_default();
+        let rpc_url = match self.rpc_client {
+            Some(_) => None,
+            None => Some(self.rpc_url.unwrap_or_else(|| cluster.rpc_url().to_string())),
+        };
+        let headers = self.rpc_headers.filter(|headers| !headers.is_empty());
+        let mut rpc_client =
+            self.rpc_client.unwrap_or_else(|| default_rpc_client(rpc_url.clone().unwrap(), headers.as_ref()));
+        let mut options = self.options;
+        options.observer = self.observer.or(options.observer);
+        options.commitment = self.commitment.unwrap_or(options.commitment);
+        options.verbosity = self.verbosity.unwrap_or(options.verbosity);
+        options.max_slot_skew = self.max_slot_skew.or(options.max_slot_skew);
+        if let Some(max) = self.max_transaction_version {
+            options.max_transaction_version = max;
+        }
+        if let Some(observer) = &options.observer {
+            rpc_client = Arc::new(Observed::new(rpc_client, observer.clone()));
+        }
+        let deployment = self.deployment.unwrap_or_else(|| cluster.deployment());
+        let registry = self.registry.unwrap_or_else(|| {
+            let mut registry = Registry::default();
+            registry.register(Box::new(MarinadeParser::new(deployment.clone())));
+            registry
+        });
+        if let Some(dir) = self.snapshot_cache {
+            options.snapshot_cache = Some(Arc::new(SnapshotCache::new(dir, cluster.clone())));
+        }
+        options.offline |= self.offline;
+        options.prefer_zstd |= self.prefer_zstd;
+        let registry = Arc::new(registry);
+        let (msol_mint, epoch) = (Arc::default(), Arc::default());
+        MarinadeClient { rpc_client, rpc_url, cluster, deployment, options, registry, msol_mint, epoch }
+    }
+}
+
+#[cfg(feature = "blocking")]
+fn default_rpc_client(rpc_url: String, headers: Option<&RpcHeaders>) -> Arc<dyn RpcFetcher> {
+    match headers {
+        Some(headers) => Arc::new(crate::headers::rpc_client(rpc_url, headers)),
+        None => Arc::new(RpcClient::new(rpc_url)),
+    }
+}
+
+#[cfg(not(feature = "blocking"))]
+fn default_rpc_client(_rpc_url: String, _headers: Option<&RpcHeaders>) -> Arc<dyn RpcFetcher> {
+    panic!("without the blocking feature there's no RpcClient, give the builder an rpc_client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::test_utils::{
+        marinade_transaction, marinade_transaction_for, msol_mint_account, sample_state, state_account, MockFetcher,
+        FIXTURE_BLOCK_TIME, FIXTURE_SLOT,
+    };
+    use crate::constants::{MARINADE_OPERATIONAL_SOL_ACCOUNT, MARINADE_STATE_PUBKEY};
+    use crate::error::ErrorKind;
+    use solana_sdk::account::Account;
+    use crate::transaction::transaction_signature;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+
+    #[test]
+    fn test_devnet_deposit_end_to_end() {
+        let state = sample_state();
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let signature = transaction_signature(&tx).unwrap();
+        let rpc = Arc::new(
+            MockFetcher::new()
+                .with_account(Cluster::Devnet.deployment().state, state_account(&state))
+                .with_transaction(signature, tx),
+        );
+
+        let client = MarinadeClient::builder().cluster(Cluster::Devnet).rpc_client(rpc.clone()).build();
+        assert_eq!(client.cluster().rpc_url(), "https://api.devnet.solana.com");
+        let tx = client.fetch_transaction(signature).unwrap();
+        let mu = client.analyze_transaction(&tx).unwrap();
+        assert_eq!(mu.msol_value, state.msol_price_lamports());
+        assert_eq!(mu.platform_program_pubkey, Cluster::Devnet.deployment().state.to_string());
+        assert_eq!(rpc.calls(), vec!["getTransaction", "getAccountInfo"]);
+
+        let (snapshot_state, metadata) = client.state_snapshot().unwrap();
+        assert_eq!(snapshot_state, state);
+        assert_eq!((metadata.slot, metadata.endpoint.as_str()), (FIXTURE_SLOT, "https://api.devnet.solana.com"));
+    }
+
+    #[test]
+    fn test_custom_cluster_resolves_its_own_addresses() {
+        let deployment = fork();
+        let cluster = Cluster::Custom { rpc_url: "http://127.0.0.1:8899".to_string(), deployment: Box::new(deployment.clone()) };
+        let rpc = MockFetcher::new().with_account(deployment.state, state_account(&sample_state()));
+        let client = MarinadeClient::builder().cluster(cluster).rpc_client(rpc).build();
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[]);
+
+        let mu = client.analyze_transaction(&tx).unwrap();
+        assert_eq!(mu.platform_program_pubkey, deployment.state.to_string());
+        // the mainnet state account isn't there
+        let err = MarinadeClient::builder().rpc_client(MockFetcher::new()).build().analyze_transaction(&tx).unwrap_err();
+        assert_eq!(err.pubkey(), Some(&DeploymentConfig::MAINNET.state));
+    }
+
+    fn fork() -> DeploymentConfig {
+        DeploymentConfig {
+            program_id: Pubkey::new_unique(),
+            state: Pubkey::new_unique(),
+            msol_mint: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            liq_pool_msol_leg: Pubkey::new_unique(),
+            referral_program_id: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_forked_deployment() {
+        use MarinadeFinanceInstruction::*;
+        let deployment = fork();
+        let state = MarinadeState { msol_mint: deployment.msol_mint, ..sample_state() };
+        let rpc = Arc::new(MockFetcher::new().with_account(deployment.state, state_account(&state)));
+        let client = MarinadeClient::builder().deployment(deployment.clone()).rpc_client(rpc.clone()).build();
+        assert_eq!(client.cluster(), &Cluster::MainnetBeta);
+
+        let mut analyzer = client.analyzer();
+        let fork_tx = |slot, ixs| marinade_transaction_for(&deployment, slot, Some(FIXTURE_BLOCK_TIME), ixs);
+        let mu = analyzer.analyze(&fork_tx(FIXTURE_SLOT, &[Deposit])).unwrap();
+        assert_eq!(mu.mint_pubkey, deployment.msol_mint.to_string());
+        assert_eq!(mu.platform_program_pubkey, deployment.state.to_string());
+        // deposits are recognized against the fork's program id...
+        assert!(analyzer.analyze(&fork_tx(FIXTURE_SLOT + 1, &[Deposit])).unwrap().state_reused);
+        // ...and calls to the mainnet program aren't marinade instructions for this deployment
+        let mainnet_tx = marinade_transaction(FIXTURE_SLOT + 2, Some(FIXTURE_BLOCK_TIME), &[Deposit]);
+        assert!(!analyzer.analyze(&mainnet_tx).unwrap().state_reused);
+        assert_eq!(rpc.call_count("getAccountInfo"), 2);
+    }
+
+    #[test]
+    fn test_commitment_reaches_the_rpc_configs() {
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let signature = Signature::new_unique();
+        let rpc = Arc::new(
+            MockFetcher::new()
+                .with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()))
+                .with_transaction(signature, tx),
+        );
+        let read = |client: &MarinadeClient| {
+            let before = rpc.commitments().len();
+            client.price(None).unwrap();
+            client.analyze_transaction(&client.fetch_transaction(signature).unwrap()).unwrap();
+            rpc.commitments()[before..].iter().map(|(method, c)| (*method, c.unwrap().commitment)).collect::<Vec<_>>()
+        };
+
+        // confirmed unless configured otherwise, for accounts and transactions alike
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let confirmed = CommitmentConfig::confirmed().commitment;
+        assert_eq!(read(&client), [("getAccountInfo", confirmed), ("getTransaction", confirmed), ("getAccountInfo", confirmed)]);
+
+        let finalized = CommitmentConfig::finalized();
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).commitment(finalized).build();
+        assert!(read(&client).iter().all(|(_, c)| *c == finalized.commitment));
+
+        // a per-call override, with transactions kept at confirmed when asked for processed
+        let processed = CommitmentConfig::processed().commitment;
+        let fast = client.with_commitment(CommitmentConfig::processed());
+        assert_eq!(read(&fast), [("getAccountInfo", processed), ("getTransaction", confirmed), ("getAccountInfo", processed)]);
+        assert_eq!(client.options().commitment, finalized);
+    }
+
+    #[test]
+    fn test_signatures_as_values_or_strings() {
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let signature = Signature::new_unique();
+        let rpc = Arc::new(
+            MockFetcher::new()
+                .with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()))
+                .with_transaction(signature, tx),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let base58 = signature.to_string();
+
+        assert_eq!(client.fetch_transaction(signature).unwrap().slot, FIXTURE_SLOT);
+        assert_eq!(client.fetch_transaction(base58.as_str()).unwrap().slot, FIXTURE_SLOT);
+        let by_value = client.analyze_signature(signature).unwrap();
+        assert_eq!(client.analyze_signature(&base58).unwrap().msol_value, by_value.msol_value);
+
+        // a bad string fails before anything is asked of the node
+        let calls = rpc.calls().len();
+        let err = client.analyze_signature("not a signature").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidSignature { input, .. } if input == "not a signature"));
+        assert_eq!(rpc.calls().len(), calls);
+        // an unknown one carries the signature it was asked for
+        let unknown = Signature::new_unique();
+        assert_eq!(client.analyze_signature(unknown.to_string()).unwrap_err().signature(), Some(&unknown));
+    }
+
+    #[test]
+    fn test_operational_balance_of_the_mainnet_account() {
+        let state = MarinadeState { operational_sol_account: MARINADE_OPERATIONAL_SOL_ACCOUNT, ..sample_state() };
+        let operational = Account { lamports: 5_000_000_000, ..Account::default() };
+        let rpc = MockFetcher::new()
+            .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+            .with_account(MARINADE_OPERATIONAL_SOL_ACCOUNT, operational);
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+
+        let (parsed, _) = client.state_snapshot().unwrap();
+        assert_eq!(parsed.operational().operational_sol_account, MARINADE_OPERATIONAL_SOL_ACCOUNT);
+        assert_eq!(client.operational_balance().unwrap(), (FIXTURE_SLOT, 5_000_000_000));
+    }
+    #[test]
+    fn test_msol_mint_is_verified_once() {
+        let deployment = DeploymentConfig::MAINNET;
+        let mut bad_mint = msol_mint_account(&deployment, 1);
+        bad_mint.data[44] = 6;
+        let bad = MarinadeClient::builder().rpc_client(MockFetcher::new().with_account(deployment.msol_mint, bad_mint));
+        let err = bad.build().simulate_deposit(1_000_000_000).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidMint(_)));
+
+        let rpc = Arc::new(MockFetcher::new().with_account(deployment.msol_mint, msol_mint_account(&deployment, 1)));
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        assert_eq!(client.verify_msol_mint().unwrap().decimals, 9);
+        assert_eq!(client.clone().verify_msol_mint().unwrap().supply, 1);
+        // the quote goes on to fetch the valuation accounts, which aren't there
+        assert!(client.simulate_deposit(1_000_000_000).is_err());
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 2);
+    }
+
+    #[test]
+    fn test_mint_and_balance_reads_use_the_client_commitment() {
+        let deployment = DeploymentConfig::MAINNET;
+        let state = MarinadeState { operational_sol_account: MARINADE_OPERATIONAL_SOL_ACCOUNT, ..sample_state() };
+        let operational = Account { lamports: 1, ..Account::default() };
+        let rpc = Arc::new(
+            MockFetcher::new()
+                .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+                .with_account(MARINADE_OPERATIONAL_SOL_ACCOUNT, operational)
+                .with_account(deployment.msol_mint, msol_mint_account(&deployment, 1)),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let client = client.with_commitment(CommitmentConfig::finalized());
+
+        client.verify_msol_mint().unwrap();
+        client.operational_balance().unwrap();
+        let reads = rpc.commitments().into_iter().filter(|(method, _)| *method == "getMultipleAccounts");
+        let reads: Vec<_> = reads.collect();
+        assert_eq!(reads, [("getMultipleAccounts", Some(CommitmentConfig::finalized())); 2]);
+    }
+
+    #[test]
+    fn test_verbosity_is_per_client() {
+        use crate::test_utils::TraceRecorder;
+        use tracing::Level;
+
+        let rpc = Arc::new(MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state())));
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        // the events one analysis emits, under a subscriber that lets everything through
+        let events = |verbosity| {
+            let client = MarinadeClient::builder().rpc_client(rpc.clone()).verbosity(verbosity).build();
+            let recorder = TraceRecorder::default();
+            recorder.capture(|| client.analyze_transaction(&tx)).unwrap();
+            let records = recorder.records().into_iter().filter(|record| record.kind == "event");
+            records.map(|record| (record.level, record.name)).collect::<Vec<_>>()
+        };
+        let has = |events: &[(Level, String)], message: &str| events.iter().any(|(_, name)| name == message);
+
+        let trace = events(Verbosity::Trace);
+        assert!(has(&trace, "account data prefix") && has(&trace, "analysis complete"));
+        let normal = events(Verbosity::Normal);
+        assert!(!has(&normal, "account data prefix") && has(&normal, "analysis complete"));
+        assert_eq!(normal.len(), trace.len() - 1);
+        // the fixture's mint isn't mainnet's, the one warning
+        let warning = "state msol_mint differs from the known mSOL mint";
+        assert_eq!(events(Verbosity::Errors), vec![(Level::WARN, warning.to_string())]);
+        assert_eq!(events(Verbosity::Off), vec![]);
+        // and the thread is back to the default once the calls return
+        assert!(crate::verbosity::enabled(Level::DEBUG) && !crate::verbosity::enabled(Level::TRACE));
+    }
+}
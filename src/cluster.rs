@@ -0,0 +1,54 @@
+//! which cluster, and which marinade deployment on it, to talk to
+
+use crate::deployment::DeploymentConfig;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Cluster {
+    #[default]
+    MainnetBeta,
+    /// marinade's devnet deployment lives at the mainnet addresses
+    Devnet,
+    /// there is no official testnet deployment; assumes the mainnet addresses like devnet
+    Testnet,
+    /// any other endpoint, e.g. a local validator with its own deployment
+    Custom { rpc_url: String, deployment: Box<DeploymentConfig> },
+}
+
+impl Cluster {
+    /// "mainnet-beta", "devnet", "testnet", or "custom"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MainnetBeta => "mainnet-beta",
+            Self::Devnet => "devnet",
+            Self::Testnet => "testnet",
+            Self::Custom { .. } => "custom",
+        }
+    }
+
+    /// the named cluster `name` is, as `name` returns it; "mainnet" is mainnet-beta too
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mainnet-beta" | "mainnet" => Some(Self::MainnetBeta),
+            "devnet" => Some(Self::Devnet),
+            "testnet" => Some(Self::Testnet),
+            _ => None,
+        }
+    }
+
+    /// the public rpc endpoint of the cluster
+    pub fn rpc_url(&self) -> &str {
+        match self {
+            Self::MainnetBeta => "https://api.mainnet-beta.solana.com",
+            Self::Devnet => "https://api.devnet.solana.com",
+            Self::Testnet => "https://api.testnet.solana.com",
+            Self::Custom { rpc_url, .. } => rpc_url,
+        }
+    }
+
+    pub fn deployment(&self) -> DeploymentConfig {
+        match self {
+            Self::MainnetBeta | Self::Devnet | Self::Testnet => DeploymentConfig::MAINNET,
+            Self::Custom { deployment, .. } => deployment.as_ref().clone(),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! side by side exchange rates of several lsts, for comparison tables
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+use crate::client::MarinadeClient;
+use crate::error::Result;
+use crate::parsers::PriceSnapshot;
+use crate::verbosity::debug;
+
+/// how far behind the newest snapshot of a report another may be before it's flagged.
+/// ~1 minute of slots; the fetches of one report normally land well within it
+pub const MAX_SLOT_SPREAD: u64 = 150;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RateComparison {
+    pub snapshot: PriceSnapshot,
+    /// SOL per token with 9 decimals, e.g. "1.100000000"
+    pub sol_per_token: String,
+    /// slots between this snapshot and the newest one in the report
+    pub slots_behind: u64,
+    /// `slots_behind` exceeds `MAX_SLOT_SPREAD`, so the rate isn't comparable as is
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RateReport {
+    /// the newest slot among the snapshots
+    pub slot: u64,
+    /// highest SOL per token first
+    pub rates: Vec<RateComparison>,
+}
+
+impl RateReport {
+    pub fn from_snapshots(snapshots: Vec<PriceSnapshot>) -> Self {
+        let slot = snapshots.iter().map(|s| s.slot).max().unwrap_or_default();
+        let mut rates: Vec<RateComparison> = snapshots
+            .into_iter()
+            .map(|snapshot| {
+                let slots_behind = slot - snapshot.slot;
+                RateComparison {
+                    sol_per_token: format_lamports(snapshot.price_lamports),
+                    slots_behind,
+                    stale: slots_behind > MAX_SLOT_SPREAD,
+                    snapshot,
+                }
+            })
+            .collect();
+        rates.sort_by(|a, b| {
+            b.snapshot.price_lamports.cmp(&a.snapshot.price_lamports).then(a.snapshot.mint.cmp(&b.snapshot.mint))
+        });
+        Self { slot, rates }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.rates.iter().any(|rate| rate.stale)
+    }
+}
+
+/// the current price of every mint, fetched back to back through the client's registry.
+/// fails on the first mint without a parser or whose price can't be fetched
+pub fn compare_rates(client: &MarinadeClient, mints: &[Pubkey]) -> Result<RateReport> {
+    let ctx = client.context();
+    let snapshots = mints
+        .iter()
+        .map(|mint| client.registry().parser_for_mint(mint)?.current_price(&ctx))
+        .collect::<Result<Vec<_>>>()?;
+    let report = RateReport::from_snapshots(snapshots);
+    debug!(slot = report.slot, rates = report.rates.len(), stale = report.is_stale(), "rates compared");
+    Ok(report)
+}
+
+/// lamports as SOL with all 9 decimals
+pub fn format_lamports(lamports: u64) -> String {
+    format!("{}.{:09}", lamports / LAMPORTS_PER_MSOL, lamports % LAMPORTS_PER_MSOL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::constants::{BSOL_MINT_PUBKEY, JITOSOL_MINT_PUBKEY, MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY};
+    use crate::error::ErrorKind;
+    use crate::parsers::marinade::MarinadeParser;
+    use crate::parsers::registry::Registry;
+    use crate::parsers::spl_stake_pool::SplStakePoolParser;
+    use crate::parsers::Protocol;
+    use crate::test_utils::{sample_stake_pool, sample_state, state_account, stake_pool_account, MockFetcher, FIXTURE_SLOT};
+
+    fn client() -> MarinadeClient {
+        let state = MarinadeState { msol_mint: MSOL_MINT_PUBKEY, ..sample_state() };
+        let rpc = MockFetcher::new()
+            .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+            .with_account(SplStakePoolParser::jito().pool(), stake_pool_account(&sample_stake_pool(JITOSOL_MINT_PUBKEY)));
+        let mut registry = Registry::empty();
+        registry.register(Box::new(MarinadeParser::default()));
+        registry.register(Box::new(SplStakePoolParser::jito()));
+        MarinadeClient::builder().rpc_client(rpc).registry(registry).build()
+    }
+
+    #[test]
+    fn test_compare_two_protocols() {
+        let report = compare_rates(&client(), &[MSOL_MINT_PUBKEY, JITOSOL_MINT_PUBKEY]).unwrap();
+        assert_eq!(report.slot, FIXTURE_SLOT);
+        assert!(!report.is_stale());
+        let rows: Vec<_> =
+            report.rates.iter().map(|r| (r.snapshot.protocol.clone(), r.sol_per_token.as_str(), r.slots_behind)).collect();
+        // sample state: 7.201M SOL behind 6M mSOL; sample pool: 7.7M behind 7M
+        assert_eq!(
+            rows,
+            vec![(Protocol::Marinade, "1.200166666", 0), (Protocol::SplStakePool, "1.100000000", 0)]
+        );
+
+        let err = compare_rates(&client(), &[MSOL_MINT_PUBKEY, BSOL_MINT_PUBKEY]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported));
+        assert_eq!(err.pubkey(), Some(&BSOL_MINT_PUBKEY));
+    }
+
+    #[test]
+    fn test_lagging_snapshot_is_flagged() {
+        let snapshot = |mint, price_lamports, slot| PriceSnapshot::new(Protocol::SplStakePool, mint, price_lamports, 0, 0, slot);
+        let report = RateReport::from_snapshots(vec![
+            snapshot(JITOSOL_MINT_PUBKEY, 1_100_000_000, FIXTURE_SLOT),
+            snapshot(BSOL_MINT_PUBKEY, 1_150_000_000, FIXTURE_SLOT - MAX_SLOT_SPREAD - 1),
+        ]);
+        assert!(report.is_stale());
+        assert_eq!(report.rates[0].snapshot.mint, BSOL_MINT_PUBKEY);
+        assert!(report.rates[0].stale);
+        assert_eq!(report.rates[0].slots_behind, MAX_SLOT_SPREAD + 1);
+        assert!(!report.rates[1].stale);
+        assert_eq!(format_lamports(1_000_000_001), "1.000000001");
+    }
+}
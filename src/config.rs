@@ -0,0 +1,217 @@
+//! client settings from the environment or a toml file, so one binary can be pointed at a
+//! different node per deployment. explicit builder values win over the environment, which wins
+//! over the file:
+//!
+//! ```toml
+//! rpc_url = "https://my-node.example.com"
+//! commitment = "finalized"
+//! cluster = "devnet"
+//! max_transaction_version = "legacy"
+//! ```
+
+use std::path::Path;
+
+use serde::Deserialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::transaction::TransactionVersion;
+
+use crate::cluster::Cluster;
+use crate::error::{ErrorKind, Result};
+
+pub const RPC_URL_VAR: &str = "MARINADE_PARSER_RPC_URL";
+pub const COMMITMENT_VAR: &str = "MARINADE_PARSER_COMMITMENT";
+pub const CLUSTER_VAR: &str = "MARINADE_PARSER_CLUSTER";
+pub const MAX_TRANSACTION_VERSION_VAR: &str = "MARINADE_PARSER_MAX_TRANSACTION_VERSION";
+
+/// the settings a deployment can override, each None when left to the builder's default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// the node to talk to instead of the cluster's public endpoint
+    pub rpc_url: Option<String>,
+    pub commitment: Option<CommitmentConfig>,
+    /// one of the named clusters; a custom deployment needs the builder
+    pub cluster: Option<Cluster>,
+    /// "legacy" or a version number, see `AnalyzeOptions::max_transaction_version`
+    pub max_transaction_version: Option<TransactionVersion>,
+}
+
+/// the file as written, before its values are checked
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    rpc_url: Option<String>,
+    commitment: Option<String>,
+    cluster: Option<String>,
+    max_transaction_version: Option<String>,
+}
+
+impl ClientConfig {
+    /// from the `MARINADE_PARSER_*` variables, the unset ones None
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| match std::env::var_os(name) {
+            None => Ok(None),
+            Some(value) => value.into_string().map(Some).map_err(|_| invalid(name, "isn't valid unicode")),
+        })
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Result<Option<String>>) -> Result<Self> {
+        Ok(Self {
+            rpc_url: var(RPC_URL_VAR)?.map(|url| parse_rpc_url(RPC_URL_VAR, url)).transpose()?,
+            commitment: var(COMMITMENT_VAR)?.map(|level| parse_commitment(COMMITMENT_VAR, &level)).transpose()?,
+            cluster: var(CLUSTER_VAR)?.map(|name| parse_cluster(CLUSTER_VAR, &name)).transpose()?,
+            max_transaction_version: var(MAX_TRANSACTION_VERSION_VAR)?
+                .map(|version| parse_version(MAX_TRANSACTION_VERSION_VAR, &version))
+                .transpose()?,
+        })
+    }
+
+    /// from a toml file with any of `rpc_url`, `commitment`, `cluster` and `max_transaction_version`;
+    /// other keys are an error
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let origin = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|e| invalid(&origin, e))?;
+        let raw: RawConfig = toml::from_str(&text).map_err(|e| invalid(&origin, e))?;
+        let key = |key: &str| format!("{}: {}", origin, key);
+        Ok(Self {
+            rpc_url: raw.rpc_url.map(|url| parse_rpc_url(&key("rpc_url"), url)).transpose()?,
+            commitment: raw.commitment.map(|level| parse_commitment(&key("commitment"), &level)).transpose()?,
+            cluster: raw.cluster.map(|name| parse_cluster(&key("cluster"), &name)).transpose()?,
+            max_transaction_version: raw
+                .max_transaction_version
+                .map(|version| parse_version(&key("max_transaction_version"), &version))
+                .transpose()?,
+        })
+    }
+
+    /// these settings, with the ones left unset taken from `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            rpc_url: self.rpc_url.or(fallback.rpc_url),
+            commitment: self.commitment.or(fallback.commitment),
+            cluster: self.cluster.or(fallback.cluster),
+            max_transaction_version: self.max_transaction_version.or(fallback.max_transaction_version),
+        }
+    }
+}
+
+fn invalid(origin: &str, reason: impl ToString) -> crate::error::Error {
+    ErrorKind::InvalidConfig { origin: origin.to_string(), reason: reason.to_string() }.into()
+}
+
+fn parse_rpc_url(origin: &str, url: String) -> Result<String> {
+    match url.starts_with("http://") || url.starts_with("https://") {
+        true => Ok(url),
+        false => Err(invalid(origin, format!("{:?} isn't an http(s) url", url))),
+    }
+}
+
+fn parse_commitment(origin: &str, level: &str) -> Result<CommitmentConfig> {
+    match level {
+        "processed" => Ok(CommitmentConfig::processed()),
+        "confirmed" => Ok(CommitmentConfig::confirmed()),
+        "finalized" => Ok(CommitmentConfig::finalized()),
+        _ => Err(invalid(origin, format!("{:?} isn't processed, confirmed or finalized", level))),
+    }
+}
+
+fn parse_cluster(origin: &str, name: &str) -> Result<Cluster> {
+    Cluster::from_name(name)
+        .ok_or_else(|| invalid(origin, format!("{:?} isn't mainnet-beta, devnet or testnet", name)))
+}
+
+fn parse_version(origin: &str, version: &str) -> Result<TransactionVersion> {
+    match version {
+        "legacy" => Ok(TransactionVersion::LEGACY),
+        _ => version
+            .parse()
+            .map(TransactionVersion::Number)
+            .map_err(|_| invalid(origin, format!("{:?} isn't legacy or a version number", version))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_is_checked() {
+        let dir = std::env::temp_dir().join(format!("marinade-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        };
+
+        let path = write("ok.toml", "rpc_url = \"http://10.0.0.1:8899\"\ncommitment = \"finalized\"\n");
+        let config = ClientConfig::from_path(&path).unwrap();
+        assert_eq!(config.rpc_url.as_deref(), Some("http://10.0.0.1:8899"));
+        assert_eq!((config.commitment, config.cluster), (Some(CommitmentConfig::finalized()), None));
+        let path = write("versions.toml", "max_transaction_version = \"legacy\"\n");
+        assert_eq!(ClientConfig::from_path(&path).unwrap().max_transaction_version, Some(TransactionVersion::LEGACY));
+        let path = write("versions.toml", "max_transaction_version = \"0\"\n");
+        let config = ClientConfig::from_path(&path).unwrap();
+        assert_eq!(config.max_transaction_version, Some(TransactionVersion::Number(0)));
+
+        for (text, origin) in [
+            ("commitment = \"max\"", "commitment"),
+            ("cluster = \"localnet\"", "cluster"),
+            ("rpc_url = \"10.0.0.1:8899\"", "rpc_url"),
+            ("max_transaction_version = \"v0\"", "max_transaction_version"),
+        ] {
+            let e = ClientConfig::from_path(write("bad.toml", text)).unwrap_err();
+            let ErrorKind::InvalidConfig { origin: found, .. } = e.kind() else { panic!("{}", e) };
+            assert!(found.ends_with(&format!("bad.toml: {}", origin)), "{}", found);
+        }
+        let e = ClientConfig::from_path(write("bad.toml", "rpc = \"http://10.0.0.1\"")).unwrap_err();
+        assert!(e.to_string().contains("unknown field `rpc`"), "{}", e);
+        assert!(ClientConfig::from_path(dir.join("missing.toml")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // the only test that touches the process environment, so nothing else races it
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_builder_over_env_over_file() {
+        use crate::client::MarinadeClient;
+        use crate::test_utils::MockFetcher;
+
+        let path = std::env::temp_dir().join(format!("marinade-config-env-{}.toml", std::process::id()));
+        std::fs::write(&path, "rpc_url = \"http://file:8899\"\ncommitment = \"processed\"\ncluster = \"testnet\"\n")
+            .unwrap();
+        std::env::set_var(RPC_URL_VAR, "https://env.example.com");
+        std::env::set_var(CLUSTER_VAR, "devnet");
+        std::env::remove_var(COMMITMENT_VAR);
+
+        let client = MarinadeClient::from_env().unwrap();
+        assert_eq!((client.rpc_url(), client.cluster()), (Some("https://env.example.com"), &Cluster::Devnet));
+        assert_eq!(client.options().commitment, CommitmentConfig::confirmed());
+
+        // the file fills in what the environment leaves out
+        let client = MarinadeClient::from_config_path(&path).unwrap();
+        assert_eq!((client.rpc_url(), client.cluster()), (Some("https://env.example.com"), &Cluster::Devnet));
+        assert_eq!(client.options().commitment, CommitmentConfig::processed());
+
+        // and explicit builder values win over both, whichever order they're given in
+        let config = ClientConfig::from_env().unwrap().or(ClientConfig::from_path(&path).unwrap());
+        let client = MarinadeClient::builder()
+            .cluster(Cluster::MainnetBeta)
+            .config(config.clone())
+            .commitment(CommitmentConfig::finalized())
+            .build();
+        assert_eq!((client.rpc_url(), client.cluster()), (Some("https://env.example.com"), &Cluster::MainnetBeta));
+        assert_eq!(client.options().commitment, CommitmentConfig::finalized());
+        let client = MarinadeClient::builder().config(config).rpc_client(MockFetcher::new()).build();
+        assert_eq!(client.rpc_url(), None);
+
+        std::env::set_var(COMMITMENT_VAR, "fast");
+        let e = MarinadeClient::from_env().err().unwrap();
+        let expected = "invalid MARINADE_PARSER_COMMITMENT: \"fast\" isn't processed, confirmed or finalized";
+        assert_eq!(e.to_string(), expected);
+        for name in [RPC_URL_VAR, COMMITMENT_VAR, CLUSTER_VAR] {
+            std::env::remove_var(name);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+//! well-known mainnet addresses, validated at compile time by `pubkey!`
+
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+/// wrapped SOL
+pub const SOL_MINT_PUBKEY: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+/// mSOL
+pub const MSOL_MINT_PUBKEY: Pubkey = pubkey!("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So");
+
+/// marinade staking program account pubkey
+pub const MARINADE_STATE_PUBKEY: Pubkey = pubkey!("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
+
+/// marinade liquid staking program id
+pub const MARINADE_PROGRAM_ID: Pubkey = pubkey!("MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD");
+
+/// the mainnet state's `operational_sol_account`
+pub const MARINADE_OPERATIONAL_SOL_ACCOUNT: Pubkey = pubkey!("opLSF7LdfyWNBby5o6FT8UFsr2A4UGKteECgtLSYrSm");
+
+/// marinade referral program id
+pub const MARINADE_REFERRAL_PROGRAM_ID: Pubkey = pubkey!("MR2LqxoSbw831bNy68utpu5n4YqBH3AzDmddkgk9LQv");
+
+/// the token-2022 program, which also owns mints laid out like spl token's
+pub const SPL_TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// decimals of the mSOL mint, matching SOL's lamports
+pub const MSOL_DECIMALS: u8 = 9;
+
+/// mSOL-SOL liquidity pool token
+pub const LP_MINT_PUBKEY: Pubkey = pubkey!("LPmSozJJ8Jh69ut2WP3XmVohTjL4ipR18yiCzxrUmVj");
+
+/// the liq pool's mSOL token account
+pub const LIQ_POOL_MSOL_LEG_PUBKEY: Pubkey = pubkey!("7GgPYjS5Dza89wV6FpZ23kUJRG5vbQ1GM25ezspYFSoE");
+
+/// spl stake pool program id
+pub const SPL_STAKE_POOL_PROGRAM_ID: Pubkey = pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+
+/// jito stake pool account
+pub const JITO_STAKE_POOL_PUBKEY: Pubkey = pubkey!("Jito4APyf642JPZPx3hGc6WWJ8zPKtRbRs4P815Awbb");
+
+/// jitoSOL
+pub const JITOSOL_MINT_PUBKEY: Pubkey = pubkey!("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn");
+
+/// blazestake stake pool account
+pub const BLAZE_STAKE_POOL_PUBKEY: Pubkey = pubkey!("stk9ApL5HeVAwPLr3TLhDXdZS8ptVu7zp6ov8HFDuMi");
+
+/// bSOL
+pub const BSOL_MINT_PUBKEY: Pubkey = pubkey!("bSo13r4TkiE4KumL71LsHTPpL2euBYLFx6h9HP3piy1");
+
+/// staker authority of stake accounts managed by marinade native
+pub const MARINADE_NATIVE_STAKER_AUTHORITY: Pubkey = pubkey!("stWirqFCf2Uts1JBL1Jsd3r6VBWhgnpdPxCTe1MFjrq");
+
+/// lido (solido) program id
+pub const LIDO_PROGRAM_ID: Pubkey = pubkey!("CrX7kMhLC3cSsXJdT7JDgqrRVWGnUpX3gfEfxxU2NVLi");
+
+/// lido state account
+pub const LIDO_STATE_PUBKEY: Pubkey = pubkey!("49Yi1TKkNyYjPAFdR9LBvoHcUjuPX4Df5T5yv39w2XTn");
+
+/// stSOL
+pub const STSOL_MINT_PUBKEY: Pubkey = pubkey!("7dHbWXmci3dT8UFYWYZweBLXgycu7Y3iL6trKn1Y7ARj");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(SOL_MINT_PUBKEY.to_string(), "So11111111111111111111111111111111111111112");
+        #[cfg(feature = "rpc")]
+        assert_eq!(SOL_MINT_PUBKEY, anchor_spl::token::spl_token::native_mint::id());
+        assert_eq!(MSOL_MINT_PUBKEY.to_string(), "mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So");
+        assert_ne!(MARINADE_STATE_PUBKEY, MARINADE_PROGRAM_ID);
+    }
+}
@@ -0,0 +1,86 @@
+//! where the current epoch stands relative to the stake-delta crank. the program only lets
+//! `stake_reserve` / `deactivate_stake` rebalance stake in the last `slots_for_stake_delta`
+//! slots of an epoch, and records the epoch of the first run in `last_stake_delta_epoch`
+
+use serde::Serialize;
+
+use crate::accounts::marinade::MarinadeState;
+use crate::epoch::EpochContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CrankStatus {
+    pub epoch: u64,
+    /// the slot the status was computed at
+    pub epoch_context: EpochContext,
+    /// the stake delta already ran during `epoch`
+    pub has_run_this_epoch: bool,
+    pub window_open: bool,
+    /// slots left until the epoch ends while the window is open, its whole length before
+    pub slots_remaining_in_window: u64,
+    /// 0 once the window is open
+    pub slots_until_window_opens: u64,
+}
+
+/// takes an `EpochContext`, or the `EpochInfo` the node returns
+pub fn crank_window_status(state: &MarinadeState, epoch_info: impl Into<EpochContext>) -> CrankStatus {
+    let epoch_info = epoch_info.into();
+    let window = state.slots_for_stake_delta().min(epoch_info.slots_in_epoch);
+    let opens_at = epoch_info.slots_in_epoch - window;
+    let window_open = epoch_info.slot_index >= opens_at;
+    CrankStatus {
+        epoch: epoch_info.epoch,
+        epoch_context: epoch_info,
+        has_run_this_epoch: state.last_stake_delta_epoch() == epoch_info.epoch,
+        window_open,
+        slots_remaining_in_window: match window_open {
+            true => epoch_info.slots_in_epoch.saturating_sub(epoch_info.slot_index),
+            false => window,
+        },
+        slots_until_window_opens: opens_at.saturating_sub(epoch_info.slot_index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sample_state;
+    use solana_sdk::epoch_info::EpochInfo;
+
+    fn at(slot_index: u64) -> EpochInfo {
+        EpochInfo {
+            epoch: 600,
+            slot_index,
+            slots_in_epoch: 432_000,
+            absolute_slot: 600 * 432_000 + slot_index,
+            block_height: 0,
+            transaction_count: None,
+        }
+    }
+
+    #[test]
+    fn test_window_boundary() {
+        let mut state = sample_state();
+        state.stake_system.slots_for_stake_delta = 18_000;
+        state.stake_system.last_stake_delta_epoch = 599;
+
+        let before = crank_window_status(&state, &at(413_999));
+        assert!(!before.window_open && !before.has_run_this_epoch);
+        assert_eq!((before.slots_remaining_in_window, before.slots_until_window_opens), (18_000, 1));
+
+        let opening = crank_window_status(&state, &at(414_000));
+        assert!(opening.window_open);
+        assert_eq!((opening.slots_remaining_in_window, opening.slots_until_window_opens), (18_000, 0));
+
+        let last = crank_window_status(&state, &at(431_999));
+        assert_eq!((last.window_open, last.slots_remaining_in_window), (true, 1));
+
+        state.stake_system.last_stake_delta_epoch = 600;
+        assert!(crank_window_status(&state, &at(420_000)).has_run_this_epoch);
+
+        // built offline from the schedule instead
+        let schedule = solana_sdk::epoch_schedule::EpochSchedule::without_warmup();
+        let offline = crank_window_status(&state, EpochContext::from_schedule(&schedule, 600 * 432_000 + 431_999));
+        assert_eq!(offline, crank_window_status(&state, &at(431_999)));
+        assert_eq!(offline.epoch_context.slots_remaining(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! `chrono` views of block times, and `DateTime<Utc>` bounds for the places that take a time
+//! range. block times are whole unix seconds; a bound is floored to its second, so a bound on a
+//! leap second (`23:59:60`, chrono's nanoseconds past 1e9) is the `23:59:59` before it
+
+use chrono::{DateTime, NaiveDate, Utc};
+use solana_program::clock::UnixTimestamp;
+
+#[cfg(feature = "rpc")]
+use std::time::Duration;
+
+#[cfg(feature = "rpc")]
+use crate::backfill::BackfillOptions;
+#[cfg(feature = "rpc")]
+use crate::flows::{aggregate_flows_between, AnalyzedTx, FlowBucket};
+#[cfg(feature = "rpc")]
+use crate::signatures::SignatureRange;
+use crate::MintUnderlying;
+
+/// `block_time` in utc, saturating at chrono's range for times it can't represent
+pub fn block_datetime(block_time: UnixTimestamp) -> DateTime<Utc> {
+    DateTime::from_timestamp(block_time, 0).unwrap_or(if block_time < 0 {
+        DateTime::<Utc>::MIN_UTC
+    } else {
+        DateTime::<Utc>::MAX_UTC
+    })
+}
+
+/// the block time `bound` falls in
+pub fn unix_bound(bound: DateTime<Utc>) -> UnixTimestamp {
+    bound.timestamp()
+}
+
+impl MintUnderlying {
+    pub fn block_datetime(&self) -> DateTime<Utc> {
+        block_datetime(self.block_time)
+    }
+
+    /// the utc day of `block_time`, for grouping results by day
+    pub fn date_utc(&self) -> NaiveDate {
+        self.block_datetime().date_naive()
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl SignatureRange {
+    /// the default range narrowed to block times in `[start, end]`
+    pub fn between_datetimes(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        Self { start_time: start.map(unix_bound), end_time: end.map(unix_bound), ..Self::default() }
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl BackfillOptions {
+    /// the default options narrowed to block times in `[start, end]`
+    pub fn between_datetimes(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        Self { start_time: start.map(unix_bound), end_time: end.map(unix_bound), ..Self::default() }
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl FlowBucket {
+    pub fn start_datetime(&self) -> DateTime<Utc> {
+        block_datetime(self.start)
+    }
+
+    /// the utc day the bucket starts on
+    pub fn date_utc(&self) -> NaiveDate {
+        self.start_datetime().date_naive()
+    }
+}
+
+/// `aggregate_flows_between` with `DateTime` bounds
+#[cfg(feature = "rpc")]
+pub fn aggregate_flows_between_datetimes<'a, R: AnalyzedTx + 'a>(
+    records: impl Iterator<Item = &'a R>,
+    bucket: Duration,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<FlowBucket> {
+    aggregate_flows_between(records, bucket, unix_bound(start), unix_bound(end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockTimeSource;
+    use chrono::{NaiveDateTime, NaiveTime};
+    use solana_program::pubkey::Pubkey;
+
+    // 2016-12-31T23:59:59Z, the second before the last leap second was inserted
+    const BEFORE_LEAP: UnixTimestamp = 1_483_228_799;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn result_at(block_time: UnixTimestamp) -> MintUnderlying {
+        let mint = Pubkey::new_unique();
+        MintUnderlying::new(block_time, BlockTimeSource::Transaction, 0, &mint, &mint, &[], vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_days_across_the_epoch_and_a_leap_second() {
+        assert_eq!(result_at(0).date_utc(), date(1970, 1, 1));
+        assert_eq!(result_at(-1).date_utc(), date(1969, 12, 31));
+        assert_eq!(result_at(-1).block_datetime().to_rfc3339(), "1969-12-31T23:59:59+00:00");
+        assert_eq!(result_at(BEFORE_LEAP).date_utc(), date(2016, 12, 31));
+        assert_eq!(result_at(BEFORE_LEAP + 1).date_utc(), date(2017, 1, 1));
+        assert_eq!(result_at(BEFORE_LEAP + 1).block_datetime().timestamp(), BEFORE_LEAP + 1);
+        assert_eq!(block_datetime(i64::MAX), DateTime::<Utc>::MAX_UTC);
+        assert_eq!(block_datetime(i64::MIN), DateTime::<Utc>::MIN_UTC);
+
+        // 23:59:60 is still the 31st, and bounds the same block time as the second before it
+        let leap_second = NaiveTime::from_hms_nano_opt(23, 59, 59, 1_500_000_000).unwrap();
+        let leap = NaiveDateTime::new(date(2016, 12, 31), leap_second).and_utc();
+        assert_eq!(leap.date_naive(), date(2016, 12, 31));
+        assert_eq!(unix_bound(leap), BEFORE_LEAP);
+        assert_eq!(unix_bound(block_datetime(-1) + chrono::Duration::milliseconds(999)), -1);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_datetime_bounds() {
+        use crate::flows::Flow;
+        use crate::pipeline::PipelineRecord;
+
+        let (epoch, before_leap) = (Some(block_datetime(0)), Some(block_datetime(BEFORE_LEAP)));
+        let options = BackfillOptions::between_datetimes(epoch, before_leap);
+        assert_eq!((options.start_time, options.end_time), (Some(0), Some(BEFORE_LEAP)));
+        assert_eq!(options.page_size, BackfillOptions::default().page_size);
+        let range = SignatureRange::between_datetimes(None, Some(block_datetime(-1)));
+        assert_eq!((range.start_time, range.end_time), (None, Some(-1)));
+
+        let record = |block_time, lamports| PipelineRecord {
+            signature: None,
+            slot: 0,
+            underlying: result_at(block_time),
+            flows: vec![Flow::Deposit { lamports }],
+            events: vec![],
+            protocol_revenue: None,
+        };
+        let records = [record(BEFORE_LEAP, 1), record(BEFORE_LEAP + 1, 2), record(BEFORE_LEAP + 86_400, 4)];
+        let day = Duration::from_secs(86_400);
+        let (start, end) = (block_datetime(BEFORE_LEAP), block_datetime(BEFORE_LEAP + 1));
+        let buckets = aggregate_flows_between_datetimes(records.iter(), day, start, end);
+        let days: Vec<_> = buckets.iter().map(|bucket| (bucket.date_utc(), bucket.deposit_lamports)).collect();
+        assert_eq!(days, vec![(date(2016, 12, 31), 1), (date(2017, 1, 1), 2)]);
+    }
+}
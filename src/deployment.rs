@@ -0,0 +1,103 @@
+//! every address the crate relies on for one deployment of the marinade program, and the
+//! pdas derived from them
+
+use solana_program::pubkey::Pubkey;
+
+use crate::constants::{
+    LIQ_POOL_MSOL_LEG_PUBKEY, LP_MINT_PUBKEY, MARINADE_PROGRAM_ID, MARINADE_REFERRAL_PROGRAM_ID, MARINADE_STATE_PUBKEY,
+    MSOL_MINT_PUBKEY,
+};
+#[cfg(feature = "rpc")]
+use crate::valuation::ValuationAddresses;
+
+pub const RESERVE_SEED: &[u8] = b"reserve";
+pub const MSOL_MINT_AUTHORITY_SEED: &[u8] = b"st_mint";
+/// seed of the liq pool's SOL leg pda, `[state, LIQ_POOL_SOL_LEG_SEED]`
+pub const LIQ_POOL_SOL_LEG_SEED: &[u8] = b"liq_sol";
+pub const LIQ_POOL_MSOL_LEG_AUTHORITY_SEED: &[u8] = b"liq_st_sol_authority";
+pub const LP_MINT_AUTHORITY_SEED: &[u8] = b"liq_mint";
+
+/// defaults to mainnet; a fork on a local validator only needs its own values here
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentConfig {
+    pub program_id: Pubkey,
+    pub state: Pubkey,
+    pub msol_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub liq_pool_msol_leg: Pubkey,
+    pub referral_program_id: Pubkey,
+}
+
+impl DeploymentConfig {
+    pub const MAINNET: Self = Self {
+        program_id: MARINADE_PROGRAM_ID,
+        state: MARINADE_STATE_PUBKEY,
+        msol_mint: MSOL_MINT_PUBKEY,
+        lp_mint: LP_MINT_PUBKEY,
+        liq_pool_msol_leg: LIQ_POOL_MSOL_LEG_PUBKEY,
+        referral_program_id: MARINADE_REFERRAL_PROGRAM_ID,
+    };
+
+    /// a state-derived pda of this deployment's program
+    pub fn state_pda(&self, seed: &[u8]) -> Pubkey {
+        Pubkey::find_program_address(&[self.state.as_ref(), seed], &self.program_id).0
+    }
+
+    pub fn reserve(&self) -> Pubkey {
+        self.state_pda(RESERVE_SEED)
+    }
+
+    pub fn msol_mint_authority(&self) -> Pubkey {
+        self.state_pda(MSOL_MINT_AUTHORITY_SEED)
+    }
+
+    pub fn liq_pool_sol_leg(&self) -> Pubkey {
+        self.state_pda(LIQ_POOL_SOL_LEG_SEED)
+    }
+
+    pub fn liq_pool_msol_leg_authority(&self) -> Pubkey {
+        self.state_pda(LIQ_POOL_MSOL_LEG_AUTHORITY_SEED)
+    }
+
+    pub fn lp_mint_authority(&self) -> Pubkey {
+        self.state_pda(LP_MINT_AUTHORITY_SEED)
+    }
+
+    /// the accounts a full valuation reads, without fetching the state first
+    #[cfg(feature = "rpc")]
+    pub fn valuation_addresses(&self) -> ValuationAddresses {
+        ValuationAddresses {
+            state: self.state,
+            liq_pool_sol_leg: self.liq_pool_sol_leg(),
+            liq_pool_msol_leg: self.liq_pool_msol_leg,
+            lp_mint: self.lp_mint,
+            msol_mint: self.msol_mint,
+        }
+    }
+}
+
+impl Default for DeploymentConfig {
+    fn default() -> Self {
+        Self::MAINNET
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::pubkey;
+
+    #[test]
+    fn test_mainnet_pdas() {
+        let mainnet = DeploymentConfig::default();
+        assert_eq!(mainnet.reserve(), pubkey!("Du3Ysj1wKbxPKkuPPnvzQLQh8oMSVifs3jGZjJWXFmHN"));
+        assert_eq!(mainnet.msol_mint_authority(), pubkey!("3JLPCS1qM2zRw3Dp6V4hZnYHd4toMNPkNesXdX9tg6KM"));
+        assert_eq!(mainnet.liq_pool_sol_leg(), pubkey!("UefNb6z6yvArqe4cJHTXCqStRsKmWhGxnZzuHbikP5Q"));
+
+        // a fork derives against its own program id
+        let fork = DeploymentConfig { program_id: Pubkey::new_unique(), ..mainnet.clone() };
+        assert_ne!(fork.reserve(), mainnet.reserve());
+        #[cfg(feature = "rpc")]
+        assert_eq!(fork.valuation_addresses().liq_pool_sol_leg, fork.liq_pool_sol_leg());
+    }
+}
@@ -0,0 +1,207 @@
+//! quoting a deposit the way the program's `deposit` instruction would fill it: mSOL from the
+//! liq pool's mSOL leg first, as far as it goes, then the rest minted against the reserve, with
+//! the program's rounding at each step. no rpc here; `MarinadeClient::simulate_deposit`
+//! fetches what it needs. the headrooms are what's left under the program's caps
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::accounts::marinade::{AmountError, MarinadeState, LAMPORTS_PER_MSOL};
+
+/// why the program would reject a deposit, checked in the order it checks them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositError {
+    Paused,
+    /// below `min_deposit`, or the part of the deposit going to the reserve would lift the
+    /// lamports under the program's control above `staking_sol_cap`
+    Amount(AmountError),
+}
+
+impl From<AmountError> for DepositError {
+    fn from(e: AmountError) -> Self {
+        Self::Amount(e)
+    }
+}
+
+impl fmt::Display for DepositError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Paused => write!(f, "the program is paused"),
+            Self::Amount(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DepositError {}
+
+/// what a deposit of `lamports` pays out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DepositQuote {
+    pub lamports: u64,
+    /// mSOL base units the depositor receives, `msol_from_liq_pool + msol_minted`
+    pub msol: u64,
+    /// swapped out of the liq pool's mSOL leg, for `lamports_to_liq_pool` of the deposit
+    pub msol_from_liq_pool: u64,
+    pub lamports_to_liq_pool: u64,
+    /// minted for the rest of the deposit, which goes to the reserve
+    pub msol_minted: u64,
+    /// lamports paid per whole mSOL received, rounded down. None when nothing is received
+    pub effective_price_lamports: Option<u64>,
+    /// context slot of the state quoted against, when it was fetched
+    pub slot: Option<u64>,
+}
+
+/// quote depositing `lamports` against `state`, with `msol_leg_amount` mSOL in the liq pool's
+/// mSOL leg
+pub fn simulate_deposit(
+    state: &MarinadeState,
+    msol_leg_amount: u64,
+    lamports: u64,
+) -> Result<DepositQuote, DepositError> {
+    if state.paused {
+        return Err(DepositError::Paused);
+    }
+    state.check_min_deposit(lamports)?;
+    let minimal = state.minimal();
+    let order = minimal.sol_to_msol(lamports);
+    let msol_from_liq_pool = order.min(msol_leg_amount);
+    let lamports_to_liq_pool = match msol_from_liq_pool {
+        0 => 0,
+        // a whole order filled from the leg takes the whole deposit, whatever the rounding
+        swapped if swapped == order => lamports,
+        swapped => minimal.msol_to_sol(swapped),
+    };
+    let remaining = lamports - lamports_to_liq_pool;
+    let msol_minted = if remaining > 0 {
+        if remaining > deposit_headroom(state) {
+            let current = state.total_lamports_under_control();
+            return Err(AmountError::ExceedsCap { cap: state.staking_sol_cap, current, got: remaining }.into());
+        }
+        minimal.sol_to_msol(remaining)
+    } else {
+        0
+    };
+    let msol = msol_from_liq_pool + msol_minted;
+    let effective_price_lamports =
+        (msol > 0).then(|| (lamports as u128 * LAMPORTS_PER_MSOL as u128 / msol as u128) as u64);
+    Ok(DepositQuote {
+        lamports,
+        msol,
+        msol_from_liq_pool,
+        lamports_to_liq_pool,
+        msol_minted,
+        effective_price_lamports,
+        slot: None,
+    })
+}
+
+/// lamports that can still be staked before the lamports under the program's control reach
+/// `staking_sol_cap`, 0 when they're already past it
+pub fn deposit_headroom(state: &MarinadeState) -> u64 {
+    state.staking_sol_cap.saturating_sub(state.total_lamports_under_control())
+}
+
+/// lamports `add_liquidity` can still put into a sol leg holding `sol_leg_lamports` above its
+/// rent-exempt reserve before it reaches `liq_pool.liquidity_sol_cap`, 0 when it's past it
+pub fn liquidity_headroom(state: &MarinadeState, sol_leg_lamports: u64) -> u64 {
+    state.liq_pool.liquidity_sol_cap.saturating_sub(sol_leg_lamports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sample_state, FIXTURE_IX_AMOUNT};
+
+    #[test]
+    fn test_deposit_fills_from_the_leg_then_mints() {
+        let state = sample_state();
+        let lamports = FIXTURE_IX_AMOUNT;
+
+        let quote = simulate_deposit(&state, u64::MAX, lamports).unwrap();
+        assert_eq!((quote.msol, quote.msol_from_liq_pool, quote.msol_minted), (833_217_608, 833_217_608, 0));
+        assert_eq!(quote.lamports_to_liq_pool, lamports);
+
+        // half a leg: the swapped mSOL is bought back at the program's rounding, the rest is minted
+        let quote = simulate_deposit(&state, 500_000_000, lamports).unwrap();
+        assert_eq!((quote.msol_from_liq_pool, quote.lamports_to_liq_pool), (500_000_000, 600_083_333));
+        assert_eq!(quote.msol_minted, 333_217_608);
+        assert_eq!(quote.msol, 833_217_608);
+        assert_eq!(quote.effective_price_lamports, Some(lamports * LAMPORTS_PER_MSOL / 833_217_608));
+        assert_eq!(quote.slot, None);
+    }
+
+    #[test]
+    fn test_deposit_rejections() {
+        let lamports = FIXTURE_IX_AMOUNT;
+        let mut state = sample_state();
+        state.staking_sol_cap = state.total_lamports_under_control() + 1;
+        // the cap only bounds what goes to the reserve
+        assert!(simulate_deposit(&state, u64::MAX, lamports).is_ok());
+        assert_eq!(
+            simulate_deposit(&state, 0, lamports),
+            Err(DepositError::Amount(AmountError::ExceedsCap {
+                cap: state.staking_sol_cap,
+                current: state.total_lamports_under_control(),
+                got: lamports,
+            }))
+        );
+
+        state.min_deposit = lamports + 1;
+        assert_eq!(
+            simulate_deposit(&state, u64::MAX, lamports),
+            Err(DepositError::Amount(AmountError::BelowMinimum { min: lamports + 1, got: lamports }))
+        );
+        state.paused = true;
+        assert_eq!(simulate_deposit(&state, u64::MAX, lamports), Err(DepositError::Paused));
+    }
+
+    #[test]
+    fn test_headroom_at_below_and_above_the_caps() {
+        let mut state = sample_state();
+        let current = state.total_lamports_under_control();
+        let lamports = FIXTURE_IX_AMOUNT;
+        state.staking_sol_cap = current + lamports;
+        assert_eq!(deposit_headroom(&state), lamports);
+        assert_eq!(simulate_deposit(&state, 0, lamports).unwrap().msol_minted, 833_217_608);
+        assert!(state.validate_deposit(lamports).is_ok() && state.validate_deposit(lamports + 1).is_err());
+
+        state.staking_sol_cap = current;
+        assert_eq!(deposit_headroom(&state), 0);
+        assert!(simulate_deposit(&state, 0, lamports).is_err());
+        state.staking_sol_cap = current - 1;
+        assert_eq!(deposit_headroom(&state), 0);
+
+        state.liq_pool.liquidity_sol_cap = 10 * lamports;
+        assert_eq!(liquidity_headroom(&state, 4 * lamports), 6 * lamports);
+        assert_eq!(liquidity_headroom(&state, 10 * lamports), 0);
+        assert_eq!(liquidity_headroom(&state, 11 * lamports), 0);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_client_quotes_against_the_fetched_leg() {
+        use crate::client::MarinadeClient;
+        use crate::deployment::DeploymentConfig;
+        use crate::error::ErrorKind;
+        use crate::test_utils::{token_account, MockFetcher, FIXTURE_SLOT};
+
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let mut state = sample_state();
+        state.staking_sol_cap = state.total_lamports_under_control() + 1;
+        let rpc = |msol_leg_amount| {
+            MockFetcher::new()
+                .with_valuation_accounts(&addresses, &state, 1)
+                .with_account(addresses.liq_pool_msol_leg, token_account(addresses.msol_mint, msol_leg_amount))
+        };
+
+        let client = MarinadeClient::builder().rpc_client(rpc(u64::MAX)).build();
+        let quote = client.simulate_deposit(FIXTURE_IX_AMOUNT).unwrap();
+        assert_eq!((quote.msol, quote.slot), (833_217_608, Some(FIXTURE_SLOT)));
+
+        let client = MarinadeClient::builder().rpc_client(rpc(0)).build();
+        let err = client.simulate_deposit(FIXTURE_IX_AMOUNT).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Deposit(DepositError::Amount(AmountError::ExceedsCap { .. }))));
+        assert_eq!(err.slot(), Some(FIXTURE_SLOT));
+    }
+}
@@ -0,0 +1,267 @@
+//! where a slot sits in its epoch, for staleness checks and ETAs. offline users build one from
+//! the cluster's `EpochSchedule`; with an rpc connection, `EpochTracker` asks the node once per
+//! epoch and works out the rest, and `get_state_at_epoch_start` reads the state an epoch
+//! opened with
+
+use serde::{Deserialize, Serialize};
+use solana_program::clock::{Epoch, Slot};
+use solana_program::epoch_schedule::EpochSchedule;
+
+#[cfg(feature = "rpc")]
+use std::sync::Mutex;
+
+#[cfg(feature = "rpc")]
+use solana_sdk::epoch_info::EpochInfo;
+
+#[cfg(feature = "rpc")]
+use solana_client::client_error::{ClientError, ClientErrorKind};
+#[cfg(feature = "rpc")]
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+};
+#[cfg(feature = "rpc")]
+use solana_client::rpc_request::RpcError;
+
+#[cfg(feature = "rpc")]
+use crate::accounts::marinade::MarinadeState;
+#[cfg(feature = "rpc")]
+use crate::client::MarinadeClient;
+#[cfg(feature = "rpc")]
+use crate::error::{Error, ErrorKind, RpcFailureKind};
+#[cfg(feature = "rpc")]
+use crate::rpc::RpcFetcher;
+#[cfg(feature = "rpc")]
+use crate::verbosity::debug;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EpochContext {
+    pub epoch: Epoch,
+    /// the slot the context describes
+    pub absolute_slot: Slot,
+    /// `absolute_slot` counted from the epoch's first slot, 0 there
+    pub slot_index: u64,
+    pub slots_in_epoch: u64,
+}
+
+impl EpochContext {
+    pub fn from_schedule(schedule: &EpochSchedule, slot: Slot) -> Self {
+        let (epoch, slot_index) = schedule.get_epoch_and_slot_index(slot);
+        Self { epoch, absolute_slot: slot, slot_index, slots_in_epoch: schedule.get_slots_in_epoch(epoch) }
+    }
+
+    pub fn first_slot(&self) -> Slot {
+        self.absolute_slot - self.slot_index
+    }
+
+    /// slots until the next epoch starts, the current one included: 1 in the epoch's last slot
+    pub fn slots_remaining(&self) -> u64 {
+        self.slots_in_epoch.saturating_sub(self.slot_index)
+    }
+
+    /// 0 at the epoch's first slot, approaching 1 towards its last
+    pub fn progress(&self) -> f64 {
+        match self.slots_in_epoch {
+            0 => 0.0,
+            slots => self.slot_index as f64 / slots as f64,
+        }
+    }
+
+    /// the same epoch's context as of `slot`, None when `slot` is outside it
+    pub fn at_slot(&self, slot: Slot) -> Option<Self> {
+        let slot_index = slot.checked_sub(self.first_slot()).filter(|index| *index < self.slots_in_epoch)?;
+        Some(Self { absolute_slot: slot, slot_index, ..*self })
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl From<&EpochInfo> for EpochContext {
+    fn from(info: &EpochInfo) -> Self {
+        Self {
+            epoch: info.epoch,
+            absolute_slot: info.absolute_slot,
+            slot_index: info.slot_index,
+            slots_in_epoch: info.slots_in_epoch,
+        }
+    }
+}
+
+/// the epoch the node was last seen in, so a slot inside it needs no `getEpochInfo`
+#[cfg(feature = "rpc")]
+#[derive(Debug, Default)]
+pub struct EpochTracker {
+    epoch: Mutex<Option<EpochContext>>,
+}
+
+#[cfg(feature = "rpc")]
+impl EpochTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the context of `slot`, asking the node only when `slot` is outside the epoch seen last.
+    /// None when even the node's current epoch doesn't hold `slot`, e.g. a slot from an epoch
+    /// long gone
+    pub fn context_at(&self, rpc_client: &dyn RpcFetcher, slot: Slot) -> crate::error::Result<Option<EpochContext>> {
+        if let Some(context) = self.epoch.lock().unwrap().and_then(|epoch| epoch.at_slot(slot)) {
+            return Ok(Some(context));
+        }
+        let current = EpochContext::from(&rpc_client.get_epoch_info()?);
+        *self.epoch.lock().unwrap() = Some(current);
+        Ok(current.at_slot(slot))
+    }
+}
+
+/// the state an epoch opened with, see `get_state_at_epoch_start`
+#[cfg(feature = "rpc")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochStartState {
+    pub epoch: Epoch,
+    /// the epoch's first slot, by mainnet's schedule
+    pub first_slot: Slot,
+    /// the first slot from `first_slot` on with a block, which the state was asked for
+    pub slot: Slot,
+    /// the slot the state was read at. a node only serves its current state, so online that's
+    /// wherever the node is; offline it's the first snapshot the cache has at or after `slot`
+    pub state_slot: Slot,
+    pub state: MarinadeState,
+}
+
+/// the state as of `epoch`'s first block: its first slot, or the first after it the leaders
+/// didn't skip. fails with `EpochInFuture` for an epoch the node has no block of yet and
+/// `HistoryUnavailable` when the node has pruned the blocks. offline there's no node to walk
+/// the blocks of, and the snapshot cache is asked for the first state from the first slot on
+#[cfg(feature = "rpc")]
+pub fn get_state_at_epoch_start(client: &MarinadeClient, epoch: Epoch) -> crate::error::Result<EpochStartState> {
+    let options = client.options();
+    let _verbosity = options.verbosity.enter();
+    let first_slot = EpochSchedule::without_warmup().get_first_slot_in_epoch(epoch);
+    let slot = match options.offline {
+        true => first_slot,
+        false => first_block(client.rpc_client(), epoch, first_slot)?,
+    };
+    let state_pubkey = &client.deployment().state;
+    let (state_slot, state) = crate::fetch_full_state(client.rpc_client(), state_pubkey, Some(slot), options)?;
+    Ok(EpochStartState { epoch, first_slot, slot, state_slot, state })
+}
+
+/// the first slot from `first_slot` up to the node's with a block
+#[cfg(feature = "rpc")]
+fn first_block(rpc_client: &dyn RpcFetcher, epoch: Epoch, first_slot: Slot) -> crate::error::Result<Slot> {
+    let current = rpc_client.get_epoch_info()?;
+    let in_future = || Error::new(ErrorKind::EpochInFuture { epoch, current: current.epoch });
+    if epoch > current.epoch {
+        return Err(in_future());
+    }
+    for slot in first_slot..=current.absolute_slot {
+        match rpc_client.get_block_time(slot) {
+            Ok(_) => return Ok(slot),
+            Err(e) if is_skipped(&e) => debug!(slot, "skipped slot"),
+            Err(e) if RpcFailureKind::of(&e) == RpcFailureKind::PrunedHistory => {
+                return Err(Error::new(ErrorKind::HistoryUnavailable { slot }).with_slot(Some(slot)));
+            }
+            Err(e) => return Err(Error::from(e).with_slot(Some(slot))),
+        }
+    }
+    Err(in_future())
+}
+
+/// whether the node answered that no block was produced in the slot
+#[cfg(feature = "rpc")]
+fn is_skipped(error: &ClientError) -> bool {
+    matches!(
+        error.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code: JSON_RPC_SERVER_ERROR_SLOT_SKIPPED | JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED,
+            ..
+        })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_middle_and_end_of_an_epoch() {
+        let schedule = EpochSchedule::without_warmup();
+        let first = 600 * schedule.slots_per_epoch;
+
+        let start = EpochContext::from_schedule(&schedule, first);
+        assert_eq!((start.epoch, start.slot_index, start.first_slot()), (600, 0, first));
+        assert_eq!((start.slots_remaining(), start.progress()), (432_000, 0.0));
+
+        let middle = EpochContext::from_schedule(&schedule, first + 216_000);
+        assert_eq!((middle.slot_index, middle.slots_remaining(), middle.progress()), (216_000, 216_000, 0.5));
+        assert_eq!(start.at_slot(first + 216_000), Some(middle));
+
+        let last = EpochContext::from_schedule(&schedule, first + 431_999);
+        assert_eq!((last.epoch, last.slot_index, last.slots_remaining()), (600, 431_999, 1));
+        assert!(last.progress() < 1.0);
+        assert_eq!(last.at_slot(first + 432_000), None);
+        assert_eq!(last.at_slot(first - 1), None);
+        assert_eq!(EpochContext::from_schedule(&schedule, first + 432_000).epoch, 601);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_tracker_asks_once_per_epoch() {
+        use crate::test_utils::MockFetcher;
+
+        let info = |absolute_slot: u64| EpochInfo {
+            epoch: absolute_slot / 432_000,
+            slot_index: absolute_slot % 432_000,
+            slots_in_epoch: 432_000,
+            absolute_slot,
+            block_height: 0,
+            transaction_count: None,
+        };
+        let first = 600 * 432_000;
+        let rpc = MockFetcher::new().with_epoch_info(info(first + 10));
+        let tracker = EpochTracker::new();
+
+        assert_eq!(tracker.context_at(&rpc, first + 5).unwrap(), Some(EpochContext::from(&info(first + 5))));
+        assert_eq!(tracker.context_at(&rpc, first + 431_999).unwrap().unwrap().slots_remaining(), 1);
+        assert_eq!(rpc.call_count("getEpochInfo"), 1);
+        // the next epoch's slot asks again, and the node hasn't got there
+        assert_eq!(tracker.context_at(&rpc, first + 432_000).unwrap(), None);
+        assert_eq!(rpc.call_count("getEpochInfo"), 2);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_state_at_epoch_start_walks_past_skipped_slots() {
+        use crate::constants::MARINADE_STATE_PUBKEY;
+        use crate::test_utils::{sample_state, state_account, MockFetcher};
+
+        let (first, state) = (600 * 432_000, sample_state());
+        let rpc = MockFetcher::new()
+            .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+            .with_epoch_info(EpochInfo {
+                epoch: 601,
+                slot_index: 20,
+                slots_in_epoch: 432_000,
+                absolute_slot: first + 432_020,
+                block_height: 0,
+                transaction_count: None,
+            })
+            .with_skipped_slots([first, first + 1, first + 2])
+            .with_skipped_slots(first + 432_000..=first + 432_020)
+            .with_block_time(first + 3, 1_700_000_000)
+            .with_first_available_block(first - 432_000);
+        let rpc = std::sync::Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+
+        let start = get_state_at_epoch_start(&client, 600).unwrap();
+        assert_eq!((start.epoch, start.first_slot, start.slot), (600, first, first + 3));
+        assert_eq!((start.state_slot, start.state), (first + 3, state));
+        assert_eq!(rpc.call_count("getBlockTime"), 4);
+
+        let future = get_state_at_epoch_start(&client, 602).unwrap_err();
+        assert!(matches!(future.kind(), ErrorKind::EpochInFuture { epoch: 602, current: 601 }), "{}", future);
+        // the epoch has started, but every slot of it the node has been through was skipped
+        let started = get_state_at_epoch_start(&client, 601).unwrap_err();
+        assert!(matches!(started.kind(), ErrorKind::EpochInFuture { epoch: 601, current: 601 }), "{}", started);
+        let pruned = get_state_at_epoch_start(&client, 598).unwrap_err();
+        assert!(matches!(pruned.kind(), ErrorKind::HistoryUnavailable { slot } if *slot == 598 * 432_000));
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SCAN_ERROR, JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP, JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET, JSON_RPC_SERVER_ERROR_KEY_EXCLUDED_FROM_SECONDARY_INDEX,
+    JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY, JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+    JSON_RPC_SERVER_ERROR_TRANSACTION_HISTORY_NOT_AVAILABLE,
+};
+use solana_client::rpc_request::RpcError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionVersion;
+
+use crate::deposit::DepositError;
+use crate::underlying::InvalidMintUnderlying;
+use crate::unstake::LiquidUnstakeError;
+use crate::valuation::MintCheckError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// what went wrong, independent of where
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// an rpc call failed
+    Rpc(ClientError),
+    /// the account doesn't exist at the requested slot
+    AccountNotFound { role: &'static str },
+    /// the account exists but its data doesn't parse as the expected type
+    InvalidAccountData { role: &'static str, source: std::io::Error },
+    /// the node returned fewer accounts than requested
+    ShortResponse { requested: usize, returned: usize },
+    /// the transaction carries no block time and it couldn't be looked up
+    MissingBlockTime,
+    /// no registered parser handles the mint or transaction
+    NotSupported,
+    /// a webhook endpoint didn't accept a payload, after every retry
+    Delivery { attempts: u32, reason: String },
+    /// offline, and the snapshot cache has no state at or after the slot (the latest when None)
+    OfflineMiss { slot: Option<u64> },
+    /// a cached snapshot was taken on another cluster or deployment than the client's
+    ClusterMismatch { expected: String, found: String },
+    /// a transaction signature given as a string doesn't parse
+    InvalidSignature { input: String, reason: String },
+    /// a raw transaction payload or its meta doesn't decode
+    InvalidTransaction { reason: String },
+    /// the program would reject the deposit being simulated
+    Deposit(DepositError),
+    /// the program would reject the liquid unstake being simulated
+    LiquidUnstake(LiquidUnstakeError),
+    /// the mSOL mint account isn't the mint balances are computed against
+    InvalidMint(MintCheckError),
+    /// the node only serves current balances, and no balance was supplied for the slot
+    NoHistoricalBalance { slot: Option<u64> },
+    /// a setting from the environment or a config file doesn't parse; `origin` is the variable,
+    /// or the file and key
+    InvalidConfig { origin: String, reason: String },
+    /// the epoch hasn't started, or has no block yet, on the node, which is in `current`
+    EpochInFuture { epoch: u64, current: u64 },
+    /// the node no longer keeps the block at the slot; an archival node might
+    HistoryUnavailable { slot: u64 },
+    /// the state was read at `state_slot`, further from the transaction's slot than the
+    /// `AnalyzeOptions::max_slot_skew` of `max`
+    SlotSkew { tx_slot: u64, state_slot: u64, max: u64 },
+    /// the valuation doesn't hold together, e.g. a state without mSOL or a transaction with a
+    /// zero block time. see `MintUnderlyingBuilder::build`
+    InvalidMintUnderlying(InvalidMintUnderlying),
+    /// a worker thread panicked on the item, with the panic's message when it had one
+    WorkerPanicked { message: String },
+    /// the transaction is of a newer version than the `AnalyzeOptions::max_transaction_version`
+    /// of `max`, so its keys and instructions can't be relied on
+    UnsupportedTransactionVersion { version: u8, max: TransactionVersion },
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "rpc error: {}", e),
+            Self::AccountNotFound { role } => write!(f, "{} account not found", role),
+            Self::InvalidAccountData { role, source } => write!(f, "failed to parse {} account: {}", role, source),
+            Self::ShortResponse { requested, returned } => {
+                write!(f, "requested {} accounts but the node returned {}", requested, returned)
+            }
+            Self::MissingBlockTime => write!(f, "transaction has no block time"),
+            Self::NotSupported => write!(f, "no registered parser supports this"),
+            Self::Delivery { attempts, reason } => write!(f, "delivery failed after {} attempts: {}", attempts, reason),
+            Self::OfflineMiss { slot: Some(slot) } => write!(f, "offline and no cached state at or after slot {}", slot),
+            Self::OfflineMiss { slot: None } => write!(f, "offline and no cached state"),
+            Self::ClusterMismatch { expected, found } => {
+                write!(f, "cached snapshot is of {}, expected {}", found, expected)
+            }
+            Self::InvalidSignature { input, reason } => write!(f, "invalid signature {:?}: {}", input, reason),
+            Self::InvalidTransaction { reason } => write!(f, "invalid transaction payload: {}", reason),
+            Self::Deposit(e) => write!(f, "deposit would be rejected: {}", e),
+            Self::LiquidUnstake(e) => write!(f, "liquid unstake would be rejected: {}", e),
+            Self::InvalidMint(e) => write!(f, "msol mint failed verification: {}", e),
+            Self::NoHistoricalBalance { slot: Some(slot) } => write!(f, "no balance supplied for slot {}", slot),
+            Self::NoHistoricalBalance { slot: None } => write!(f, "no balance supplied"),
+            Self::InvalidConfig { origin, reason } => write!(f, "invalid {}: {}", origin, reason),
+            Self::EpochInFuture { epoch, current } => {
+                write!(f, "epoch {} has no block yet, the node is in epoch {}", epoch, current)
+            }
+            Self::HistoryUnavailable { slot } => write!(f, "the node no longer has the block at slot {}", slot),
+            Self::SlotSkew { tx_slot, state_slot, max } => write!(
+                f,
+                "state read at slot {}, {} slots from the transaction at {}, more than the {} allowed",
+                state_slot,
+                state_slot.abs_diff(*tx_slot),
+                tx_slot,
+                max
+            ),
+            Self::InvalidMintUnderlying(e) => write!(f, "invalid valuation: {}", e),
+            Self::WorkerPanicked { message } => write!(f, "worker panicked: {}", message),
+            Self::UnsupportedTransactionVersion { version, max: TransactionVersion::Legacy(_) } => {
+                write!(f, "transaction version {} is newer than legacy, the max configured", version)
+            }
+            Self::UnsupportedTransactionVersion { version, max: TransactionVersion::Number(max) } => {
+                write!(f, "transaction version {} is newer than {}, the max configured", version, max)
+            }
+        }
+    }
+}
+
+/// how an rpc failure should be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpcFailureKind {
+    /// rate limits, timeouts, unreachable or unhealthy nodes: worth retrying the same call
+    Transient,
+    /// the node answered and will answer the same again, e.g. a transaction it doesn't have or
+    /// a malformed request
+    Permanent,
+    /// the node no longer has (or never kept) the slot or history asked about; another node,
+    /// typically an archival one, might
+    PrunedHistory,
+}
+
+impl RpcFailureKind {
+    /// by the transport error or the json-rpc error code. failures that can't be told apart
+    /// count as transient
+    pub fn of(error: &ClientError) -> Self {
+        match error.kind() {
+            ClientErrorKind::Io(_) => Self::Transient,
+            ClientErrorKind::Reqwest(e) => match e.status() {
+                Some(status) if status.as_u16() == 429 || status.is_server_error() => Self::Transient,
+                Some(status) if status.is_client_error() => Self::Permanent,
+                _ => Self::Transient,
+            },
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => Self::of_code(*code),
+            ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => Self::Transient,
+            ClientErrorKind::RpcError(RpcError::ParseError(_) | RpcError::ForUser(_)) => Self::Permanent,
+            // what `RpcClient` makes of a `null` result, e.g. an unknown transaction
+            ClientErrorKind::SerdeJson(_) => Self::Permanent,
+            ClientErrorKind::SigningError(_) | ClientErrorKind::TransactionError(_) => Self::Permanent,
+            ClientErrorKind::Custom(_) => Self::Transient,
+        }
+    }
+
+    fn of_code(code: i64) -> Self {
+        match code {
+            JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP
+            | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+            | JSON_RPC_SERVER_ERROR_SLOT_SKIPPED
+            | JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED
+            | JSON_RPC_SERVER_ERROR_TRANSACTION_HISTORY_NOT_AVAILABLE
+            | JSON_RPC_SERVER_ERROR_KEY_EXCLUDED_FROM_SECONDARY_INDEX => Self::PrunedHistory,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+            | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+            | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+            | JSON_RPC_SCAN_ERROR => Self::Transient,
+            // some providers put the http status of a rate limit in the json-rpc error too;
+            // -32603 is the server's internal error
+            429 | -32603 => Self::Transient,
+            _ => Self::Permanent,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    kind: ErrorKind,
+    pubkey: Option<Pubkey>,
+    slot: Option<u64>,
+    signature: Option<Signature>,
+}
+
+/// the crate's error: an `ErrorKind` plus whatever context was known where it happened -
+/// the account being fetched, the requested slot and the transaction being analyzed
+#[derive(Debug)]
+pub struct Error {
+    inner: Box<Inner>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self { inner: Box::new(Inner { kind, pubkey: None, slot: None, signature: None }) }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner.kind
+    }
+
+    pub fn pubkey(&self) -> Option<&Pubkey> {
+        self.inner.pubkey.as_ref()
+    }
+
+    pub fn slot(&self) -> Option<u64> {
+        self.inner.slot
+    }
+
+    pub fn signature(&self) -> Option<&Signature> {
+        self.inner.signature.as_ref()
+    }
+
+    /// the underlying rpc error, if any
+    pub fn rpc_error(&self) -> Option<&ClientError> {
+        match &self.inner.kind {
+            ErrorKind::Rpc(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// how to handle the underlying rpc error, None when it isn't one
+    pub fn rpc_failure(&self) -> Option<RpcFailureKind> {
+        self.rpc_error().map(RpcFailureKind::of)
+    }
+
+    /// the underlying deserialization (borsh / spl pack) error, if any
+    pub fn parse_error(&self) -> Option<&std::io::Error> {
+        match &self.inner.kind {
+            ErrorKind::InvalidAccountData { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// attach the account involved; keeps an already recorded one
+    pub fn with_pubkey(mut self, pubkey: Pubkey) -> Self {
+        self.inner.pubkey.get_or_insert(pubkey);
+        self
+    }
+
+    /// attach the requested slot; keeps an already recorded one
+    pub fn with_slot(mut self, slot: Option<u64>) -> Self {
+        if self.inner.slot.is_none() {
+            self.inner.slot = slot;
+        }
+        self
+    }
+
+    /// attach the transaction being analyzed; keeps an already recorded one
+    pub fn with_signature(mut self, signature: Option<Signature>) -> Self {
+        if self.inner.signature.is_none() {
+            self.inner.signature = signature;
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inner.kind)?;
+        if let Some(pubkey) = &self.inner.pubkey {
+            write!(f, " (pubkey: {})", pubkey)?;
+        }
+        if let Some(slot) = self.inner.slot {
+            write!(f, " (slot: {})", slot)?;
+        }
+        if let Some(signature) = &self.inner.signature {
+            write!(f, " (signature: {})", signature)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.inner.kind {
+            ErrorKind::Rpc(e) => Some(e),
+            ErrorKind::InvalidAccountData { source, .. } => Some(source),
+            ErrorKind::Deposit(e) => Some(e),
+            ErrorKind::LiquidUnstake(e) => Some(e),
+            ErrorKind::InvalidMint(e) => Some(e),
+            ErrorKind::InvalidMintUnderlying(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Self::new(ErrorKind::Rpc(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_request::RpcResponseErrorData;
+    use solana_sdk::transaction::TransactionError;
+
+    fn response_error(code: i64) -> ClientError {
+        let data = RpcResponseErrorData::Empty;
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message: String::new(), data }).into()
+    }
+
+    #[test]
+    fn test_rpc_failure_kinds() {
+        use RpcFailureKind::*;
+        let null_result = serde_json::from_value::<u64>(serde_json::Value::Null).unwrap_err();
+        let timed_out = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let cases = [
+            (ClientErrorKind::Io(timed_out).into(), Transient),
+            (ClientErrorKind::RpcError(RpcError::RpcRequestError("connection refused".into())).into(), Transient),
+            (response_error(429), Transient),
+            (response_error(JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY), Transient),
+            (response_error(JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED), Transient),
+            (response_error(-32603), Transient),
+            (ClientErrorKind::SerdeJson(null_result).into(), Permanent),
+            (response_error(-32602), Permanent),
+            (ClientErrorKind::TransactionError(TransactionError::AccountNotFound).into(), Permanent),
+            (response_error(JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE), PrunedHistory),
+            (response_error(JSON_RPC_SERVER_ERROR_SLOT_SKIPPED), PrunedHistory),
+            (response_error(JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED), PrunedHistory),
+            (response_error(JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP), PrunedHistory),
+            (response_error(JSON_RPC_SERVER_ERROR_TRANSACTION_HISTORY_NOT_AVAILABLE), PrunedHistory),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(RpcFailureKind::of(&error), kind, "{}", error);
+            assert_eq!(Error::from(error).rpc_failure(), Some(kind));
+        }
+        assert_eq!(Error::new(ErrorKind::MissingBlockTime).rpc_failure(), None);
+    }
+}
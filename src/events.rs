@@ -0,0 +1,1138 @@
+//! typed events for the marinade instructions worth alerting on, read off a transaction's
+//! instructions and effects. `decode_events` needs nothing but the transaction;
+//! `EventClassifier` also resolves the list indices an instruction carries against the stake
+//! and validator lists, one transaction or a whole block at a time
+//!
+//! an `EventSink` is where a watcher hands them on, with `SeverityFilter` in front of the ones
+//! that should only hear about e.g. an `AuthorityChanged`
+
+use serde::{Serialize, Serializer};
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiConfirmedBlock};
+
+use crate::accounts::instructions::{
+    decode_args, ChangeAuthorityArgs, DeactivateStakeArgs, EmergencyUnstakeArgs, MarinadeFinanceInstruction,
+    MergeStakesArgs, PartialUnstakeArgs, RedelegateArgs, StakeReserveArgs, UpdateDeactivatedArgs,
+    WithdrawStakeAccountArgs,
+};
+use crate::accounts::lists::{list_item, StakeRecord, ValidatorRecord};
+use crate::accounts::marinade::{MarinadeState, OperationalConfig};
+use crate::deployment::DeploymentConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::rpc::RpcFetcher;
+use crate::transaction::{MarinadeCall, TransactionInput};
+use crate::unstake::WithdrawStakeAccountSplit;
+use crate::valuation::fetch_multiple_accounts;
+use crate::{fetch_full_state, AnalyzeOptions};
+use crate::verbosity::{debug, warn};
+
+/// the transaction an event came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventContext {
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    #[serde(serialize_with = "serialize_signature")]
+    pub signature: Option<Signature>,
+}
+
+fn serialize_signature<S: Serializer>(
+    signature: &Option<Signature>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match signature {
+        Some(signature) => serializer.collect_str(signature),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// how urgently an event wants someone's attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// the program's day to day running
+    Info,
+    /// a stake account pulled out of rotation, typically over a delinquent validator, or the
+    /// program resuming
+    Warning,
+    /// governance: who controls the program, its funds or its pause switch changed, or the
+    /// program was paused
+    Critical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MarinadeEvent {
+    AuthorityChanged(AuthorityChangedEvent),
+    Pause(PauseEvent),
+    Resume(PauseEvent),
+    EmergencyUnstake(EmergencyUnstakeEvent),
+    Redelegate(RedelegateEvent),
+    MergeStakes(MergeStakesEvent),
+    PartialUnstake(PartialUnstakeEvent),
+    StakeReserve(StakeReserveEvent),
+    DeactivateStake(DeactivateStakeEvent),
+    UpdateDeactivated(UpdateDeactivatedEvent),
+    WithdrawStakeAccount(WithdrawStakeAccountEvent),
+}
+
+impl MarinadeEvent {
+    pub fn context(&self) -> &EventContext {
+        match self {
+            Self::AuthorityChanged(event) => &event.context,
+            Self::Pause(event) | Self::Resume(event) => &event.context,
+            Self::EmergencyUnstake(event) => &event.context,
+            Self::Redelegate(event) => &event.context,
+            Self::MergeStakes(event) => &event.context,
+            Self::PartialUnstake(event) => &event.context,
+            Self::StakeReserve(event) => &event.context,
+            Self::DeactivateStake(event) => &event.context,
+            Self::UpdateDeactivated(event) => &event.context,
+            Self::WithdrawStakeAccount(event) => &event.context,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::AuthorityChanged(_) | Self::Pause(_) => Severity::Critical,
+            Self::EmergencyUnstake(_) | Self::Resume(_) => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+
+    /// whether resolving the event reads the state the transaction left behind
+    fn reads_state(&self) -> bool {
+        matches!(self, Self::Pause(_) | Self::Resume(_)) || self.reads_lists()
+    }
+
+    /// whether resolving the event reads the stake and validator lists
+    fn reads_lists(&self) -> bool {
+        matches!(
+            self,
+            Self::EmergencyUnstake(_) | Self::Redelegate(_) | Self::DeactivateStake(_) | Self::WithdrawStakeAccount(_)
+        )
+    }
+
+    fn resolve(&mut self, state: &MarinadeState, validator_list: &[u8], stake_list: &[u8]) {
+        match self {
+            Self::EmergencyUnstake(event) => event.resolve(state, validator_list, stake_list),
+            Self::Redelegate(event) => event.resolve(state, validator_list, stake_list),
+            Self::DeactivateStake(event) => event.resolve(state, stake_list),
+            Self::Pause(event) | Self::Resume(event) => event.resolve(state),
+            Self::WithdrawStakeAccount(event) => event.resolve(state, stake_list),
+            Self::AuthorityChanged(_)
+            | Self::MergeStakes(_)
+            | Self::PartialUnstake(_)
+            | Self::StakeReserve(_)
+            | Self::UpdateDeactivated(_) => {}
+        }
+    }
+}
+
+/// one of the state's authorities or accounts `change_authority` can replace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthorityRole {
+    Admin,
+    ValidatorManager,
+    OperationalSolAccount,
+    TreasuryMsolAccount,
+    PauseAuthority,
+}
+
+impl AuthorityRole {
+    /// the role's current holder in `config`
+    pub fn holder(&self, config: &OperationalConfig) -> Pubkey {
+        match self {
+            Self::Admin => config.admin_authority,
+            Self::ValidatorManager => config.validator_manager_authority,
+            Self::OperationalSolAccount => config.operational_sol_account,
+            Self::TreasuryMsolAccount => config.treasury_msol_account,
+            Self::PauseAuthority => config.pause_authority,
+        }
+    }
+}
+
+/// a role `change_authority` set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AuthorityChange {
+    pub role: AuthorityRole,
+    /// the holder in the state before the transaction. None until resolved, and when the state
+    /// read already has `new`: either the transaction set what was there, or the node could only
+    /// serve a state from after it
+    #[serde(serialize_with = "crate::serde_pubkey::serialize_option")]
+    pub old: Option<Pubkey>,
+    #[serde(with = "crate::serde_pubkey")]
+    pub new: Pubkey,
+}
+
+/// a `change_authority`: the admin handing one or more of the program's authorities, or the
+/// accounts its fees go to, to new keys. whoever holds them can drain or halt the program, so
+/// mSOL holders want to hear about every one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthorityChangedEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    /// the admin that signed
+    #[serde(with = "crate::serde_pubkey")]
+    pub admin_authority: Pubkey,
+    /// the roles the instruction set, in the order the instruction lists them
+    pub changes: Vec<AuthorityChange>,
+}
+
+impl AuthorityChangedEvent {
+    fn decode(context: EventContext, call: &MarinadeCall) -> Option<Self> {
+        let args: ChangeAuthorityArgs = decode_args(&call.data)?;
+        let data = args.data;
+        let changes = [
+            (AuthorityRole::Admin, data.admin),
+            (AuthorityRole::ValidatorManager, data.validator_manager),
+            (AuthorityRole::OperationalSolAccount, data.operational_sol_account),
+            (AuthorityRole::TreasuryMsolAccount, data.treasury_msol_account),
+            (AuthorityRole::PauseAuthority, data.pause_authority),
+        ]
+        .into_iter()
+        .filter_map(|(role, new)| Some(AuthorityChange { role, old: None, new: new? }))
+        .collect();
+        Some(Self { context, admin_authority: call.account("admin_authority")?, changes })
+    }
+
+    /// fill in each change's `old` from the state as it was before the transaction
+    pub fn resolve(&mut self, pre_state: &MarinadeState) {
+        let config = pre_state.operational();
+        for change in &mut self.changes {
+            let holder = change.role.holder(&config);
+            change.old = (holder != change.new).then_some(holder);
+        }
+    }
+}
+
+/// a `pause` or `resume`: the pause authority stopping deposits, unstakes, liquidity and the
+/// cranks, or letting them run again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PauseEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    /// the signer
+    #[serde(with = "crate::serde_pubkey")]
+    pub pause_authority: Pubkey,
+    /// the flag the instruction sets, true for a `pause`
+    pub paused: bool,
+    /// the state's `paused` after the transaction. None until resolved
+    pub state_paused: Option<bool>,
+}
+
+impl PauseEvent {
+    fn decode(context: EventContext, call: &MarinadeCall) -> Option<Self> {
+        let paused = call.instruction == MarinadeFinanceInstruction::Pause;
+        Some(Self { context, pause_authority: call.account("pause_authority")?, paused, state_paused: None })
+    }
+
+    /// fill in `state_paused` from the state as of the transaction's slot
+    pub fn resolve(&mut self, state: &MarinadeState) {
+        self.state_paused = Some(state.paused);
+        if state.paused != self.paused {
+            warn!(paused = self.paused, state_paused = state.paused, "state doesn't show the pause flag set");
+        }
+    }
+
+    /// whether the state shows the flag the instruction set, None until resolved. a node past
+    /// the slot can also show a later `pause` or `resume` instead
+    pub fn verified(&self) -> Option<bool> {
+        self.state_paused.map(|state_paused| state_paused == self.paused)
+    }
+}
+
+/// an `emergency_unstake`: the validator manager deactivating a whole stake account outside the
+/// crank's rebalancing, typically because its validator went delinquent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EmergencyUnstakeEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    pub validator_index: u32,
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    /// the stake account's balance after the transaction, rent reserve included, all of it now
+    /// cooling down. None when the meta carries no balances
+    pub stake_account_lamports: Option<u64>,
+    /// the validator at `validator_index`, from the validator list
+    #[serde(serialize_with = "crate::serde_pubkey::serialize_option")]
+    pub vote_account: Option<Pubkey>,
+    /// the stake record's delegation as of its last update, which is what the validator loses.
+    /// None until resolved, or when the record at `stake_index` is for another account by then
+    pub delegated_lamports: Option<u64>,
+}
+
+impl EmergencyUnstakeEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: EmergencyUnstakeArgs = decode_args(&call.data)?;
+        let stake_account = call.account("stake_account")?;
+        Some(Self {
+            context,
+            stake_index: args.stake_index,
+            validator_index: args.validator_index,
+            stake_account,
+            stake_account_lamports: balances.post(&stake_account),
+            vote_account: None,
+            delegated_lamports: None,
+        })
+    }
+
+    /// fill in `vote_account` and `delegated_lamports` from the lists' account data. the lists the
+    /// state describes are read as they are, so a record that moved since leaves its field None
+    pub fn resolve(&mut self, state: &MarinadeState, validator_list: &[u8], stake_list: &[u8]) {
+        let validators = &state.validator_system.validator_list;
+        match list_item::<ValidatorRecord>(validators, validator_list, self.validator_index) {
+            Ok(record) => self.vote_account = Some(record.validator_account),
+            Err(e) => debug!(error = %e, index = self.validator_index, "validator record not readable"),
+        }
+        self.delegated_lamports = recorded_delegation(state, stake_list, self.stake_index, &self.stake_account);
+    }
+}
+
+/// a `redelegate`: stake moved from one validator to another without a cooldown, into a new
+/// stake account the stake list gets a record for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedelegateEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    pub source_validator_index: u32,
+    pub dest_validator_index: u32,
+    /// the stake account the stake is moved out of
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    /// the new stake account delegated to the destination
+    #[serde(with = "crate::serde_pubkey")]
+    pub redelegate_stake_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub dest_vote_account: Pubkey,
+    /// the validator at `source_validator_index`, from the validator list
+    #[serde(serialize_with = "crate::serde_pubkey::serialize_option")]
+    pub source_vote_account: Option<Pubkey>,
+    /// the new stake account's balance after the transaction, rent reserve included. None when
+    /// the meta carries no balances
+    pub redelegate_stake_account_lamports: Option<u64>,
+    /// the delegation of the new stake account's record in the stake list. None until resolved,
+    /// or when the list no longer has a record for it
+    pub lamports_moved: Option<u64>,
+}
+
+impl RedelegateEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: RedelegateArgs = decode_args(&call.data)?;
+        let redelegate_stake_account = call.account("redelegate_stake_account")?;
+        Some(Self {
+            context,
+            stake_index: args.stake_index,
+            source_validator_index: args.source_validator_index,
+            dest_validator_index: args.dest_validator_index,
+            stake_account: call.account("stake_account")?,
+            redelegate_stake_account,
+            dest_vote_account: call.account("dest_validator_account")?,
+            source_vote_account: None,
+            redelegate_stake_account_lamports: balances.post(&redelegate_stake_account),
+            lamports_moved: None,
+        })
+    }
+
+    /// fill in `source_vote_account` and `lamports_moved` from the lists' account data. the new
+    /// record is searched for by account, since it's appended wherever the list ends
+    pub fn resolve(&mut self, state: &MarinadeState, validator_list: &[u8], stake_list: &[u8]) {
+        let (validators, stakes) = (&state.validator_system.validator_list, &state.stake_system.stake_list);
+        match list_item::<ValidatorRecord>(validators, validator_list, self.source_validator_index) {
+            Ok(record) => self.source_vote_account = Some(record.validator_account),
+            Err(e) => debug!(error = %e, index = self.source_validator_index, "validator record not readable"),
+        }
+        self.lamports_moved = (0..stakes.count)
+            .filter_map(|index| list_item::<StakeRecord>(stakes, stake_list, index).ok())
+            .find(|record| record.stake_account == self.redelegate_stake_account)
+            .map(|record| record.last_update_delegated_lamports);
+        if self.lamports_moved.is_none() {
+            debug!(account = %self.redelegate_stake_account, "no stake record for the redelegated stake");
+        }
+    }
+}
+
+/// a `merge_stakes`: two of a validator's stake accounts merged into one by the crank
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeStakesEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub destination_stake_index: u32,
+    pub source_stake_index: u32,
+    pub validator_index: u32,
+    #[serde(with = "crate::serde_pubkey")]
+    pub destination_stake: Pubkey,
+    /// closed by the merge
+    #[serde(with = "crate::serde_pubkey")]
+    pub source_stake: Pubkey,
+    /// the source's balance before the merge, all of which moves. None when the meta carries no
+    /// balances
+    pub lamports_merged: Option<u64>,
+}
+
+impl MergeStakesEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: MergeStakesArgs = decode_args(&call.data)?;
+        let source_stake = call.account("source_stake")?;
+        Some(Self {
+            context,
+            destination_stake_index: args.destination_stake_index,
+            source_stake_index: args.source_stake_index,
+            validator_index: args.validator_index,
+            destination_stake: call.account("destination_stake")?,
+            source_stake,
+            lamports_merged: balances.pre(&source_stake),
+        })
+    }
+}
+
+/// a `partial_unstake`: the crank deactivating part of a stake account to rebalance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartialUnstakeEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    pub validator_index: u32,
+    pub desired_unstake_amount: u64,
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub split_stake_account: Pubkey,
+    /// the split-off account's balance after the transaction, rent reserve included. 0 when the
+    /// program deactivated the whole stake account instead of splitting, None when the meta
+    /// carries no balances
+    pub lamports_split: Option<u64>,
+}
+
+impl PartialUnstakeEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: PartialUnstakeArgs = decode_args(&call.data)?;
+        let split_stake_account = call.account("split_stake_account")?;
+        Some(Self {
+            context,
+            stake_index: args.stake_index,
+            validator_index: args.validator_index,
+            desired_unstake_amount: args.desired_unstake_amount,
+            stake_account: call.account("stake_account")?,
+            split_stake_account,
+            lamports_split: balances.post(&split_stake_account),
+        })
+    }
+}
+
+/// a `stake_reserve`: the crank delegating lamports from the reserve to a validator, in a new
+/// stake account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StakeReserveEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub validator_index: u32,
+    #[serde(with = "crate::serde_pubkey")]
+    pub validator_vote: Pubkey,
+    /// the new stake account
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    /// what left the reserve, pre minus post balance. the new account's rent comes from the rent
+    /// payer, so this is all delegated. None when the meta carries no balances
+    pub lamports_staked: Option<u64>,
+}
+
+impl StakeReserveEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: StakeReserveArgs = decode_args(&call.data)?;
+        let reserve = call.account("reserve_pda")?;
+        let lamports_staked =
+            balances.pre(&reserve).zip(balances.post(&reserve)).map(|(pre, post)| pre.saturating_sub(post));
+        Some(Self {
+            context,
+            validator_index: args.validator_index,
+            validator_vote: call.account("validator_vote")?,
+            stake_account: call.account("stake_account")?,
+            lamports_staked,
+        })
+    }
+}
+
+/// a `deactivate_stake`: the crank deactivating stake to fund the delayed unstakes due next epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeactivateStakeEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    pub validator_index: u32,
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub split_stake_account: Pubkey,
+    /// the split-off account's balance after the transaction, rent reserve included. 0 when the
+    /// whole stake account was deactivated, None when the meta carries no balances
+    pub split_stake_lamports: Option<u64>,
+    /// the stake record's delegation as of its last update. None until resolved, or when the
+    /// record at `stake_index` is for another account by then
+    pub delegated_lamports: Option<u64>,
+}
+
+impl DeactivateStakeEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: DeactivateStakeArgs = decode_args(&call.data)?;
+        let split_stake_account = call.account("split_stake_account")?;
+        Some(Self {
+            context,
+            stake_index: args.stake_index,
+            validator_index: args.validator_index,
+            stake_account: call.account("stake_account")?,
+            split_stake_account,
+            split_stake_lamports: balances.post(&split_stake_account),
+            delegated_lamports: None,
+        })
+    }
+
+    /// fill in `delegated_lamports` from the stake list's account data
+    pub fn resolve(&mut self, state: &MarinadeState, stake_list: &[u8]) {
+        self.delegated_lamports = recorded_delegation(state, stake_list, self.stake_index, &self.stake_account);
+    }
+
+    /// what started cooling down: the split-off account when there was a split, the whole
+    /// delegation otherwise. None when neither is known
+    pub fn deactivated_lamports(&self) -> Option<u64> {
+        match self.split_stake_lamports {
+            Some(0) => self.delegated_lamports,
+            split => split,
+        }
+    }
+}
+
+/// an `update_deactivated`: a fully cooled-down stake account withdrawn into the reserve
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UpdateDeactivatedEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    /// the reserve's balance change. the stake account's rent reserve goes to the operational
+    /// account instead, so it isn't counted. None when the meta carries no balances
+    pub reclaimed_lamports: Option<u64>,
+}
+
+impl UpdateDeactivatedEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: UpdateDeactivatedArgs = decode_args(&call.data)?;
+        let reserve = call.account("reserve_pda")?;
+        let reclaimed_lamports =
+            balances.pre(&reserve).zip(balances.post(&reserve)).map(|(pre, post)| post.saturating_sub(pre));
+        let stake_account = call.account("stake_account")?;
+        Some(Self { context, stake_index: args.stake_index, stake_account, reclaimed_lamports })
+    }
+}
+
+/// a `withdraw_stake_account`: a user burning mSOL for part of a stake account, split off into a
+/// new one they're given the authorities of, instead of unstaking
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WithdrawStakeAccountEvent {
+    #[serde(flatten)]
+    pub context: EventContext,
+    pub stake_index: u32,
+    pub validator_index: u32,
+    /// mSOL given up, fee included
+    pub msol_amount: u64,
+    /// who the split stake account's authorities went to
+    #[serde(with = "crate::serde_pubkey")]
+    pub beneficiary: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub stake_account: Pubkey,
+    #[serde(with = "crate::serde_pubkey")]
+    pub split_stake_account: Pubkey,
+    /// the split stake account's balance after the transaction, the rent reserve its payer put in
+    /// included. None when the meta carries no balances
+    pub split_stake_account_lamports: Option<u64>,
+    /// the fee, burn and stake delivered at the state's fee and price. None until resolved
+    pub split: Option<WithdrawStakeAccountSplit>,
+    /// what the stake record was left delegating, which the program keeps at `min_stake` or
+    /// above. below it only when the record has been updated since. None until resolved, or
+    /// when the record at `stake_index` is for another account by then
+    pub remainder_lamports: Option<u64>,
+}
+
+impl WithdrawStakeAccountEvent {
+    fn decode(context: EventContext, call: &MarinadeCall, balances: &Balances) -> Option<Self> {
+        let args: WithdrawStakeAccountArgs = decode_args(&call.data)?;
+        let split_stake_account = call.account("split_stake_account")?;
+        Some(Self {
+            context,
+            stake_index: args.stake_index,
+            validator_index: args.validator_index,
+            msol_amount: args.msol_amount,
+            beneficiary: args.beneficiary,
+            stake_account: call.account("stake_account")?,
+            split_stake_account,
+            split_stake_account_lamports: balances.post(&split_stake_account),
+            split: None,
+            remainder_lamports: None,
+        })
+    }
+
+    /// fill in `split` from the state's fee and price and `remainder_lamports` from the stake
+    /// list, both as of the transaction's slot
+    pub fn resolve(&mut self, state: &MarinadeState, stake_list: &[u8]) {
+        self.split = Some(WithdrawStakeAccountSplit::new(state, self.msol_amount));
+        self.remainder_lamports = recorded_delegation(state, stake_list, self.stake_index, &self.stake_account);
+        if let Some(remainder) = self.remainder_lamports.filter(|remainder| *remainder < state.stake_system.min_stake) {
+            debug!(remainder, min_stake = state.stake_system.min_stake, "withdrawal left less than the minimum stake");
+        }
+    }
+}
+
+/// the delegation the stake record at `index` last saw, when the record is still `stake_account`'s
+fn recorded_delegation(state: &MarinadeState, stake_list: &[u8], index: u32, stake_account: &Pubkey) -> Option<u64> {
+    match list_item::<StakeRecord>(&state.stake_system.stake_list, stake_list, index) {
+        Ok(record) if record.stake_account == *stake_account => Some(record.last_update_delegated_lamports),
+        Ok(record) => {
+            debug!(found = %record.stake_account, "stake record is for another account");
+            None
+        }
+        Err(e) => {
+            debug!(error = %e, index, "stake record not readable");
+            None
+        }
+    }
+}
+
+/// a transaction's lamport balances by account
+struct Balances {
+    keys: Vec<Pubkey>,
+    pre: Vec<u64>,
+    post: Vec<u64>,
+}
+
+impl Balances {
+    fn of(tx: &TransactionInput<'_>) -> Self {
+        Self { keys: tx.account_keys().unwrap_or_default(), pre: tx.pre_balances(), post: tx.post_balances() }
+    }
+
+    fn pre(&self, account: &Pubkey) -> Option<u64> {
+        self.pre.get(self.keys.iter().position(|key| key == account)?).copied()
+    }
+
+    fn post(&self, account: &Pubkey) -> Option<u64> {
+        self.post.get(self.keys.iter().position(|key| key == account)?).copied()
+    }
+}
+
+/// the events in a transaction, unresolved, in instruction order. none for a failed one
+pub fn decode_events<'t>(tx: impl Into<TransactionInput<'t>>, program_id: &Pubkey) -> Vec<MarinadeEvent> {
+    let tx = tx.into();
+    if tx.failed() {
+        return Vec::new();
+    }
+    let context = EventContext { slot: tx.slot(), block_time: tx.block_time(), signature: tx.signature() };
+    let balances = Balances::of(&tx);
+    tx.marinade_calls(program_id)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|call| match call.instruction {
+            MarinadeFinanceInstruction::ChangeAuthority => {
+                AuthorityChangedEvent::decode(context, call).map(MarinadeEvent::AuthorityChanged)
+            }
+            MarinadeFinanceInstruction::Pause => PauseEvent::decode(context, call).map(MarinadeEvent::Pause),
+            MarinadeFinanceInstruction::Resume => PauseEvent::decode(context, call).map(MarinadeEvent::Resume),
+            MarinadeFinanceInstruction::EmergencyUnstake => {
+                EmergencyUnstakeEvent::decode(context, call, &balances).map(MarinadeEvent::EmergencyUnstake)
+            }
+            MarinadeFinanceInstruction::Redelegate => {
+                RedelegateEvent::decode(context, call, &balances).map(MarinadeEvent::Redelegate)
+            }
+            MarinadeFinanceInstruction::MergeStakes => {
+                MergeStakesEvent::decode(context, call, &balances).map(MarinadeEvent::MergeStakes)
+            }
+            MarinadeFinanceInstruction::PartialUnstake => {
+                PartialUnstakeEvent::decode(context, call, &balances).map(MarinadeEvent::PartialUnstake)
+            }
+            MarinadeFinanceInstruction::StakeReserve => {
+                StakeReserveEvent::decode(context, call, &balances).map(MarinadeEvent::StakeReserve)
+            }
+            MarinadeFinanceInstruction::DeactivateStake => {
+                DeactivateStakeEvent::decode(context, call, &balances).map(MarinadeEvent::DeactivateStake)
+            }
+            MarinadeFinanceInstruction::UpdateDeactivated => {
+                UpdateDeactivatedEvent::decode(context, call, &balances).map(MarinadeEvent::UpdateDeactivated)
+            }
+            MarinadeFinanceInstruction::WithdrawStakeAccount => {
+                WithdrawStakeAccountEvent::decode(context, call, &balances).map(MarinadeEvent::WithdrawStakeAccount)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// receives the events a watcher classifies, like `PriceSink` does its snapshots, and deals with
+/// its own delivery failures the same way
+pub trait EventSink: Send + Sync {
+    fn publish_event(&self, event: &MarinadeEvent);
+}
+
+impl<F: Fn(&MarinadeEvent) + Send + Sync> EventSink for F {
+    fn publish_event(&self, event: &MarinadeEvent) {
+        self(event)
+    }
+}
+
+/// a sink publishing to `inner` only the events at `min` severity or above, e.g. the critical
+/// ones to a pager's webhook
+pub struct SeverityFilter<S> {
+    inner: S,
+    min: Severity,
+}
+
+impl<S: EventSink> SeverityFilter<S> {
+    pub fn new(inner: S, min: Severity) -> Self {
+        Self { inner, min }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: EventSink> EventSink for SeverityFilter<S> {
+    fn publish_event(&self, event: &MarinadeEvent) {
+        if event.severity() >= self.min {
+            self.inner.publish_event(event);
+        }
+    }
+}
+
+/// the events of every transaction in a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockEvents {
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    /// transactions in the block, marinade or not
+    pub transactions: usize,
+    pub events: Vec<MarinadeEvent>,
+}
+
+/// decodes events and resolves them against the lists at the transaction's slot
+pub struct EventClassifier<'a> {
+    rpc_client: &'a dyn RpcFetcher,
+    options: AnalyzeOptions,
+    deployment: DeploymentConfig,
+}
+
+impl<'a> EventClassifier<'a> {
+    pub fn new(rpc_client: &'a dyn RpcFetcher, options: AnalyzeOptions) -> Self {
+        Self { rpc_client, options, deployment: DeploymentConfig::MAINNET }
+    }
+
+    pub fn with_deployment(mut self, deployment: DeploymentConfig) -> Self {
+        self.deployment = deployment;
+        self
+    }
+
+    /// the transaction's events, resolved. only fetches when an event needs resolving
+    pub fn classify<'t>(&self, tx: impl Into<TransactionInput<'t>>) -> Result<Vec<MarinadeEvent>> {
+        let tx = tx.into();
+        let mut events = decode_events(tx, &self.deployment.program_id);
+        self.resolve(tx.slot(), &mut events).map_err(|e| e.with_signature(tx.signature()))?;
+        Ok(events)
+    }
+
+    /// a block as `getBlock` returns it with full transaction details, at `slot`. the lists are
+    /// fetched once for the whole block
+    pub fn analyze_block(&self, slot: Slot, block: &UiConfirmedBlock) -> Result<BlockEvents> {
+        let Some(transactions) = &block.transactions else {
+            return Err(Error::new(ErrorKind::InvalidTransaction {
+                reason: "block was fetched without transaction details".to_string(),
+            })
+            .with_slot(Some(slot)));
+        };
+        let mut events = Vec::new();
+        for transaction in transactions {
+            let tx = EncodedConfirmedTransactionWithStatusMeta {
+                slot,
+                transaction: transaction.clone(),
+                block_time: block.block_time,
+            };
+            events.extend(decode_events(&tx, &self.deployment.program_id));
+        }
+        self.resolve(slot, &mut events)?;
+        Ok(BlockEvents { slot, block_time: block.block_time, transactions: transactions.len(), events })
+    }
+
+    fn resolve(&self, slot: Slot, events: &mut [MarinadeEvent]) -> Result<()> {
+        if events.iter().any(|event| matches!(event, MarinadeEvent::AuthorityChanged(_))) {
+            // as of the slot before, which a node that's moved on can only serve as it is now
+            let pre_slot = Some(slot.saturating_sub(1));
+            let (_, pre_state) = fetch_full_state(self.rpc_client, &self.deployment.state, pre_slot, &self.options)?;
+            for event in events.iter_mut() {
+                if let MarinadeEvent::AuthorityChanged(event) = event {
+                    event.resolve(&pre_state);
+                }
+            }
+        }
+        if !events.iter().any(MarinadeEvent::reads_state) {
+            return Ok(());
+        }
+        let (_, state) = fetch_full_state(self.rpc_client, &self.deployment.state, Some(slot), &self.options)?;
+        let (validator_list, stake_list) = match events.iter().any(MarinadeEvent::reads_lists) {
+            true => {
+                let lists = [state.validator_system.validator_list.account, state.stake_system.stake_list.account];
+                let roles = ["validator_list", "stake_list"];
+                let (_, mut accounts) =
+                    fetch_multiple_accounts(self.rpc_client, &lists, &roles, Some(slot), &self.options)?;
+                (std::mem::take(&mut accounts[0].data), std::mem::take(&mut accounts[1].data))
+            }
+            false => (Vec::new(), Vec::new()),
+        };
+        for event in events.iter_mut() {
+            event.resolve(&state, &validator_list, &stake_list);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::List;
+    use crate::client::MarinadeClient;
+    use crate::constants::{MARINADE_PROGRAM_ID, MARINADE_STATE_PUBKEY};
+    use crate::test_utils::{
+        block_of, list_account, marinade_call, marinade_transaction, sample_state, state_account, transaction_with,
+        with_balance, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT,
+    };
+    use solana_sdk::transaction::TransactionError;
+
+    const STAKE_RECORD_SIZE: usize = 49;
+    const VALIDATOR_RECORD_SIZE: usize = 53;
+
+    /// lists shaped like mainnet's: two validators, a stake account each, and a third stake
+    /// account a redelegation created
+    struct Fixture {
+        state: MarinadeState,
+        stake_records: Vec<StakeRecord>,
+        validator_records: Vec<ValidatorRecord>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let mut state = sample_state();
+            let list = |item_size: usize| List {
+                account: Pubkey::new_unique(),
+                item_size: item_size as u32,
+                ..List::default()
+            };
+            state.stake_system.stake_list = list(STAKE_RECORD_SIZE);
+            state.validator_system.validator_list = list(VALIDATOR_RECORD_SIZE);
+            let stake_record = |lamports| StakeRecord {
+                stake_account: Pubkey::new_unique(),
+                last_update_delegated_lamports: lamports,
+                last_update_epoch: 600,
+                is_emergency_unstaking: 0,
+            };
+            let validator_record =
+                || ValidatorRecord { validator_account: Pubkey::new_unique(), ..ValidatorRecord::default() };
+            Self {
+                state,
+                stake_records: vec![
+                    stake_record(1_000_000_000_000),
+                    stake_record(250_000_000_000_000),
+                    stake_record(400_000_000_000),
+                ],
+                validator_records: vec![validator_record(), validator_record()],
+            }
+        }
+
+        fn rpc(&self) -> MockFetcher {
+            let mut state = self.state.clone();
+            state.stake_system.stake_list.count = self.stake_records.len() as u32;
+            state.validator_system.validator_list.count = self.validator_records.len() as u32;
+            let (stakes, validators) = (&state.stake_system.stake_list, &state.validator_system.validator_list);
+            MockFetcher::new()
+                .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+                .with_account(stakes.account, list_account(&self.stake_records, STAKE_RECORD_SIZE))
+                .with_account(validators.account, list_account(&self.validator_records, VALIDATOR_RECORD_SIZE))
+        }
+
+        /// the second validator's stake account deactivated whole
+        fn emergency_unstake(&self, slot: u64) -> EncodedConfirmedTransactionWithStatusMeta {
+            let stake_account = self.stake_records[1].stake_account;
+            let ix = marinade_call(
+                MarinadeFinanceInstruction::EmergencyUnstake,
+                EmergencyUnstakeArgs { stake_index: 1, validator_index: 1 },
+                &[("state", MARINADE_STATE_PUBKEY), ("stake_account", stake_account)],
+            );
+            let tx = transaction_with(slot, Some(FIXTURE_BLOCK_TIME), &[ix]);
+            with_balance(tx, &stake_account, 250_002_282_880, 250_002_282_880)
+        }
+
+        /// 400 SOL of the first validator's stake moved to the second, into the third record
+        fn redelegate(&self, slot: u64) -> EncodedConfirmedTransactionWithStatusMeta {
+            let (source, new) = (self.stake_records[0].stake_account, self.stake_records[2].stake_account);
+            let ix = marinade_call(
+                MarinadeFinanceInstruction::Redelegate,
+                RedelegateArgs { stake_index: 0, source_validator_index: 0, dest_validator_index: 1 },
+                &[
+                    ("state", MARINADE_STATE_PUBKEY),
+                    ("stake_account", source),
+                    ("dest_validator_account", self.validator_records[1].validator_account),
+                    ("redelegate_stake_account", new),
+                ],
+            );
+            let tx = transaction_with(slot, Some(FIXTURE_BLOCK_TIME), &[ix]);
+            let tx = with_balance(tx, &source, 1_000_002_282_880, 600_002_282_880);
+            with_balance(tx, &new, 0, 400_002_282_880)
+        }
+    }
+
+    #[test]
+    fn test_emergency_unstake_is_resolved_against_the_lists() {
+        let fixture = Fixture::new();
+        let tx = fixture.emergency_unstake(FIXTURE_SLOT);
+        let unresolved = decode_events(&tx, &MARINADE_PROGRAM_ID);
+        let [MarinadeEvent::EmergencyUnstake(event)] = unresolved.as_slice() else {
+            panic!("expected an emergency unstake, got {:?}", unresolved);
+        };
+        assert_eq!((event.stake_index, event.validator_index, event.vote_account), (1, 1, None));
+        assert_eq!(event.stake_account, fixture.stake_records[1].stake_account);
+        assert_eq!(event.stake_account_lamports, Some(250_002_282_880));
+
+        let client = MarinadeClient::builder().rpc_client(fixture.rpc()).build();
+        let events = client.event_classifier().classify(&tx).unwrap();
+        let [MarinadeEvent::EmergencyUnstake(event)] = events.as_slice() else {
+            panic!("expected an emergency unstake, got {:?}", events);
+        };
+        assert_eq!(event.vote_account, Some(fixture.validator_records[1].validator_account));
+        assert_eq!(event.delegated_lamports, Some(250_000_000_000_000));
+        assert_eq!((event.context.slot, event.context.block_time), (FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME)));
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["kind"], "emergency_unstake");
+        assert_eq!(json["vote_account"], fixture.validator_records[1].validator_account.to_string());
+        assert_eq!(json["slot"], FIXTURE_SLOT);
+
+        // a failed one didn't happen, and nothing is fetched without an event to resolve
+        let mut failed = fixture.emergency_unstake(FIXTURE_SLOT);
+        failed.transaction.meta.as_mut().unwrap().err = Some(TransactionError::AccountInUse);
+        let rpc = fixture.rpc();
+        let classifier = EventClassifier::new(&rpc, AnalyzeOptions::default());
+        assert_eq!(classifier.classify(&failed).unwrap(), vec![]);
+        let deposit = marinade_transaction(FIXTURE_SLOT, None, &[MarinadeFinanceInstruction::Deposit]);
+        assert_eq!(classifier.classify(&deposit).unwrap(), vec![]);
+        assert!(rpc.calls().is_empty());
+    }
+
+    #[test]
+    fn test_redelegate_moves_into_the_new_record() {
+        let mut fixture = Fixture::new();
+        let tx = fixture.redelegate(FIXTURE_SLOT);
+        let rpc = fixture.rpc();
+        let events = EventClassifier::new(&rpc, AnalyzeOptions::default()).classify(&tx).unwrap();
+        let [MarinadeEvent::Redelegate(event)] = events.as_slice() else {
+            panic!("expected a redelegate, got {:?}", events);
+        };
+        let source = fixture.validator_records[0].validator_account;
+        let dest = fixture.validator_records[1].validator_account;
+        assert_eq!((event.source_vote_account, event.dest_vote_account), (Some(source), dest));
+        assert_eq!((event.stake_index, event.source_validator_index, event.dest_validator_index), (0, 0, 1));
+        assert_eq!(
+            (event.stake_account, event.redelegate_stake_account),
+            (fixture.stake_records[0].stake_account, fixture.stake_records[2].stake_account)
+        );
+        assert_eq!(event.lamports_moved, Some(400_000_000_000));
+        assert_eq!(event.redelegate_stake_account_lamports, Some(400_002_282_880));
+        assert_eq!(serde_json::to_value(&events[0]).unwrap()["dest_vote_account"], dest.to_string());
+
+        // without a record for the new account there's nothing to take the amount from
+        fixture.stake_records.pop();
+        let rpc = fixture.rpc();
+        let events = EventClassifier::new(&rpc, AnalyzeOptions::default()).classify(&tx).unwrap();
+        let MarinadeEvent::Redelegate(event) = &events[0] else { panic!("expected a redelegate") };
+        assert_eq!((event.lamports_moved, event.source_vote_account), (None, Some(source)));
+    }
+
+    #[test]
+    fn test_stake_reserve_is_read_off_the_reserve_balance() {
+        let (vote, stake_account, reserve) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = marinade_call(
+            MarinadeFinanceInstruction::StakeReserve,
+            StakeReserveArgs { validator_index: 5 },
+            &[("validator_vote", vote), ("stake_account", stake_account), ("reserve_pda", reserve)],
+        );
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix]);
+        let tx = with_balance(tx, &reserve, 400_000_000_000_000, 399_000_000_000_000);
+        let rpc = MockFetcher::new();
+        let events = EventClassifier::new(&rpc, AnalyzeOptions::default()).classify(&tx).unwrap();
+        let expected = StakeReserveEvent {
+            // the fixture is unsigned
+            context: EventContext {
+                slot: FIXTURE_SLOT,
+                block_time: Some(FIXTURE_BLOCK_TIME),
+                signature: Some(Signature::default()),
+            },
+            validator_index: 5,
+            validator_vote: vote,
+            stake_account,
+            lamports_staked: Some(1_000_000_000_000),
+        };
+        assert_eq!(events, vec![MarinadeEvent::StakeReserve(expected)]);
+        assert!(rpc.calls().is_empty());
+    }
+
+    #[test]
+    fn test_withdraw_stake_account_is_valued_at_the_state() {
+        use crate::accounts::marinade::FeeCents;
+
+        let mut fixture = Fixture::new();
+        // 1 SOL per mSOL, a 0.1% fee on the mSOL, and the first validator's stake account left
+        // with 5 SOL, below the 10 SOL minimum
+        fixture.state.msol_supply = fixture.state.total_virtual_staked_lamports();
+        fixture.state.withdraw_stake_account_fee = FeeCents { bp_cents: 1_000 };
+        fixture.state.stake_system.min_stake = 10_000_000_000;
+        fixture.stake_records[0].last_update_delegated_lamports = 5_000_000_000;
+        let (stake_account, split, beneficiary) =
+            (fixture.stake_records[0].stake_account, Pubkey::new_unique(), Pubkey::new_unique());
+        let args = WithdrawStakeAccountArgs {
+            stake_index: 0,
+            validator_index: 0,
+            msol_amount: 995_000_000_000,
+            beneficiary,
+        };
+        let ix = marinade_call(
+            MarinadeFinanceInstruction::WithdrawStakeAccount,
+            args,
+            &[("state", MARINADE_STATE_PUBKEY), ("stake_account", stake_account), ("split_stake_account", split)],
+        );
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix]);
+        let tx = with_balance(tx, &split, 0, 994_007_282_880);
+
+        let unresolved = decode_events(&tx, &MARINADE_PROGRAM_ID);
+        let [MarinadeEvent::WithdrawStakeAccount(event)] = unresolved.as_slice() else {
+            panic!("expected a stake account withdrawal, got {:?}", unresolved);
+        };
+        assert_eq!((event.msol_amount, event.beneficiary, event.split), (995_000_000_000, beneficiary, None));
+        assert_eq!(event.split_stake_account_lamports, Some(994_007_282_880));
+
+        let rpc = fixture.rpc();
+        let events = EventClassifier::new(&rpc, AnalyzeOptions::default()).classify(&tx).unwrap();
+        let MarinadeEvent::WithdrawStakeAccount(event) = &events[0] else { panic!("expected a withdrawal") };
+        let split = event.split.unwrap();
+        assert_eq!((split.fee_msol, split.msol_burned), (995_000_000, 994_005_000_000));
+        assert_eq!((split.lamports, split.effective_rate_lamports), (994_005_000_000, 999_000_000));
+        // the split account got the delegated lamports on top of its rent reserve
+        assert_eq!(event.split_stake_account_lamports.unwrap() - split.lamports, 2_282_880);
+        assert_eq!(event.remainder_lamports, Some(5_000_000_000));
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["kind"], "withdraw_stake_account");
+        assert_eq!(json["split"]["msol_burned"], 994_005_000_000u64);
+    }
+
+    #[test]
+    fn test_authority_change_is_critical_and_routed() {
+        use crate::accounts::instructions::ChangeAuthorityData;
+        use std::sync::Mutex;
+
+        let fixture = Fixture::new();
+        let (admin, new_admin) = (fixture.state.admin_authority, Pubkey::new_unique());
+        let data = ChangeAuthorityData {
+            admin: Some(new_admin),
+            validator_manager: None,
+            operational_sol_account: None,
+            treasury_msol_account: None,
+            // set to what it already is
+            pause_authority: Some(fixture.state.pause_authority),
+        };
+        let ix = marinade_call(
+            MarinadeFinanceInstruction::ChangeAuthority,
+            ChangeAuthorityArgs { data },
+            &[("state", MARINADE_STATE_PUBKEY), ("admin_authority", admin)],
+        );
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix]);
+        let rpc = fixture.rpc();
+        let events = EventClassifier::new(&rpc, AnalyzeOptions::default()).classify(&tx).unwrap();
+        let [MarinadeEvent::AuthorityChanged(event)] = events.as_slice() else {
+            panic!("expected an authority change, got {:?}", events);
+        };
+        assert_eq!(event.admin_authority, admin);
+        let expected = vec![
+            AuthorityChange { role: AuthorityRole::Admin, old: Some(admin), new: new_admin },
+            AuthorityChange { role: AuthorityRole::PauseAuthority, old: None, new: fixture.state.pause_authority },
+        ];
+        assert_eq!(event.changes, expected);
+        // only the state is read, as of the slot before
+        assert_eq!(rpc.calls(), vec!["getAccountInfo"]);
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["kind"], "authority_changed");
+        assert_eq!((json["changes"][0]["role"].as_str(), json["changes"][1]["old"].as_str()), (Some("admin"), None));
+
+        let paged = Mutex::new(Vec::new());
+        let page = |event: &MarinadeEvent| paged.lock().unwrap().push(event.severity());
+        let sink = SeverityFilter::new(page, Severity::Critical);
+        let unstake = decode_events(&fixture.emergency_unstake(FIXTURE_SLOT), &MARINADE_PROGRAM_ID);
+        for event in unstake.iter().chain(&events) {
+            sink.publish_event(event);
+        }
+        assert_eq!(unstake[0].severity(), Severity::Warning);
+        assert_eq!(*paged.lock().unwrap(), vec![Severity::Critical]);
+    }
+
+    #[test]
+    fn test_pause_is_verified_against_the_post_state() {
+        let mut fixture = Fixture::new();
+        fixture.state.paused = true;
+        let pause_authority = fixture.state.pause_authority;
+        let call = |instruction| {
+            let ix = marinade_call(instruction, (), &[("pause_authority", pause_authority)]);
+            transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix])
+        };
+        let rpc = fixture.rpc();
+        let classifier = EventClassifier::new(&rpc, AnalyzeOptions::default());
+
+        let events = classifier.classify(&call(MarinadeFinanceInstruction::Pause)).unwrap();
+        let [event @ MarinadeEvent::Pause(pause)] = events.as_slice() else {
+            panic!("expected a pause, got {:?}", events);
+        };
+        assert_eq!((pause.pause_authority, pause.paused, pause.state_paused), (pause_authority, true, Some(true)));
+        assert_eq!((pause.verified(), event.severity()), (Some(true), Severity::Critical));
+        // the state is read, the lists aren't
+        assert_eq!(rpc.calls(), vec!["getAccountInfo"]);
+
+        // a resume the state doesn't show
+        let events = classifier.classify(&call(MarinadeFinanceInstruction::Resume)).unwrap();
+        let [event @ MarinadeEvent::Resume(resume)] = events.as_slice() else {
+            panic!("expected a resume, got {:?}", events);
+        };
+        assert_eq!((resume.paused, resume.verified(), event.severity()), (false, Some(false), Severity::Warning));
+        assert_eq!(serde_json::to_value(event).unwrap()["kind"], "resume");
+    }
+
+    #[test]
+    fn test_block_is_resolved_with_one_fetch_of_the_lists() {
+        let mut fixture = Fixture::new();
+        let deposit = marinade_transaction(FIXTURE_SLOT, None, &[MarinadeFinanceInstruction::Deposit]);
+        let unstake = || fixture.emergency_unstake(FIXTURE_SLOT);
+        let block = block_of(&[unstake(), deposit, unstake(), fixture.redelegate(FIXTURE_SLOT)]);
+        // the record at the index has since been swapped for another stake account
+        fixture.stake_records[1].stake_account = Pubkey::new_unique();
+        let rpc = fixture.rpc();
+
+        let classifier = EventClassifier::new(&rpc, AnalyzeOptions::default());
+        let analyzed = classifier.analyze_block(FIXTURE_SLOT, &block).unwrap();
+        assert_eq!((analyzed.transactions, analyzed.events.len()), (4, 3));
+        assert_eq!(analyzed.block_time, Some(FIXTURE_BLOCK_TIME));
+        for event in &analyzed.events[..2] {
+            let MarinadeEvent::EmergencyUnstake(event) = event else { panic!("expected an emergency unstake") };
+            assert_eq!(event.vote_account, Some(fixture.validator_records[1].validator_account));
+            assert_eq!(event.delegated_lamports, None);
+        }
+        assert!(matches!(&analyzed.events[2], MarinadeEvent::Redelegate(event) if event.lamports_moved.is_some()));
+        assert_eq!(rpc.calls(), vec!["getAccountInfo", "getMultipleAccounts"]);
+
+        let signatures_only = UiConfirmedBlock { transactions: None, ..block };
+        let err = classifier.analyze_block(FIXTURE_SLOT, &signatures_only).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidTransaction { .. }));
+    }
+}
@@ -0,0 +1,184 @@
+//! C ABI over the state parsing and mSOL conversions. the header is `include/marinade_ffi.h`,
+//! regenerated with `cbindgen --config cbindgen.toml --output include/marinade_ffi.h` after
+//! any change here. build the shared library with `cargo build --release --features ffi`
+
+use std::ptr;
+
+use crate::accounts::marinade::{self, parse_marinade_state_with, ParseMode};
+
+/// what went wrong in a call, `MARINADE_STATUS_OK` when nothing did
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarinadeStatus {
+    Ok = 0,
+    /// a required pointer argument was null
+    NullPointer = 1,
+    /// the state buffer is shorter than a state account
+    ShortBuffer = 2,
+    /// the buffer isn't a marinade state account, e.g. another account's discriminator,
+    /// trailing bytes or balances that overflow the price math
+    InvalidData = 3,
+}
+
+/// the price components of a marinade state account
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarinadeStatePrice {
+    /// lamports per whole mSOL
+    pub msol_price_lamports: u64,
+    /// the SOL backing the whole supply
+    pub total_virtual_staked_lamports: u64,
+    pub msol_supply: u64,
+}
+
+/// parse the price components out of `len` bytes of state account data. returns null on
+/// failure, with the reason in `status` when it isn't null. release the result with
+/// `marinade_price_free`
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `status` must be null or writable
+#[no_mangle]
+pub unsafe extern "C" fn marinade_parse_state(
+    data: *const u8,
+    len: usize,
+    status: *mut MarinadeStatus,
+) -> *mut MarinadeStatePrice {
+    let (price, result) = match parse(data, len) {
+        Ok(price) => (Box::into_raw(Box::new(price)), MarinadeStatus::Ok),
+        Err(err) => (ptr::null_mut(), err),
+    };
+    if !status.is_null() {
+        *status = result;
+    }
+    price
+}
+
+unsafe fn parse(data: *const u8, len: usize) -> Result<MarinadeStatePrice, MarinadeStatus> {
+    if data.is_null() {
+        return Err(MarinadeStatus::NullPointer);
+    }
+    let parsed = parse_marinade_state_with(std::slice::from_raw_parts(data, len), ParseMode::Strict);
+    let state = parsed
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => MarinadeStatus::ShortBuffer,
+            _ => MarinadeStatus::InvalidData,
+        })?
+        .state
+        .minimal();
+    // a panic can't unwind into the caller, so crafted balances are refused rather than added
+    let (Some(msol_price_lamports), Some(total_virtual_staked_lamports)) =
+        (state.checked_msol_price_lamports(), state.checked_total_virtual_staked_lamports())
+    else {
+        return Err(MarinadeStatus::InvalidData);
+    };
+    Ok(MarinadeStatePrice { msol_price_lamports, total_virtual_staked_lamports, msol_supply: state.msol_supply })
+}
+
+/// mSOL base units `lamports` buy at `price`, written to `msol`
+///
+/// # Safety
+///
+/// `price` must be null or point to a `MarinadeStatePrice`, `msol` must be null or writable
+#[no_mangle]
+pub unsafe extern "C" fn marinade_sol_to_msol(
+    price: *const MarinadeStatePrice,
+    lamports: u64,
+    msol: *mut u64,
+) -> MarinadeStatus {
+    let (Some(price), false) = (price.as_ref(), msol.is_null()) else {
+        return MarinadeStatus::NullPointer;
+    };
+    *msol = marinade::sol_to_msol(lamports, price.total_virtual_staked_lamports, price.msol_supply);
+    MarinadeStatus::Ok
+}
+
+/// lamports `msol` base units are worth at `price`, written to `lamports`
+///
+/// # Safety
+///
+/// `price` must be null or point to a `MarinadeStatePrice`, `lamports` must be null or writable
+#[no_mangle]
+pub unsafe extern "C" fn marinade_msol_to_sol(
+    price: *const MarinadeStatePrice,
+    msol: u64,
+    lamports: *mut u64,
+) -> MarinadeStatus {
+    let (Some(price), false) = (price.as_ref(), lamports.is_null()) else {
+        return MarinadeStatus::NullPointer;
+    };
+    *lamports = marinade::msol_to_sol(msol, price.total_virtual_staked_lamports, price.msol_supply);
+    MarinadeStatus::Ok
+}
+
+/// release a price returned by `marinade_parse_state`. null is ignored
+///
+/// # Safety
+///
+/// `price` must be null or come from `marinade_parse_state`, and not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn marinade_price_free(price: *mut MarinadeStatePrice) {
+    if !price.is_null() {
+        drop(Box::from_raw(price));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::test_utils::{sample_state, state_account_data};
+
+    #[test]
+    fn test_parse_and_convert() {
+        let state = sample_state();
+        let data = state_account_data(&state);
+        let mut status = MarinadeStatus::NullPointer;
+        unsafe {
+            let price = marinade_parse_state(data.as_ptr(), data.len(), &mut status);
+            assert_eq!(status, MarinadeStatus::Ok);
+            assert_eq!((*price).msol_price_lamports, state.msol_price_lamports());
+            assert_eq!((*price).msol_supply, state.msol_supply);
+
+            let mut out = 0;
+            assert_eq!(marinade_sol_to_msol(price, 7_201, &mut out), MarinadeStatus::Ok);
+            assert_eq!(out, 6_000);
+            assert_eq!(marinade_msol_to_sol(price, 6_000, &mut out), MarinadeStatus::Ok);
+            assert_eq!(out, 7_201);
+            assert_eq!(marinade_msol_to_sol(price, 1, ptr::null_mut()), MarinadeStatus::NullPointer);
+            marinade_price_free(price);
+
+            assert!(marinade_parse_state(data.as_ptr(), 10, &mut status).is_null());
+            assert_eq!(status, MarinadeStatus::ShortBuffer);
+            assert!(marinade_parse_state(data[8..].as_ptr(), data.len() - 8, &mut status).is_null());
+            assert_eq!(status, MarinadeStatus::InvalidData);
+            let reallocated = [&data[..], &[0; 16]].concat();
+            assert!(marinade_parse_state(reallocated.as_ptr(), reallocated.len(), &mut status).is_null());
+            assert_eq!(status, MarinadeStatus::InvalidData);
+            assert!(marinade_parse_state(ptr::null(), 0, ptr::null_mut()).is_null());
+            assert_eq!(marinade_sol_to_msol(ptr::null(), 1, &mut out), MarinadeStatus::NullPointer);
+        }
+    }
+
+    #[test]
+    fn test_overflowing_balances_are_invalid_data() {
+        let mut state = sample_state();
+        state.validator_system.total_active_balance = u64::MAX;
+        state.available_reserve_balance = 1;
+        let data = state_account_data(&state);
+        assert!(parse_marinade_state_with(&data, ParseMode::Strict).is_ok());
+        let mut status = MarinadeStatus::Ok;
+        unsafe {
+            assert!(marinade_parse_state(data.as_ptr(), data.len(), &mut status).is_null());
+        }
+        assert_eq!(status, MarinadeStatus::InvalidData);
+
+        // a price past u64, from a supply far below the stake
+        let state = MarinadeState { msol_supply: 1, ..sample_state() };
+        let data = state_account_data(&state);
+        unsafe {
+            assert!(marinade_parse_state(data.as_ptr(), data.len(), &mut status).is_null());
+        }
+        assert_eq!(status, MarinadeStatus::InvalidData);
+    }
+}
@@ -0,0 +1,280 @@
+//! deposit and unstake volume: each analyzed transaction's user-facing marinade instructions as
+//! `Flow`s, and `aggregate_flows` to bucket them by time, e.g. over a month of backfill records
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use solana_sdk::clock::UnixTimestamp;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::instructions::{decode_args, MarinadeFinanceInstruction};
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+use crate::backfill::BackfillRecord;
+use crate::pipeline::PipelineRecord;
+use crate::transaction::TransactionInput;
+
+/// one user-facing instruction's amounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Flow {
+    Deposit { lamports: u64 },
+    /// `lamports` is what left the liq pool's SOL leg, None when the meta carries no balances
+    LiquidUnstake { msol_amount: u64, lamports: Option<u64> },
+    OrderUnstake { msol_amount: u64 },
+    /// `lamports` is what left the reserve, None when the meta carries no balances
+    Claim { lamports: Option<u64> },
+}
+
+/// the flows of a transaction, top level then inner instructions. none for a failed one
+pub fn transaction_flows<'t>(tx: impl Into<TransactionInput<'t>>, program_id: &Pubkey) -> Vec<Flow> {
+    let tx = tx.into();
+    if tx.failed() {
+        return Vec::new();
+    }
+    let (keys, pre, post) = (tx.account_keys().unwrap_or_default(), tx.pre_balances(), tx.post_balances());
+    let outflow = |account: Option<Pubkey>| {
+        let index = keys.iter().position(|key| Some(*key) == account)?;
+        Some(pre.get(index)?.saturating_sub(*post.get(index)?))
+    };
+    tx.marinade_calls(program_id)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|call| match call.instruction {
+            MarinadeFinanceInstruction::Deposit => decode_args(&call.data).map(|lamports| Flow::Deposit { lamports }),
+            MarinadeFinanceInstruction::LiquidUnstake => decode_args(&call.data).map(|msol_amount| Flow::LiquidUnstake {
+                msol_amount,
+                lamports: outflow(call.account("liq_pool_sol_leg_pda")),
+            }),
+            MarinadeFinanceInstruction::OrderUnstake => {
+                decode_args(&call.data).map(|msol_amount| Flow::OrderUnstake { msol_amount })
+            }
+            MarinadeFinanceInstruction::Claim => Some(Flow::Claim { lamports: outflow(call.account("reserve_pda")) }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// what `aggregate_flows` reads off a record
+pub trait AnalyzedTx {
+    fn block_time(&self) -> i64;
+
+    /// lamports per mSOL the transaction was valued at
+    fn msol_price_lamports(&self) -> u64;
+
+    fn flows(&self) -> &[Flow];
+}
+
+impl AnalyzedTx for BackfillRecord {
+    fn block_time(&self) -> i64 {
+        self.underlying.block_time
+    }
+
+    fn msol_price_lamports(&self) -> u64 {
+        self.underlying.msol_value
+    }
+
+    fn flows(&self) -> &[Flow] {
+        &self.flows
+    }
+}
+
+impl AnalyzedTx for PipelineRecord {
+    fn block_time(&self) -> i64 {
+        self.underlying.block_time
+    }
+
+    fn msol_price_lamports(&self) -> u64 {
+        self.underlying.msol_value
+    }
+
+    fn flows(&self) -> &[Flow] {
+        &self.flows
+    }
+}
+
+/// the flows of the records whose block time falls in `[start, start + bucket)`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FlowBucket {
+    /// unix seconds, a multiple of the bucket length
+    pub start: i64,
+    pub deposits: usize,
+    pub deposit_lamports: u64,
+    pub liquid_unstakes: usize,
+    /// liquid unstakes without balances aren't summed
+    pub liquid_unstake_lamports: u64,
+    pub order_unstakes: usize,
+    /// the tickets' mSOL at the price each was valued at
+    pub order_unstake_lamports: u64,
+    pub claims: usize,
+    /// claims without balances aren't summed
+    pub claim_lamports: u64,
+}
+
+impl FlowBucket {
+    fn add(&mut self, flow: &Flow, msol_price_lamports: u64) {
+        match *flow {
+            Flow::Deposit { lamports } => {
+                self.deposits += 1;
+                self.deposit_lamports += lamports;
+            }
+            Flow::LiquidUnstake { lamports, .. } => {
+                self.liquid_unstakes += 1;
+                self.liquid_unstake_lamports += lamports.unwrap_or(0);
+            }
+            Flow::OrderUnstake { msol_amount } => {
+                self.order_unstakes += 1;
+                self.order_unstake_lamports +=
+                    (msol_amount as u128 * msol_price_lamports as u128 / LAMPORTS_PER_MSOL as u128) as u64;
+            }
+            Flow::Claim { lamports } => {
+                self.claims += 1;
+                self.claim_lamports += lamports.unwrap_or(0);
+            }
+        }
+    }
+}
+
+/// totals per `bucket` of block time, from the earliest record's bucket to the latest's, with
+/// the buckets in between present as zeros. buckets are aligned to the unix epoch, and a
+/// `bucket` under a second is taken as one second. records can come in any order
+pub fn aggregate_flows<'a, R: AnalyzedTx + 'a>(
+    records: impl Iterator<Item = &'a R>,
+    bucket: Duration,
+) -> Vec<FlowBucket> {
+    bucketed(records, bucket, None)
+}
+
+/// `aggregate_flows` over the records with block times in `[start_time, end_time]`, the others
+/// skipped, and every bucket the range touches present whether or not anything landed in it
+pub fn aggregate_flows_between<'a, R: AnalyzedTx + 'a>(
+    records: impl Iterator<Item = &'a R>,
+    bucket: Duration,
+    start_time: UnixTimestamp,
+    end_time: UnixTimestamp,
+) -> Vec<FlowBucket> {
+    bucketed(records, bucket, Some((start_time, end_time)))
+}
+
+fn bucketed<'a, R: AnalyzedTx + 'a>(
+    records: impl Iterator<Item = &'a R>,
+    bucket: Duration,
+    range: Option<(UnixTimestamp, UnixTimestamp)>,
+) -> Vec<FlowBucket> {
+    let length = bucket.as_secs().max(1) as i64;
+    let bucket_start = |time: i64| time.div_euclid(length) * length;
+    let mut buckets = BTreeMap::<i64, FlowBucket>::new();
+    for record in records {
+        if range.is_some_and(|(start, end)| !(start..=end).contains(&record.block_time())) {
+            continue;
+        }
+        let start = bucket_start(record.block_time());
+        let bucket = buckets.entry(start).or_insert(FlowBucket { start, ..FlowBucket::default() });
+        for flow in record.flows() {
+            bucket.add(flow, record.msol_price_lamports());
+        }
+    }
+    let bounds = match range {
+        Some((start, end)) if start <= end => Some((bucket_start(start), bucket_start(end))),
+        Some(_) => None,
+        None => buckets.keys().next().copied().zip(buckets.keys().next_back().copied()),
+    };
+    let Some((first, last)) = bounds else {
+        return Vec::new();
+    };
+    (first..=last)
+        .step_by(length as usize)
+        .map(|start| buckets.remove(&start).unwrap_or(FlowBucket { start, ..FlowBucket::default() }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MARINADE_PROGRAM_ID, MARINADE_STATE_PUBKEY, SOL_MINT_PUBKEY};
+    use crate::test_utils::{marinade_call, transaction_with, with_balance, FIXTURE_IX_AMOUNT, FIXTURE_SLOT};
+    use crate::{BlockTimeSource, MintUnderlying};
+
+    const HOUR: Duration = Duration::from_secs(3_600);
+    /// 1.2 SOL per mSOL
+    const PRICE: u64 = 1_200_000_000;
+
+    fn record(block_time: i64, flows: Vec<Flow>) -> PipelineRecord {
+        let underlying = MintUnderlying::new(
+            block_time,
+            BlockTimeSource::Transaction,
+            PRICE,
+            &Pubkey::new_unique(),
+            &MARINADE_STATE_PUBKEY,
+            &[SOL_MINT_PUBKEY],
+            vec![0],
+        )
+        .unwrap();
+        let (events, protocol_revenue) = (vec![], None);
+        PipelineRecord { signature: None, slot: FIXTURE_SLOT, underlying, flows, events, protocol_revenue }
+    }
+
+    #[test]
+    fn test_flows_are_read_off_args_and_balances() {
+        let (sol_leg, reserve) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let sol_leg_role = [("liq_pool_sol_leg_pda", sol_leg)];
+        let ixs = [
+            marinade_call(MarinadeFinanceInstruction::Deposit, FIXTURE_IX_AMOUNT, &[]),
+            marinade_call(MarinadeFinanceInstruction::LiquidUnstake, 500_000_000u64, &sol_leg_role),
+            marinade_call(MarinadeFinanceInstruction::OrderUnstake, 700_000_000u64, &[]),
+            marinade_call(MarinadeFinanceInstruction::Claim, (), &[("reserve_pda", reserve)]),
+        ];
+        let tx = transaction_with(FIXTURE_SLOT, None, &ixs);
+        let tx = with_balance(with_balance(tx, &sol_leg, 10_000_000_000, 9_403_000_000), &reserve, 5_000_000_000, 0);
+        assert_eq!(
+            transaction_flows(&tx, &MARINADE_PROGRAM_ID),
+            vec![
+                Flow::Deposit { lamports: FIXTURE_IX_AMOUNT },
+                Flow::LiquidUnstake { msol_amount: 500_000_000, lamports: Some(597_000_000) },
+                Flow::OrderUnstake { msol_amount: 700_000_000 },
+                Flow::Claim { lamports: Some(5_000_000_000) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buckets_across_boundaries_with_gaps() {
+        let start = 1_708_002_000; // 13:00 utc, on an hour boundary
+        let deposit = |lamports| Flow::Deposit { lamports };
+        let records = [
+            record(start + 7_200, vec![Flow::Claim { lamports: Some(3) }, Flow::Claim { lamports: None }]),
+            record(start, vec![deposit(10), Flow::OrderUnstake { msol_amount: 1_000_000_000 }]),
+            record(start + 3_599, vec![deposit(5), Flow::LiquidUnstake { msol_amount: 1, lamports: Some(2) }]),
+            // in the same bucket as the first one listed
+            record(start + 7_201, vec![deposit(1)]),
+        ];
+
+        let buckets = aggregate_flows(records.iter(), HOUR);
+        let starts: Vec<i64> = buckets.iter().map(|bucket| bucket.start).collect();
+        assert_eq!(starts, vec![start, start + 3_600, start + 7_200]);
+        let first = FlowBucket {
+            start,
+            deposits: 2,
+            deposit_lamports: 15,
+            liquid_unstakes: 1,
+            liquid_unstake_lamports: 2,
+            order_unstakes: 1,
+            order_unstake_lamports: PRICE,
+            ..FlowBucket::default()
+        };
+        assert_eq!(buckets[0], first);
+        assert_eq!(buckets[1], FlowBucket { start: start + 3_600, ..FlowBucket::default() });
+        assert_eq!((buckets[2].claims, buckets[2].claim_lamports, buckets[2].deposits), (2, 3, 1));
+
+        // a bucket under a second is a second, and nothing in means nothing out
+        assert_eq!(aggregate_flows(records[..1].iter(), Duration::ZERO)[0].start, start + 7_200);
+        assert_eq!(aggregate_flows(records[..0].iter(), HOUR), vec![]);
+
+        // a range keeps its empty edges and drops what's outside it
+        let between = aggregate_flows_between(records.iter(), HOUR, start - 3_600, start + 3_600);
+        assert_eq!(between.len(), 3);
+        assert_eq!((between[0].start, between[0].deposits), (start - 3_600, 0));
+        assert_eq!(between[1], first);
+        assert_eq!(between[2], FlowBucket { start: start + 3_600, ..FlowBucket::default() });
+    }
+}
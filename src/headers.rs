@@ -0,0 +1,243 @@
+//! custom http headers sent with every rpc request, for providers that authenticate with an
+//! `Authorization` or `x-api-key` header instead of a token in the url. solana-client's
+//! `HttpSender` has no way to add headers, so `HeaderSender` stands in for it behind
+//! `RpcClient::new_sender`. header values never reach `Debug` output or the crate's logs
+
+use std::collections::HashMap;
+use std::fmt;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::error::{ErrorKind, Result};
+
+/// header names and values, the values marked sensitive and left out of `Debug`
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct RpcHeaders(HeaderMap);
+
+impl RpcHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// with `name: value` added, replacing an earlier value of the same name. fails with
+    /// `ErrorKind::InvalidConfig` when either isn't valid in a header
+    pub fn insert(mut self, name: &str, value: &str) -> Result<Self> {
+        let origin = || format!("rpc header {:?}", name);
+        let invalid = |reason: String| ErrorKind::InvalidConfig { origin: origin(), reason };
+        let header = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+        // the error would only say the value is invalid, never echo it
+        let mut value = HeaderValue::from_str(value).map_err(|e| invalid(e.to_string()))?;
+        value.set_sensitive(true);
+        self.0.insert(header, value);
+        Ok(self)
+    }
+
+    /// every entry of `headers`, see `insert`
+    pub fn from_map(headers: &HashMap<String, String>) -> Result<Self> {
+        headers.iter().try_fold(Self::new(), |headers, (name, value)| headers.insert(name, value))
+    }
+
+    /// the names, lowercased
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|name| name.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for RpcHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.names().map(|name| (name, "<redacted>"))).finish()
+    }
+}
+
+/// an `RpcClient` on `url` sending `headers` with every request
+#[cfg(feature = "blocking")]
+pub fn rpc_client(url: impl Into<String>, headers: &RpcHeaders) -> solana_client::rpc_client::RpcClient {
+    solana_client::rpc_client::RpcClient::new_sender(sender::HeaderSender::new(url, headers), sender::config())
+}
+
+/// the nonblocking counterpart of `rpc_client`, for the reads in `nonblocking`
+#[cfg(feature = "nonblocking")]
+pub fn nonblocking_rpc_client(
+    url: impl Into<String>,
+    headers: &RpcHeaders,
+) -> solana_client::nonblocking::rpc_client::RpcClient {
+    let sender = sender::HeaderSender::new(url, headers);
+    solana_client::nonblocking::rpc_client::RpcClient::new_sender(sender, sender::config())
+}
+
+#[cfg(any(feature = "blocking", feature = "nonblocking"))]
+mod sender {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use reqwest::header::CONTENT_TYPE;
+    use solana_client::client_error::Result as ClientResult;
+    use solana_client::rpc_client::RpcClientConfig;
+    use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+    use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+    use solana_sdk::commitment_config::CommitmentConfig;
+
+    use super::RpcHeaders;
+
+    // `HttpSender`'s
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// `RpcClient::new`'s config: every call of the crate passes its commitment explicitly
+    pub(super) fn config() -> RpcClientConfig {
+        RpcClientConfig::with_commitment(CommitmentConfig::default())
+    }
+
+    /// `HttpSender` with the caller's headers, except that a 429 comes back as the error instead
+    /// of being waited out; `RateLimited` keeps a client under the provider's limit
+    pub(super) struct HeaderSender {
+        client: reqwest::Client,
+        url: String,
+        request_id: AtomicU64,
+        stats: Mutex<RpcTransportStats>,
+    }
+
+    impl HeaderSender {
+        pub(super) fn new(url: impl Into<String>, headers: &RpcHeaders) -> Self {
+            let client = reqwest::Client::builder()
+                .default_headers(headers.0.clone())
+                .timeout(TIMEOUT)
+                .pool_idle_timeout(TIMEOUT)
+                .build()
+                .expect("http client builds");
+            Self { client, url: url.into(), request_id: AtomicU64::new(0), stats: Mutex::default() }
+        }
+    }
+
+    #[async_trait]
+    impl RpcSender for HeaderSender {
+        async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+            let started = Instant::now();
+            let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+            let body = request.build_request_json(request_id, params).to_string();
+            let result = async {
+                let request = self.client.post(&self.url).header(CONTENT_TYPE, "application/json").body(body);
+                let response = request.send().await?.error_for_status()?;
+                let mut json: serde_json::Value = serde_json::from_slice(&response.bytes().await?)?;
+                match json["error"].is_object() {
+                    true => Err(response_error(&json["error"]).into()),
+                    false => Ok(json["result"].take()),
+                }
+            }
+            .await;
+            let mut stats = self.stats.lock().unwrap();
+            stats.request_count += 1;
+            stats.elapsed_time += started.elapsed();
+            result
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.stats.lock().unwrap().clone()
+        }
+
+        fn url(&self) -> String {
+            self.url.clone()
+        }
+    }
+
+    /// the json-rpc error object as the error `HttpSender` gives, without the data it decodes
+    /// for the transaction calls the crate doesn't make
+    fn response_error(error: &serde_json::Value) -> RpcError {
+        match (error["code"].as_i64(), error["message"].as_str()) {
+            (Some(code), Some(message)) => {
+                RpcError::RpcResponseError { code, message: message.to_string(), data: RpcResponseErrorData::Empty }
+            }
+            _ => RpcError::RpcRequestError(format!("Failed to deserialize RPC error response: {}", error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_stay_out_of_debug() {
+        let map = HashMap::from([("x-api-key".to_string(), "hunter3".to_string())]);
+        let headers = RpcHeaders::from_map(&map).unwrap().insert("authorization", "Bearer hunter2").unwrap();
+        let debug = format!("{:?}", headers);
+        assert!(!debug.contains("hunter"), "{}", debug);
+        assert!(debug.contains("\"authorization\": \"<redacted>\""), "{}", debug);
+        assert_eq!(headers.names().count(), 2);
+
+        let e = RpcHeaders::new().insert("x-api-key", "line\nbreak hunter4").unwrap_err();
+        assert!(matches!(e.kind(), ErrorKind::InvalidConfig { .. }));
+        assert!(!e.to_string().contains("hunter4"), "{}", e);
+        assert!(RpcHeaders::new().insert("bad name", "v").is_err());
+    }
+
+    /// answers `responses` connections with a null account, sending each request's lowercased
+    /// header lines back
+    #[cfg(any(feature = "blocking", feature = "nonblocking"))]
+    fn endpoint(responses: usize) -> (String, std::sync::mpsc::Receiver<Vec<String>>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (requests, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for _ in 0..responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    headers.push(line.trim().to_lowercase());
+                }
+                let len = headers.iter().find_map(|h| h.strip_prefix("content-length: ")).unwrap().parse().unwrap();
+                reader.read_exact(&mut vec![0; len]).unwrap();
+                let body = r#"{"jsonrpc":"2.0","result":{"context":{"slot":7},"value":null},"id":0}"#;
+                let head = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close";
+                write!(reader.get_mut(), "{}\r\nContent-Length: {}\r\n\r\n{}", head, body.len(), body).unwrap();
+                requests.send(headers).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    #[cfg(any(feature = "blocking", feature = "nonblocking"))]
+    #[test]
+    fn test_headers_arrive_on_both_transports() {
+        let headers = RpcHeaders::new().insert("Authorization", "Bearer t0ken").unwrap();
+        let headers = headers.insert("x-api-key", "k3y").unwrap();
+        let expect_headers = |received: Vec<String>| {
+            assert!(received.contains(&"authorization: bearer t0ken".to_string()), "{:?}", received);
+            assert!(received.contains(&"x-api-key: k3y".to_string()), "{:?}", received);
+        };
+        let not_found = |e: crate::error::Error| assert!(matches!(e.kind(), ErrorKind::AccountNotFound { .. }), "{}", e);
+
+        #[cfg(feature = "blocking")]
+        {
+            let (url, received) = endpoint(1);
+            let client = crate::client::MarinadeClient::builder().rpc_url(url).rpc_headers(headers.clone()).build();
+            not_found(client.price(None).unwrap_err());
+            expect_headers(received.recv().unwrap());
+        }
+        #[cfg(feature = "nonblocking")]
+        {
+            use crate::deployment::DeploymentConfig;
+            use crate::AnalyzeOptions;
+
+            let (url, received) = endpoint(1);
+            let rpc = nonblocking_rpc_client(url, &headers);
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let (deployment, options) = (DeploymentConfig::MAINNET, AnalyzeOptions::default());
+            not_found(runtime.block_on(crate::nonblocking::price(&rpc, &deployment, None, &options)).unwrap_err());
+            expect_headers(received.recv().unwrap());
+        }
+    }
+}
@@ -1,212 +1,381 @@
-mod accounts;
-
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::signature::Signature;
-use solana_sdk::pubkey::Pubkey;
-use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
-use solana_sdk::commitment_config::CommitmentConfig;
-use solana_client::rpc_config::RpcAccountInfoConfig;
-use solana_account_decoder::UiAccountEncoding;
-use std::str::FromStr;
-use log::{debug, error};
-use crate::accounts::marinade::{MarinadeState, parse_marinade_state};
-
-const SOL_MINT_PUBKEY: &str = "So11111111111111111111111111111111111111112";
-const MSOL_MINT_PUBKEY: &str = "mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK1iNKhS3nZF";
-
-// marinade staking program account pubkey
-const MARINADE_STATE_PUBKEY: &str = "8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC";
+pub mod accounts;
+#[cfg(feature = "rpc")]
+mod analysis;
+#[cfg(feature = "rpc")]
+pub mod analyzer;
+#[cfg(feature = "rpc")]
+pub mod backfill;
+#[cfg(feature = "rpc")]
+pub mod batch;
+pub mod cache;
+#[cfg(feature = "rpc")]
+pub mod client;
+pub mod cluster;
+#[cfg(feature = "rpc")]
+pub mod config;
+#[cfg(feature = "rpc")]
+pub mod compare;
+pub mod constants;
+#[cfg(feature = "rpc")]
+pub mod crank;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+pub mod deployment;
+pub mod deposit;
+pub mod epoch;
+#[cfg(feature = "rpc")]
+pub mod error;
+#[cfg(feature = "rpc")]
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "rpc")]
+pub mod flows;
+#[cfg(feature = "rpc")]
+pub mod headers;
+#[cfg(feature = "rpc")]
+pub mod liquidity;
+#[cfg(feature = "rpc")]
+pub mod native;
+#[cfg(feature = "nonblocking")]
+pub mod nonblocking;
+#[cfg(feature = "rpc")]
+pub mod observer;
+#[cfg(feature = "rpc")]
+pub mod operator;
+#[cfg(feature = "rpc")]
+pub mod parsers;
+#[cfg(feature = "rpc")]
+pub mod pipeline;
+#[cfg(feature = "rpc")]
+pub mod portfolio;
+pub mod records;
+pub mod schema;
+#[cfg(feature = "rpc")]
+pub mod raw;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+mod serde_pubkey;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "rpc")]
+pub mod shutdown;
+#[cfg(feature = "rpc")]
+pub mod signatures;
+#[cfg(feature = "rpc")]
+pub mod sink;
+pub mod snapshot;
+pub mod sol;
+#[cfg(feature = "rpc")]
+pub mod supply;
+#[cfg(feature = "rpc")]
+pub mod transaction;
+#[cfg(feature = "rpc")]
+pub mod treasury;
+#[cfg(feature = "rpc")]
+pub mod tvl;
+pub mod twap;
+pub mod underlying;
+pub mod unstake;
+#[cfg(feature = "rpc")]
+pub mod valuation;
+pub mod verbosity;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+#[cfg(test)]
+mod test_utils;
+
+#[cfg(feature = "rpc")]
+pub use crate::analysis::{
+    analyze_transaction, analyze_transaction_with_options, analyze_transaction_with_state,
+    fetch_transaction_with_options,
+};
+#[cfg(feature = "blocking")]
+#[allow(deprecated)]
+pub use crate::analysis::fetch_transaction;
+#[cfg(feature = "rpc")]
+pub(crate) use crate::analysis::{
+    analyze_span, analyze_with, fetch_account_data, fetch_full_state, fetch_post_state, fetch_state,
+    find_and_parse_marinade_state, find_and_parse_marinade_state_raw, mint_underlying_from_state, report_failure,
+    resolve_block_time,
+};
+
+/// where `MintUnderlying::block_time` was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockTimeSource {
+    /// the `block_time` carried by the transaction itself
+    Transaction,
+    /// looked up with `get_block_time` because the transaction had none
+    Rpc,
+    /// supplied by the caller alongside an archived account snapshot
+    Snapshot,
+}
 
-#[derive(Debug, Clone)]
+/// serialized with a leading `schema_version`, see `schema`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(remote = "Self")]
 pub struct MintUnderlying {
     pub block_time: i64,
+    pub block_time_source: BlockTimeSource,
     pub msol_value: u64,
     pub mint_pubkey: String,
     pub platform_program_pubkey: String,
+    /// the mints backing one `mint_pubkey`, each paired with the amount at the same index
+    #[deprecated(note = "read them paired through `underlyings` or `amount_for`, set them through `new`")]
     pub mints: Vec<String>,
+    #[deprecated(note = "read them paired through `underlyings` or `amount_for`, set them through `new`")]
     pub total_underlying_amounts: Vec<u64>,
+    /// the valuation used a state fetched for an earlier tx instead of a fresh fetch,
+    /// see `Analyzer`
+    #[serde(default)]
+    pub state_reused: bool,
+    /// the first transaction valued past an epoch boundary: the states kept for the earlier
+    /// epoch, from before its closing crank, were dropped and a fresh one fetched. see
+    /// `batch::analyze_signatures`
+    #[serde(default)]
+    pub crossed_epoch_boundary: bool,
+    /// the context slot the state was read at, None when the caller supplied the state. see
+    /// `AnalyzeOptions::max_slot_skew`
+    #[serde(default)]
+    pub state_slot: Option<u64>,
+    /// mSOL base units the tx's token instructions minted and burned, see
+    /// `supply::msol_supply_change`
+    #[serde(default)]
+    pub msol_minted: u64,
+    #[serde(default)]
+    pub msol_burned: u64,
+    /// those differ from what the tx's marinade instructions account for
+    #[serde(default)]
+    pub msol_discrepancy: bool,
 }
 
-/// fetch account data for given a public key
-fn fetch_account_data(rpc_client: &RpcClient, pubkey: &Pubkey, slot: Option<u64>) -> Option<Vec<u8>> {
-    debug!("entering fetch_account_data");
-    debug!("pubkey: {:?}, slot: {:?}", pubkey, slot);
-
-    let config = RpcAccountInfoConfig {
-        encoding: Some(UiAccountEncoding::Base64),
-        commitment: Some(CommitmentConfig::processed()),
-        data_slice: None,
-        min_context_slot: slot,
-    };
-
-    let response = rpc_client.get_account_with_config(pubkey, config);
-    
-    match response {
-        Ok(account_data) => {
-            match account_data.value {
-                Some(account) => {
-                    debug!("account data fetched successfully, length: {}", account.data.len());
-                    Some(account.data)
-                },
-                None => {
-                    error!("account data is None");
-                    None
-                }
-            }
-        },
-        Err(e) => {
-            error!("error fetching account data: {}", e);
-            None
+schema::versioned_serde!(MintUnderlying);
+
+#[allow(deprecated)]
+impl MintUnderlying {
+    /// fails unless `mints` and `amounts` are the same length, the amount of each mint being
+    /// the one at its index. `state_reused`, `crossed_epoch_boundary` and `msol_discrepancy`
+    /// start out false, `state_slot` None and the mSOL minted and burned 0
+    pub fn new(
+        block_time: i64,
+        block_time_source: BlockTimeSource,
+        msol_value: u64,
+        mint: &solana_program::pubkey::Pubkey,
+        platform_program: &solana_program::pubkey::Pubkey,
+        mints: &[solana_program::pubkey::Pubkey],
+        amounts: Vec<u64>,
+    ) -> std::result::Result<Self, UnderlyingsMismatch> {
+        if mints.len() != amounts.len() {
+            return Err(UnderlyingsMismatch { mints: mints.len(), amounts: amounts.len() });
         }
+        Ok(Self {
+            block_time,
+            block_time_source,
+            msol_value,
+            mint_pubkey: mint.to_string(),
+            platform_program_pubkey: platform_program.to_string(),
+            mints: mints.iter().map(|mint| mint.to_string()).collect(),
+            total_underlying_amounts: amounts,
+            state_reused: false,
+            crossed_epoch_boundary: false,
+            state_slot: None,
+            msol_minted: 0,
+            msol_burned: 0,
+            msol_discrepancy: false,
+        })
     }
-}
-/// 
-fn find_and_parse_marinade_state(rpc_client: &RpcClient, pubkey: &Pubkey, slot: Option<u64>) -> Option<MarinadeState> {
-    debug!("entering find_and_parse_marinade_state");
-    debug!("pubkey: {:?}, slot: {:?}", pubkey, slot);
-
-    // Fetch account data, passing the optional slot
-    let account_data = match fetch_account_data(rpc_client, pubkey, slot) {
-        Some(data) => {
-            debug!("account data fetched successfully, length: {}", data.len());
-            data
-        },
-        None => {
-            error!("failed to fetch account data");
-            return None;
+
+    /// each underlying mint with its amount. fails on the records only poking the fields directly,
+    /// or deserializing someone else's, can produce: a mint that isn't a valid pubkey, or mints
+    /// and amounts that don't pair up
+    pub fn underlyings(&self) -> std::result::Result<Vec<(solana_program::pubkey::Pubkey, u64)>, InvalidUnderlyings> {
+        let (mints, amounts) = (self.mints.len(), self.total_underlying_amounts.len());
+        if mints != amounts {
+            return Err(InvalidUnderlyings::Unpaired(UnderlyingsMismatch { mints, amounts }));
         }
-    };
+        let paired = self.mints.iter().zip(&self.total_underlying_amounts).enumerate();
+        paired
+            .map(|(index, (mint, amount))| {
+                let pubkey = mint.parse().map_err(|_| InvalidUnderlyings::Mint { index, mint: mint.clone() })?;
+                Ok((pubkey, *amount))
+            })
+            .collect()
+    }
 
-    // Log the first few bytes of the account data
-    debug!("first 16 bytes of account data: {:?}", &account_data.get(..16).unwrap_or(&[]));
+    /// the amount of `mint` behind this one, None when it isn't an underlying
+    pub fn amount_for(&self, mint: &solana_program::pubkey::Pubkey) -> Option<u64> {
+        let index = self.mints.iter().position(|underlying| *underlying == mint.to_string())?;
+        self.total_underlying_amounts.get(index).copied()
+    }
+}
 
-    match parse_marinade_state(&account_data) {
-        Ok(state) => Some(state),
-        Err(e) => {
-            error!("failed to parse Marinade state: {:?}", e);
-            None
-        }
+/// from `MintUnderlying::new`: the mints and amounts it was given don't pair up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnderlyingsMismatch {
+    pub mints: usize,
+    pub amounts: usize,
+}
+
+impl std::fmt::Display for UnderlyingsMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} underlying mints but {} amounts", self.mints, self.amounts)
     }
 }
 
-/// analyze a tx to check if it affects the Marinade state and if so, convert the data into MintUnderlying and return
-pub fn analyze_transaction(rpc_client: &RpcClient, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<MintUnderlying> {
-    debug!("starting analyze_transaction");
-    let marinade_state_pubkey = match Pubkey::from_str(MARINADE_STATE_PUBKEY) {
-        Ok(pubkey) => pubkey,
-        Err(e) => {
-            error!("failed to parse MARINADE_STATE_PUBKEY: {}", e);
-            return None;
-        }
-    };
+impl std::error::Error for UnderlyingsMismatch {}
 
-    let slot = tx.slot;
-    debug!("tx slot: {}", slot);
+/// from `MintUnderlying::underlyings`: the record's fields don't hold a set of underlyings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidUnderlyings {
+    Unpaired(UnderlyingsMismatch),
+    /// the mint at `index` isn't a base58 pubkey
+    Mint { index: usize, mint: String },
+}
 
-    debug!("fetching Marinade state for slot: {}", slot);
-    let post_state = match find_and_parse_marinade_state(rpc_client, &marinade_state_pubkey, Some(slot)) {
-        Some(state) => state,
-        None => {
-            error!("Failed to find and parse Marinade state");
-            return None;
+impl std::fmt::Display for InvalidUnderlyings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unpaired(mismatch) => mismatch.fmt(f),
+            Self::Mint { index, mint } => write!(f, "underlying mint {} {:?} isn't a pubkey", index, mint),
         }
-    };
-    debug!("marinade state fetched successfully");
+    }
+}
 
-    let sol_amount = post_state.validator_system.total_active_balance + post_state.emergency_cooling_down + post_state.available_reserve_balance - post_state.circulating_ticket_balance;
-    let msol_value = sol_amount / post_state.msol_supply;
+impl std::error::Error for InvalidUnderlyings {}
 
-    debug!("calculated sol_amount: {}", sol_amount);
-    debug!("calculated msol_value: {}", msol_value);
+/// knobs for `analyze_transaction_with_options`
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    /// when the transaction has no `block_time`, ask the node via `get_block_time(slot)`
+    /// instead of giving up. costs one extra rpc call for such transactions
+    pub fallback_block_time: bool,
+    /// read only the price fields of the state account (`parse_marinade_state_minimal`)
+    /// instead of deserializing all of it
+    pub minimal_parse: bool,
+    /// ask the node for only the first `MINIMAL_STATE_LEN` bytes of the state account, via
+    /// `data_slice`, and parse those as with `minimal_parse`. falls back to a full fetch when the
+    /// node doesn't slice or the bytes don't look like a state. ignored with a `snapshot_cache`,
+    /// which needs the whole account
+    pub sliced_fetch: bool,
+    /// ask for account data as base64+zstd, which shrinks the large state and list accounts a
+    /// lot on the wire. a node that refuses it, or data that doesn't decompress, is read again
+    /// as plain base64; `Observer::on_account_read` tells which was used
+    pub prefer_zstd: bool,
+    /// how a full state is parsed, strict by default. a lenient parse logs its warnings
+    pub parse_mode: accounts::marinade::ParseMode,
+    /// when the node hasn't reached the slot of a transaction yet, poll it until it has, for up
+    /// to this long, instead of failing. off by default, which suits historical reads; the
+    /// pipeline turns it on for transactions fresh off the chain
+    pub wait_for_slot: Option<std::time::Duration>,
+    /// fail an analysis with `ErrorKind::SlotSkew` when the state it values against was read
+    /// more than this many slots from the transaction's, reused states included. off by default:
+    /// a node ahead of a historical transaction still returns its current state
+    pub max_slot_skew: Option<u64>,
+    /// also log failed analyses at error level. off by default: errors are returned to the
+    /// caller, who decides whether they are worth reporting
+    pub verbose: bool,
+    /// how much of the crate's own logging the calls made with these options emit, `Normal`
+    /// by default. see `verbosity`
+    pub verbosity: verbosity::Verbosity,
+    /// write every fetched state through to this cache
+    pub snapshot_cache: Option<std::sync::Arc<cache::SnapshotCache>>,
+    /// read states only from `snapshot_cache`, failing with `ErrorKind::OfflineMiss` for slots
+    /// it doesn't cover. other rpc calls, e.g. for transactions, still go to the fetcher
+    pub offline: bool,
+    /// told about rpc calls, cache hits and completed analyses
+    #[cfg(feature = "rpc")]
+    pub observer: Option<std::sync::Arc<dyn observer::Observer>>,
+    /// what every account and transaction read asks the node for, `confirmed` by default.
+    /// `processed` sees a price a slot or two sooner but can read a fork that gets dropped;
+    /// `finalized` never rolls back but trails the tip by ~32 slots. transactions and signature
+    /// lists can't be read at `processed`, so those go at `confirmed` instead
+    #[cfg(feature = "rpc")]
+    pub commitment: solana_sdk::commitment_config::CommitmentConfig,
+    /// the newest transaction version read, v0 by default. a fetch asks the node for no newer, and
+    /// a newer transaction fails with `ErrorKind::UnsupportedTransactionVersion` rather than being
+    /// read with keys or instructions missing. `TransactionVersion::LEGACY` reads only legacy ones
+    #[cfg(feature = "rpc")]
+    pub max_transaction_version: solana_sdk::transaction::TransactionVersion,
+}
 
-    let block_time = match tx.block_time {
-        Some(time) => time,
-        None => {
-            error!("tx block time is None");
-            return None;
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            fallback_block_time: true,
+            minimal_parse: false,
+            sliced_fetch: false,
+            prefer_zstd: false,
+            parse_mode: accounts::marinade::ParseMode::Strict,
+            wait_for_slot: None,
+            max_slot_skew: None,
+            verbose: false,
+            verbosity: verbosity::Verbosity::Normal,
+            snapshot_cache: None,
+            offline: false,
+            #[cfg(feature = "rpc")]
+            observer: None,
+            #[cfg(feature = "rpc")]
+            commitment: solana_sdk::commitment_config::CommitmentConfig::confirmed(),
+            #[cfg(feature = "rpc")]
+            max_transaction_version: solana_sdk::transaction::TransactionVersion::Number(0),
         }
-    };
-
-    let mu = MintUnderlying {
-        block_time,
-        msol_value,
-        mint_pubkey: MSOL_MINT_PUBKEY.to_string(),
-        platform_program_pubkey: MARINADE_STATE_PUBKEY.to_string(),
-        mints: vec![SOL_MINT_PUBKEY.to_string()],
-        total_underlying_amounts: vec![sol_amount],
-    };
-    debug!("created MintUnderlying: {:?}", mu);
-    Some(mu)
+    }
 }
 
-
-pub fn fetch_transaction(signature: &str) -> Result<EncodedConfirmedTransactionWithStatusMeta, Box<dyn std::error::Error>> {
-    let rpc_client = RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
-    let tx_data = rpc_client.get_transaction_with_config(
-        &Signature::from_str(signature)?,
-        solana_client::rpc_config::RpcTransactionConfig {
-            encoding: Some(UiTransactionEncoding::Base64),
-            commitment: Some(CommitmentConfig::confirmed()),
-            max_supported_transaction_version: Some(0),
-        },
-    )?;
-
-    Ok(tx_data)
+#[cfg(feature = "rpc")]
+impl AnalyzeOptions {
+    /// run `f` against the observer, if there is one
+    pub(crate) fn observe(&self, f: impl FnOnce(&dyn observer::Observer)) {
+        if let Some(observer) = &self.observer {
+            f(observer.as_ref());
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use env_logger;
+    use crate::constants::{MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY, SOL_MINT_PUBKEY};
+
+    use solana_program::pubkey::Pubkey;
+
+    fn mint_underlying(mints: &[Pubkey], amounts: Vec<u64>) -> Result<MintUnderlying, UnderlyingsMismatch> {
+        MintUnderlying::new(0, BlockTimeSource::Transaction, 1, &MSOL_MINT_PUBKEY, &MARINADE_STATE_PUBKEY, mints, amounts)
+    }
+
+    #[test]
+    fn test_underlyings_stay_paired() {
+        let mu = mint_underlying(&[SOL_MINT_PUBKEY, MSOL_MINT_PUBKEY], vec![7, 8]).unwrap();
+        assert_eq!(mu.underlyings().unwrap(), [(SOL_MINT_PUBKEY, 7), (MSOL_MINT_PUBKEY, 8)]);
+        assert_eq!((mu.amount_for(&MSOL_MINT_PUBKEY), mu.amount_for(&MARINADE_STATE_PUBKEY)), (Some(8), None));
+        assert!(!mu.state_reused);
+
+        let mismatch = mint_underlying(&[SOL_MINT_PUBKEY], vec![]).unwrap_err();
+        assert_eq!(mismatch, UnderlyingsMismatch { mints: 1, amounts: 0 });
+        assert_eq!(mismatch.to_string(), "1 underlying mints but 0 amounts");
+        assert!(mint_underlying(&[], vec![1, 2]).is_err());
+        let none = mint_underlying(&[], vec![]).unwrap();
+        assert_eq!(none.underlyings().unwrap().len(), 0);
+    }
 
     #[test]
-    fn test_deposit_transaction() {
-        env_logger::init();  // Initialize logger
-
-        debug!("starting test_deposit_transaction");
-        let rpc_client = RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
-        let deposit_signature = "4uL95njGxnL7oPRBv6qb9ZKeWbTfKifbJgKe5zJ98FFyh7TJofUghQ2tcp4gR9fUHsX5exHayzcK9Zt1SR1Cwy7k";
-        let expected_sol_deposit_value: f64 = 0.020890732;
-        let expected_msol_returned_value: f64 = 0.017192933;
-
-        debug!("fetching transaction with signature: {}", deposit_signature);
-        let tx = fetch_transaction(deposit_signature).expect("failed to fetch deposit transaction");
-        debug!("transaction fetched successfully");
-
-        debug!("analyzing transaction");
-        let result = analyze_transaction(&rpc_client, &tx);
-        debug!("analysis result: {:?}", result);
-        assert!(result.is_some(), "deposit transaction should produce a result");
-
-        let mint_underlying = result.unwrap();
-        debug!("MintUnderlying: {:?}", mint_underlying);
-
-        assert_eq!(mint_underlying.mint_pubkey, MSOL_MINT_PUBKEY);
-        assert_eq!(mint_underlying.platform_program_pubkey, MARINADE_STATE_PUBKEY);
-        assert_eq!(mint_underlying.mints, vec![MSOL_MINT_PUBKEY.to_string()]);
-
-        let total_underlying_sol = mint_underlying.total_underlying_amounts[0];
-        let expected_min = (expected_sol_deposit_value * 1_000_000_000.0_f64).round() as u64;
-        let expected_max = expected_min + 10;
-
-        debug!("total underlying SOL: {}", total_underlying_sol);
-        debug!("expected range: {} to {}", expected_min, expected_max);
-        assert!(
-            total_underlying_sol >= expected_min && total_underlying_sol <= expected_max,
-            "total underlying SOL is outside the expected range"
-        );
-
-        let msol_value = mint_underlying.msol_value;
-        let expected_msol_min = (expected_msol_returned_value * 1_000_000_000.0_f64).round() as u64;
-        let expected_msol_max = expected_msol_min + 10;
-
-        debug!("msol value: {}", msol_value);
-        debug!("expected msol range: {} to {}", expected_msol_min, expected_msol_max);
-        assert!(
-            msol_value >= expected_msol_min && msol_value <= expected_msol_max,
-            "msol value is outside the expected range"
-        );
-
-        debug!("test_deposit_transaction completed successfully");
+    #[allow(deprecated)]
+    fn test_poked_underlyings_are_refused() {
+        let mut mu = mint_underlying(&[SOL_MINT_PUBKEY, MSOL_MINT_PUBKEY], vec![7, 8]).unwrap();
+        mu.mints[0] = "not a pubkey".to_string();
+        let bad_mint = InvalidUnderlyings::Mint { index: 0, mint: "not a pubkey".to_string() };
+        assert_eq!(mu.underlyings(), Err(bad_mint));
+        assert_eq!(mu.underlyings().unwrap_err().to_string(), r#"underlying mint 0 "not a pubkey" isn't a pubkey"#);
+        // the other mint is still found
+        assert_eq!((mu.amount_for(&SOL_MINT_PUBKEY), mu.amount_for(&MSOL_MINT_PUBKEY)), (None, Some(8)));
+
+        mu.mints[0] = SOL_MINT_PUBKEY.to_string();
+        mu.total_underlying_amounts.pop();
+        let unpaired = InvalidUnderlyings::Unpaired(UnderlyingsMismatch { mints: 2, amounts: 1 });
+        assert_eq!(mu.underlyings(), Err(unpaired));
+        assert_eq!((mu.amount_for(&SOL_MINT_PUBKEY), mu.amount_for(&MSOL_MINT_PUBKEY)), (Some(7), None));
     }
 }
@@ -0,0 +1,292 @@
+//! liq pool flows. `add_liquidity` puts SOL into the pool's sol leg for newly minted LP tokens,
+//! `remove_liquidity` burns LP tokens for a share of both legs, SOL and mSOL. `LiquidityAnalyzer`
+//! reads the exact amounts off a transaction's balance deltas and prices the LP token with the
+//! mSOL price the transaction left behind
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::TransactionTokenBalance;
+
+use crate::accounts::instructions::MarinadeFinanceInstruction;
+use crate::accounts::marinade::{MinimalState, LAMPORTS_PER_MSOL};
+use crate::constants::SOL_MINT_PUBKEY;
+use crate::deployment::DeploymentConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::rpc::RpcFetcher;
+use crate::transaction::{MarinadeCall, TransactionInput};
+use crate::{fetch_full_state, AnalyzeOptions, BlockTimeSource, MintUnderlying};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidityAction {
+    /// SOL in, LP minted
+    Add,
+    /// LP burned, SOL and mSOL out
+    Remove,
+}
+
+/// one `add_liquidity` or `remove_liquidity`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LiquidityRecord {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub action: LiquidityAction,
+    /// LP tokens minted to `mint_to` or burned from `burn_from`
+    pub lp_amount: u64,
+    /// lamports into or out of the sol leg
+    pub lamports: u64,
+    /// mSOL out of the msol leg, 0 for an add
+    pub msol_amount: u64,
+    /// lamports per mSOL after the transaction
+    pub msol_price_lamports: u64,
+    /// lamports one whole LP token was worth: `lamports` plus `msol_amount` at
+    /// `msol_price_lamports`, per `lp_amount`. 0 when no LP moved
+    pub lp_price_lamports: u64,
+}
+
+impl LiquidityRecord {
+    /// the LP tokens moved, as `deployment`'s lp mint valued at `lp_price_lamports` and backed by
+    /// both legs' amounts, SOL then mSOL. None without a block time
+    pub fn mint_underlying(&self, deployment: &DeploymentConfig) -> Option<MintUnderlying> {
+        let mints = [SOL_MINT_PUBKEY, deployment.msol_mint];
+        let (mint, platform) = (&deployment.lp_mint, &deployment.state);
+        let amounts = vec![self.lamports, self.msol_amount];
+        let (block_time, source, price) = (self.block_time?, BlockTimeSource::Transaction, self.lp_price_lamports);
+        let underlying = MintUnderlying::new(block_time, source, price, mint, platform, &mints, amounts);
+        Some(underlying.expect("two mints, two amounts"))
+    }
+}
+
+/// reads the liquidity flows of transactions, skipping everything else
+pub struct LiquidityAnalyzer<'a> {
+    rpc_client: &'a dyn RpcFetcher,
+    options: AnalyzeOptions,
+    deployment: DeploymentConfig,
+}
+
+impl<'a> LiquidityAnalyzer<'a> {
+    pub fn new(rpc_client: &'a dyn RpcFetcher, options: AnalyzeOptions) -> Self {
+        Self { rpc_client, options, deployment: DeploymentConfig::MAINNET }
+    }
+
+    pub fn with_deployment(mut self, deployment: DeploymentConfig) -> Self {
+        self.deployment = deployment;
+        self
+    }
+
+    /// a record per liquidity instruction, in instruction order; none for a failed transaction
+    /// or one without any, which isn't fetched for. fails when the meta is missing a balance an
+    /// amount is read off. two instructions on the same accounts both see the combined deltas
+    pub fn analyze<'t>(&self, tx: impl Into<TransactionInput<'t>>) -> Result<Vec<LiquidityRecord>> {
+        let tx = tx.into();
+        self.analyze_inner(tx).map_err(|e| e.with_signature(tx.signature()))
+    }
+
+    fn analyze_inner(&self, tx: TransactionInput<'_>) -> Result<Vec<LiquidityRecord>> {
+        if tx.failed() {
+            return Ok(Vec::new());
+        }
+        let calls: Vec<MarinadeCall> = tx
+            .marinade_calls(&self.deployment.program_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|call| {
+                use MarinadeFinanceInstruction::{AddLiquidity, RemoveLiquidity};
+                matches!(call.instruction, AddLiquidity | RemoveLiquidity)
+            })
+            .collect();
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (_, state) = fetch_full_state(self.rpc_client, &self.deployment.state, Some(tx.slot()), &self.options)?;
+        let deltas = Deltas::of(tx);
+        calls.iter().map(|call| deltas.record(call, &state.minimal())).collect()
+    }
+}
+
+/// a transaction's balances, to read an instruction's amounts off
+struct Deltas {
+    slot: u64,
+    block_time: Option<i64>,
+    keys: Vec<Pubkey>,
+    pre: Vec<u64>,
+    post: Vec<u64>,
+    pre_tokens: Vec<TransactionTokenBalance>,
+    post_tokens: Vec<TransactionTokenBalance>,
+}
+
+impl Deltas {
+    fn of(tx: TransactionInput<'_>) -> Self {
+        Self {
+            slot: tx.slot(),
+            block_time: tx.block_time(),
+            keys: tx.account_keys().unwrap_or_default(),
+            pre: tx.pre_balances(),
+            post: tx.post_balances(),
+            pre_tokens: tx.pre_token_balances(),
+            post_tokens: tx.post_token_balances(),
+        }
+    }
+
+    fn record(&self, call: &MarinadeCall, state: &MinimalState) -> Result<LiquidityRecord> {
+        let (action, lp_amount, lamports, msol_amount) = match call.instruction {
+            MarinadeFinanceInstruction::AddLiquidity => {
+                let (lp_pre, lp_post) = self.tokens(call, "mint_to")?;
+                let (sol_pre, sol_post) = self.lamports(call, "liq_pool_sol_leg_pda")?;
+                (LiquidityAction::Add, lp_post.saturating_sub(lp_pre), sol_post.saturating_sub(sol_pre), 0)
+            }
+            _ => {
+                let (lp_pre, lp_post) = self.tokens(call, "burn_from")?;
+                let (sol_pre, sol_post) = self.lamports(call, "liq_pool_sol_leg_pda")?;
+                let (msol_pre, msol_post) = self.tokens(call, "liq_pool_msol_leg")?;
+                let msol_amount = msol_pre.saturating_sub(msol_post);
+                let lp_amount = lp_pre.saturating_sub(lp_post);
+                (LiquidityAction::Remove, lp_amount, sol_pre.saturating_sub(sol_post), msol_amount)
+            }
+        };
+        let value = lamports as u128 + state.msol_to_sol(msol_amount) as u128;
+        let lp_price_lamports = match lp_amount {
+            0 => 0,
+            _ => (value * LAMPORTS_PER_MSOL as u128 / lp_amount as u128) as u64,
+        };
+        Ok(LiquidityRecord {
+            slot: self.slot,
+            block_time: self.block_time,
+            action,
+            lp_amount,
+            lamports,
+            msol_amount,
+            msol_price_lamports: state.msol_price_lamports(),
+            lp_price_lamports,
+        })
+    }
+
+    fn account(&self, call: &MarinadeCall, role: &str) -> Result<(Pubkey, usize)> {
+        let missing = || self.missing(format!("no {} account", role), None);
+        let account = call.account(role).ok_or_else(missing)?;
+        let index = self.keys.iter().position(|key| *key == account).ok_or_else(missing)?;
+        Ok((account, index))
+    }
+
+    fn lamports(&self, call: &MarinadeCall, role: &str) -> Result<(u64, u64)> {
+        let (account, index) = self.account(call, role)?;
+        match (self.pre.get(index), self.post.get(index)) {
+            (Some(pre), Some(post)) => Ok((*pre, *post)),
+            _ => Err(self.missing(format!("no balances for the {}", role), Some(account))),
+        }
+    }
+
+    /// an account created or closed in the transaction has no token balance on that side, which
+    /// reads as 0; it's only an error when neither side has one
+    fn tokens(&self, call: &MarinadeCall, role: &str) -> Result<(u64, u64)> {
+        let (account, index) = self.account(call, role)?;
+        let amount = |balances: &[TransactionTokenBalance]| {
+            balances
+                .iter()
+                .find(|balance| balance.account_index as usize == index)
+                .and_then(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+        };
+        match (amount(&self.pre_tokens), amount(&self.post_tokens)) {
+            (None, None) => Err(self.missing(format!("no token balances for the {}", role), Some(account))),
+            (pre, post) => Ok((pre.unwrap_or(0), post.unwrap_or(0))),
+        }
+    }
+
+    fn missing(&self, reason: String, account: Option<Pubkey>) -> Error {
+        let e = Error::new(ErrorKind::InvalidTransaction { reason }).with_slot(Some(self.slot));
+        match account {
+            Some(account) => e.with_pubkey(account),
+            None => e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::{AddLiquidityArgs, RemoveLiquidityArgs};
+    use crate::constants::{LIQ_POOL_MSOL_LEG_PUBKEY, LP_MINT_PUBKEY, MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY};
+    use crate::test_utils::{
+        marinade_call, marinade_transaction, sample_state, state_account, transaction_with, with_balance,
+        with_token_balance, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT,
+    };
+    use solana_transaction_status::option_serializer::OptionSerializer;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn analyzer(rpc: &MockFetcher) -> LiquidityAnalyzer<'_> {
+        LiquidityAnalyzer::new(rpc, AnalyzeOptions::default())
+    }
+
+    #[test]
+    fn test_add_liquidity_prices_the_minted_lp() {
+        let state = sample_state();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let (sol_leg, mint_to) = (DeploymentConfig::MAINNET.liq_pool_sol_leg(), Pubkey::new_unique());
+        let ix = marinade_call(
+            MarinadeFinanceInstruction::AddLiquidity,
+            AddLiquidityArgs { lamports: 100 * SOL },
+            &[("state", MARINADE_STATE_PUBKEY), ("liq_pool_sol_leg_pda", sol_leg), ("mint_to", mint_to)],
+        );
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix]);
+        let tx = with_balance(tx, &sol_leg, 20_000 * SOL, 20_100 * SOL);
+        // a fresh LP account, with no balance before
+        let mut tx = with_token_balance(tx, &mint_to, &LP_MINT_PUBKEY, 0, 80 * SOL);
+        tx.transaction.meta.as_mut().unwrap().pre_token_balances = OptionSerializer::Some(vec![]);
+
+        let records = analyzer(&rpc).analyze(&tx).unwrap();
+        let [record] = records.as_slice() else { panic!("expected one record, got {:?}", records) };
+        assert_eq!((record.action, record.lp_amount), (LiquidityAction::Add, 80 * SOL));
+        assert_eq!((record.lamports, record.msol_amount), (100 * SOL, 0));
+        assert_eq!((record.lp_price_lamports, record.msol_price_lamports), (1_250_000_000, 1_200_166_666));
+
+        // nothing is fetched for a transaction without liquidity instructions
+        let rpc = MockFetcher::new();
+        let deposit = marinade_transaction(FIXTURE_SLOT, None, &[MarinadeFinanceInstruction::Deposit]);
+        assert_eq!(analyzer(&rpc).analyze(&deposit).unwrap(), vec![]);
+        assert!(rpc.calls().is_empty());
+    }
+
+    #[test]
+    fn test_remove_liquidity_pays_out_both_legs() {
+        let state = sample_state();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let (sol_leg, burn_from) = (DeploymentConfig::MAINNET.liq_pool_sol_leg(), Pubkey::new_unique());
+        let ix = marinade_call(
+            MarinadeFinanceInstruction::RemoveLiquidity,
+            RemoveLiquidityArgs { tokens: 50 * SOL },
+            &[
+                ("state", MARINADE_STATE_PUBKEY),
+                ("burn_from", burn_from),
+                ("liq_pool_sol_leg_pda", sol_leg),
+                ("liq_pool_msol_leg", LIQ_POOL_MSOL_LEG_PUBKEY),
+            ],
+        );
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix]);
+        let tx = with_balance(tx, &sol_leg, 20_000 * SOL, 19_960 * SOL);
+        let tx = with_token_balance(tx, &burn_from, &LP_MINT_PUBKEY, 80 * SOL, 30 * SOL);
+        let tx = with_token_balance(tx, &LIQ_POOL_MSOL_LEG_PUBKEY, &MSOL_MINT_PUBKEY, 1_000 * SOL, 980 * SOL);
+
+        let records = analyzer(&rpc).analyze(&tx).unwrap();
+        let [record] = records.as_slice() else { panic!("expected one record, got {:?}", records) };
+        assert_eq!((record.action, record.lp_amount), (LiquidityAction::Remove, 50 * SOL));
+        assert_eq!((record.lamports, record.msol_amount), (40 * SOL, 20 * SOL));
+        // 40 SOL and 20 mSOL at 1.200166666 for 50 LP
+        let msol_value = state.minimal().msol_to_sol(20 * SOL);
+        assert_eq!(msol_value, 24_003_333_333);
+        assert_eq!(record.lp_price_lamports, 1_280_066_666);
+
+        let underlying = record.mint_underlying(&DeploymentConfig::MAINNET).unwrap();
+        assert_eq!(underlying.mint_pubkey, LP_MINT_PUBKEY.to_string());
+        assert_eq!(underlying.msol_value, record.lp_price_lamports);
+        let underlyings: Vec<_> = underlying.underlyings().unwrap();
+        assert_eq!(underlyings, vec![(SOL_MINT_PUBKEY, 40 * SOL), (MSOL_MINT_PUBKEY, 20 * SOL)]);
+
+        let mut unbalanced = tx;
+        let meta = unbalanced.transaction.meta.as_mut().unwrap();
+        (meta.pre_token_balances, meta.post_token_balances) = (OptionSerializer::None, OptionSerializer::None);
+        let err = analyzer(&rpc).analyze(&unbalanced).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidTransaction { .. }));
+        assert_eq!(err.pubkey(), Some(&burn_from));
+    }
+}
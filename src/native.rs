@@ -0,0 +1,183 @@
+//! marinade native: stake accounts marinade delegates on the owner's behalf. there is no pool
+//! token, so instead of a price this reports the SOL staked per wallet, broken down like
+//! `MintUnderlying`'s underlying amounts
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::clock::Epoch;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake;
+use solana_sdk::stake_history::StakeHistory;
+use solana_sdk::sysvar;
+use tracing::instrument;
+
+use crate::accounts::stake::{
+    parse_stake_account, stake_balances, StakeBalances, STAKER_OFFSET, STAKE_ACCOUNT_LEN, WITHDRAWER_OFFSET,
+};
+use crate::constants::{MARINADE_NATIVE_STAKER_AUTHORITY, SOL_MINT_PUBKEY};
+use crate::error::{Error, ErrorKind, Result};
+use crate::{fetch_account_data, AnalyzeOptions};
+use crate::rpc::RpcFetcher;
+use crate::verbosity::debug;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeStakeAccount {
+    pub pubkey: Pubkey,
+    /// the account balance, including rent and anything not delegated
+    pub lamports: u64,
+    pub balances: StakeBalances,
+}
+
+/// a wallet's marinade native stake at one epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeStakeValuation {
+    pub wallet: Pubkey,
+    pub epoch: Epoch,
+    pub accounts: Vec<NativeStakeAccount>,
+    /// sums over `accounts`
+    pub balances: StakeBalances,
+    /// same convention as `MintUnderlying`: SOL, and the staked lamports behind the wallet
+    pub mints: Vec<String>,
+    pub total_underlying_amounts: Vec<u64>,
+}
+
+/// find the stake accounts withdrawable by `wallet` and staked through marinade native's
+/// staker authority, and value them at the current epoch
+#[instrument(level = "debug", skip(rpc_client), fields(wallet = %wallet))]
+pub fn fetch_native_stake(rpc_client: &dyn RpcFetcher, wallet: &Pubkey) -> Result<NativeStakeValuation> {
+    fetch_native_stake_with_authority(rpc_client, wallet, &MARINADE_NATIVE_STAKER_AUTHORITY)
+}
+
+/// same as `fetch_native_stake` for another staker authority
+pub fn fetch_native_stake_with_authority(
+    rpc_client: &dyn RpcFetcher,
+    wallet: &Pubkey,
+    staker_authority: &Pubkey,
+) -> Result<NativeStakeValuation> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(STAKE_ACCOUNT_LEN as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(STAKER_OFFSET, staker_authority.as_ref())),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(WITHDRAWER_OFFSET, wallet.as_ref())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let found = rpc_client
+        .get_program_accounts_with_config(&stake::program::id(), config)
+        .map_err(|e| Error::from(e).with_pubkey(*wallet))?;
+
+    let epoch = rpc_client.get_epoch_info()?.epoch;
+    let history = fetch_stake_history(rpc_client)?;
+
+    let accounts = found
+        .into_iter()
+        .map(|(pubkey, account)| {
+            let state = parse_stake_account(&account.data).map_err(|source| {
+                Error::new(ErrorKind::InvalidAccountData { role: "stake account", source }).with_pubkey(pubkey)
+            })?;
+            Ok(NativeStakeAccount {
+                pubkey,
+                lamports: account.lamports,
+                balances: stake_balances(&state, epoch, &history),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let balances = accounts.iter().fold(StakeBalances::default(), |sum, account| StakeBalances {
+        active: sum.active + account.balances.active,
+        activating: sum.activating + account.balances.activating,
+        deactivating: sum.deactivating + account.balances.deactivating,
+    });
+    debug!(epoch, accounts = accounts.len(), ?balances, "native stake valued");
+
+    Ok(NativeStakeValuation {
+        wallet: *wallet,
+        epoch,
+        accounts,
+        balances,
+        mints: vec![SOL_MINT_PUBKEY.to_string()],
+        total_underlying_amounts: vec![balances.total()],
+    })
+}
+
+fn fetch_stake_history(rpc_client: &dyn RpcFetcher) -> Result<StakeHistory> {
+    let options = AnalyzeOptions::default();
+    let (_, data) = fetch_account_data(rpc_client, &sysvar::stake_history::id(), "stake history sysvar", None, &options)?;
+    parse_stake_history(&data).ok_or_else(|| {
+        let source = std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed stake history");
+        Error::new(ErrorKind::InvalidAccountData { role: "stake history sysvar", source })
+            .with_pubkey(sysvar::stake_history::id())
+    })
+}
+
+// sysvars are bincode; go through the sdk's account helper rather than depending on bincode
+fn parse_stake_history(data: &[u8]) -> Option<StakeHistory> {
+    let account = solana_sdk::account::Account { data: data.to_vec(), ..solana_sdk::account::Account::default() };
+    solana_sdk::account::from_account(&account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{stake_account, stake_history_account, MockFetcher};
+
+    #[test]
+    fn test_native_stake_breakdown() {
+        let wallet = Pubkey::new_unique();
+        let (active, activating, deactivating) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let current = crate::test_utils::FIXTURE_SLOT / 432_000;
+        let rpc = MockFetcher::new()
+            .with_account(sysvar::stake_history::id(), stake_history_account())
+            .with_account(
+                active,
+                stake_account(&MARINADE_NATIVE_STAKER_AUTHORITY, &wallet, 5_000_000_000, current - 10, u64::MAX),
+            )
+            .with_account(
+                activating,
+                stake_account(&MARINADE_NATIVE_STAKER_AUTHORITY, &wallet, 2_000_000_000, current, u64::MAX),
+            )
+            .with_account(
+                deactivating,
+                stake_account(&MARINADE_NATIVE_STAKER_AUTHORITY, &wallet, 3_000_000_000, current - 10, current),
+            )
+            // same wallet, staked elsewhere
+            .with_account(
+                Pubkey::new_unique(),
+                stake_account(&Pubkey::new_unique(), &wallet, 7_000_000_000, current - 10, u64::MAX),
+            )
+            // marinade native, someone else's
+            .with_account(
+                Pubkey::new_unique(),
+                stake_account(&MARINADE_NATIVE_STAKER_AUTHORITY, &Pubkey::new_unique(), 1, 0, u64::MAX),
+            );
+
+        let valuation = fetch_native_stake(&rpc, &wallet).unwrap();
+        assert_eq!(valuation.epoch, current);
+        assert_eq!(valuation.accounts.len(), 3);
+        assert_eq!(
+            valuation.balances,
+            StakeBalances { active: 5_000_000_000, activating: 2_000_000_000, deactivating: 3_000_000_000 }
+        );
+        let by_key = |key: Pubkey| valuation.accounts.iter().find(|a| a.pubkey == key).unwrap().balances;
+        assert_eq!(by_key(activating).activating, 2_000_000_000);
+        assert_eq!(by_key(deactivating).deactivating, 3_000_000_000);
+        assert_eq!(valuation.mints, vec![SOL_MINT_PUBKEY.to_string()]);
+        assert_eq!(valuation.total_underlying_amounts, vec![10_000_000_000]);
+        assert_eq!(rpc.calls(), vec!["getProgramAccounts", "getEpochInfo", "getAccountInfo"]);
+    }
+
+    #[test]
+    fn test_wallet_without_native_stake() {
+        let rpc = MockFetcher::new().with_account(sysvar::stake_history::id(), stake_history_account());
+        let valuation = fetch_native_stake(&rpc, &Pubkey::new_unique()).unwrap();
+        assert!(valuation.accounts.is_empty());
+        assert_eq!(valuation.total_underlying_amounts, vec![0]);
+    }
+}
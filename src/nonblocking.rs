@@ -0,0 +1,79 @@
+//! async state and price reads over solana-client's nonblocking `RpcClient`, for services
+//! already running on tokio. the bytes go through the same parsing and price math as the
+//! blocking path; what's left out is everything built on `RpcFetcher`: reads go straight to the
+//! node, without the snapshot cache, the observer, or waiting for a node behind `slot`
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::marinade::MarinadeState;
+use crate::analysis::{account_data, account_info_config, parse_fetched_state};
+use crate::deployment::DeploymentConfig;
+use crate::error::{Error, Result};
+use crate::parsers::PriceSnapshot;
+use crate::AnalyzeOptions;
+
+/// the marinade state as of at least `slot`, or wherever the node is when None, with the context
+/// slot it was read at. `options` supplies the commitment, parse mode and verbosity
+pub async fn fetch_full_state(
+    rpc_client: &RpcClient,
+    state_pubkey: &Pubkey,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, MarinadeState)> {
+    let config = account_info_config(slot, options, None);
+    let response = rpc_client
+        .get_account_with_config(state_pubkey, config)
+        .await
+        .map_err(|e| Error::from(e).with_pubkey(*state_pubkey).with_slot(slot))?;
+    // the verbosity is the thread's, so it's only entered between awaits
+    let _verbosity = options.verbosity.enter();
+    let (context_slot, account) = account_data(response, state_pubkey, "marinade state", slot)?;
+    Ok((context_slot, parse_fetched_state(&account.data, state_pubkey, slot, options)?))
+}
+
+/// the mSOL price of `deployment` as of at least `slot`, like `MarinadeClient::price`
+pub async fn price(
+    rpc_client: &RpcClient,
+    deployment: &DeploymentConfig,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<PriceSnapshot> {
+    let (context_slot, state) = fetch_full_state(rpc_client, &deployment.state, slot, options).await?;
+    Ok(PriceSnapshot::from_marinade_state(&state.minimal(), context_slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_account_decoder::{UiAccount, UiAccountEncoding};
+    use solana_client::rpc_client::Mocks;
+    use solana_client::rpc_request::RpcRequest;
+
+    use crate::error::ErrorKind;
+    use crate::test_utils::{sample_state, state_account, FIXTURE_SLOT};
+
+    fn mock_client(account: Option<UiAccount>) -> RpcClient {
+        let response = serde_json::json!({ "context": { "slot": FIXTURE_SLOT }, "value": account });
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), Mocks::from([(RpcRequest::GetAccountInfo, response)]))
+    }
+
+    #[test]
+    fn test_price_matches_the_blocking_path() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (deployment, state) = (DeploymentConfig::MAINNET, sample_state());
+        let account = state_account(&state);
+        let encoded = UiAccount::encode(&deployment.state, &account, UiAccountEncoding::Base64, None, None);
+
+        let rpc = mock_client(Some(encoded));
+        let snapshot = runtime.block_on(price(&rpc, &deployment, None, &AnalyzeOptions::default())).unwrap();
+        let mut expected = PriceSnapshot::from_marinade_state(&state.minimal(), FIXTURE_SLOT);
+        expected.fetched_at = snapshot.fetched_at;
+        assert_eq!(snapshot, expected);
+
+        let missing = runtime.block_on(price(&mock_client(None), &deployment, Some(5), &AnalyzeOptions::default()));
+        let e = missing.unwrap_err();
+        assert!(matches!(e.kind(), ErrorKind::AccountNotFound { role: "marinade state" }));
+        assert_eq!((e.pubkey(), e.slot()), (Some(&deployment.state), Some(5)));
+    }
+}
@@ -0,0 +1,327 @@
+//! callbacks for what the crate does on the caller's behalf, for feeding whatever metrics
+//! system is in use. `StatsObserver` is a ready-made one that just counts.
+//!
+//! set one with `MarinadeClientBuilder::observer` or `AnalyzeOptions::observer`. rpc calls
+//! are reported by wrapping the fetcher in `Observed`, which the client does itself; a fetcher
+//! handed to `Analyzer::new` directly has to be wrapped by the caller
+
+// `Observed` passes `RpcClient`'s results through as they are
+#![allow(clippy::result_large_err)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::account::Account;
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::epoch_info::EpochInfo;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus};
+
+use crate::rpc::RpcFetcher;
+
+/// the `RpcFetcher` methods, by the json-rpc method each issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RpcCallKind {
+    GetAccountInfo,
+    GetMultipleAccounts,
+    GetTransaction,
+    GetBlockTime,
+    GetProgramAccounts,
+    GetEpochInfo,
+    GetSignaturesForAddress,
+    GetSlot,
+    GetSignatureStatuses,
+}
+
+impl RpcCallKind {
+    /// the json-rpc method name, e.g. `getAccountInfo`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GetAccountInfo => "getAccountInfo",
+            Self::GetMultipleAccounts => "getMultipleAccounts",
+            Self::GetTransaction => "getTransaction",
+            Self::GetBlockTime => "getBlockTime",
+            Self::GetProgramAccounts => "getProgramAccounts",
+            Self::GetEpochInfo => "getEpochInfo",
+            Self::GetSignaturesForAddress => "getSignaturesForAddress",
+            Self::GetSlot => "getSlot",
+            Self::GetSignatureStatuses => "getSignatureStatuses",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcOutcome {
+    Ok,
+    Error,
+}
+
+/// the caches whose hits save rpc calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    /// a state reused for another transaction, by `Analyzer` or a batch
+    State,
+    /// `AnalyzeOptions::snapshot_cache` read offline
+    Snapshot,
+}
+
+/// every callback defaults to doing nothing. called from whichever thread did the work
+pub trait Observer: Send + Sync {
+    fn on_rpc_call(&self, _kind: RpcCallKind, _duration: Duration, _outcome: RpcOutcome) {}
+
+    fn on_cache_hit(&self, _cache: CacheKind) {}
+
+    fn on_cache_miss(&self, _cache: CacheKind) {}
+
+    /// a transaction at `slot` was valued
+    fn on_analysis_complete(&self, _slot: Slot) {}
+
+    /// an account read, on its own or in a `getMultipleAccounts`, arrived in `encoding`: base64, or base64+zstd
+    /// when `AnalyzeOptions::prefer_zstd` asked for it and the node obliged
+    fn on_account_read(&self, _encoding: UiAccountEncoding) {}
+}
+
+impl fmt::Debug for dyn Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// counts and latencies of one kind of rpc call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RpcCallStats {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl RpcCallStats {
+    pub fn mean_latency(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => self.total_latency / calls as u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub rpc: BTreeMap<RpcCallKind, RpcCallStats>,
+    pub state_cache_hits: u64,
+    pub state_cache_misses: u64,
+    pub snapshot_cache_hits: u64,
+    pub snapshot_cache_misses: u64,
+    pub analyses: u64,
+    /// of the most recently completed analysis
+    pub last_analyzed_slot: Option<Slot>,
+    /// account reads that came back zstd compressed, and as plain base64
+    pub zstd_account_reads: u64,
+    pub base64_account_reads: u64,
+}
+
+impl Stats {
+    /// rpc calls of every kind
+    pub fn rpc_calls(&self) -> u64 {
+        self.rpc.values().map(|stats| stats.calls).sum()
+    }
+}
+
+/// aggregates everything it's told into `Stats`
+#[derive(Debug, Default)]
+pub struct StatsObserver {
+    stats: Mutex<Stats>,
+}
+
+impl StatsObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a copy of the counts so far
+    pub fn stats(&self) -> Stats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.stats.lock().unwrap() = Stats::default();
+    }
+}
+
+impl Observer for StatsObserver {
+    fn on_rpc_call(&self, kind: RpcCallKind, duration: Duration, outcome: RpcOutcome) {
+        let mut stats = self.stats.lock().unwrap();
+        let call = stats.rpc.entry(kind).or_default();
+        call.calls += 1;
+        call.errors += (outcome == RpcOutcome::Error) as u64;
+        call.total_latency += duration;
+        call.max_latency = call.max_latency.max(duration);
+    }
+
+    fn on_cache_hit(&self, cache: CacheKind) {
+        let mut stats = self.stats.lock().unwrap();
+        match cache {
+            CacheKind::State => stats.state_cache_hits += 1,
+            CacheKind::Snapshot => stats.snapshot_cache_hits += 1,
+        }
+    }
+
+    fn on_cache_miss(&self, cache: CacheKind) {
+        let mut stats = self.stats.lock().unwrap();
+        match cache {
+            CacheKind::State => stats.state_cache_misses += 1,
+            CacheKind::Snapshot => stats.snapshot_cache_misses += 1,
+        }
+    }
+
+    fn on_analysis_complete(&self, slot: Slot) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.analyses += 1;
+        stats.last_analyzed_slot = Some(slot);
+    }
+
+    fn on_account_read(&self, encoding: UiAccountEncoding) {
+        let mut stats = self.stats.lock().unwrap();
+        match encoding {
+            UiAccountEncoding::Base64Zstd => stats.zstd_account_reads += 1,
+            _ => stats.base64_account_reads += 1,
+        }
+    }
+}
+
+/// reports every call into `inner` to an observer, timed
+pub struct Observed<F> {
+    inner: F,
+    observer: Arc<dyn Observer>,
+}
+
+impl<F: RpcFetcher> Observed<F> {
+    pub fn new(inner: F, observer: Arc<dyn Observer>) -> Self {
+        Self { inner, observer }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    fn timed<T, E>(&self, kind: RpcCallKind, call: impl FnOnce(&F) -> Result<T, E>) -> Result<T, E> {
+        let started = Instant::now();
+        let result = call(&self.inner);
+        let outcome = if result.is_ok() { RpcOutcome::Ok } else { RpcOutcome::Error };
+        self.observer.on_rpc_call(kind, started.elapsed(), outcome);
+        result
+    }
+}
+
+impl<F: RpcFetcher> RpcFetcher for Observed<F> {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        self.timed(RpcCallKind::GetAccountInfo, |inner| inner.get_account_with_config(pubkey, config))
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        self.timed(RpcCallKind::GetMultipleAccounts, |inner| inner.get_multiple_accounts_with_config(pubkeys, config))
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.timed(RpcCallKind::GetTransaction, |inner| inner.get_transaction_with_config(signature, config))
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.timed(RpcCallKind::GetBlockTime, |inner| inner.get_block_time(slot))
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.timed(RpcCallKind::GetProgramAccounts, |inner| inner.get_program_accounts_with_config(program_id, config))
+    }
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        self.timed(RpcCallKind::GetEpochInfo, |inner| inner.get_epoch_info())
+    }
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.timed(RpcCallKind::GetSlot, |inner| inner.get_slot_with_commitment(commitment))
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.timed(RpcCallKind::GetSignaturesForAddress, |inner| {
+            inner.get_signatures_for_address_with_config(address, config)
+        })
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.timed(RpcCallKind::GetSignatureStatuses, |inner| inner.get_signature_statuses_with_history(signatures))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::client::MarinadeClient;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::AnalyzeOptions;
+
+    #[test]
+    fn test_stats_after_scripted_calls() {
+        let observer = Arc::new(StatsObserver::new());
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let client = MarinadeClient::builder().rpc_client(rpc).observer(observer.clone()).build();
+        let deposit = |slot| marinade_transaction(slot, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+
+        client.price(None).unwrap();
+        client.analyze_transaction(&deposit(FIXTURE_SLOT)).unwrap();
+        assert!(client.fetch_transaction(Signature::new_unique()).is_err());
+        // the second deposit reuses the first one's state
+        let mut analyzer = client.analyzer();
+        analyzer.analyze(&deposit(FIXTURE_SLOT + 1)).unwrap();
+        analyzer.analyze(&deposit(FIXTURE_SLOT + 2)).unwrap();
+
+        let stats = observer.stats();
+        let accounts = stats.rpc[&RpcCallKind::GetAccountInfo];
+        assert_eq!((accounts.calls, accounts.errors), (3, 0));
+        assert!(accounts.max_latency >= accounts.mean_latency());
+        let transactions = stats.rpc[&RpcCallKind::GetTransaction];
+        assert_eq!((transactions.calls, transactions.errors), (1, 1));
+        assert_eq!(stats.rpc_calls(), 4);
+        assert_eq!((stats.state_cache_hits, stats.state_cache_misses), (1, 1));
+        assert_eq!((stats.analyses, stats.last_analyzed_slot), (3, Some(FIXTURE_SLOT + 2)));
+
+        // the free functions report through the options' observer
+        observer.reset();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let options = AnalyzeOptions { observer: Some(observer.clone()), ..AnalyzeOptions::default() };
+        crate::analyze_transaction_with_options(&rpc, &deposit(FIXTURE_SLOT), &options).unwrap();
+        let stats = observer.stats();
+        assert_eq!((stats.rpc_calls(), stats.analyses), (1, 1));
+        assert_eq!(RpcCallKind::GetAccountInfo.name(), rpc.calls()[0]);
+    }
+}
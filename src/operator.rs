@@ -0,0 +1,243 @@
+//! how busy the crank operator has been, over blocks `EventClassifier::analyze_block` produced:
+//! `merge_stakes` and `partial_unstake` counted and summed, as a whole or per epoch, and the
+//! delayed-unstake pipeline's `deactivate_stake` and `update_deactivated` per epoch
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use solana_sdk::epoch_schedule::{Epoch, EpochSchedule};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::events::{BlockEvents, MarinadeEvent};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OperatorActivitySummary {
+    pub blocks: usize,
+    /// first and last slot of the blocks, None when there were none
+    pub slots: Option<(Slot, Slot)>,
+    pub merges: usize,
+    pub lamports_merged: u64,
+    pub partial_unstakes: usize,
+    /// what the partial unstakes asked for
+    pub desired_unstake_lamports: u64,
+    /// what they split off, rent reserves included
+    pub lamports_split: u64,
+    /// merges and partial unstakes whose meta had no balances, so their lamports aren't summed
+    pub missing_balances: usize,
+}
+
+impl OperatorActivitySummary {
+    pub fn from_blocks<'a>(blocks: impl IntoIterator<Item = &'a BlockEvents>) -> Self {
+        let mut summary = Self::default();
+        for block in blocks {
+            summary.add(block);
+        }
+        summary
+    }
+
+    /// one summary per epoch the blocks fall in
+    pub fn per_epoch<'a>(
+        blocks: impl IntoIterator<Item = &'a BlockEvents>,
+        epoch_schedule: &EpochSchedule,
+    ) -> BTreeMap<Epoch, Self> {
+        let mut epochs = BTreeMap::<Epoch, Self>::new();
+        for block in blocks {
+            epochs.entry(epoch_schedule.get_epoch(block.slot)).or_default().add(block);
+        }
+        epochs
+    }
+
+    fn add(&mut self, block: &BlockEvents) {
+        self.blocks += 1;
+        self.slots = Some(match self.slots {
+            Some((first, last)) => (first.min(block.slot), last.max(block.slot)),
+            None => (block.slot, block.slot),
+        });
+        for event in &block.events {
+            let lamports = match event {
+                MarinadeEvent::MergeStakes(merge) => {
+                    self.merges += 1;
+                    merge.lamports_merged.map(|lamports| self.lamports_merged += lamports)
+                }
+                MarinadeEvent::PartialUnstake(unstake) => {
+                    self.partial_unstakes += 1;
+                    self.desired_unstake_lamports += unstake.desired_unstake_amount;
+                    unstake.lamports_split.map(|lamports| self.lamports_split += lamports)
+                }
+                _ => continue,
+            };
+            self.missing_balances += lamports.is_none() as usize;
+        }
+    }
+}
+
+/// stake deactivated and reclaimed into the reserve over one epoch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeactivationReport {
+    pub epoch: Epoch,
+    /// see `DeactivateStakeEvent::deactivated_lamports`
+    pub deactivated_lamports: u64,
+    pub reclaimed_lamports: u64,
+    /// distinct stake accounts deactivated, split off or reclaimed
+    pub stake_accounts_touched: usize,
+    /// deactivations and reclaims whose lamports weren't known, so aren't summed
+    pub missing_lamports: usize,
+}
+
+impl DeactivationReport {
+    /// from the blocks that fall in `epoch`; the others are skipped
+    pub fn for_epoch<'a>(
+        epoch: Epoch,
+        blocks: impl IntoIterator<Item = &'a BlockEvents>,
+        epoch_schedule: &EpochSchedule,
+    ) -> Self {
+        let mut report = Self { epoch, ..Self::default() };
+        let mut touched = BTreeSet::<Pubkey>::new();
+        let events = blocks
+            .into_iter()
+            .filter(|block| epoch_schedule.get_epoch(block.slot) == epoch)
+            .flat_map(|block| block.events.iter());
+        for event in events {
+            let lamports = match event {
+                MarinadeEvent::DeactivateStake(deactivate) => {
+                    touched.insert(deactivate.stake_account);
+                    if deactivate.split_stake_lamports.is_some_and(|lamports| lamports > 0) {
+                        touched.insert(deactivate.split_stake_account);
+                    }
+                    deactivate.deactivated_lamports().map(|lamports| report.deactivated_lamports += lamports)
+                }
+                MarinadeEvent::UpdateDeactivated(update) => {
+                    touched.insert(update.stake_account);
+                    update.reclaimed_lamports.map(|lamports| report.reclaimed_lamports += lamports)
+                }
+                _ => continue,
+            };
+            report.missing_lamports += lamports.is_none() as usize;
+        }
+        report.stake_accounts_touched = touched.len();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::{
+        DeactivateStakeArgs, MarinadeFinanceInstruction, MergeStakesArgs, PartialUnstakeArgs, UpdateDeactivatedArgs,
+    };
+    use crate::accounts::lists::{StakeRecord, ValidatorRecord};
+    use crate::accounts::marinade::List;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::events::EventClassifier;
+    use crate::test_utils::{
+        block_of, list_account, marinade_call, sample_state, state_account, transaction_with, with_balance, MockFetcher,
+        FIXTURE_SLOT,
+    };
+    use crate::AnalyzeOptions;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn merge(source: Pubkey) -> Instruction {
+        let args = MergeStakesArgs { destination_stake_index: 3, source_stake_index: 7, validator_index: 2 };
+        marinade_call(MarinadeFinanceInstruction::MergeStakes, args, &[("source_stake", source)])
+    }
+
+    fn partial_unstake(split: Pubkey, desired_unstake_amount: u64) -> Instruction {
+        let args = PartialUnstakeArgs { stake_index: 4, validator_index: 2, desired_unstake_amount };
+        marinade_call(MarinadeFinanceInstruction::PartialUnstake, args, &[("split_stake_account", split)])
+    }
+
+    /// a crank run: a merge and a split in one transaction, then a partial unstake that took
+    /// the whole account, then a merge whose meta lost its balances
+    fn crank_block(slot: Slot) -> BlockEvents {
+        let (source, split, whole) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let both = transaction_with(slot, None, &[merge(source), partial_unstake(split, 5_000_000_000_000)]);
+        let both = with_balance(with_balance(both, &source, 30_002_282_880, 0), &split, 0, 5_000_002_282_880);
+        let whole_account = transaction_with(slot, None, &[partial_unstake(whole, 1_000_000_000)]);
+        let whole_account = with_balance(whole_account, &whole, 0, 0);
+        let mut no_balances = transaction_with(slot, None, &[merge(Pubkey::new_unique())]);
+        no_balances.transaction.meta = None;
+
+        let rpc = MockFetcher::new();
+        let block = block_of(&[both, whole_account, no_balances]);
+        let block = EventClassifier::new(&rpc, AnalyzeOptions::default()).analyze_block(slot, &block);
+        assert!(rpc.calls().is_empty());
+        block.unwrap()
+    }
+
+    #[test]
+    fn test_summary_over_a_crank_block() {
+        let schedule = EpochSchedule::without_warmup();
+        let next_epoch = schedule.get_first_slot_in_epoch(schedule.get_epoch(FIXTURE_SLOT) + 1);
+        let blocks = [crank_block(FIXTURE_SLOT), crank_block(FIXTURE_SLOT + 1), crank_block(next_epoch)];
+        assert_eq!(blocks[0].events.len(), 4);
+
+        let block = OperatorActivitySummary::from_blocks(&blocks[..1]);
+        assert_eq!((block.merges, block.partial_unstakes, block.missing_balances), (2, 2, 1));
+        assert_eq!((block.lamports_merged, block.lamports_split), (30_002_282_880, 5_000_002_282_880));
+        assert_eq!(block.desired_unstake_lamports, 5_001_000_000_000);
+        assert_eq!((block.blocks, block.slots), (1, Some((FIXTURE_SLOT, FIXTURE_SLOT))));
+
+        let epochs = OperatorActivitySummary::per_epoch(&blocks, &schedule);
+        let first = &epochs[&schedule.get_epoch(FIXTURE_SLOT)];
+        assert_eq!((first.blocks, first.merges, first.lamports_merged), (2, 4, 2 * 30_002_282_880));
+        assert_eq!(first.slots, Some((FIXTURE_SLOT, FIXTURE_SLOT + 1)));
+        let expected = OperatorActivitySummary { slots: Some((next_epoch, next_epoch)), ..block };
+        assert_eq!(epochs[&schedule.get_epoch(next_epoch)], expected);
+        assert_eq!(OperatorActivitySummary::from_blocks(&blocks).blocks, 3);
+    }
+
+    #[test]
+    fn test_deactivation_report_for_an_epoch() {
+        let [split_from, split, whole, cooled, reserve] = [(); 5].map(|_| Pubkey::new_unique());
+        let mut state = sample_state();
+        let list = || List { account: Pubkey::new_unique(), item_size: 49, count: 2, ..List::default() };
+        state.stake_system.stake_list = list();
+        state.validator_system.validator_list = List { count: 0, ..list() };
+        let record = |stake_account, lamports| StakeRecord {
+            stake_account,
+            last_update_delegated_lamports: lamports,
+            ..StakeRecord::default()
+        };
+        let records = [record(split_from, 90_000_000_000_000), record(whole, 1_500_000_000_000)];
+        let rpc = MockFetcher::new()
+            .with_account(MARINADE_STATE_PUBKEY, state_account(&state))
+            .with_account(state.stake_system.stake_list.account, list_account(&records, 49))
+            .with_account(state.validator_system.validator_list.account, list_account::<ValidatorRecord>(&[], 53));
+
+        let deactivate = |stake_index, stake_account, split_stake_account| {
+            let args = DeactivateStakeArgs { stake_index, validator_index: 0 };
+            let named = [("stake_account", stake_account), ("split_stake_account", split_stake_account)];
+            marinade_call(MarinadeFinanceInstruction::DeactivateStake, args, &named)
+        };
+        let with_split = transaction_with(FIXTURE_SLOT, None, &[deactivate(0, split_from, split)]);
+        let with_split = with_balance(with_split, &split, 0, 20_002_282_880);
+        let fresh = Pubkey::new_unique();
+        let whole_account = transaction_with(FIXTURE_SLOT, None, &[deactivate(1, whole, fresh)]);
+        let whole_account = with_balance(whole_account, &fresh, 0, 0);
+        let update = marinade_call(
+            MarinadeFinanceInstruction::UpdateDeactivated,
+            UpdateDeactivatedArgs { stake_index: 1 },
+            &[("stake_account", cooled), ("reserve_pda", reserve)],
+        );
+        let update = with_balance(transaction_with(FIXTURE_SLOT, None, &[update]), &reserve, 1_000, 700_000_001_000);
+
+        let classifier = EventClassifier::new(&rpc, AnalyzeOptions::default());
+        let block = classifier.analyze_block(FIXTURE_SLOT, &block_of(&[with_split, whole_account, update])).unwrap();
+        let schedule = EpochSchedule::without_warmup();
+        let epoch = schedule.get_epoch(FIXTURE_SLOT);
+
+        let report = DeactivationReport::for_epoch(epoch, [&block], &schedule);
+        let expected = DeactivationReport {
+            epoch,
+            deactivated_lamports: 20_002_282_880 + 1_500_000_000_000,
+            reclaimed_lamports: 700_000_000_000,
+            stake_accounts_touched: 4,
+            missing_lamports: 0,
+        };
+        assert_eq!(report, expected);
+        // blocks of other epochs don't count
+        assert_eq!(DeactivationReport::for_epoch(epoch + 1, [&block], &schedule).stake_accounts_touched, 0);
+    }
+}
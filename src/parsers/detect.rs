@@ -0,0 +1,141 @@
+//! which protocols a transaction involves, from its account keys and invoked programs alone
+
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiInstruction};
+
+use crate::parsers::registry::Registry;
+use crate::parsers::{LstValueParser, Protocol};
+use crate::transaction::account_keys;
+
+/// how a protocol showed up in a tx, strongest evidence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HitSource {
+    /// the tx calls the program directly
+    Instruction,
+    /// the program is reached through a cpi, e.g. an aggregator route
+    InnerInstruction,
+    /// the protocol's accounts are referenced but its program isn't called, e.g. a token transfer
+    AccountKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolHit {
+    pub protocol: Protocol,
+    pub mint: Pubkey,
+    pub program_id: Pubkey,
+    pub source: HitSource,
+}
+
+/// the built-in protocols involved in `tx`, see `Registry::detect_protocol`
+pub fn detect_protocol(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<ProtocolHit> {
+    Registry::default().detect_protocol(tx)
+}
+
+/// one hit per parser whose accounts `tx` references, ordered by `HitSource` and then by the
+/// order `parsers` come in. empty when the tx payload can't be decoded
+pub(crate) fn detect_with<'a>(
+    parsers: impl Iterator<Item = &'a dyn LstValueParser>,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Vec<ProtocolHit> {
+    let Some(versioned) = tx.transaction.transaction.decode() else {
+        return Vec::new();
+    };
+    let keys = account_keys(tx, &versioned.message);
+    let program_at = |index: u8| keys.get(index as usize).copied();
+
+    let top_level: Vec<Pubkey> =
+        versioned.message.instructions().iter().filter_map(|ix| program_at(ix.program_id_index)).collect();
+    let mut inner = Vec::new();
+    if let Some(meta) = &tx.transaction.meta {
+        if let OptionSerializer::Some(inner_instructions) = &meta.inner_instructions {
+            for ix in inner_instructions.iter().flat_map(|inner| inner.instructions.iter()) {
+                if let UiInstruction::Compiled(compiled) = ix {
+                    inner.extend(program_at(compiled.program_id_index));
+                }
+            }
+        }
+    }
+
+    let mut hits: Vec<ProtocolHit> = parsers
+        .filter(|parser| parser.matches_accounts(&keys))
+        .map(|parser| {
+            let program_id = parser.program_id();
+            let source = if top_level.contains(&program_id) {
+                HitSource::Instruction
+            } else if inner.contains(&program_id) {
+                HitSource::InnerInstruction
+            } else {
+                HitSource::AccountKey
+            };
+            ProtocolHit { protocol: parser.protocol(), mint: parser.mint(), program_id, source }
+        })
+        .collect();
+    // stable, so registration order breaks ties
+    hits.sort_by_key(|hit| hit.source);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::constants::{
+        JITOSOL_MINT_PUBKEY, JITO_STAKE_POOL_PUBKEY, MARINADE_PROGRAM_ID, MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY,
+        SPL_STAKE_POOL_PROGRAM_ID,
+    };
+    use crate::test_utils::{marinade_transaction, transaction_with, with_inner_instructions, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+
+    #[test]
+    fn test_single_protocol() {
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        assert_eq!(
+            detect_protocol(&tx),
+            vec![ProtocolHit {
+                protocol: Protocol::Marinade,
+                mint: MSOL_MINT_PUBKEY,
+                program_id: MARINADE_PROGRAM_ID,
+                source: HitSource::Instruction,
+            }]
+        );
+        // a jitoSOL transfer only references the mint
+        let transfer = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![AccountMeta::new(JITOSOL_MINT_PUBKEY, false)]);
+        let hits = detect_protocol(&transaction_with(FIXTURE_SLOT, None, &[transfer]));
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].mint, hits[0].source), (JITOSOL_MINT_PUBKEY, HitSource::AccountKey));
+    }
+
+    #[test]
+    fn test_aggregator_route_through_two_protocols() {
+        let aggregator = Pubkey::new_unique();
+        let route = Instruction::new_with_bytes(
+            aggregator,
+            &[],
+            vec![
+                AccountMeta::new_readonly(MARINADE_PROGRAM_ID, false),
+                AccountMeta::new(MARINADE_STATE_PUBKEY, false),
+                AccountMeta::new_readonly(SPL_STAKE_POOL_PROGRAM_ID, false),
+                AccountMeta::new(JITO_STAKE_POOL_PUBKEY, false),
+            ],
+        );
+        let tx = with_inner_instructions(
+            transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[route]),
+            &[
+                Instruction::new_with_bytes(MARINADE_PROGRAM_ID, &[], vec![AccountMeta::new(MARINADE_STATE_PUBKEY, false)]),
+                Instruction::new_with_bytes(SPL_STAKE_POOL_PROGRAM_ID, &[], vec![AccountMeta::new(JITO_STAKE_POOL_PUBKEY, false)]),
+            ],
+        );
+
+        let hits = detect_protocol(&tx);
+        let found: Vec<_> = hits.iter().map(|hit| (hit.protocol.clone(), hit.mint, hit.source)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Protocol::Marinade, MSOL_MINT_PUBKEY, HitSource::InnerInstruction),
+                // the blaze pool shares the program but isn't referenced
+                (Protocol::SplStakePool, JITOSOL_MINT_PUBKEY, HitSource::InnerInstruction),
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use solana_sdk::clock::UnixTimestamp;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::lido::{parse_lido_state, LidoState};
+use crate::constants::{LIDO_PROGRAM_ID, LIDO_STATE_PUBKEY, SOL_MINT_PUBKEY, STSOL_MINT_PUBKEY};
+use crate::error::{Error, ErrorKind, Result};
+use crate::parsers::{LstValueParser, ParserContext, PriceSnapshot, Protocol};
+use crate::transaction::transaction_signature;
+use crate::{fetch_account_data, resolve_block_time, BlockTimeSource, MintUnderlying};
+
+const ROLE: &str = "lido state";
+
+/// stSOL. lido is winding down, so besides the rpc-backed trait methods this can value
+/// archived state snapshots without a connection, see `mint_underlying_from_snapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LidoParser {
+    state: Pubkey,
+    mint: Pubkey,
+}
+
+impl Default for LidoParser {
+    fn default() -> Self {
+        Self { state: LIDO_STATE_PUBKEY, mint: STSOL_MINT_PUBKEY }
+    }
+}
+
+impl LidoParser {
+    pub fn new(state: Pubkey, mint: Pubkey) -> Self {
+        Self { state, mint }
+    }
+
+    pub fn state(&self) -> Pubkey {
+        self.state
+    }
+
+    fn parse(&self, account_data: &[u8], slot: Option<u64>) -> Result<LidoState> {
+        let invalid = |source| Error::new(ErrorKind::InvalidAccountData { role: ROLE, source }).with_pubkey(self.state).with_slot(slot);
+        let state = parse_lido_state(account_data).map_err(invalid)?;
+        if state.st_sol_mint != self.mint {
+            return Err(invalid(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("stSOL mint is {}, expected {}", state.st_sol_mint, self.mint),
+            )));
+        }
+        Ok(state)
+    }
+
+    fn mint_underlying(&self, state: &LidoState, block_time: i64, block_time_source: BlockTimeSource) -> MintUnderlying {
+        let (price, sol_balance) = (state.price_lamports(), state.exchange_rate.sol_balance);
+        let (mint, platform) = (&self.mint, &self.state);
+        MintUnderlying::new(block_time, block_time_source, price, mint, platform, &[SOL_MINT_PUBKEY], vec![sol_balance])
+            .expect("one mint, one amount")
+    }
+
+    /// value an archived copy of the state account, taken at `block_time`. no rpc involved
+    pub fn mint_underlying_from_snapshot(&self, account_data: &[u8], block_time: UnixTimestamp) -> Result<MintUnderlying> {
+        let state = self.parse(account_data, None)?;
+        Ok(self.mint_underlying(&state, block_time, BlockTimeSource::Snapshot))
+    }
+}
+
+impl LstValueParser for LidoParser {
+    fn mint(&self) -> Pubkey {
+        self.mint
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::Lido
+    }
+
+    fn program_id(&self) -> Pubkey {
+        LIDO_PROGRAM_ID
+    }
+
+    fn matches_accounts(&self, account_keys: &[Pubkey]) -> bool {
+        account_keys.contains(&self.state) || account_keys.contains(&self.mint)
+    }
+
+    fn analyze(&self, ctx: &ParserContext<'_>, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying> {
+        let with_signature = |e: Error| e.with_signature(transaction_signature(tx));
+        let fetched = fetch_account_data(ctx.rpc_client, &self.state, ROLE, Some(tx.slot), ctx.options);
+        let (_, data) = fetched.map_err(with_signature)?;
+        let state = self.parse(&data, Some(tx.slot)).map_err(with_signature)?;
+        let (block_time, block_time_source) = resolve_block_time(ctx.rpc_client, tx.into(), ctx.options).map_err(with_signature)?;
+        Ok(self.mint_underlying(&state, block_time, block_time_source))
+    }
+
+    fn current_price(&self, ctx: &ParserContext<'_>) -> Result<PriceSnapshot> {
+        let (slot, data) = fetch_account_data(ctx.rpc_client, &self.state, ROLE, None, ctx.options)?;
+        let state = self.parse(&data, None)?;
+        let rate = &state.exchange_rate;
+        Ok(PriceSnapshot::new(Protocol::Lido, self.mint, state.price_lamports(), rate.sol_balance, rate.st_sol_supply, slot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{lido_account, sample_lido_state, sample_transaction, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::AnalyzeOptions;
+
+    #[test]
+    fn test_offline_snapshot_matches_rpc_analysis() {
+        let parser = LidoParser::default();
+        let snapshot = lido_account(&sample_lido_state()).data;
+
+        let offline = parser.mint_underlying_from_snapshot(&snapshot, FIXTURE_BLOCK_TIME).unwrap();
+        assert_eq!(offline.mint_pubkey, STSOL_MINT_PUBKEY.to_string());
+        assert_eq!(offline.msol_value, 1_166_666_666);
+        assert_eq!(offline.underlyings().unwrap(), [(SOL_MINT_PUBKEY, 10_500_000_000_000_000)]);
+        assert_eq!(offline.block_time_source, BlockTimeSource::Snapshot);
+
+        let rpc = MockFetcher::new().with_account(LIDO_STATE_PUBKEY, lido_account(&sample_lido_state()));
+        let options = AnalyzeOptions::default();
+        let ctx = ParserContext::new(&rpc, &options);
+        let parser: Box<dyn LstValueParser> = Box::new(parser);
+        let online = parser.analyze(&ctx, &sample_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME))).unwrap();
+        assert_eq!(online.msol_value, offline.msol_value);
+        assert_eq!(online.amount_for(&SOL_MINT_PUBKEY), offline.amount_for(&SOL_MINT_PUBKEY));
+        assert_eq!(parser.current_price(&ctx).unwrap().price_lamports, offline.msol_value);
+    }
+}
@@ -0,0 +1,83 @@
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::deployment::DeploymentConfig;
+use crate::error::Result;
+use crate::parsers::{LstValueParser, ParserContext, PriceSnapshot, Protocol};
+use crate::{analyze_with, fetch_state, MintUnderlying};
+
+/// mSOL, for the mainnet deployment unless told otherwise
+#[derive(Debug, Clone, Default)]
+pub struct MarinadeParser {
+    deployment: DeploymentConfig,
+}
+
+impl MarinadeParser {
+    pub fn new(deployment: DeploymentConfig) -> Self {
+        Self { deployment }
+    }
+
+    pub fn deployment(&self) -> &DeploymentConfig {
+        &self.deployment
+    }
+}
+
+impl LstValueParser for MarinadeParser {
+    fn mint(&self) -> Pubkey {
+        self.deployment.msol_mint
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::Marinade
+    }
+
+    fn program_id(&self) -> Pubkey {
+        self.deployment.program_id
+    }
+
+    /// every marinade instruction takes the state account
+    fn matches_accounts(&self, account_keys: &[Pubkey]) -> bool {
+        account_keys.contains(&self.deployment.state) || account_keys.contains(&self.deployment.msol_mint)
+    }
+
+    fn analyze(&self, ctx: &ParserContext<'_>, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying> {
+        analyze_with(ctx.rpc_client, tx.into(), &self.deployment, ctx.options)
+    }
+
+    fn current_price(&self, ctx: &ParserContext<'_>) -> Result<PriceSnapshot> {
+        let (slot, state) = fetch_state(ctx.rpc_client, &self.deployment.state, None, ctx.options)?;
+        Ok(PriceSnapshot::from_marinade_state(&state, slot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::{analyze_transaction, AnalyzeOptions};
+
+    #[test]
+    fn test_marinade_through_the_trait_object() {
+        let state = MarinadeState { msol_mint: DeploymentConfig::MAINNET.msol_mint, ..sample_state() };
+        let rpc = MockFetcher::new().with_account(DeploymentConfig::MAINNET.state, state_account(&state));
+        let options = AnalyzeOptions::default();
+        let ctx = ParserContext::new(&rpc, &options);
+        let parsers: Vec<Box<dyn LstValueParser>> = vec![Box::new(MarinadeParser::default())];
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+
+        let parser = &parsers[0];
+        assert_eq!(parser.mint(), DeploymentConfig::MAINNET.msol_mint);
+        let mu = parser.analyze(&ctx, &tx).unwrap();
+        assert_eq!(mu.msol_value, analyze_transaction(&rpc, &tx).unwrap().msol_value);
+
+        let snapshot = parser.current_price(&ctx).unwrap();
+        assert_eq!(snapshot.price_lamports, state.msol_price_lamports());
+        assert_eq!(snapshot.mint, parser.mint());
+        assert_eq!(snapshot.protocol, Protocol::Marinade);
+        assert_eq!(snapshot.supply, state.msol_supply);
+        // no slot requested: whatever the node is at
+        assert_eq!(snapshot.slot, FIXTURE_SLOT);
+    }
+}
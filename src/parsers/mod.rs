@@ -0,0 +1,399 @@
+//! one `LstValueParser` per liquid staking protocol, all producing `MintUnderlying`
+
+pub mod detect;
+pub mod lido;
+pub mod marinade;
+pub mod registry;
+pub mod spl_stake_pool;
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::UnixTimestamp;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::marinade::{MinimalState, LAMPORTS_PER_MSOL};
+use crate::epoch::EpochContext;
+use crate::error::Result;
+use crate::rpc::RpcFetcher;
+use crate::{AnalyzeOptions, MintUnderlying};
+
+/// the liquid staking protocol behind a parser. serializes as its display name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Protocol {
+    Marinade,
+    SplStakePool,
+    Lido,
+    /// a parser registered by the caller
+    Custom(String),
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Marinade => write!(f, "marinade"),
+            Self::SplStakePool => write!(f, "spl-stake-pool"),
+            Self::Lido => write!(f, "lido"),
+            Self::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl From<String> for Protocol {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "marinade" => Self::Marinade,
+            "spl-stake-pool" => Self::SplStakePool,
+            "lido" => Self::Lido,
+            _ => Self::Custom(name),
+        }
+    }
+}
+
+impl From<Protocol> for String {
+    fn from(protocol: Protocol) -> Self {
+        protocol.to_string()
+    }
+}
+
+/// what a parser may use while working: the connection and the caller's options
+#[derive(Clone, Copy)]
+pub struct ParserContext<'a> {
+    pub rpc_client: &'a dyn RpcFetcher,
+    pub options: &'a AnalyzeOptions,
+}
+
+impl<'a> ParserContext<'a> {
+    pub fn new(rpc_client: &'a dyn RpcFetcher, options: &'a AnalyzeOptions) -> Self {
+        Self { rpc_client, options }
+    }
+}
+
+/// the price of one lst at one slot, the same shape for every protocol.
+/// equality, ordering and hashing ignore `fetched_at`, so polling the same slot twice yields
+/// duplicates: sorting a series orders it by protocol, mint then slot, and `dedup` collapses it.
+/// serialized with a leading `schema_version`, see `crate::schema`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct PriceSnapshot {
+    pub protocol: Protocol,
+    #[serde(with = "crate::serde_pubkey")]
+    pub mint: Pubkey,
+    /// lamports per whole token
+    pub price_lamports: u64,
+    /// the SOL behind the whole supply
+    pub underlying_lamports: u64,
+    /// token supply, in the mint's base units
+    pub supply: u64,
+    /// context slot of the data the price was computed from
+    pub slot: u64,
+    /// wall clock time the data was fetched, unix seconds
+    pub fetched_at: UnixTimestamp,
+    /// marinade only: what the next stake-delta crank would stake (positive) or deactivate,
+    /// see `MinimalState::pending_stake_delta`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stake_delta_lamports: Option<i128>,
+    /// where `slot` sits in its epoch, when it was looked up, see `PriceSnapshot::with_epoch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<EpochContext>,
+}
+
+crate::schema::versioned_serde!(PriceSnapshot);
+
+impl PriceSnapshot {
+    /// a snapshot fetched now
+    pub fn new(
+        protocol: Protocol,
+        mint: Pubkey,
+        price_lamports: u64,
+        underlying_lamports: u64,
+        supply: u64,
+        slot: u64,
+    ) -> Self {
+        let fetched_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as UnixTimestamp);
+        Self {
+            protocol,
+            mint,
+            price_lamports,
+            underlying_lamports,
+            supply,
+            slot,
+            fetched_at,
+            stake_delta_lamports: None,
+            epoch: None,
+        }
+    }
+
+    /// the mSOL price in a marinade state read at `slot`
+    pub fn from_marinade_state(state: &MinimalState, slot: u64) -> Self {
+        let snapshot = Self::new(
+            Protocol::Marinade,
+            state.msol_mint,
+            state.msol_price_lamports(),
+            state.total_virtual_staked_lamports(),
+            state.msol_supply,
+            slot,
+        );
+        Self { stake_delta_lamports: Some(state.pending_stake_delta()), ..snapshot }
+    }
+
+    /// the snapshot with the epoch context of its `slot`
+    pub fn with_epoch(self, epoch: EpochContext) -> Self {
+        Self { epoch: Some(epoch), ..self }
+    }
+
+    fn key(&self) -> (&Protocol, &Pubkey, u64, u64, u64, u64) {
+        (&self.protocol, &self.mint, self.slot, self.price_lamports, self.underlying_lamports, self.supply)
+    }
+}
+
+/// what changed from one `PriceSnapshot` to a later one, `later - earlier` throughout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    pub earlier_slot: u64,
+    pub later_slot: u64,
+    pub earlier_price_lamports: u64,
+    /// minted (positive) or burned, in the mint's base units
+    pub supply_change: i128,
+    /// added (positive) or removed
+    pub underlying_change_lamports: i128,
+    pub price_change_lamports: i128,
+    /// see `SnapshotDelta::with_fee_mint`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<ImpliedSplit>,
+}
+
+/// the underlying change split into what deposits and unstakes moved and what the stake earned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImpliedSplit {
+    /// the supply deposits and unstakes minted or burned, at the earlier price
+    pub flow_lamports: i128,
+    /// the rest of the underlying change: rewards, less slashing and the like
+    pub reward_lamports: i128,
+}
+
+impl PriceSnapshot {
+    pub fn delta(earlier: &Self, later: &Self) -> SnapshotDelta {
+        let change = |earlier: u64, later: u64| later as i128 - earlier as i128;
+        SnapshotDelta {
+            earlier_slot: earlier.slot,
+            later_slot: later.slot,
+            earlier_price_lamports: earlier.price_lamports,
+            supply_change: change(earlier.supply, later.supply),
+            underlying_change_lamports: change(earlier.underlying_lamports, later.underlying_lamports),
+            price_change_lamports: change(earlier.price_lamports, later.price_lamports),
+            split: None,
+        }
+    }
+}
+
+impl SnapshotDelta {
+    /// splits the underlying change once the instructions between the two snapshots are known.
+    /// `fee_supply` is what was minted as protocol fees rather than for deposits, 0 when no
+    /// reward crank ran in between; the rest of the supply change is taken as flows
+    pub fn with_fee_mint(self, fee_supply: u64) -> Self {
+        let flow_supply = self.supply_change - fee_supply as i128;
+        let flow_lamports = flow_supply * self.earlier_price_lamports as i128 / LAMPORTS_PER_MSOL as i128;
+        let reward_lamports = self.underlying_change_lamports - flow_lamports;
+        Self { split: Some(ImpliedSplit { flow_lamports, reward_lamports }), ..self }
+    }
+}
+
+impl std::fmt::Display for SnapshotDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "slot {} -> {}: supply {:+}, underlying {:+} lamports, price {:+} lamports",
+            self.earlier_slot,
+            self.later_slot,
+            self.supply_change,
+            self.underlying_change_lamports,
+            self.price_change_lamports
+        )?;
+        if let Some(split) = self.split {
+            write!(f, " ({:+} flows, {:+} rewards)", split.flow_lamports, split.reward_lamports)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for PriceSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PriceSnapshot {}
+
+impl PartialOrd for PriceSnapshot {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriceSnapshot {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for PriceSnapshot {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// values one liquid staking token. object safe, so a mixed set of protocols can be held
+/// as `Vec<Box<dyn LstValueParser>>`
+pub trait LstValueParser: Send + Sync {
+    /// the token this parser values
+    fn mint(&self) -> Pubkey;
+
+    fn protocol(&self) -> Protocol;
+
+    /// the program whose instructions move the token
+    fn program_id(&self) -> Pubkey;
+
+    /// whether a tx referencing `account_keys` involves this token, as opposed to another one
+    /// run by the same program. by default, whether the mint is among them
+    fn matches_accounts(&self, account_keys: &[Pubkey]) -> bool {
+        account_keys.contains(&self.mint())
+    }
+
+    fn analyze(&self, ctx: &ParserContext<'_>, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying>;
+
+    /// the latest price the node knows
+    fn current_price(&self, ctx: &ParserContext<'_>) -> Result<PriceSnapshot>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MSOL_MINT_PUBKEY;
+    use crate::test_utils::{sample_state, FIXTURE_SLOT};
+
+    fn snapshot(slot: u64, fetched_at: UnixTimestamp) -> PriceSnapshot {
+        PriceSnapshot {
+            protocol: Protocol::Marinade,
+            mint: MSOL_MINT_PUBKEY,
+            price_lamports: 1_200_000_000,
+            underlying_lamports: 7_200_000_000_000_000,
+            supply: 6_000_000_000_000_000,
+            slot,
+            fetched_at,
+            stake_delta_lamports: None,
+            epoch: None,
+        }
+    }
+
+    #[test]
+    fn test_price_snapshot_serialization_is_stable() {
+        let json = serde_json::json!({
+            "schema_version": 1,
+            "protocol": "marinade",
+            "mint": "mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So",
+            "price_lamports": 1_200_000_000u64,
+            "underlying_lamports": 7_200_000_000_000_000u64,
+            "supply": 6_000_000_000_000_000u64,
+            "slot": FIXTURE_SLOT,
+            "fetched_at": 1_708_000_000,
+        });
+        assert_eq!(serde_json::to_value(snapshot(FIXTURE_SLOT, 1_708_000_000)).unwrap(), json);
+        let parsed: PriceSnapshot = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.fetched_at, 1_708_000_000);
+
+        let custom = PriceSnapshot { protocol: Protocol::Custom("acme".to_string()), ..parsed };
+        let value = serde_json::to_value(&custom).unwrap();
+        assert_eq!(value["protocol"], "acme");
+        assert_eq!(serde_json::from_value::<PriceSnapshot>(value).unwrap().protocol, custom.protocol);
+    }
+
+    #[test]
+    fn test_v1_price_snapshot_keeps_reading() {
+        let v1 = include_str!("../../tests/schema/v1/price_snapshot.json");
+        let parsed: PriceSnapshot = serde_json::from_str(v1).unwrap();
+        assert_eq!(parsed, snapshot(230_000_000, 0));
+        assert_eq!((parsed.fetched_at, parsed.stake_delta_lamports), (1_708_000_000, Some(-4_000_000_000)));
+        assert_eq!(parsed.epoch.map(|epoch| epoch.slot_index), Some(176_000));
+    }
+
+    #[test]
+    fn test_price_snapshot_dedup() {
+        // two polls of the same slot are the same point
+        assert_eq!(snapshot(FIXTURE_SLOT, 1), snapshot(FIXTURE_SLOT, 2));
+        let mut series = vec![snapshot(FIXTURE_SLOT + 1, 3), snapshot(FIXTURE_SLOT, 1), snapshot(FIXTURE_SLOT, 2)];
+        series.sort();
+        series.dedup();
+        assert_eq!(series.iter().map(|s| s.slot).collect::<Vec<_>>(), vec![FIXTURE_SLOT, FIXTURE_SLOT + 1]);
+        // the first poll survives
+        assert_eq!(series[0].fetched_at, 1);
+    }
+
+    #[test]
+    fn test_snapshot_deltas() {
+        let earlier = snapshot(FIXTURE_SLOT, 1);
+        assert_eq!(
+            PriceSnapshot::delta(&earlier, &earlier),
+            SnapshotDelta {
+                earlier_slot: FIXTURE_SLOT,
+                later_slot: FIXTURE_SLOT,
+                earlier_price_lamports: 1_200_000_000,
+                supply_change: 0,
+                underlying_change_lamports: 0,
+                price_change_lamports: 0,
+                split: None,
+            }
+        );
+
+        // 10 mSOL deposited and a lamport of price in rewards
+        let later = PriceSnapshot {
+            price_lamports: 1_200_000_001,
+            underlying_lamports: 7_200_000_000_000_000 + 12_000_000_000 + 6_000_010_000,
+            supply: 6_000_000_000_000_000 + 10_000_000_000,
+            ..snapshot(FIXTURE_SLOT + 10, 2)
+        };
+        let up = PriceSnapshot::delta(&earlier, &later).with_fee_mint(0);
+        assert_eq!((up.supply_change, up.underlying_change_lamports), (10_000_000_000, 18_000_010_000));
+        assert_eq!(up.price_change_lamports, 1);
+        assert_eq!(up.split, Some(ImpliedSplit { flow_lamports: 12_000_000_000, reward_lamports: 6_000_010_000 }));
+        assert_eq!(
+            up.to_string(),
+            format!(
+                "slot {} -> {}: supply +10000000000, underlying +18000010000 lamports, price +1 lamports \
+                 (+12000000000 flows, +6000010000 rewards)",
+                FIXTURE_SLOT,
+                FIXTURE_SLOT + 10
+            )
+        );
+        // minted as fees, the same supply change is no flow at all
+        assert_eq!(up.with_fee_mint(10_000_000_000).split.unwrap().reward_lamports, 18_000_010_000);
+
+        // burned by unstakes, past what u64 subtraction could hold
+        let down = PriceSnapshot::delta(&later, &earlier);
+        assert_eq!((down.supply_change, down.price_change_lamports, down.split), (-10_000_000_000, -1, None));
+        let emptied = PriceSnapshot { supply: 0, ..snapshot(2, 0) };
+        let down = PriceSnapshot::delta(&snapshot(1, 0), &emptied).with_fee_mint(0);
+        assert_eq!(down.split.unwrap().flow_lamports, -7_200_000_000_000_000);
+        assert_eq!(down.split.unwrap().reward_lamports, 7_200_000_000_000_000);
+        let json = serde_json::to_value(down).unwrap();
+        assert_eq!(json["supply_change"], -6_000_000_000_000_000i64);
+        assert_eq!(serde_json::from_value::<SnapshotDelta>(json).unwrap(), down);
+    }
+
+    #[test]
+    fn test_from_marinade_state() {
+        let state = sample_state().minimal();
+        let snapshot = PriceSnapshot::from_marinade_state(&state, FIXTURE_SLOT);
+        assert_eq!(snapshot.protocol, Protocol::Marinade);
+        assert_eq!(snapshot.mint, state.msol_mint);
+        assert_eq!(snapshot.price_lamports, state.msol_price_lamports());
+        assert_eq!(snapshot.underlying_lamports, state.total_virtual_staked_lamports());
+        assert_eq!(snapshot.supply, state.msol_supply);
+        assert_eq!(snapshot.stake_delta_lamports, Some(201_000_000_000_000));
+        assert!(snapshot.fetched_at > 0);
+    }
+}
@@ -0,0 +1,166 @@
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::parsers::detect::{detect_with, ProtocolHit};
+use crate::parsers::lido::LidoParser;
+use crate::parsers::marinade::MarinadeParser;
+use crate::parsers::spl_stake_pool::SplStakePoolParser;
+use crate::parsers::{LstValueParser, ParserContext};
+use crate::transaction::transaction_signature;
+use crate::MintUnderlying;
+use crate::verbosity::debug;
+
+/// the parsers known to a process, looked up by mint or by the protocols a transaction involves.
+/// the default holds every built-in parser at its mainnet addresses
+pub struct Registry {
+    parsers: Vec<Box<dyn LstValueParser>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Box::new(MarinadeParser::default()));
+        registry.register(Box::new(SplStakePoolParser::jito()));
+        registry.register(Box::new(SplStakePoolParser::blaze()));
+        registry.register(Box::new(LidoParser::default()));
+        registry
+    }
+}
+
+impl Registry {
+    /// a registry without any parser
+    pub fn empty() -> Self {
+        Self { parsers: Vec::new() }
+    }
+
+    /// add a parser, replacing the one already registered for its mint
+    pub fn register(&mut self, parser: Box<dyn LstValueParser>) {
+        let mint = parser.mint();
+        self.parsers.retain(|p| p.mint() != mint);
+        self.parsers.push(parser);
+    }
+
+    pub fn parsers(&self) -> impl Iterator<Item = &dyn LstValueParser> {
+        self.parsers.iter().map(|p| p.as_ref())
+    }
+
+    pub fn parser_for_mint(&self, mint: &Pubkey) -> Result<&dyn LstValueParser> {
+        self.parsers().find(|p| p.mint() == *mint).ok_or_else(|| Error::new(ErrorKind::NotSupported).with_pubkey(*mint))
+    }
+
+    /// the registered protocols `tx` involves, without any rpc call
+    pub fn detect_protocol(&self, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Vec<ProtocolHit> {
+        detect_with(self.parsers(), tx)
+    }
+
+    /// analyze `tx` with the parser of its strongest `detect_protocol` hit
+    pub fn analyze_transaction(
+        &self,
+        ctx: &ParserContext<'_>,
+        tx: &EncodedConfirmedTransactionWithStatusMeta,
+    ) -> Result<MintUnderlying> {
+        let hits = self.detect_protocol(tx);
+        let Some(hit) = hits.first() else {
+            return Err(Error::new(ErrorKind::NotSupported).with_signature(transaction_signature(tx)));
+        };
+        debug!(protocol = %hit.protocol, mint = %hit.mint, source = ?hit.source, hits = hits.len(), "dispatching transaction");
+        self.parser_for_mint(&hit.mint)?.analyze(ctx, tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::constants::{JITOSOL_MINT_PUBKEY, MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY, SOL_MINT_PUBKEY};
+    use crate::parsers::{PriceSnapshot, Protocol};
+    use crate::test_utils::{
+        marinade_transaction, sample_state, state_account, transaction_with, MockFetcher, FIXTURE_BLOCK_TIME,
+        FIXTURE_SLOT,
+    };
+    use crate::{AnalyzeOptions, BlockTimeSource};
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+
+    #[test]
+    fn test_dispatch_to_marinade() {
+        let state = sample_state();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let options = AnalyzeOptions::default();
+        let ctx = ParserContext::new(&rpc, &options);
+        let registry = Registry::default();
+
+        assert_eq!(registry.parser_for_mint(&MSOL_MINT_PUBKEY).unwrap().mint(), MSOL_MINT_PUBKEY);
+        assert_eq!(registry.parser_for_mint(&JITOSOL_MINT_PUBKEY).unwrap().mint(), JITOSOL_MINT_PUBKEY);
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let mu = registry.analyze_transaction(&ctx, &tx).unwrap();
+        assert_eq!(mu.platform_program_pubkey, MARINADE_STATE_PUBKEY.to_string());
+        assert_eq!(mu.msol_value, state.msol_price_lamports());
+    }
+
+    #[test]
+    fn test_unknown_mint_is_not_supported() {
+        let registry = Registry::default();
+        let unknown = Pubkey::new_unique();
+        let err = registry.parser_for_mint(&unknown).err().unwrap();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported));
+        assert_eq!(err.pubkey(), Some(&unknown));
+
+        let rpc = MockFetcher::new();
+        let options = AnalyzeOptions::default();
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[]);
+        let err = registry.analyze_transaction(&ParserContext::new(&rpc, &options), &tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NotSupported));
+        assert!(err.signature().is_some());
+        assert!(rpc.calls().is_empty());
+    }
+
+    /// a fixed-price token, claimed through the default mint check
+    struct Dummy(Pubkey);
+
+    impl LstValueParser for Dummy {
+        fn mint(&self) -> Pubkey {
+            self.0
+        }
+
+        fn protocol(&self) -> Protocol {
+            Protocol::Custom("dummy".to_string())
+        }
+
+        fn program_id(&self) -> Pubkey {
+            Pubkey::default()
+        }
+
+        fn analyze(&self, _: &ParserContext<'_>, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying> {
+            let (block_time, source) = (tx.block_time.unwrap_or_default(), BlockTimeSource::Transaction);
+            Ok(MintUnderlying::new(block_time, source, 2_000_000_000, &self.0, &self.0, &[SOL_MINT_PUBKEY], vec![0]).unwrap())
+        }
+
+        fn current_price(&self, _: &ParserContext<'_>) -> Result<PriceSnapshot> {
+            Ok(PriceSnapshot::new(self.protocol(), self.0, 2_000_000_000, 0, 0, FIXTURE_SLOT))
+        }
+    }
+
+    #[test]
+    fn test_custom_parser_registered_at_runtime() {
+        let mint = Pubkey::new_unique();
+        let mut registry = Registry::default();
+        let builtins = registry.parsers().count();
+        registry.register(Box::new(Dummy(mint)));
+        assert_eq!(registry.parsers().count(), builtins + 1);
+        // re-registering a mint replaces its parser
+        registry.register(Box::new(Dummy(mint)));
+        assert_eq!(registry.parsers().count(), builtins + 1);
+
+        let rpc = MockFetcher::new();
+        let options = AnalyzeOptions::default();
+        let ctx = ParserContext::new(&rpc, &options);
+        assert_eq!(registry.parser_for_mint(&mint).unwrap().current_price(&ctx).unwrap().price_lamports, 2_000_000_000);
+
+        let transfer = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![AccountMeta::new(mint, false)]);
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[transfer]);
+        let mu = registry.analyze_transaction(&ctx, &tx).unwrap();
+        assert_eq!(mu.mint_pubkey, mint.to_string());
+        assert_eq!(mu.msol_value, 2_000_000_000);
+    }
+}
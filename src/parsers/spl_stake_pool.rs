@@ -0,0 +1,138 @@
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::accounts::spl_stake_pool::{parse_stake_pool, StakePool};
+use crate::constants::{
+    BLAZE_STAKE_POOL_PUBKEY, BSOL_MINT_PUBKEY, JITOSOL_MINT_PUBKEY, JITO_STAKE_POOL_PUBKEY, SOL_MINT_PUBKEY,
+    SPL_STAKE_POOL_PROGRAM_ID,
+};
+use crate::error::{Error, ErrorKind, Result};
+use crate::parsers::{LstValueParser, ParserContext, PriceSnapshot, Protocol};
+use crate::transaction::transaction_signature;
+use crate::{fetch_account_data, resolve_block_time, MintUnderlying};
+use crate::verbosity::debug;
+
+const ROLE: &str = "stake pool";
+
+/// any spl stake pool, identified by its pool account and pool token mint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplStakePoolParser {
+    pool: Pubkey,
+    mint: Pubkey,
+}
+
+impl SplStakePoolParser {
+    pub fn new(pool: Pubkey, mint: Pubkey) -> Self {
+        Self { pool, mint }
+    }
+
+    /// jitoSOL
+    pub fn jito() -> Self {
+        Self::new(JITO_STAKE_POOL_PUBKEY, JITOSOL_MINT_PUBKEY)
+    }
+
+    /// bSOL
+    pub fn blaze() -> Self {
+        Self::new(BLAZE_STAKE_POOL_PUBKEY, BSOL_MINT_PUBKEY)
+    }
+
+    pub fn pool(&self) -> Pubkey {
+        self.pool
+    }
+
+    /// fetch and parse the pool account, checking it really is the pool of `mint`
+    pub fn fetch_pool(&self, ctx: &ParserContext<'_>, slot: Option<u64>) -> Result<(u64, StakePool)> {
+        let (context_slot, data) = fetch_account_data(ctx.rpc_client, &self.pool, ROLE, slot, ctx.options)?;
+        let invalid = |source| Error::new(ErrorKind::InvalidAccountData { role: ROLE, source }).with_pubkey(self.pool).with_slot(slot);
+        let pool = parse_stake_pool(&data).map_err(invalid)?;
+        if pool.pool_mint != self.mint {
+            return Err(invalid(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("pool mint is {}, expected {}", pool.pool_mint, self.mint),
+            )));
+        }
+        Ok((context_slot, pool))
+    }
+}
+
+impl LstValueParser for SplStakePoolParser {
+    fn mint(&self) -> Pubkey {
+        self.mint
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::SplStakePool
+    }
+
+    fn program_id(&self) -> Pubkey {
+        SPL_STAKE_POOL_PROGRAM_ID
+    }
+
+    /// the program is shared by every pool, so go by the pool account
+    fn matches_accounts(&self, account_keys: &[Pubkey]) -> bool {
+        account_keys.contains(&self.pool) || account_keys.contains(&self.mint)
+    }
+
+    fn analyze(&self, ctx: &ParserContext<'_>, tx: &EncodedConfirmedTransactionWithStatusMeta) -> Result<MintUnderlying> {
+        let with_signature = |e: Error| e.with_signature(transaction_signature(tx));
+        let (_, pool) = self.fetch_pool(ctx, Some(tx.slot)).map_err(with_signature)?;
+        let (block_time, block_time_source) = resolve_block_time(ctx.rpc_client, tx.into(), ctx.options).map_err(with_signature)?;
+        let price = pool.price_lamports();
+        debug!(pool = %self.pool, price, total_lamports = pool.total_lamports, "stake pool analysis complete");
+        let amounts = vec![pool.total_lamports];
+        Ok(MintUnderlying::new(block_time, block_time_source, price, &self.mint, &self.pool, &[SOL_MINT_PUBKEY], amounts)
+            .expect("one mint, one amount"))
+    }
+
+    fn current_price(&self, ctx: &ParserContext<'_>) -> Result<PriceSnapshot> {
+        let (slot, pool) = self.fetch_pool(ctx, None)?;
+        Ok(PriceSnapshot::new(
+            Protocol::SplStakePool,
+            self.mint,
+            pool.price_lamports(),
+            pool.total_lamports,
+            pool.pool_token_supply,
+            slot,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sample_stake_pool, sample_transaction, stake_pool_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::AnalyzeOptions;
+
+    #[test]
+    fn test_presets_through_the_trait() {
+        let jito = SplStakePoolParser::jito();
+        let blaze = SplStakePoolParser::blaze();
+        let rpc = MockFetcher::new()
+            .with_account(jito.pool(), stake_pool_account(&sample_stake_pool(JITOSOL_MINT_PUBKEY)))
+            .with_account(blaze.pool(), stake_pool_account(&sample_stake_pool(BSOL_MINT_PUBKEY)));
+        let options = AnalyzeOptions::default();
+        let ctx = ParserContext::new(&rpc, &options);
+        let tx = sample_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME));
+
+        let parsers: Vec<Box<dyn LstValueParser>> = vec![Box::new(jito), Box::new(blaze)];
+        for parser in &parsers {
+            let mu = parser.analyze(&ctx, &tx).unwrap();
+            assert_eq!(mu.mint_pubkey, parser.mint().to_string());
+            assert_eq!(mu.msol_value, 1_100_000_000);
+            assert_eq!(mu.underlyings().unwrap(), [(SOL_MINT_PUBKEY, 7_700_000_000_000_000)]);
+            assert_eq!(mu.block_time, FIXTURE_BLOCK_TIME);
+            assert_eq!(parser.current_price(&ctx).unwrap().price_lamports, 1_100_000_000);
+        }
+    }
+
+    #[test]
+    fn test_wrong_pool_mint_is_rejected() {
+        let jito = SplStakePoolParser::jito();
+        let rpc = MockFetcher::new().with_account(jito.pool(), stake_pool_account(&sample_stake_pool(BSOL_MINT_PUBKEY)));
+        let options = AnalyzeOptions::default();
+
+        let err = jito.current_price(&ParserContext::new(&rpc, &options)).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAccountData { role: "stake pool", .. }));
+        assert_eq!(err.pubkey(), Some(&JITO_STAKE_POOL_PUBKEY));
+    }
+}
@@ -0,0 +1,472 @@
+//! a long-running pipeline: signatures or transactions go in through a channel, analyzed
+//! records come out of another, with fetching, per-slot state caching and retries of failed
+//! rpc calls done by a pool of worker threads in between.
+//!
+//! records come out in the order workers finish them, not the order items went in. see
+//! `Pipeline::shutdown` for stopping it without waiting for everything queued
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use solana_sdk::clock::Slot;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+use crate::batch::{analyze_fetched, SlotStates};
+use crate::client::MarinadeClient;
+use crate::error::{Result, RpcFailureKind};
+use crate::events::MarinadeEvent;
+use crate::flows::{transaction_flows, Flow};
+use crate::shutdown::{ShutdownHandle, ShutdownSummary};
+use crate::transaction::transaction_signature;
+use crate::treasury::ProtocolRevenue;
+use crate::MintUnderlying;
+use crate::verbosity::{debug, warn};
+
+/// what goes into a `Pipeline`
+#[derive(Debug)]
+pub enum PipelineItem {
+    /// fetched by the pipeline
+    Signature(Signature),
+    /// already fetched, e.g. by a subscription
+    Transaction(Box<EncodedConfirmedTransactionWithStatusMeta>),
+}
+
+impl From<Signature> for PipelineItem {
+    fn from(signature: Signature) -> Self {
+        Self::Signature(signature)
+    }
+}
+
+impl From<EncodedConfirmedTransactionWithStatusMeta> for PipelineItem {
+    fn from(tx: EncodedConfirmedTransactionWithStatusMeta) -> Self {
+        Self::Transaction(Box::new(tx))
+    }
+}
+
+/// what comes out of a `Pipeline` for an item that was analyzed
+#[derive(Debug, Clone)]
+pub struct PipelineRecord {
+    /// the one submitted, or the transaction's. None for a transaction that doesn't decode far
+    /// enough to tell
+    pub signature: Option<Signature>,
+    pub slot: Slot,
+    pub underlying: MintUnderlying,
+    pub flows: Vec<Flow>,
+    /// classified and resolved like `EventClassifier::classify` does, so e.g. a `pause` comes
+    /// out as soon as its transaction is through. resolving is the only fetch it adds
+    pub events: Vec<MarinadeEvent>,
+    /// see `TreasuryAnalyzer::revenue`
+    pub protocol_revenue: Option<ProtocolRevenue>,
+}
+
+pub struct PipelineBuilder {
+    client: MarinadeClient,
+    workers: usize,
+    input_buffer: usize,
+    output_buffer: usize,
+    retries: u32,
+    retry_backoff: Duration,
+    state_slots: usize,
+    wait_for_slot: Option<Duration>,
+    shutdown: ShutdownHandle,
+}
+
+// how often an idle worker looks at the shutdown handle
+const SHUTDOWN_POLL: Duration = Duration::from_millis(50);
+
+/// what the workers got through, for `Pipeline::shutdown`
+#[derive(Default)]
+struct Progress {
+    completed: AtomicUsize,
+    in_flight: AtomicUsize,
+    /// taken off the queue after the shutdown was triggered, and dropped
+    dropped: AtomicUsize,
+}
+
+impl PipelineBuilder {
+    /// threads fetching and analyzing, 4 by default
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// items that can wait for a worker before `submit` blocks, 64 by default
+    pub fn input_buffer(mut self, input_buffer: usize) -> Self {
+        self.input_buffer = input_buffer;
+        self
+    }
+
+    /// results that can wait for the reader before workers block, 64 by default
+    pub fn output_buffer(mut self, output_buffer: usize) -> Self {
+        self.output_buffer = output_buffer;
+        self
+    }
+
+    /// times an item is tried again after a transient rpc error, waiting `backoff` and doubling
+    /// it in between. 3 times from 250ms by default; other errors, including rpc ones that would
+    /// fail the same way again, are never retried
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// distinct slots whose state is kept for reuse, the most recent ones. 1024 by default
+    pub fn state_slots(mut self, state_slots: usize) -> Self {
+        self.state_slots = state_slots;
+        self
+    }
+
+    /// how long to wait for a node that hasn't reached a transaction's slot yet, see
+    /// `AnalyzeOptions::wait_for_slot`. 10s by default, since items tend to be fresh off the chain;
+    /// None fails them right away
+    pub fn wait_for_slot(mut self, deadline: Option<Duration>) -> Self {
+        self.wait_for_slot = deadline;
+        self
+    }
+
+    /// stop when `handle` is triggered, e.g. from a signal handler, instead of only through
+    /// `Pipeline::shutdown`, which otherwise uses a handle of the pipeline's own
+    pub fn shutdown(mut self, handle: ShutdownHandle) -> Self {
+        self.shutdown = handle;
+        self
+    }
+
+    /// start the workers
+    pub fn build(self) -> Pipeline {
+        let client = self.client.with_slot_wait(self.wait_for_slot);
+        let (input, items) = mpsc::sync_channel::<PipelineItem>(self.input_buffer);
+        let (results, output) = mpsc::sync_channel(self.output_buffer);
+        let items = Arc::new(Mutex::new(items));
+        let states = Arc::new(SlotStates::new(self.state_slots));
+        let progress = Arc::new(Progress::default());
+        let workers = (0..self.workers)
+            .map(|_| {
+                let (client, items, results) = (client.clone(), items.clone(), results.clone());
+                let (states, progress, shutdown) = (states.clone(), progress.clone(), self.shutdown.clone());
+                let (retries, retry_backoff) = (self.retries, self.retry_backoff);
+                std::thread::spawn(move || loop {
+                    // the lock is only held while waiting for the next item
+                    let item = items.lock().unwrap().recv_timeout(SHUTDOWN_POLL);
+                    let item = match item {
+                        Ok(item) => item,
+                        Err(RecvTimeoutError::Timeout) if shutdown.is_triggered() => break,
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    };
+                    // what's still queued once triggered is only taken off to be counted
+                    if shutdown.is_triggered() {
+                        progress.dropped.fetch_add(1, Ordering::SeqCst);
+                        continue;
+                    }
+                    progress.in_flight.fetch_add(1, Ordering::SeqCst);
+                    let result = process(&client, &states, item, retries, retry_backoff, &shutdown);
+                    progress.completed.fetch_add(1, Ordering::SeqCst);
+                    progress.in_flight.fetch_sub(1, Ordering::SeqCst);
+                    if results.send(result).is_err() {
+                        debug!("pipeline output dropped, worker exiting");
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Pipeline { input, output, workers, items, progress, shutdown: self.shutdown }
+    }
+}
+
+/// see the module docs. dropping it without `finish` lets the workers run out in the background
+pub struct Pipeline {
+    input: SyncSender<PipelineItem>,
+    output: Receiver<Result<PipelineRecord>>,
+    workers: Vec<JoinHandle<()>>,
+    items: Arc<Mutex<Receiver<PipelineItem>>>,
+    progress: Arc<Progress>,
+    shutdown: ShutdownHandle,
+}
+
+impl Pipeline {
+    pub fn builder(client: MarinadeClient) -> PipelineBuilder {
+        PipelineBuilder {
+            client,
+            workers: 4,
+            input_buffer: 64,
+            output_buffer: 64,
+            retries: 3,
+            retry_backoff: Duration::from_millis(250),
+            state_slots: 1024,
+            wait_for_slot: Some(Duration::from_secs(10)),
+            shutdown: ShutdownHandle::new(),
+        }
+    }
+
+    /// queue `item`, blocking while the input buffer is full. hands the item back if every
+    /// worker is gone or the shutdown was triggered
+    pub fn submit(&self, item: impl Into<PipelineItem>) -> std::result::Result<(), PipelineItem> {
+        if self.shutdown.is_triggered() {
+            return Err(item.into());
+        }
+        self.input.send(item.into()).map_err(|e| e.0)
+    }
+
+    /// queue `item` only if the input buffer has room, `Disconnected` once the shutdown was
+    /// triggered
+    pub fn try_submit(&self, item: impl Into<PipelineItem>) -> std::result::Result<(), TrySendError<PipelineItem>> {
+        if self.shutdown.is_triggered() {
+            return Err(TrySendError::Disconnected(item.into()));
+        }
+        self.input.try_send(item.into())
+    }
+
+    /// another handle for feeding the pipeline, e.g. from a source thread. what it queues after
+    /// the shutdown was triggered is dropped, not processed
+    pub fn sender(&self) -> SyncSender<PipelineItem> {
+        self.input.clone()
+    }
+
+    /// the results as they are ready
+    pub fn results(&self) -> &Receiver<Result<PipelineRecord>> {
+        &self.output
+    }
+
+    /// stop taking items and return the results of everything still queued or in flight, once
+    /// the workers are done with it. handles from `sender` must be dropped as well, or this
+    /// waits for them
+    pub fn finish(self) -> Vec<Result<PipelineRecord>> {
+        let Self { input, output, workers, .. } = self;
+        drop(input);
+        let drained: Vec<_> = output.iter().collect();
+        for worker in workers {
+            if worker.join().is_err() {
+                warn!("pipeline worker panicked");
+            }
+        }
+        drained
+    }
+
+    /// trigger the shutdown handle and return the results the workers finish within its grace
+    /// period. queued items are dropped, a retry waiting out its backoff gives up with its
+    /// error, and items still in flight at the deadline are abandoned, their workers left to run
+    /// out in the background. the summary counts every item since the pipeline started
+    pub fn shutdown(self) -> (Vec<Result<PipelineRecord>>, ShutdownSummary) {
+        let Self { input, output, workers, items, progress, shutdown } = self;
+        drop(input);
+        shutdown.trigger();
+        let deadline = shutdown.deadline().expect("triggered");
+        let mut drained = Vec::new();
+        loop {
+            match output.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(result) => drained.push(result),
+                Err(RecvTimeoutError::Timeout) => {
+                    warn!("pipeline grace period ran out with items in flight");
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    for worker in workers {
+                        if worker.join().is_err() {
+                            warn!("pipeline worker panicked");
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        // left queued by workers that didn't get back to the queue in time
+        let queued = items.lock().map_or(0, |items| items.try_iter().count());
+        let completed = progress.completed.load(Ordering::SeqCst);
+        let abandoned = progress.dropped.load(Ordering::SeqCst) + progress.in_flight.load(Ordering::SeqCst) + queued;
+        debug!(completed, abandoned, "pipeline shut down");
+        (drained, ShutdownSummary { completed, abandoned })
+    }
+}
+
+fn process(
+    client: &MarinadeClient,
+    states: &SlotStates,
+    item: PipelineItem,
+    retries: u32,
+    retry_backoff: Duration,
+    shutdown: &ShutdownHandle,
+) -> Result<PipelineRecord> {
+    let mut attempt = 0;
+    loop {
+        let result = match &item {
+            PipelineItem::Signature(signature) => {
+                let record = client.fetch_transaction(signature).and_then(|tx| analyze(client, states, &tx));
+                record.map(|record| PipelineRecord { signature: Some(*signature), ..record })
+            }
+            PipelineItem::Transaction(tx) => analyze(client, states, tx),
+        };
+        match result {
+            Err(e) if e.rpc_failure() == Some(RpcFailureKind::Transient) && attempt < retries => {
+                let backoff = retry_backoff.saturating_mul(1 << attempt.min(16));
+                warn!(error = %e, attempt, ?backoff, "pipeline item failed, retrying");
+                if !shutdown.sleep(backoff) {
+                    return Err(e);
+                }
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn analyze(
+    client: &MarinadeClient,
+    states: &SlotStates,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<PipelineRecord> {
+    let underlying = analyze_fetched(client, states, tx)?;
+    let flows = transaction_flows(tx, &client.deployment().program_id);
+    let events = client.event_classifier().classify(tx)?;
+    let protocol_revenue = client.treasury_analyzer().revenue(tx)?;
+    let signature = transaction_signature(tx);
+    Ok(PipelineRecord { signature, slot: tx.slot, underlying, flows, events, protocol_revenue })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use std::collections::HashSet;
+
+    fn tx(i: u64) -> EncodedConfirmedTransactionWithStatusMeta {
+        let block_time = Some(FIXTURE_BLOCK_TIME + i as i64);
+        marinade_transaction(FIXTURE_SLOT + i % 5, block_time, &[MarinadeFinanceInstruction::Deposit])
+    }
+
+    #[test]
+    fn test_hundreds_of_items_through_the_pipeline() {
+        let signatures: Vec<Signature> = (0..200).map(|_| Signature::new_unique()).collect();
+        let mut rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        for (i, signature) in signatures.iter().enumerate() {
+            rpc = rpc.with_transaction(*signature, tx(i as u64));
+        }
+        let rpc = Arc::new(rpc);
+        rpc.fail_next("getTransaction", 2);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let retry_backoff = Duration::from_millis(1);
+        let pipeline = Pipeline::builder(client).workers(4).input_buffer(8).output_buffer(8).retries(3, retry_backoff).build();
+
+        let sender = pipeline.sender();
+        let source = signatures.clone();
+        let producer = std::thread::spawn(move || {
+            for (i, signature) in source.into_iter().enumerate() {
+                sender.send(signature.into()).unwrap();
+                sender.send(tx(200 + i as u64 % 100).into()).unwrap();
+            }
+        });
+        // read live while the source is still producing, then shut down and drain the rest
+        let mut results: Vec<_> = pipeline.results().iter().take(100).collect();
+        results.extend(pipeline.finish());
+        producer.join().unwrap();
+
+        assert_eq!(results.len(), 400);
+        let records: Vec<PipelineRecord> = results.into_iter().map(Result::unwrap).collect();
+        let fetched: HashSet<Signature> =
+            records.iter().filter_map(|r| r.signature).filter(|s| *s != Signature::default()).collect();
+        assert_eq!(fetched, signatures.iter().copied().collect());
+        assert!(records.iter().all(|r| r.underlying.msol_value == sample_state().msol_price_lamports()));
+        // five slots, so five state fetches between all the workers
+        assert_eq!(rpc.call_count("getAccountInfo"), 5);
+        assert_eq!(rpc.call_count("getTransaction"), 202);
+    }
+
+    #[test]
+    fn test_finish_drains_queued_items() {
+        let rpc = Arc::new(MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state())));
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let pipeline = Pipeline::builder(client).workers(2).input_buffer(16).retries(3, Duration::ZERO).build();
+        for i in 0..10 {
+            pipeline.submit(tx(i)).unwrap();
+        }
+        // a transaction the node doesn't have isn't retried
+        pipeline.submit(Signature::new_unique()).unwrap();
+        let results = pipeline.finish();
+        assert_eq!(results.len(), 11);
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 10);
+        assert_eq!(rpc.call_count("getTransaction"), 1);
+    }
+
+    #[test]
+    fn test_pause_comes_out_as_an_event() {
+        use crate::test_utils::{marinade_call, transaction_with};
+
+        let mut state = sample_state();
+        state.paused = true;
+        let rpc = Arc::new(MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state)));
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let pipeline = Pipeline::builder(client).workers(1).build();
+        pipeline.submit(tx(0)).unwrap();
+        let ix = marinade_call(MarinadeFinanceInstruction::Pause, (), &[("pause_authority", state.pause_authority)]);
+        pipeline.submit(transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[ix])).unwrap();
+        let mut records: Vec<PipelineRecord> = pipeline.finish().into_iter().map(Result::unwrap).collect();
+        records.sort_by_key(|record| record.events.len());
+
+        assert!(records[0].events.is_empty());
+        let [MarinadeEvent::Pause(pause)] = records[1].events.as_slice() else {
+            panic!("expected a pause, got {:?}", records[1].events);
+        };
+        assert_eq!(pause.verified(), Some(true));
+    }
+
+    #[test]
+    fn test_waits_for_a_lagging_node() {
+        let rpc = Arc::new(
+            MockFetcher::new()
+                .with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()))
+                .with_node_slots([FIXTURE_SLOT - 1, FIXTURE_SLOT]),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let pipeline = Pipeline::builder(client).workers(1).retries(0, Duration::ZERO).build();
+        pipeline.submit(tx(0)).unwrap();
+        assert!(pipeline.finish()[0].is_ok());
+        assert_eq!(rpc.calls(), ["getAccountInfo", "getSlot", "getAccountInfo"]);
+    }
+
+    /// a pipeline of `workers` over `len` signatures, each fetch taking `latency`
+    fn slow_pipeline(len: usize, workers: usize, latency: Duration, grace: Duration) -> (Pipeline, ShutdownHandle) {
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let mut rpc = rpc.with_latency(latency);
+        let signatures: Vec<Signature> = (0..len).map(|_| Signature::new_unique()).collect();
+        for (i, signature) in signatures.iter().enumerate() {
+            rpc = rpc.with_transaction(*signature, tx(i as u64));
+        }
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let handle = ShutdownHandle::with_grace_period(grace);
+        let pipeline = Pipeline::builder(client).workers(workers).input_buffer(len).shutdown(handle.clone()).build();
+        for signature in signatures {
+            pipeline.submit(signature).unwrap();
+        }
+        (pipeline, handle)
+    }
+
+    #[test]
+    fn test_shutdown_drains_in_flight_and_drops_the_queue() {
+        let (pipeline, handle) = slow_pipeline(20, 2, Duration::from_millis(10), Duration::from_secs(10));
+        let first: Vec<_> = pipeline.results().iter().take(3).collect();
+        handle.trigger();
+        assert!(pipeline.submit(tx(0)).is_err());
+        assert!(matches!(pipeline.try_submit(tx(0)), Err(TrySendError::Disconnected(_))));
+
+        let (rest, summary) = pipeline.shutdown();
+        assert!(first.iter().chain(&rest).all(Result::is_ok));
+        assert_eq!(summary.completed, first.len() + rest.len());
+        assert_eq!(summary.completed + summary.abandoned, 20);
+        assert!(summary.abandoned > 0, "{:?}", summary);
+
+        // a worker still fetching at the deadline is abandoned with the queue behind it
+        let (pipeline, _) = slow_pipeline(3, 1, Duration::from_millis(400), Duration::from_millis(20));
+        std::thread::sleep(Duration::from_millis(50));
+        let started = Instant::now();
+        let (results, summary) = pipeline.shutdown();
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert!(results.is_empty());
+        assert_eq!(summary, ShutdownSummary { completed: 0, abandoned: 3 });
+    }
+}
@@ -0,0 +1,236 @@
+//! a wallet's mSOL valued at a series of past points, e.g. for tax reporting. prices come from
+//! the client, so historical ones need its snapshot cache, `offline` for states the node no
+//! longer serves. the node only knows current balances too: a historical point values the
+//! balance the caller supplies for it, and only `HistoryPoint::Latest` can fetch its own
+
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token::state::Account as TokenAccount;
+use solana_sdk::clock::{Epoch, Slot, UnixTimestamp};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+use tracing::instrument;
+
+use crate::accounts::marinade::msol_to_sol;
+use crate::client::MarinadeClient;
+use crate::error::{Error, ErrorKind, Result};
+use crate::valuation::{fetch_multiple_accounts, ConsistentSnapshot};
+use crate::verbosity::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistoryPoint {
+    Slot(Slot),
+    /// the epoch's first slot, by mainnet's schedule
+    Epoch(Epoch),
+    /// wherever the node, or offline the snapshot cache, is
+    Latest,
+}
+
+impl HistoryPoint {
+    /// the slot to price at, None for the latest
+    pub fn slot(&self) -> Option<Slot> {
+        match *self {
+            Self::Slot(slot) => Some(slot),
+            Self::Epoch(epoch) => Some(EpochSchedule::without_warmup().get_first_slot_in_epoch(epoch)),
+            Self::Latest => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceSource {
+    SnapshotCache,
+    Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BalanceSource {
+    Supplied,
+    /// the owner's associated mSOL token account, as the node has it now
+    Fetched,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointValuation {
+    /// the slot of the state the price came from, at or after the point's
+    pub price_slot: Slot,
+    /// when `price_slot` was produced, if the node could say; not looked up offline
+    pub block_time: Option<UnixTimestamp>,
+    pub msol_amount: u64,
+    pub price_lamports: u64,
+    /// `msol_amount` in lamports, rounded down the way the program does
+    pub value_lamports: u64,
+    pub price_source: PriceSource,
+    pub balance_source: BalanceSource,
+}
+
+impl PointValuation {
+    /// `msol_amount` at the price of a snapshot already read, e.g. to value a wallet next to the
+    /// LP price and TVL of the same slot
+    pub fn from_snapshot(snapshot: &ConsistentSnapshot, msol_amount: u64) -> Self {
+        let minimal = snapshot.state.minimal();
+        Self {
+            price_slot: snapshot.slot,
+            block_time: None,
+            msol_amount,
+            price_lamports: minimal.msol_price_lamports(),
+            value_lamports: minimal.msol_to_sol(msol_amount),
+            price_source: PriceSource::Node,
+            balance_source: BalanceSource::Supplied,
+        }
+    }
+}
+
+/// one point of the series, valued or with what kept it from being
+#[derive(Debug)]
+pub struct PortfolioPoint {
+    pub point: HistoryPoint,
+    pub valuation: Result<PointValuation>,
+}
+
+/// `owner`'s mSOL at each of `points`, with the balance in mSOL base units when the caller
+/// knows it. a point that can't be valued gets its own error. the mSOL mint is verified first
+/// when online, see `MarinadeClient::verify_msol_mint`, and a failure there fails the lot
+#[instrument(level = "debug", skip(client, points), fields(owner = %owner, points = points.len()))]
+pub fn portfolio_history(
+    client: &MarinadeClient,
+    owner: &Pubkey,
+    points: &[(HistoryPoint, Option<u64>)],
+) -> Result<Vec<PortfolioPoint>> {
+    let offline = client.options().offline;
+    if !offline {
+        client.verify_msol_mint()?;
+    }
+    let series: Vec<_> = points
+        .iter()
+        .map(|&(point, balance)| PortfolioPoint { point, valuation: value_point(client, owner, point, balance) })
+        .collect();
+    debug!(valued = series.iter().filter(|point| point.valuation.is_ok()).count(), "portfolio history valued");
+    Ok(series)
+}
+
+fn value_point(
+    client: &MarinadeClient,
+    owner: &Pubkey,
+    point: HistoryPoint,
+    balance: Option<u64>,
+) -> Result<PointValuation> {
+    let offline = client.options().offline;
+    let (msol_amount, balance_source) = match (balance, point) {
+        (Some(amount), _) => (amount, BalanceSource::Supplied),
+        (None, HistoryPoint::Latest) if !offline => (fetch_msol_balance(client, owner)?, BalanceSource::Fetched),
+        (None, _) => return Err(Error::new(ErrorKind::NoHistoricalBalance { slot: point.slot() }).with_pubkey(*owner)),
+    };
+    let snapshot = client.price(point.slot())?;
+    let block_time = match offline {
+        true => None,
+        false => client.rpc_client().get_block_time(snapshot.slot).ok(),
+    };
+    Ok(PointValuation {
+        price_slot: snapshot.slot,
+        block_time,
+        msol_amount,
+        price_lamports: snapshot.price_lamports,
+        value_lamports: msol_to_sol(msol_amount, snapshot.underlying_lamports, snapshot.supply),
+        price_source: if offline { PriceSource::SnapshotCache } else { PriceSource::Node },
+        balance_source,
+    })
+}
+
+/// the owner's associated token account balance; no account holds nothing
+fn fetch_msol_balance(client: &MarinadeClient, owner: &Pubkey) -> Result<u64> {
+    let address = get_associated_token_address(owner, &client.deployment().msol_mint);
+    let role = "owner's msol token account";
+    match fetch_multiple_accounts(client.rpc_client(), &[address], &[role], None, client.options()) {
+        Ok((_, accounts)) => TokenAccount::unpack(&accounts[0].data).map(|account| account.amount).map_err(|e| {
+            let source = std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string());
+            Error::new(ErrorKind::InvalidAccountData { role, source }).with_pubkey(address)
+        }),
+        Err(e) if matches!(e.kind(), ErrorKind::AccountNotFound { .. }) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::cache::SnapshotCache;
+    use crate::cluster::Cluster;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::deployment::DeploymentConfig;
+    use crate::snapshot::SnapshotMetadata;
+    use crate::test_utils::{msol_mint_account, sample_state, state_account, token_account, MockFetcher, FIXTURE_SLOT};
+
+    const MSOL: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_partial_history_from_the_snapshot_cache() {
+        let dir = std::env::temp_dir().join(format!("marinade-portfolio-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = SnapshotCache::new(&dir, Cluster::MainnetBeta);
+        let early = sample_state();
+        // the same underlying behind a fifth less supply, a quarter more per mSOL
+        let late = MarinadeState { msol_supply: early.msol_supply / 5 * 4, ..sample_state() };
+        for (slot, state) in [(1_000, &early), (2_000, &late)] {
+            cache.put(state, &SnapshotMetadata::new(&Cluster::MainnetBeta, MARINADE_STATE_PUBKEY, slot)).unwrap();
+        }
+        let client =
+            MarinadeClient::builder().rpc_client(MockFetcher::new()).snapshot_cache(&dir).offline(true).build();
+
+        let points = [
+            (HistoryPoint::Slot(500), Some(10 * MSOL)),
+            (HistoryPoint::Slot(1_500), Some(10 * MSOL)),
+            (HistoryPoint::Slot(1_800), None),
+            (HistoryPoint::Slot(2_500), Some(10 * MSOL)),
+        ];
+        let series = portfolio_history(&client, &Pubkey::new_unique(), &points).unwrap();
+        let valued: Vec<_> = series.iter().map(|point| point.valuation.as_ref().ok().map(|v| v.price_slot)).collect();
+        assert_eq!(valued, vec![Some(1_000), Some(2_000), None, None]);
+
+        let first = series[0].valuation.as_ref().unwrap();
+        let value =
+            |state: &MarinadeState| msol_to_sol(10 * MSOL, state.total_virtual_staked_lamports(), state.msol_supply);
+        assert_eq!((first.price_lamports, first.value_lamports), (early.msol_price_lamports(), value(&early)));
+        assert_eq!((first.price_source, first.balance_source), (PriceSource::SnapshotCache, BalanceSource::Supplied));
+        assert_eq!(first.block_time, None);
+        let second = series[1].valuation.as_ref().unwrap();
+        assert_eq!(second.value_lamports, value(&late));
+        assert!(second.price_lamports > first.price_lamports);
+        let missing = series[2].valuation.as_ref().unwrap_err();
+        assert!(matches!(missing.kind(), ErrorKind::NoHistoricalBalance { slot: Some(1_800) }));
+        let beyond = series[3].valuation.as_ref().unwrap_err();
+        assert!(matches!(beyond.kind(), ErrorKind::OfflineMiss { slot: Some(2_500) }));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_latest_balance_is_fetched() {
+        let deployment = DeploymentConfig::MAINNET;
+        let (owner, state) = (Pubkey::new_unique(), sample_state());
+        let rpc = MockFetcher::new()
+            .with_account(deployment.state, state_account(&state))
+            .with_account(deployment.msol_mint, msol_mint_account(&deployment, state.msol_supply))
+            .with_account(
+                get_associated_token_address(&owner, &deployment.msol_mint),
+                token_account(deployment.msol_mint, 3 * MSOL),
+            );
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+
+        let points =
+            [(HistoryPoint::Latest, None), (HistoryPoint::Latest, Some(MSOL)), (HistoryPoint::Epoch(600), None)];
+        let series = portfolio_history(&client, &owner, &points).unwrap();
+        let fetched = series[0].valuation.as_ref().unwrap();
+        assert_eq!((fetched.msol_amount, fetched.balance_source), (3 * MSOL, BalanceSource::Fetched));
+        assert_eq!((fetched.price_slot, fetched.price_source), (FIXTURE_SLOT, PriceSource::Node));
+        assert_eq!(series[1].valuation.as_ref().unwrap().balance_source, BalanceSource::Supplied);
+        assert!(matches!(
+            series[2].valuation.as_ref().unwrap_err().kind(),
+            ErrorKind::NoHistoricalBalance { slot: Some(slot) } if *slot == 600 * 432_000
+        ));
+
+        // an owner without a token account holds nothing
+        let empty = portfolio_history(&client, &Pubkey::new_unique(), &points[..1]).unwrap();
+        assert_eq!(empty[0].valuation.as_ref().unwrap().value_lamports, 0);
+    }
+}
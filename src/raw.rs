@@ -0,0 +1,101 @@
+//! the bytes behind a parsed state, for when the parser and the chain disagree.
+//! `MarinadeClient::raw_state` returns the state account as the node served it, alongside the
+//! state parsed from it, and `RawAccount::write_fixture` saves the account as the `getAccountInfo`
+//! response a replaying `RpcFetcher` serves back. that's a read of its own: the usual fetches
+//! drop the bytes once they're parsed, with or without this module
+
+use std::io;
+use std::path::Path;
+
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::rpc_response::{Response, RpcResponseContext};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::marinade::MarinadeState;
+
+/// an account as one read returned it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAccount {
+    pub pubkey: Pubkey,
+    /// the slot the node read the account at
+    pub context_slot: u64,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+impl RawAccount {
+    pub fn new(pubkey: Pubkey, context_slot: u64, account: Account) -> Self {
+        let Account { lamports, data, owner, executable, rent_epoch } = account;
+        Self { pubkey, context_slot, owner, lamports, data, executable, rent_epoch }
+    }
+
+    /// the account as an `RpcFetcher` hands it out
+    pub fn account(&self) -> Account {
+        Account {
+            lamports: self.lamports,
+            data: self.data.clone(),
+            owner: self.owner,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+        }
+    }
+
+    /// write the account to `path` as the json of a base64 `getAccountInfo` result, context slot
+    /// included
+    pub fn write_fixture(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let account = AccountSharedData::from(self.account());
+        let value = UiAccount::encode(&self.pubkey, &account, UiAccountEncoding::Base64, None, None);
+        let response = Response { context: RpcResponseContext { slot: self.context_slot, api_version: None }, value };
+        std::fs::write(path, serde_json::to_string_pretty(&response)?)
+    }
+
+    /// read a file written by `write_fixture`, or any saved `getAccountInfo` result of `pubkey`
+    /// in a binary encoding
+    pub fn read_fixture(path: impl AsRef<Path>, pubkey: Pubkey) -> io::Result<Self> {
+        let response: Response<UiAccount> = serde_json::from_slice(&std::fs::read(path)?)?;
+        let account = response.value.decode::<Account>().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "account data isn't in a binary encoding")
+        })?;
+        Ok(Self::new(pubkey, response.context.slot, account))
+    }
+}
+
+/// a state along with the account it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawState {
+    pub state: MarinadeState,
+    pub raw: RawAccount,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::{parse_marinade_state_with, ParseMode};
+    use crate::client::MarinadeClient;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{sample_state, state_account, MockFetcher, FIXTURE_SLOT};
+
+    #[test]
+    fn test_captured_bytes_reparse_and_replay() {
+        let account = state_account(&sample_state());
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, account.clone());
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let RawState { state, raw } = client.raw_state(Some(FIXTURE_SLOT)).unwrap();
+        assert_eq!(parse_marinade_state_with(&raw.data, ParseMode::Strict).unwrap().state, state);
+        assert_eq!((raw.pubkey, raw.context_slot, raw.lamports), (MARINADE_STATE_PUBKEY, FIXTURE_SLOT, 1_000_000_000));
+        assert_eq!(raw.account(), account);
+
+        let path = std::env::temp_dir().join(format!("marinade-raw-state-{}.json", std::process::id()));
+        raw.write_fixture(&path).unwrap();
+        let replayed = RawAccount::read_fixture(&path, MARINADE_STATE_PUBKEY).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replayed, raw);
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, replayed.account());
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        assert_eq!(client.raw_state(None).unwrap().state, state);
+    }
+}
@@ -0,0 +1,319 @@
+//! borsh encoding of the output structs, for storing them next to account data.
+//!
+//! every record starts with a version byte, followed by its fields in the order listed in the
+//! `versioned_record!` invocations below. the lists are the format: fields are only ever
+//! appended, in a `since N { .. }` block with the version bumped to N, and never reordered or
+//! removed. a record written before a field was appended still deserializes, with that field's
+//! `Default`. records nest, each carrying its own version byte
+
+use std::io::{self, Read, Write};
+
+use anchor_lang::prelude::borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::accounts::stake::StakeBalances;
+use crate::epoch::EpochContext;
+use crate::{BlockTimeSource, MintUnderlying};
+
+macro_rules! versioned_record {
+    (
+        $type:ty, version $version:literal { $($field:ident),* $(,)? }
+        $(since $since:literal { $($late:ident),* $(,)? })*
+    ) => {
+        // the format outlives a field's deprecation for callers
+        #[allow(deprecated)]
+        impl BorshSerialize for $type {
+            fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&[$version])?;
+                $(self.$field.serialize(writer)?;)*
+                $($(self.$late.serialize(writer)?;)*)*
+                Ok(())
+            }
+        }
+
+        #[allow(deprecated)]
+        impl BorshDeserialize for $type {
+            fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+                let _version = read_version(reader, $version, stringify!($type))?;
+                Ok(Self {
+                    $($field: BorshDeserialize::deserialize_reader(reader)?,)*
+                    $($($late: if _version >= $since {
+                        BorshDeserialize::deserialize_reader(reader)?
+                    } else {
+                        Default::default()
+                    },)*)*
+                })
+            }
+        }
+    };
+}
+
+/// the version byte of a record, rejecting ones written by a newer version of the crate
+fn read_version<R: Read>(reader: &mut R, current: u8, record: &str) -> io::Result<u8> {
+    let version = u8::deserialize_reader(reader)?;
+    if version == 0 || version > current {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown {} record version {}, expected 1 to {}", record, version, current),
+        ));
+    }
+    Ok(version)
+}
+
+versioned_record! {
+    MintUnderlying, version 4 {
+        block_time,
+        block_time_source,
+        msol_value,
+        mint_pubkey,
+        platform_program_pubkey,
+        mints,
+        total_underlying_amounts,
+        state_reused,
+    }
+    since 2 { crossed_epoch_boundary }
+    since 3 { state_slot }
+    since 4 { msol_minted, msol_burned, msol_discrepancy }
+}
+
+versioned_record! {
+    StakeBalances, version 1 { active, activating, deactivating }
+}
+
+versioned_record! {
+    EpochContext, version 1 { epoch, absolute_slot, slot_index, slots_in_epoch }
+}
+
+/// one byte: 0 transaction, 1 rpc, 2 snapshot
+impl BorshSerialize for BlockTimeSource {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let tag: u8 = match self {
+            Self::Transaction => 0,
+            Self::Rpc => 1,
+            Self::Snapshot => 2,
+        };
+        tag.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for BlockTimeSource {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        match u8::deserialize_reader(reader)? {
+            0 => Ok(Self::Transaction),
+            1 => Ok(Self::Rpc),
+            2 => Ok(Self::Snapshot),
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown block time source {}", tag))),
+        }
+    }
+}
+
+#[cfg(feature = "rpc")]
+mod rpc_records {
+    use super::*;
+    use crate::native::{NativeStakeAccount, NativeStakeValuation};
+    use crate::parsers::{PriceSnapshot, Protocol};
+    use crate::treasury::TreasuryAccrual;
+    use crate::valuation::LiqPoolBalances;
+
+    versioned_record! {
+        PriceSnapshot, version 3 {
+            protocol,
+            mint,
+            price_lamports,
+            underlying_lamports,
+            supply,
+            slot,
+            fetched_at,
+        }
+        since 2 { stake_delta_lamports }
+        since 3 { epoch }
+    }
+
+    versioned_record! {
+        NativeStakeAccount, version 1 { pubkey, lamports, balances }
+    }
+
+    versioned_record! {
+        NativeStakeValuation, version 1 { wallet, epoch, accounts, balances, mints, total_underlying_amounts }
+    }
+
+    versioned_record! {
+        LiqPoolBalances, version 1 { sol_leg_lamports, msol_leg_amount, lp_supply }
+    }
+
+    versioned_record! {
+        TreasuryAccrual, version 1 {
+            slot,
+            block_time,
+            epoch,
+            msol_minted,
+            msol_price_lamports,
+            lamports_value,
+            reward_fee_bps,
+            implied_rewards_lamports,
+        }
+    }
+
+    /// by name, the same string as its json form
+    impl BorshSerialize for Protocol {
+        fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+            self.to_string().serialize(writer)
+        }
+    }
+
+    impl BorshDeserialize for Protocol {
+        fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+            String::deserialize_reader(reader).map(Protocol::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY, SOL_MINT_PUBKEY};
+    use crate::test_utils::FIXTURE_BLOCK_TIME;
+
+    fn mint_underlying() -> MintUnderlying {
+        let (time, source) = (FIXTURE_BLOCK_TIME, BlockTimeSource::Rpc);
+        let (mint, platform) = (&MSOL_MINT_PUBKEY, &MARINADE_STATE_PUBKEY);
+        let mu = MintUnderlying::new(time, source, 1_200_166_666, mint, platform, &[SOL_MINT_PUBKEY], vec![7_201]).unwrap();
+        MintUnderlying { state_reused: true, state_slot: Some(250_001_616), msol_minted: 500_000_000, ..mu }
+    }
+
+    fn round_trip<T: BorshSerialize + BorshDeserialize>(value: &T) -> T {
+        T::try_from_slice(&value.try_to_vec().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let mu = round_trip(&mint_underlying());
+        assert_eq!(format!("{:?}", mu), format!("{:?}", mint_underlying()));
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_rpc_record_round_trips() {
+        use crate::native::{NativeStakeAccount, NativeStakeValuation};
+        use crate::parsers::{PriceSnapshot, Protocol};
+        use crate::test_utils::FIXTURE_SLOT;
+        use crate::valuation::LiqPoolBalances;
+        use solana_sdk::pubkey::Pubkey;
+
+        let mut snapshot = PriceSnapshot::new(Protocol::Custom("dummy".to_string()), MSOL_MINT_PUBKEY, 1, 2, 3, FIXTURE_SLOT);
+        snapshot.fetched_at = FIXTURE_BLOCK_TIME;
+        let epoch = EpochContext { epoch: 600, absolute_slot: FIXTURE_SLOT, slot_index: 7, slots_in_epoch: 432_000 };
+        let snapshot = snapshot.with_epoch(epoch);
+        let decoded = round_trip(&snapshot);
+        assert_eq!(decoded.epoch, Some(epoch));
+        assert_eq!((decoded.protocol.clone(), decoded.fetched_at), (snapshot.protocol.clone(), snapshot.fetched_at));
+        assert_eq!(decoded, snapshot);
+
+        let balances = StakeBalances { active: 1, activating: 2, deactivating: 3 };
+        let valuation = NativeStakeValuation {
+            wallet: Pubkey::new_unique(),
+            epoch: 500,
+            accounts: vec![NativeStakeAccount { pubkey: Pubkey::new_unique(), lamports: 7, balances }],
+            balances,
+            mints: vec![SOL_MINT_PUBKEY.to_string()],
+            total_underlying_amounts: vec![6],
+        };
+        assert_eq!(round_trip(&valuation), valuation);
+        let pool = LiqPoolBalances { sol_leg_lamports: 1, msol_leg_amount: 2, lp_supply: 3 };
+        assert_eq!(round_trip(&pool), pool);
+    }
+
+    // the strings are kept short to keep the hex readable, which only the fields allow
+    #[allow(deprecated)]
+    #[test]
+    fn test_golden_bytes() {
+        let bytes = StakeBalances { active: 1, activating: 2, deactivating: 3 }.try_to_vec().unwrap();
+        assert_eq!(bytes, [&[1][..], &1u64.to_le_bytes(), &2u64.to_le_bytes(), &3u64.to_le_bytes()].concat());
+
+        let mu = MintUnderlying { mints: vec!["a".to_string()], mint_pubkey: "m".to_string(), ..mint_underlying() };
+        let mu = MintUnderlying { platform_program_pubkey: "p".to_string(), ..mu };
+        let hex: String = mu.try_to_vec().unwrap().iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            concat!(
+                "04",                       // version
+                "0003ce6500000000",         // block_time
+                "01",                       // block_time_source: rpc
+                "0a17894700000000",         // msol_value
+                "010000006d",               // mint_pubkey
+                "0100000070",               // platform_program_pubkey
+                "010000000100000061",       // mints
+                "01000000211c000000000000", // total_underlying_amounts
+                "01",                       // state_reused
+                "00",                       // crossed_epoch_boundary
+                "01d0b8e60e00000000",       // state_slot: Some(250_001_616)
+                "0065cd1d00000000",         // msol_minted
+                "0000000000000000",         // msol_burned
+                "00",                       // msol_discrepancy
+            )
+        );
+        // a record written before mints and burns were counted, one before state slots were kept,
+        // and one before epoch crossings were noted
+        let mut v3 = mu.try_to_vec().unwrap();
+        v3.truncate(v3.len() - 17);
+        v3[0] = 3;
+        assert_eq!(MintUnderlying::try_from_slice(&v3).unwrap().msol_minted, 0);
+        let mut v2 = v3;
+        v2.truncate(v2.len() - 9);
+        v2[0] = 2;
+        assert_eq!(MintUnderlying::try_from_slice(&v2).unwrap().state_slot, None);
+        let mut v1 = v2;
+        v1.pop();
+        v1[0] = 1;
+        assert!(!MintUnderlying::try_from_slice(&v1).unwrap().crossed_epoch_boundary);
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_snapshot_golden_bytes() {
+        use crate::parsers::{PriceSnapshot, Protocol};
+        use solana_sdk::pubkey::Pubkey;
+
+        let snapshot = PriceSnapshot { fetched_at: 1, ..PriceSnapshot::new(Protocol::Marinade, Pubkey::default(), 2, 3, 4, 5) };
+        let mut expected = String::from("03080000006d6172696e616465"); // version, protocol
+        expected += &"00".repeat(32); // mint
+        expected += "0200000000000000030000000000000004000000000000000500000000000000"; // price, underlying, supply, slot
+        expected += "0100000000000000"; // fetched_at
+        let v1_hex = expected.clone();
+        expected += "00"; // stake_delta_lamports: none
+        expected += "00"; // epoch: none
+        let hex: String = snapshot.try_to_vec().unwrap().iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, expected);
+
+        // a snapshot written before the stake delta was recorded
+        let mut v1: Vec<u8> =
+            (0..v1_hex.len()).step_by(2).map(|i| u8::from_str_radix(&v1_hex[i..i + 2], 16).unwrap()).collect();
+        v1[0] = 1;
+        assert_eq!(PriceSnapshot::try_from_slice(&v1).unwrap().stake_delta_lamports, None);
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Grown {
+        a: u64,
+        b: u64,
+        c: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Original {
+        a: u64,
+    }
+
+    versioned_record! { Original, version 1 { a } }
+    versioned_record! { Grown, version 3 { a } since 2 { b } since 3 { c } }
+
+    #[test]
+    fn test_appended_fields_default_in_old_records() {
+        let old = Original { a: 7 }.try_to_vec().unwrap();
+        assert_eq!(Grown::try_from_slice(&old).unwrap(), Grown { a: 7, ..Grown::default() });
+        let grown = Grown { a: 1, b: 2, c: vec![3] };
+        assert_eq!(round_trip(&grown), grown);
+        // a record from a newer crate is refused rather than misread
+        let err = Original::try_from_slice(&grown.try_to_vec().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
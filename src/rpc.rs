@@ -0,0 +1,394 @@
+// signatures mirror `RpcClient`, whose `ClientError` is what it is
+#![allow(clippy::result_large_err)]
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+#[cfg(feature = "blocking")]
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED;
+use solana_client::rpc_request::RpcError;
+use solana_client::rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcResult};
+use solana_sdk::account::Account;
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::epoch_info::EpochInfo;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus};
+
+use crate::verbosity::debug;
+
+/// first wait between `get_slot` polls while a node catches up, doubling up to `MAX_SLOT_POLL_BACKOFF`
+const SLOT_POLL_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_SLOT_POLL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// `commitment`, raised to `confirmed` for the calls the node refuses to serve at `processed`
+/// (`getTransaction`, `getSignaturesForAddress`)
+pub(crate) fn at_least_confirmed(commitment: CommitmentConfig) -> CommitmentConfig {
+    if commitment.is_at_least_confirmed() {
+        commitment
+    } else {
+        CommitmentConfig::confirmed()
+    }
+}
+
+/// whether the node refused a call for not having reached its `min_context_slot` yet
+fn is_min_context_slot_not_reached(error: &ClientError) -> bool {
+    matches!(
+        error.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code: JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, .. })
+    )
+}
+
+/// run `call`, and when the node answers that it isn't at `min_slot` yet, poll its slot with a
+/// growing backoff until it is, then run `call` again. gives up with the node's error once
+/// `deadline` has passed; without a deadline or a slot `call` just runs once
+pub(crate) fn wait_for_min_context_slot<T>(
+    rpc_client: &dyn RpcFetcher,
+    min_slot: Option<Slot>,
+    commitment: CommitmentConfig,
+    deadline: Option<Duration>,
+    mut call: impl FnMut() -> ClientResult<T>,
+) -> ClientResult<T> {
+    let (Some(min_slot), Some(deadline)) = (min_slot, deadline) else {
+        return call();
+    };
+    let error = match call() {
+        Err(e) if is_min_context_slot_not_reached(&e) => e,
+        result => return result,
+    };
+    let give_up = Instant::now() + deadline;
+    let mut backoff = SLOT_POLL_BACKOFF;
+    loop {
+        let node_slot = rpc_client.get_slot_with_commitment(commitment)?;
+        if node_slot >= min_slot {
+            debug!(node_slot, min_slot, "node caught up, retrying");
+            return call();
+        }
+        let now = Instant::now();
+        if now >= give_up {
+            debug!(node_slot, min_slot, ?deadline, "node didn't catch up in time");
+            return Err(error);
+        }
+        debug!(node_slot, min_slot, ?backoff, "waiting for the node to reach the slot");
+        std::thread::sleep(backoff.min(give_up - now));
+        backoff = (backoff * 2).min(MAX_SLOT_POLL_BACKOFF);
+    }
+}
+
+/// the subset of rpc calls the parser relies on, so the analysis code can run against
+/// something other than a live `RpcClient` (tests, replay tooling)
+pub trait RpcFetcher: Send + Sync {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>>;
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>>;
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta>;
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp>;
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>>;
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo>;
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot>;
+
+    /// newest first, from before `config.before` down to after `config.until`, both exclusive
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>>;
+
+    /// one status per signature, None for one the node doesn't know, searching its whole
+    /// history rather than just the recent status cache. the node takes up to 256 at a time
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>>;
+}
+
+impl<T: RpcFetcher + ?Sized> RpcFetcher for &T {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        (**self).get_account_with_config(pubkey, config)
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        (**self).get_multiple_accounts_with_config(pubkeys, config)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        (**self).get_transaction_with_config(signature, config)
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        (**self).get_block_time(slot)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        (**self).get_program_accounts_with_config(program_id, config)
+    }
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        (**self).get_epoch_info()
+    }
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        (**self).get_slot_with_commitment(commitment)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        (**self).get_signatures_for_address_with_config(address, config)
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        (**self).get_signature_statuses_with_history(signatures)
+    }
+}
+
+impl<T: RpcFetcher + ?Sized> RpcFetcher for std::sync::Arc<T> {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        (**self).get_account_with_config(pubkey, config)
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        (**self).get_multiple_accounts_with_config(pubkeys, config)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        (**self).get_transaction_with_config(signature, config)
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        (**self).get_block_time(slot)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        (**self).get_program_accounts_with_config(program_id, config)
+    }
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        (**self).get_epoch_info()
+    }
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        (**self).get_slot_with_commitment(commitment)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        (**self).get_signatures_for_address_with_config(address, config)
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        (**self).get_signature_statuses_with_history(signatures)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl RpcFetcher for RpcClient {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        RpcClient::get_account_with_config(self, pubkey, config)
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        RpcClient::get_multiple_accounts_with_config(self, pubkeys, config)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        RpcClient::get_transaction_with_config(self, signature, config)
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        RpcClient::get_block_time(self, slot)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        RpcClient::get_program_accounts_with_config(self, program_id, config)
+    }
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        RpcClient::get_epoch_info(self)
+    }
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        RpcClient::get_slot_with_commitment(self, commitment)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        RpcClient::get_signatures_for_address_with_config(self, address, config)
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        RpcClient::get_signature_statuses_with_history(self, signatures)
+    }
+}
+
+/// spaces the calls into a fetcher at least `1 / max_per_second` apart, across every thread
+/// sharing it. wrap the client once and share it, e.g. through `MarinadeClient`, so parallel
+/// callers split the budget instead of each getting their own
+pub struct RateLimited<F> {
+    inner: F,
+    interval: Duration,
+    next_call: Mutex<Instant>,
+}
+
+impl<F: RpcFetcher> RateLimited<F> {
+    pub fn new(inner: F, max_per_second: u32) -> Self {
+        let interval = Duration::from_secs(1) / max_per_second.max(1);
+        Self { inner, interval, next_call: Mutex::new(Instant::now()) }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// claim the next free call time, then sleep until it outside the lock
+    fn wait(&self) {
+        let at = {
+            let mut next_call = self.next_call.lock().unwrap();
+            let at = (*next_call).max(Instant::now());
+            *next_call = at + self.interval;
+            at
+        };
+        std::thread::sleep(at.saturating_duration_since(Instant::now()));
+    }
+}
+
+impl<F: RpcFetcher> RpcFetcher for RateLimited<F> {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        self.wait();
+        self.inner.get_account_with_config(pubkey, config)
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        self.wait();
+        self.inner.get_multiple_accounts_with_config(pubkeys, config)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        self.wait();
+        self.inner.get_transaction_with_config(signature, config)
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        self.wait();
+        self.inner.get_block_time(slot)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        self.wait();
+        self.inner.get_program_accounts_with_config(program_id, config)
+    }
+
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        self.wait();
+        self.inner.get_epoch_info()
+    }
+
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        self.wait();
+        self.inner.get_slot_with_commitment(commitment)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        self.wait();
+        self.inner.get_signatures_for_address_with_config(address, config)
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        self.wait();
+        self.inner.get_signature_statuses_with_history(signatures)
+    }
+}
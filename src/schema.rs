@@ -0,0 +1,111 @@
+//! the `schema_version` the serde forms of `MintUnderlying` and `PriceSnapshot` lead with, for
+//! output kept longer than the crate that wrote it: a data lake replayed by a later release. a
+//! field added to either comes with `#[serde(default)]` and `CURRENT_SCHEMA_VERSION` bumped, so
+//! an older record reads with the field defaulted and is written back at the current version.
+//! output from before the version was written counts as version 1; one from a newer crate is
+//! refused instead of read with its new fields dropped. the borsh forms in `records` carry
+//! their own version byte. reading goes through a json map: serde's `flatten` buffers values in
+//! a form without `i128`, which `PriceSnapshot::stake_delta_lamports` is
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// the version written, and the newest one read
+pub const CURRENT_SCHEMA_VERSION: u16 = 1;
+
+/// a type's fields, as its `#[serde(remote = "Self")]` derive writes them, behind the version
+#[derive(Serialize)]
+pub(crate) struct Versioned<T> {
+    pub(crate) schema_version: u16,
+    #[serde(flatten)]
+    pub(crate) fields: T,
+}
+
+/// the fields of a serialized record, its `schema_version` checked and taken out: absent is
+/// version 1, 0 or one newer than `CURRENT_SCHEMA_VERSION` an error
+pub(crate) fn unversioned<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    let mut fields = Map::deserialize(deserializer)?;
+    let version = match fields.remove("schema_version") {
+        Some(version) => u16::deserialize(version).map_err(de::Error::custom)?,
+        None => 1,
+    };
+    match version {
+        0 => Err(de::Error::custom("schema_version 0 was never written, versions start at 1")),
+        version if version > CURRENT_SCHEMA_VERSION => Err(de::Error::custom(format!(
+            "schema_version {} is newer than {}, the latest this build reads",
+            version, CURRENT_SCHEMA_VERSION
+        ))),
+        _ => Ok(Value::Object(fields)),
+    }
+}
+
+/// `Serialize` and `Deserialize` for `$type`, which derives them with `#[serde(remote = "Self")]`,
+/// through `Versioned`
+macro_rules! versioned_serde {
+    ($type:ty) => {
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error> {
+                struct Fields<'a>(&'a $type);
+                impl serde::Serialize for Fields<'_> {
+                    fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                    where
+                        S: serde::Serializer,
+                    {
+                        <$type>::serialize(self.0, serializer)
+                    }
+                }
+                let schema_version = $crate::schema::CURRENT_SCHEMA_VERSION;
+                $crate::schema::Versioned { schema_version, fields: Fields(self) }.serialize(serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> ::core::result::Result<Self, D::Error> {
+                let fields = $crate::schema::unversioned(deserializer)?;
+                <$type>::deserialize(fields).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+pub(crate) use versioned_serde;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockTimeSource, MintUnderlying};
+
+    // written by the crate at schema_version 1; they have to keep reading as fields are added
+    const MINT_UNDERLYING_V1: &str = include_str!("../tests/schema/v1/mint_underlying.json");
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_v1_output_keeps_reading() {
+        let mu: MintUnderlying = serde_json::from_str(MINT_UNDERLYING_V1).unwrap();
+        assert_eq!((mu.block_time, mu.block_time_source, mu.msol_value), (1_700_000_000, BlockTimeSource::Rpc, 1_250));
+        assert_eq!(mu.total_underlying_amounts, vec![1_250]);
+        assert_eq!((mu.state_slot, mu.msol_minted, mu.msol_discrepancy), (Some(230_000_000), 1_000, false));
+
+        let written = serde_json::to_value(&mu).unwrap();
+        assert_eq!(written["schema_version"], CURRENT_SCHEMA_VERSION);
+        assert_eq!(serde_json::from_value::<MintUnderlying>(written).unwrap().mints, mu.mints);
+    }
+
+    #[test]
+    fn test_unversioned_reads_as_v1_and_newer_is_refused() {
+        let mut json: serde_json::Value = serde_json::from_str(MINT_UNDERLYING_V1).unwrap();
+        json.as_object_mut().unwrap().remove("schema_version");
+        // fields added after the first release default when missing
+        for field in ["state_reused", "crossed_epoch_boundary", "state_slot", "msol_minted", "msol_burned"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let mu: MintUnderlying = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!((mu.state_reused, mu.state_slot, mu.msol_minted), (false, None, 0));
+
+        json["schema_version"] = (CURRENT_SCHEMA_VERSION + 1).into();
+        let e = serde_json::from_value::<MintUnderlying>(json.clone()).unwrap_err().to_string();
+        let newer = format!("schema_version {} is newer than {}", CURRENT_SCHEMA_VERSION + 1, CURRENT_SCHEMA_VERSION);
+        assert!(e.contains(&newer), "{}", e);
+        json["schema_version"] = 0.into();
+        assert!(serde_json::from_value::<MintUnderlying>(json).is_err());
+    }
+}
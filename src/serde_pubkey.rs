@@ -0,0 +1,25 @@
+//! `#[serde(with = "crate::serde_pubkey")]`: pubkeys as base58 strings, the way rpc responses
+//! and explorers show them, instead of serde's default 32-number array
+
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+use solana_program::pubkey::Pubkey;
+
+pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Pubkey::from_str(&s).map_err(de::Error::custom)
+}
+
+/// `#[serde(serialize_with = "crate::serde_pubkey::serialize_option")]`, for an optional pubkey
+#[cfg(feature = "rpc")]
+pub fn serialize_option<S: Serializer>(pubkey: &Option<Pubkey>, serializer: S) -> Result<S::Ok, S::Error> {
+    match pubkey {
+        Some(pubkey) => serializer.collect_str(pubkey),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,303 @@
+//! a json http service over one `MarinadeClient`:
+//!
+//! - `GET /price`: the current mSOL `PriceSnapshot`
+//! - `GET /price?slot=N`: a snapshot read at slot N or later
+//! - `GET /analyze/{signature}`: the `MintUnderlying` of a transaction, through the client's registry
+//!
+//! rpc calls run on tokio's blocking pool. `/price` is answered from a snapshot cached for a ttl,
+//! and concurrent misses wait on a single fetch, so load on the server isn't passed on to the node.
+//! with a `ShutdownHandle` it stops accepting connections once that's triggered and gives the
+//! requests in flight until the end of the grace period
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use solana_sdk::signature::Signature;
+use tokio::sync::Mutex;
+
+use crate::client::MarinadeClient;
+use crate::error::{Error, ErrorKind, Result};
+use crate::parsers::PriceSnapshot;
+use crate::shutdown::{ShutdownHandle, ShutdownSummary};
+use crate::verbosity::{debug, warn};
+
+/// how long `/price` reuses a snapshot by default, ~5 slots
+pub const DEFAULT_PRICE_TTL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct PriceServer {
+    client: MarinadeClient,
+    cache: Arc<PriceCache>,
+    shutdown: Option<ShutdownHandle>,
+    requests: Arc<RequestCounts>,
+}
+
+#[derive(Default)]
+struct RequestCounts {
+    completed: AtomicUsize,
+    in_flight: AtomicUsize,
+}
+
+struct PriceCache {
+    ttl: Duration,
+    latest: Mutex<Option<(Instant, PriceSnapshot)>>,
+}
+
+impl PriceServer {
+    pub fn new(client: MarinadeClient) -> Self {
+        let cache = Arc::new(PriceCache { ttl: DEFAULT_PRICE_TTL, latest: Mutex::new(None) });
+        Self { client, cache, shutdown: None, requests: Arc::default() }
+    }
+
+    /// how long a fetched snapshot answers `/price` before the node is asked again
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        Self { cache: Arc::new(PriceCache { ttl, latest: Mutex::new(None) }), ..self }
+    }
+
+    /// stop serving once `handle` is triggered, see the module docs
+    pub fn with_shutdown(self, handle: ShutdownHandle) -> Self {
+        Self { shutdown: Some(handle), ..self }
+    }
+
+    /// the requests answered so far, and those dropped unanswered when the grace period ran
+    /// out. shared by clones, so a clone kept aside can tell once `serve` returns
+    pub fn summary(&self) -> ShutdownSummary {
+        let (completed, in_flight) = (&self.requests.completed, &self.requests.in_flight);
+        ShutdownSummary { completed: completed.load(Ordering::SeqCst), abandoned: in_flight.load(Ordering::SeqCst) }
+    }
+
+    /// serve on `addr` until the runtime shuts down, or the shutdown handle is triggered
+    pub async fn serve(self, addr: SocketAddr) -> std::io::Result<()> {
+        self.serve_listener(TcpListener::bind(addr)?).await
+    }
+
+    /// serve on an already bound listener, e.g. one on an ephemeral port
+    pub async fn serve_listener(self, listener: TcpListener) -> std::io::Result<()> {
+        listener.set_nonblocking(true)?;
+        debug!(addr = ?listener.local_addr(), "serving");
+        let shutdown = self.shutdown.clone();
+        let make_service = make_service_fn(move |_| {
+            let server = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(req).await) }
+                }))
+            }
+        });
+        let server = Server::from_tcp(listener).map_err(std::io::Error::other)?.serve(make_service);
+        let Some(shutdown) = shutdown else {
+            return server.await.map_err(std::io::Error::other);
+        };
+        let mut server = Box::pin(server.with_graceful_shutdown(shutdown.triggered()));
+        let mut triggered = Box::pin(shutdown.triggered());
+        // serve until the server ends by itself or the shutdown starts, whichever is first
+        let ended = std::future::poll_fn(|cx| match server.as_mut().poll(cx) {
+            Poll::Ready(result) => Poll::Ready(Some(result)),
+            Poll::Pending => triggered.as_mut().poll(cx).map(|()| None),
+        })
+        .await;
+        if let Some(result) = ended {
+            return result.map_err(std::io::Error::other);
+        }
+        let deadline = tokio::time::Instant::from_std(shutdown.deadline().expect("triggered"));
+        match tokio::time::timeout_at(deadline, server).await {
+            Ok(result) => result.map_err(std::io::Error::other),
+            Err(_) => {
+                warn!("grace period ran out with requests in flight");
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        // a request dropped at the deadline stays counted as in flight
+        self.requests.in_flight.fetch_add(1, Ordering::SeqCst);
+        let response = self.respond(req).await;
+        self.requests.completed.fetch_add(1, Ordering::SeqCst);
+        self.requests.in_flight.fetch_sub(1, Ordering::SeqCst);
+        response
+    }
+
+    async fn respond(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET {
+            return error_response(StatusCode::METHOD_NOT_ALLOWED, "only GET is supported");
+        }
+        let path = req.uri().path();
+        let response = if path == "/price" {
+            match query_slot(req.uri().query()) {
+                Ok(slot) => self.price(slot).await.map(|snapshot| json_response(StatusCode::OK, &snapshot)),
+                Err(message) => Ok(error_response(StatusCode::BAD_REQUEST, message)),
+            }
+        } else if let Some(signature) = path.strip_prefix("/analyze/") {
+            match signature.parse::<Signature>() {
+                Ok(signature) => self.analyze(signature).await.map(|mu| json_response(StatusCode::OK, &mu)),
+                Err(_) => Ok(error_response(StatusCode::BAD_REQUEST, "invalid signature")),
+            }
+        } else {
+            Ok(error_response(StatusCode::NOT_FOUND, "no such endpoint"))
+        };
+        let response = response.unwrap_or_else(|e| error_response(error_status(&e), &e.to_string()));
+        debug!(path, status = response.status().as_u16(), "request served");
+        response
+    }
+
+    /// the cached snapshot while it's fresh and at least as new as `slot`
+    async fn price(&self, slot: Option<u64>) -> Result<PriceSnapshot> {
+        let mut latest = self.cache.latest.lock().await;
+        if let Some((fetched_at, snapshot)) = latest.as_ref() {
+            if fetched_at.elapsed() < self.cache.ttl && slot.is_none_or(|slot| snapshot.slot >= slot) {
+                return Ok(snapshot.clone());
+            }
+        }
+        let client = self.client.clone();
+        let snapshot = blocking(move || client.price(slot)).await?;
+        *latest = Some((Instant::now(), snapshot.clone()));
+        Ok(snapshot)
+    }
+
+    async fn analyze(&self, signature: Signature) -> Result<crate::MintUnderlying> {
+        let client = self.client.clone();
+        blocking(move || {
+            let tx = client.fetch_transaction(signature)?;
+            client.registry().analyze_transaction(&client.context(), &tx)
+        })
+        .await
+    }
+}
+
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f).await.expect("rpc call panicked")
+}
+
+fn query_slot(query: Option<&str>) -> std::result::Result<Option<u64>, &'static str> {
+    let Some(value) = query.into_iter().flat_map(|q| q.split('&')).find_map(|pair| pair.strip_prefix("slot=")) else {
+        return Ok(None);
+    };
+    value.parse().map(Some).map_err(|_| "invalid slot")
+}
+
+fn error_status(e: &Error) -> StatusCode {
+    match e.kind() {
+        ErrorKind::AccountNotFound { .. }
+        | ErrorKind::NotSupported
+        | ErrorKind::OfflineMiss { .. }
+        | ErrorKind::NoHistoricalBalance { .. }
+        | ErrorKind::HistoryUnavailable { .. } => StatusCode::NOT_FOUND,
+        ErrorKind::ClusterMismatch { .. }
+        | ErrorKind::InvalidMint(_)
+        | ErrorKind::InvalidConfig { .. }
+        | ErrorKind::WorkerPanicked { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorKind::MissingBlockTime
+        | ErrorKind::Deposit(_)
+        | ErrorKind::LiquidUnstake(_)
+        | ErrorKind::EpochInFuture { .. }
+        | ErrorKind::InvalidMintUnderlying(_)
+        | ErrorKind::UnsupportedTransactionVersion { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorKind::InvalidSignature { .. } | ErrorKind::InvalidTransaction { .. } => StatusCode::BAD_REQUEST,
+        ErrorKind::Rpc(_)
+        | ErrorKind::InvalidAccountData { .. }
+        | ErrorKind::ShortResponse { .. }
+        | ErrorKind::Delivery { .. }
+        | ErrorKind::SlotSkew { .. } => StatusCode::BAD_GATEWAY,
+    }
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(body).expect("response bodies serialize");
+    Response::builder().status(status).header(CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    json_response(status, &serde_json::json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::constants::{MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY};
+    use crate::test_utils::{marinade_transaction, sample_state, state_account, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT};
+    use crate::transaction::transaction_signature;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_endpoints_on_an_ephemeral_port() {
+        let state = MarinadeState { msol_mint: MSOL_MINT_PUBKEY, ..sample_state() };
+        let tx = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[MarinadeFinanceInstruction::Deposit]);
+        let signature = transaction_signature(&tx).unwrap();
+        let rpc = Arc::new(
+            MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state)).with_transaction(signature, tx),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(PriceServer::new(client).with_ttl(Duration::from_secs(60)).serve_listener(listener));
+
+        let (status, body) = get(addr, "/price");
+        assert_eq!(status, 200);
+        assert_eq!(body["price_lamports"], state.msol_price_lamports());
+        assert_eq!(body["slot"], FIXTURE_SLOT);
+        // served from the cache, including for a slot it already covers
+        assert_eq!(get(addr, "/price").1, body);
+        assert_eq!(get(addr, &format!("/price?slot={}", FIXTURE_SLOT)).1, body);
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+        // a newer slot than cached goes to the node
+        assert_eq!(get(addr, &format!("/price?slot={}", FIXTURE_SLOT + 10)).1["slot"], FIXTURE_SLOT + 10);
+        assert_eq!(rpc.call_count("getAccountInfo"), 2);
+
+        let (status, body) = get(addr, &format!("/analyze/{}", signature));
+        assert_eq!(status, 200);
+        assert_eq!(body["msol_value"], state.msol_price_lamports());
+        assert_eq!(body["block_time_source"], "transaction");
+
+        assert_eq!(get(addr, "/price?slot=soon").0, 400);
+        assert_eq!(get(addr, "/analyze/nope").0, 400);
+        assert_eq!(get(addr, &format!("/analyze/{}", Signature::new_unique())).0, 502);
+        assert_eq!(get(addr, "/nothing").0, 404);
+    }
+
+    #[test]
+    fn test_shutdown_answers_the_requests_in_flight() {
+        let state_account = state_account(&sample_state());
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account);
+        let rpc = rpc.with_latency(Duration::from_millis(300));
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = ShutdownHandle::with_grace_period(Duration::from_secs(10));
+        let server = PriceServer::new(client).with_shutdown(handle.clone());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let served = runtime.spawn(server.clone().serve_listener(listener));
+
+        let slow = std::thread::spawn(move || get(addr, "/price"));
+        std::thread::sleep(Duration::from_millis(100));
+        handle.trigger();
+        assert_eq!(slow.join().unwrap().0, 200);
+        runtime.block_on(served).unwrap().unwrap();
+        assert_eq!(server.summary(), ShutdownSummary { completed: 1, abandoned: 0 });
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! cooperative shutdown for the long-running parts of the crate: `Pipeline`, `Backfill` and
+//! `PriceServer` take a `ShutdownHandle`, and once it's triggered, e.g. from a SIGTERM handler,
+//! stop taking new work and finish what they're on within the handle's grace period. what's
+//! left then is abandoned, and each reports a `ShutdownSummary` of how it went
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// the default of `ShutdownHandle::with_grace_period`
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// clones share one trigger
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    grace_period: Duration,
+    /// when `trigger` was first called
+    triggered: Mutex<Option<Instant>>,
+    changed: Condvar,
+    /// tasks waiting on `triggered()`
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::with_grace_period(DEFAULT_GRACE_PERIOD)
+    }
+
+    /// how long work in flight gets to finish once the handle is triggered
+    pub fn with_grace_period(grace_period: Duration) -> Self {
+        let (triggered, changed) = (Mutex::new(None), Condvar::new());
+        let inner = Inner { grace_period, triggered, changed, wakers: Mutex::default() };
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// start the shutdown. later calls don't move the deadline
+    pub fn trigger(&self) {
+        self.inner.triggered.lock().unwrap().get_or_insert_with(Instant::now);
+        self.inner.changed.notify_all();
+        for waker in self.inner.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.lock().unwrap().is_some()
+    }
+
+    /// when the grace period runs out, None until triggered
+    pub fn deadline(&self) -> Option<Instant> {
+        self.inner.triggered.lock().unwrap().map(|triggered| triggered + self.inner.grace_period)
+    }
+
+    /// sleep for `duration`, or until triggered if that's sooner. false when it was triggered
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let triggered = self.inner.triggered.lock().unwrap();
+        let (triggered, _) = self.inner.changed.wait_timeout_while(triggered, duration, |t| t.is_none()).unwrap();
+        triggered.is_none()
+    }
+
+    /// resolves once triggered, for async callers
+    pub fn triggered(&self) -> Triggered {
+        Triggered { handle: self.clone() }
+    }
+}
+
+/// see `ShutdownHandle::triggered`
+#[derive(Debug)]
+pub struct Triggered {
+    handle: ShutdownHandle,
+}
+
+impl Future for Triggered {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // registered before checking, so a trigger in between still wakes this task
+        let mut wakers = self.handle.inner.wakers.lock().unwrap();
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        drop(wakers);
+        match self.handle.is_triggered() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// how far a shut down component got: items it finished, successfully or not, and items it
+/// had accepted but dropped, queued or still in flight when the grace period ran out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    pub completed: usize,
+    pub abandoned: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trigger_wakes_sleepers() {
+        let handle = ShutdownHandle::with_grace_period(Duration::from_secs(5));
+        assert!(handle.sleep(Duration::from_millis(1)));
+        assert_eq!(handle.deadline(), None);
+
+        let sleeper = handle.clone();
+        let started = Instant::now();
+        let sleeping = std::thread::spawn(move || sleeper.sleep(Duration::from_secs(60)));
+        std::thread::sleep(Duration::from_millis(20));
+        handle.trigger();
+        assert!(!sleeping.join().unwrap());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let deadline = handle.deadline().unwrap();
+        handle.trigger();
+        assert_eq!(handle.deadline(), Some(deadline));
+        assert!(handle.is_triggered() && !handle.sleep(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,368 @@
+//! the signatures of transactions that touched an account, newest first, fetched a page at a
+//! time as the iterator is advanced, and status checks that weed out the ones not worth a
+//! `get_transaction`
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+
+use crate::client::MarinadeClient;
+use crate::error::{Error, ErrorKind, Result};
+use crate::rpc::{at_least_confirmed, RpcFetcher};
+use crate::verbosity::{debug, warn};
+
+/// the most signatures the node returns per `get_signatures_for_address` call
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// the most signatures the node takes per `get_signature_statuses` call
+pub const MAX_STATUS_BATCH: usize = 256;
+
+/// which signatures `signature_stream` yields. times are block times, both ends inclusive
+#[derive(Debug, Clone)]
+pub struct SignatureRange {
+    /// stop at the first signature older than this
+    pub start_time: Option<UnixTimestamp>,
+    /// skip signatures newer than this
+    pub end_time: Option<UnixTimestamp>,
+    /// stop before this signature, exclusive
+    pub until: Option<Signature>,
+    /// signatures per page, capped at `MAX_PAGE_SIZE`
+    pub page_size: usize,
+    /// wait between pages, to stay under public rpc rate limits
+    pub page_delay: Duration,
+}
+
+impl Default for SignatureRange {
+    fn default() -> Self {
+        Self {
+            start_time: None,
+            end_time: None,
+            until: None,
+            page_size: MAX_PAGE_SIZE,
+            page_delay: Duration::from_millis(200),
+        }
+    }
+}
+
+impl SignatureRange {
+    /// everything between two block times
+    pub fn between(start_time: UnixTimestamp, end_time: UnixTimestamp) -> Self {
+        Self { start_time: Some(start_time), end_time: Some(end_time), ..Self::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureInfo {
+    pub signature: Signature,
+    pub slot: Slot,
+    /// None when the node doesn't know it
+    pub block_time: Option<UnixTimestamp>,
+    /// why the transaction failed on chain, None when it succeeded
+    pub err: Option<TransactionError>,
+}
+
+/// the signatures on `client`'s state account within `range`, at the client's commitment
+pub fn signature_stream(client: &MarinadeClient, range: SignatureRange) -> SignatureStream<'_> {
+    SignatureStream::new(client.rpc_client(), client.deployment().state, range)
+        .with_commitment(client.options().commitment)
+}
+
+/// see `signature_stream`. a page is only requested once the previous one is used up, so stopping
+/// early saves the rest of the range. a failed page request is yielded and ends the stream
+pub struct SignatureStream<'a> {
+    rpc_client: &'a dyn RpcFetcher,
+    address: Pubkey,
+    range: SignatureRange,
+    page: VecDeque<SignatureInfo>,
+    before: Option<Signature>,
+    pages: usize,
+    done: bool,
+    commitment: CommitmentConfig,
+}
+
+impl<'a> SignatureStream<'a> {
+    /// the signatures on any `address`
+    pub fn new(rpc_client: &'a dyn RpcFetcher, address: Pubkey, range: SignatureRange) -> Self {
+        let commitment = CommitmentConfig::confirmed();
+        Self { rpc_client, address, range, page: VecDeque::new(), before: None, pages: 0, done: false, commitment }
+    }
+
+    /// `confirmed` by default. `processed` is raised to `confirmed`, the node doesn't list
+    /// signatures at it
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = at_least_confirmed(commitment);
+        self
+    }
+
+    /// pages requested so far
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    fn fetch_page(&mut self) -> Result<()> {
+        if self.pages > 0 {
+            std::thread::sleep(self.range.page_delay);
+        }
+        let page_size = self.range.page_size.clamp(1, MAX_PAGE_SIZE);
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before: self.before,
+            until: self.range.until,
+            limit: Some(page_size),
+            commitment: Some(self.commitment),
+        };
+        let page = self
+            .rpc_client
+            .get_signatures_for_address_with_config(&self.address, config)
+            .map_err(|e| Error::from(e).with_pubkey(self.address))?;
+        self.pages += 1;
+        debug!(len = page.len(), before = ?self.before, "signature page");
+        // the cursor moves even if the oldest signature of the page can't be parsed
+        self.before = page.last().and_then(|status| Signature::from_str(&status.signature).ok());
+        self.done = page.len() < page_size || self.before.is_none();
+        for status in page {
+            match Signature::from_str(&status.signature) {
+                Ok(signature) => self.page.push_back(SignatureInfo {
+                    signature,
+                    slot: status.slot,
+                    block_time: status.block_time,
+                    err: status.err,
+                }),
+                Err(e) => warn!(signature = status.signature, error = %e, "skipping unparseable signature"),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for SignatureStream<'_> {
+    type Item = Result<SignatureInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.page.is_empty() {
+                if self.done {
+                    return None;
+                }
+                if let Err(e) = self.fetch_page() {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            let info = self.page.pop_front()?;
+            let block_time = info.block_time;
+            if block_time.zip(self.range.start_time).is_some_and(|(t, start)| t < start) {
+                self.done = true;
+                self.page.clear();
+                return None;
+            }
+            if block_time.zip(self.range.end_time).is_some_and(|(t, end)| t > end) {
+                continue;
+            }
+            return Some(Ok(info));
+        }
+    }
+}
+
+/// why `check_signature_statuses` dropped a signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// the transaction failed on chain
+    Failed,
+    /// the node has no status for it: it never landed, or went with a dropped fork
+    Unknown,
+    /// it landed, but hasn't reached the client's commitment yet
+    Unconfirmed,
+}
+
+/// which signatures `check_signature_statuses` drops, every kind by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusFilter {
+    pub skip_failed: bool,
+    /// also covers `SkipReason::Unconfirmed`
+    pub skip_unknown: bool,
+}
+
+impl Default for StatusFilter {
+    fn default() -> Self {
+        Self { skip_failed: true, skip_unknown: true }
+    }
+}
+
+impl StatusFilter {
+    pub fn skips(&self, reason: SkipReason) -> bool {
+        match reason {
+            SkipReason::Failed => self.skip_failed,
+            SkipReason::Unknown | SkipReason::Unconfirmed => self.skip_unknown,
+        }
+    }
+}
+
+/// a signature `check_signature_statuses` kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckedSignature {
+    pub signature: Signature,
+    /// None when the node has no status for it
+    pub slot: Option<Slot>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusCheck {
+    /// in the order given
+    pub kept: Vec<CheckedSignature>,
+    pub skipped: Vec<(Signature, SkipReason)>,
+}
+
+impl StatusCheck {
+    pub fn skipped_for(&self, reason: SkipReason) -> usize {
+        self.skipped.iter().filter(|(_, skipped)| *skipped == reason).count()
+    }
+}
+
+/// the statuses of `signatures` at the client's commitment, `MAX_STATUS_BATCH` per call, with the
+/// ones `filter` drops set aside. one status call per batch instead of a `get_transaction` per
+/// signature that was never worth fetching
+pub fn check_signature_statuses(
+    client: &MarinadeClient,
+    signatures: &[Signature],
+    filter: StatusFilter,
+) -> Result<StatusCheck> {
+    let _verbosity = client.options().verbosity.enter();
+    let commitment = client.options().commitment;
+    let mut check = StatusCheck::default();
+    for batch in signatures.chunks(MAX_STATUS_BATCH) {
+        let statuses = client.rpc_client().get_signature_statuses_with_history(batch).map_err(Error::from)?.value;
+        if statuses.len() != batch.len() {
+            return Err(ErrorKind::ShortResponse { requested: batch.len(), returned: statuses.len() }.into());
+        }
+        for (signature, status) in batch.iter().zip(statuses) {
+            let reason = match &status {
+                None => Some(SkipReason::Unknown),
+                Some(status) if status.err.is_some() => Some(SkipReason::Failed),
+                Some(status) if !status.satisfies_commitment(commitment) => Some(SkipReason::Unconfirmed),
+                Some(_) => None,
+            };
+            match reason.filter(|reason| filter.skips(*reason)) {
+                Some(reason) => check.skipped.push((*signature, reason)),
+                None => check.kept.push(CheckedSignature { signature: *signature, slot: status.map(|s| s.slot) }),
+            }
+        }
+    }
+    let failed = check.skipped_for(SkipReason::Failed);
+    let (unknown, unconfirmed) = (check.skipped_for(SkipReason::Unknown), check.skipped_for(SkipReason::Unconfirmed));
+    debug!(kept = check.kept.len(), failed, unknown, unconfirmed, "signature statuses checked");
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{MockFetcher, FIXTURE_BLOCK_TIME};
+    use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+    use std::sync::Arc;
+
+    /// ten signatures at slots 0..10, one second apart
+    fn history() -> (Arc<MockFetcher>, Vec<Signature>) {
+        let signatures: Vec<Signature> = (0..10).map(|_| Signature::new_unique()).collect();
+        let statuses = signatures
+            .iter()
+            .enumerate()
+            .map(|(slot, signature)| RpcConfirmedTransactionStatusWithSignature {
+                signature: signature.to_string(),
+                slot: slot as Slot,
+                err: None,
+                memo: None,
+                block_time: Some(FIXTURE_BLOCK_TIME + slot as i64),
+                confirmation_status: None,
+            })
+            .collect();
+        (Arc::new(MockFetcher::new().with_signatures(MARINADE_STATE_PUBKEY, statuses)), signatures)
+    }
+
+    fn range(start: i64, end: i64) -> SignatureRange {
+        let between = SignatureRange::between(FIXTURE_BLOCK_TIME + start, FIXTURE_BLOCK_TIME + end);
+        SignatureRange { page_size: 3, page_delay: Duration::ZERO, ..between }
+    }
+
+    #[test]
+    fn test_stops_early_without_fetching_the_rest() {
+        let (rpc, signatures) = history();
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let newest: Vec<Signature> =
+            signature_stream(&client, range(0, 100)).take(2).map(|info| info.unwrap().signature).collect();
+        assert_eq!(newest, [signatures[9], signatures[8]]);
+        assert_eq!(rpc.call_count("getSignaturesForAddress"), 1);
+
+        // the start time ends the stream mid-page, sparing the pages behind it
+        let mut stream = signature_stream(&client, range(5, 7));
+        let slots: Vec<Slot> = stream.by_ref().map(|info| info.unwrap().slot).collect();
+        assert_eq!(slots, [7, 6, 5]);
+        assert_eq!(stream.pages(), 2);
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_status_check_in_batches() {
+        use solana_sdk::instruction::InstructionError;
+        use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+        let status = |slot, err: Option<TransactionError>, level| TransactionStatus {
+            slot,
+            confirmations: None,
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status: Some(level),
+        };
+        let failed = Some(TransactionError::InstructionError(0, InstructionError::Custom(1)));
+        // 300 signatures over two batches: every tenth failed, every seventh only processed, the
+        // last five unknown
+        let signatures: Vec<Signature> = (0..300).map(|_| Signature::new_unique()).collect();
+        let mut rpc = MockFetcher::new();
+        for (i, signature) in signatures.iter().enumerate().take(295) {
+            let (err, level) = match i {
+                _ if i % 10 == 0 => (failed.clone(), TransactionConfirmationStatus::Finalized),
+                _ if i % 7 == 0 => (None, TransactionConfirmationStatus::Processed),
+                _ => (None, TransactionConfirmationStatus::Confirmed),
+            };
+            rpc = rpc.with_signature_status(*signature, status(i as Slot, err, level));
+        }
+        let rpc = Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+
+        let check = check_signature_statuses(&client, &signatures, StatusFilter::default()).unwrap();
+        assert_eq!(rpc.call_count("getSignatureStatuses"), 2);
+        let (failed, unconfirmed) = (30, (0..295).filter(|i| i % 10 != 0 && i % 7 == 0).count());
+        assert_eq!(check.skipped_for(SkipReason::Failed), failed);
+        assert_eq!(check.skipped_for(SkipReason::Unconfirmed), unconfirmed);
+        assert_eq!(check.skipped_for(SkipReason::Unknown), 5);
+        assert_eq!(check.kept.len(), 300 - failed - unconfirmed - 5);
+        assert_eq!(check.kept[0], CheckedSignature { signature: signatures[1], slot: Some(1) });
+
+        // keeping the unknown ones, which have no slot to go by
+        let filter = StatusFilter { skip_unknown: false, ..StatusFilter::default() };
+        let check = check_signature_statuses(&client, &signatures, filter).unwrap();
+        assert_eq!((check.skipped.len(), check.kept.len()), (failed, 300 - failed));
+        assert_eq!(check.kept.last(), Some(&CheckedSignature { signature: signatures[299], slot: None }));
+    }
+
+    #[test]
+    fn test_empty_ranges() {
+        let (rpc, _) = history();
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        assert_eq!(signature_stream(&client, range(20, 30)).count(), 0);
+        assert_eq!(signature_stream(&client, range(5, 4)).count(), 0);
+        // an account without any signatures
+        let client = MarinadeClient::builder().rpc_client(MockFetcher::new()).build();
+        let mut stream = signature_stream(&client, SignatureRange::default());
+        assert!(stream.next().is_none());
+        assert_eq!(stream.pages(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! where watchers hand new price snapshots, and `Deduplicated`, which holds back the ones that
+//! barely moved
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use solana_sdk::clock::UnixTimestamp;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::parsers::PriceSnapshot;
+
+/// receives every new snapshot a watcher sees. publishing can't fail from the watcher's side:
+/// a sink deals with its own delivery failures, e.g. by logging or dead-lettering them
+pub trait PriceSink: Send + Sync {
+    fn publish(&self, snapshot: &PriceSnapshot);
+}
+
+impl<F: Fn(&PriceSnapshot) + Send + Sync> PriceSink for F {
+    fn publish(&self, snapshot: &PriceSnapshot) {
+        self(snapshot)
+    }
+}
+
+/// how far a price has to move from the last one let through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeThreshold {
+    Lamports(u64),
+    /// of the last price let through
+    Bps(u64),
+}
+
+impl ChangeThreshold {
+    fn reached(&self, last: u64, price: u64) -> bool {
+        let change = last.abs_diff(price);
+        match *self {
+            Self::Lamports(lamports) => change >= lamports,
+            Self::Bps(bps) => change as u128 * 10_000 >= bps as u128 * last as u128,
+        }
+    }
+}
+
+/// lets a snapshot through when its mint has none let through yet, its price moved by at least
+/// the threshold since the last one, or, with a heartbeat, that one is `max_silence` old by
+/// `fetched_at`. moves are measured from the last snapshot let through, so a slow drift gets
+/// through eventually. a zero threshold lets everything through
+#[derive(Debug)]
+pub struct ChangeFilter {
+    threshold: ChangeThreshold,
+    max_silence: Option<Duration>,
+    // per mint: the price and fetch time of the last snapshot let through
+    last: Mutex<HashMap<Pubkey, (u64, UnixTimestamp)>>,
+}
+
+impl ChangeFilter {
+    pub fn new(threshold: ChangeThreshold) -> Self {
+        Self { threshold, max_silence: None, last: Mutex::default() }
+    }
+
+    /// also let a snapshot through once `max_silence` passed since the last one
+    pub fn with_heartbeat(mut self, max_silence: Duration) -> Self {
+        self.max_silence = Some(max_silence);
+        self
+    }
+
+    /// whether `snapshot` should be emitted, remembering it when it should
+    pub fn admit(&self, snapshot: &PriceSnapshot) -> bool {
+        let mut last = self.last.lock().unwrap();
+        let admitted = match last.get(&snapshot.mint) {
+            None => true,
+            Some(&(price, fetched_at)) => {
+                let silence = snapshot.fetched_at.saturating_sub(fetched_at).max(0) as u64;
+                self.threshold.reached(price, snapshot.price_lamports)
+                    || self.max_silence.is_some_and(|max| silence >= max.as_secs())
+            }
+        };
+        if admitted {
+            last.insert(snapshot.mint, (snapshot.price_lamports, snapshot.fetched_at));
+        }
+        admitted
+    }
+}
+
+/// a sink publishing to `inner` only what its `ChangeFilter` lets through
+pub struct Deduplicated<S> {
+    inner: S,
+    filter: ChangeFilter,
+}
+
+impl<S: PriceSink> Deduplicated<S> {
+    pub fn new(inner: S, filter: ChangeFilter) -> Self {
+        Self { inner, filter }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: PriceSink> PriceSink for Deduplicated<S> {
+    fn publish(&self, snapshot: &PriceSnapshot) {
+        if self.filter.admit(snapshot) {
+            self.inner.publish(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MSOL_MINT_PUBKEY;
+    use crate::parsers::Protocol;
+
+    fn snapshot(fetched_at: UnixTimestamp, price_lamports: u64) -> PriceSnapshot {
+        let snapshot = PriceSnapshot::new(Protocol::Marinade, MSOL_MINT_PUBKEY, price_lamports, 0, 0, fetched_at as u64);
+        PriceSnapshot { fetched_at, ..snapshot }
+    }
+
+    fn emitted(filter: ChangeFilter, prices: &[(UnixTimestamp, u64)]) -> Vec<u64> {
+        let sink = Deduplicated::new(Mutex::new(Vec::new()), filter);
+        for &(fetched_at, price) in prices {
+            sink.publish(&snapshot(fetched_at, price));
+        }
+        let slots = sink.inner().lock().unwrap().clone();
+        slots
+    }
+
+    impl PriceSink for Mutex<Vec<u64>> {
+        fn publish(&self, snapshot: &PriceSnapshot) {
+            self.lock().unwrap().push(snapshot.slot);
+        }
+    }
+
+    #[test]
+    fn test_jitter_is_held_back() {
+        // a slot a second, jittering by up to 3 lamports around 1.2 SOL, then a 1bp jump
+        let mut prices: Vec<_> = (0..10).map(|i| (i, 1_200_000_000 + i as u64 % 4)).collect();
+        prices.extend([(10, 1_200_120_000), (11, 1_200_120_001)]);
+
+        assert_eq!(emitted(ChangeFilter::new(ChangeThreshold::Lamports(1_000)), &prices), vec![0, 10]);
+        assert_eq!(emitted(ChangeFilter::new(ChangeThreshold::Bps(1)), &prices), vec![0, 10]);
+        // the heartbeat lets one through after 4 silent seconds, and counts from it
+        let heartbeat = ChangeFilter::new(ChangeThreshold::Bps(1)).with_heartbeat(Duration::from_secs(4));
+        assert_eq!(emitted(heartbeat, &prices), vec![0, 4, 8, 10]);
+        assert_eq!(emitted(ChangeFilter::new(ChangeThreshold::Lamports(0)), &prices).len(), prices.len());
+    }
+}
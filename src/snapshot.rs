@@ -0,0 +1,130 @@
+//! a full `MarinadeState` as a pretty json file, with where and when it was read, so numbers
+//! can be reproduced later without an archive node
+
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use solana_program::clock::UnixTimestamp;
+use solana_program::pubkey::Pubkey;
+
+use crate::accounts::marinade::{MarinadeState, OperationalConfig};
+use crate::cluster::Cluster;
+
+/// the snapshot format `save_snapshot` writes and `load_snapshot` reads
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    /// format of the file, `SNAPSHOT_VERSION` when written by this crate
+    pub version: u32,
+    /// context slot the state was read at
+    pub slot: u64,
+    /// wall clock time the state was fetched, unix seconds
+    pub fetched_at: UnixTimestamp,
+    /// the rpc url the state came from
+    pub endpoint: String,
+    /// `Cluster::name` of the endpoint's cluster
+    #[serde(default)]
+    pub cluster: String,
+    /// the state account, which tells deployments on the same cluster apart
+    #[serde(default, with = "crate::serde_pubkey")]
+    pub state: Pubkey,
+}
+
+impl SnapshotMetadata {
+    /// metadata for `state` on `cluster`, fetched now
+    pub fn new(cluster: &Cluster, state: Pubkey, slot: u64) -> Self {
+        let fetched_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as UnixTimestamp);
+        Self {
+            version: SNAPSHOT_VERSION,
+            slot,
+            fetched_at,
+            endpoint: cluster.rpc_url().to_string(),
+            cluster: cluster.name().to_string(),
+            state,
+        }
+    }
+
+    /// whether the snapshot was taken of `state` on `cluster`
+    pub fn matches(&self, cluster: &Cluster, state: &Pubkey) -> bool {
+        self.cluster == cluster.name() && self.state == *state
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    metadata: &'a SnapshotMetadata,
+    state: &'a MarinadeState,
+    /// for reading, derived from `state` and ignored by `load_snapshot`
+    operational: OperationalConfig,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    metadata: SnapshotMetadata,
+    state: MarinadeState,
+}
+
+impl MarinadeState {
+    /// write this state and `metadata` to `path` as pretty json
+    pub fn save_snapshot(&self, path: impl AsRef<Path>, metadata: &SnapshotMetadata) -> io::Result<()> {
+        let snapshot = SnapshotRef { metadata, state: self, operational: self.operational() };
+        let json = serde_json::to_string_pretty(&snapshot)?;
+        std::fs::write(path, json)
+    }
+
+    /// read a file written by `save_snapshot`. snapshots of another `SNAPSHOT_VERSION` are
+    /// refused before anything else in them is read
+    pub fn load_snapshot(path: impl AsRef<Path>) -> io::Result<(Self, SnapshotMetadata)> {
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        match json["metadata"]["version"].as_u64() {
+            Some(version) if version == SNAPSHOT_VERSION as u64 => {}
+            version => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported snapshot version {:?}, expected {}", version, SNAPSHOT_VERSION),
+                ))
+            }
+        }
+        let snapshot: Snapshot = serde_json::from_value(json)?;
+        Ok((snapshot.state, snapshot.metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::marinade::parse_marinade_state;
+    use crate::constants::MARINADE_STATE_PUBKEY;
+    use crate::test_utils::{sample_state, state_data, FIXTURE_SLOT};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        // as captured: the state parsed out of account data
+        let state = parse_marinade_state(&state_data(&sample_state())).unwrap();
+        let metadata = SnapshotMetadata::new(&Cluster::MainnetBeta, MARINADE_STATE_PUBKEY, FIXTURE_SLOT);
+        let path = std::env::temp_dir().join(format!("marinade-snapshot-{}.json", std::process::id()));
+        state.save_snapshot(&path, &metadata).unwrap();
+
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(json.contains(&format!("\"msol_mint\": \"{}\"", state.msol_mint)));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let operational = &value["operational"];
+        assert_eq!(operational["operational_sol_account"], state.operational_sol_account.to_string());
+        assert_eq!(operational["rent_exempt_for_token_acc"], state.rent_exempt_for_token_acc);
+        let (loaded, loaded_metadata) = MarinadeState::load_snapshot(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded_metadata, metadata);
+        assert!(loaded_metadata.matches(&Cluster::MainnetBeta, &MARINADE_STATE_PUBKEY));
+        assert!(!loaded_metadata.matches(&Cluster::Devnet, &MARINADE_STATE_PUBKEY));
+        assert_eq!(loaded.msol_price_lamports(), sample_state().msol_price_lamports());
+
+        std::fs::write(&path, json.replace("\"version\": 1", "\"version\": 2")).unwrap();
+        let err = MarinadeState::load_snapshot(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version Some(2)"));
+    }
+}
@@ -0,0 +1,110 @@
+//! `Sol`, an amount of SOL as a 9-decimal fixed-point number, for showing lamport amounts
+//! without going through floats
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+
+/// lamports, displayed and serialized as SOL with all 9 decimals, e.g. `"1.200000000"`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sol(pub u64);
+
+impl Sol {
+    pub fn from_lamports(lamports: u64) -> Self {
+        Self(lamports)
+    }
+
+    pub fn lamports(&self) -> u64 {
+        self.0
+    }
+
+    /// whole SOL, rounded down
+    pub fn whole(&self) -> u64 {
+        self.0 / LAMPORTS_PER_MSOL
+    }
+
+    /// lossy past 2^53 lamports, about 9M SOL; for charts, not sums
+    pub fn as_f64(&self) -> f64 {
+        self.0 as f64 / LAMPORTS_PER_MSOL as f64
+    }
+}
+
+impl fmt::Display for Sol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:09}", self.whole(), self.0 % LAMPORTS_PER_MSOL)
+    }
+}
+
+/// why a string isn't a `Sol`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSolError(String);
+
+impl fmt::Display for ParseSolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid SOL amount {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSolError {}
+
+impl FromStr for Sol {
+    type Err = ParseSolError;
+
+    /// whole SOL with up to 9 decimals, e.g. `1`, `1.5` or `0.000000001`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseSolError(s.to_string());
+        let (whole, fraction) = match s.split_once('.') {
+            Some((_, "")) => return Err(invalid()),
+            Some(parts) => parts,
+            None => (s, ""),
+        };
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !digits(whole) || !digits(fraction) || fraction.len() > 9 {
+            return Err(invalid());
+        }
+        let fraction = match fraction {
+            "" => 0,
+            fraction => fraction.parse::<u64>().map_err(|_| invalid())? * 10u64.pow(9 - fraction.len() as u32),
+        };
+        let whole: u64 = whole.parse().map_err(|_| invalid())?;
+        let lamports = whole.checked_mul(LAMPORTS_PER_MSOL).and_then(|lamports| lamports.checked_add(fraction));
+        lamports.map(Sol).ok_or_else(invalid)
+    }
+}
+
+impl Serialize for Sol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse() {
+        assert_eq!(Sol(1_200_166_666).to_string(), "1.200166666");
+        assert_eq!(Sol(1).to_string(), "0.000000001");
+        assert_eq!(Sol(u64::MAX).to_string(), "18446744073.709551615");
+        for s in ["1.200166666", "0.000000001", "18446744073.709551615", "7.000000000"] {
+            assert_eq!(s.parse::<Sol>().unwrap().to_string(), s);
+        }
+        assert_eq!("1.5".parse(), Ok(Sol(1_500_000_000)));
+        assert_eq!("3".parse(), Ok(Sol(3_000_000_000)));
+        for bad in ["", ".5", "1.", "-1", "1.0000000001", "1e9", "18446744074"] {
+            assert!(bad.parse::<Sol>().is_err(), "{}", bad);
+        }
+        assert_eq!(serde_json::to_string(&Sol(1_500_000_000)).unwrap(), "\"1.500000000\"");
+        assert_eq!(serde_json::from_str::<Sol>("\"1.5\"").unwrap(), Sol(1_500_000_000));
+    }
+}
@@ -0,0 +1,180 @@
+//! the mSOL a transaction minted and burned, read off its token-program instructions instead
+//! of marinade's own accounting: `MintTo` and `Burn`, checked or not, on the mSOL mint, top
+//! level and inner. token-2022 shares those instructions' layout, so they're read for either
+//! program. what the marinade instructions' args account for is checked against the totals
+
+use anchor_spl::token::spl_token::{self, instruction::TokenInstruction};
+
+use crate::accounts::instructions::{
+    decode_args, DepositArgs, MarinadeFinanceInstruction, OrderUnstakeArgs, WithdrawStakeAccountArgs,
+};
+use crate::accounts::marinade::MinimalState;
+use crate::constants::SPL_TOKEN_2022_PROGRAM_ID;
+use crate::deployment::DeploymentConfig;
+use crate::transaction::{ResolvedInstruction, TransactionInput};
+use crate::verbosity::debug;
+
+/// mSOL base units minted and burned by one transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsolSupplyChange {
+    pub minted: u64,
+    pub burned: u64,
+    /// the token instructions moved more or less than the marinade instructions' args account
+    /// for, see `msol_supply_change`
+    pub discrepancy: bool,
+}
+
+impl MsolSupplyChange {
+    /// minted less burned
+    pub fn net(&self) -> i128 {
+        self.minted as i128 - self.burned as i128
+    }
+}
+
+/// the mSOL `tx` minted and burned, nothing for a failed one. a deposit accounts for minting up
+/// to its lamports' worth at `post_state`'s price, a base unit of rounding included, since the
+/// liq pool fills what it can first; an order unstake or a stake account withdrawal for burning
+/// exactly its `msol_amount`. a crank or stake account deposit mints an amount its args don't
+/// carry, so with one in the transaction the mints aren't checked
+pub fn msol_supply_change<'t>(
+    tx: impl Into<TransactionInput<'t>>,
+    deployment: &DeploymentConfig,
+    post_state: &MinimalState,
+) -> MsolSupplyChange {
+    let tx = tx.into();
+    if tx.failed() {
+        return MsolSupplyChange::default();
+    }
+    let instructions = tx.instructions().unwrap_or_default();
+    let (mut minted, mut burned) = (0u64, 0u64);
+    for ix in instructions.iter().filter(|ix| is_token_program(ix)) {
+        match (TokenInstruction::unpack(&ix.data), ix.accounts.first(), ix.accounts.get(1)) {
+            (Ok(TokenInstruction::MintTo { amount } | TokenInstruction::MintToChecked { amount, .. }), Some(mint), _)
+                if *mint == deployment.msol_mint =>
+            {
+                minted = minted.saturating_add(amount)
+            }
+            (Ok(TokenInstruction::Burn { amount } | TokenInstruction::BurnChecked { amount, .. }), _, Some(mint))
+                if *mint == deployment.msol_mint =>
+            {
+                burned = burned.saturating_add(amount)
+            }
+            _ => {}
+        }
+    }
+
+    let (max_minted, expected_burned) = accounted_for(&instructions, deployment, post_state);
+    let discrepancy =
+        max_minted.is_some_and(|max| minted > max) || expected_burned.is_some_and(|expected| burned != expected);
+    if discrepancy {
+        debug!(minted, burned, ?max_minted, ?expected_burned, "msol supply change differs from the instructions");
+    }
+    MsolSupplyChange { minted, burned, discrepancy }
+}
+
+fn is_token_program(ix: &ResolvedInstruction) -> bool {
+    ix.program_id == spl_token::ID || ix.program_id == SPL_TOKEN_2022_PROGRAM_ID
+}
+
+/// the most the marinade instructions let be minted and what they burn, None for either when
+/// an instruction moves an amount its args don't carry
+fn accounted_for(
+    instructions: &[ResolvedInstruction],
+    deployment: &DeploymentConfig,
+    post_state: &MinimalState,
+) -> (Option<u64>, Option<u64>) {
+    let (mut max_minted, mut burned) = (Some(0u64), Some(0u64));
+    let marinade = instructions.iter().filter(|ix| ix.program_id == deployment.program_id);
+    for ix in marinade {
+        let Some(instruction) = MarinadeFinanceInstruction::try_from_data(&ix.data) else {
+            continue;
+        };
+        match instruction {
+            MarinadeFinanceInstruction::Deposit => {
+                let order = decode_args::<DepositArgs>(&ix.data).map(|args| post_state.sol_to_msol(args.lamports) + 1);
+                max_minted = max_minted.zip(order).map(|(max, order)| max.saturating_add(order));
+            }
+            MarinadeFinanceInstruction::OrderUnstake => {
+                let amount = decode_args::<OrderUnstakeArgs>(&ix.data).map(|args| args.msol_amount);
+                burned = burned.zip(amount).map(|(burned, amount)| burned.saturating_add(amount));
+            }
+            MarinadeFinanceInstruction::WithdrawStakeAccount => {
+                let amount = decode_args::<WithdrawStakeAccountArgs>(&ix.data).map(|args| args.msol_amount);
+                burned = burned.zip(amount).map(|(burned, amount)| burned.saturating_add(amount));
+            }
+            MarinadeFinanceInstruction::DepositStakeAccount
+            | MarinadeFinanceInstruction::UpdateActive
+            | MarinadeFinanceInstruction::UpdateDeactivated => max_minted = None,
+            _ => {}
+        }
+    }
+    (max_minted, burned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::LiquidUnstakeArgs;
+    use crate::test_utils::{
+        marinade_call, sample_state, transaction_with, with_inner_instructions, FIXTURE_BLOCK_TIME, FIXTURE_SLOT,
+    };
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::pubkey::Pubkey;
+
+    const DEPLOYMENT: DeploymentConfig = DeploymentConfig::MAINNET;
+
+    #[test]
+    fn test_deposit_mints_within_its_order() {
+        let state = sample_state().minimal();
+        let (lamports, to, authority) = (1_000_000_000, Pubkey::new_unique(), DEPLOYMENT.msol_mint_authority());
+        let roles = [
+            ("msol_mint", DEPLOYMENT.msol_mint),
+            ("mint_to", to),
+            ("msol_mint_authority", authority),
+            ("token_program", spl_token::ID),
+        ];
+        let deposit = marinade_call(MarinadeFinanceInstruction::Deposit, DepositArgs { lamports }, &roles);
+        let tx = |minted: u64| {
+            let mint = DEPLOYMENT.msol_mint;
+            let mint_to = spl_token::instruction::mint_to(&spl_token::ID, &mint, &to, &authority, &[], minted);
+            let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), std::slice::from_ref(&deposit));
+            with_inner_instructions(tx, &[mint_to.unwrap()])
+        };
+
+        let order = state.sol_to_msol(lamports);
+        let change = msol_supply_change(&tx(order), &DEPLOYMENT, &state);
+        assert_eq!(change, MsolSupplyChange { minted: order, burned: 0, discrepancy: false });
+        assert_eq!(change.net(), order as i128);
+        // a fill from the liq pool mints less, never more
+        assert!(!msol_supply_change(&tx(order / 2), &DEPLOYMENT, &state).discrepancy);
+        assert!(msol_supply_change(&tx(order + 2), &DEPLOYMENT, &state).discrepancy);
+
+        let mu = crate::analyze_transaction_with_state(&tx(order + 2), &state).unwrap();
+        assert_eq!((mu.msol_minted, mu.msol_burned, mu.msol_discrepancy), (order + 2, 0, true));
+    }
+
+    #[test]
+    fn test_unstake_burns() {
+        let state = sample_state().minimal();
+        let (msol_amount, from, owner) = (500_000_000, Pubkey::new_unique(), Pubkey::new_unique());
+        let tx = |unstake: &Instruction, burned| {
+            let burn = spl_token::instruction::burn(&spl_token::ID, &from, &DEPLOYMENT.msol_mint, &owner, &[], burned);
+            let tx = transaction_with(FIXTURE_SLOT, None, std::slice::from_ref(unstake));
+            with_inner_instructions(tx, &[burn.unwrap()])
+        };
+        let (mint, token_program) = (("msol_mint", DEPLOYMENT.msol_mint), ("token_program", spl_token::ID));
+
+        // a liquid unstake swaps the mSOL into the liq pool, so a burn alongside it is unaccounted for
+        let roles = [mint, ("get_msol_from", from), ("get_msol_from_authority", owner), token_program];
+        let args = LiquidUnstakeArgs { msol_amount };
+        let liquid = marinade_call(MarinadeFinanceInstruction::LiquidUnstake, args, &roles);
+        let change = msol_supply_change(&tx(&liquid, msol_amount), &DEPLOYMENT, &state);
+        assert_eq!(change, MsolSupplyChange { minted: 0, burned: msol_amount, discrepancy: true });
+        assert_eq!(change.net(), -(msol_amount as i128));
+
+        let roles = [mint, ("burn_msol_from", from), ("burn_msol_authority", owner), token_program];
+        let order = marinade_call(MarinadeFinanceInstruction::OrderUnstake, OrderUnstakeArgs { msol_amount }, &roles);
+        assert!(!msol_supply_change(&tx(&order, msol_amount), &DEPLOYMENT, &state).discrepancy);
+        assert!(msol_supply_change(&tx(&order, msol_amount - 1), &DEPLOYMENT, &state).discrepancy);
+    }
+}
@@ -0,0 +1,240 @@
+//! shared fixtures for unit tests. the byte-level ones build with `--no-default-features`; the
+//! accounts, transactions and the scriptable in-memory `RpcFetcher` in `rpc.rs` need the `rpc`
+//! feature
+// not every helper is used by every test build
+#![allow(dead_code)]
+
+#[cfg(feature = "rpc")]
+mod rpc;
+#[cfg(feature = "rpc")]
+pub use rpc::*;
+
+use anchor_lang::AnchorSerialize;
+use solana_program::clock::{Slot, UnixTimestamp};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+use crate::accounts::lido::{ExchangeRate, LidoState, LIDO_ACCOUNT_TYPE};
+use crate::accounts::marinade::{
+    Fee, FeeCents, LiqPool, MarinadeState, StakeSystem, ValidatorSystem, STATE_DISCRIMINATOR,
+};
+use crate::accounts::spl_stake_pool::{AccountType, Fee as PoolFee, StakePool};
+use crate::constants::STSOL_MINT_PUBKEY;
+
+pub const FIXTURE_SLOT: Slot = 250_000_000;
+/// the amount argument of every instruction `marinade_transaction` builds
+pub const FIXTURE_IX_AMOUNT: u64 = 1_000_000_000;
+pub const FIXTURE_BLOCK_TIME: UnixTimestamp = 1_708_000_000;
+
+/// a mainnet-shaped state snapshot with round numbers so expected values are easy to derive by hand
+pub fn sample_state() -> MarinadeState {
+    MarinadeState {
+        msol_mint: Pubkey::new_unique(),
+        admin_authority: Pubkey::new_unique(),
+        operational_sol_account: Pubkey::new_unique(),
+        treasury_msol_account: Pubkey::new_unique(),
+        reserve_bump_seed: 254,
+        msol_mint_authority_bump_seed: 255,
+        rent_exempt_for_token_acc: 2_039_280,
+        reward_fee: Fee { basis_points: 600 },
+        stake_system: StakeSystem {
+            delayed_unstake_cooling_down: 1_000_000_000_000,
+            slots_for_stake_delta: 18_000,
+            min_stake: 1_000_000_000,
+            ..StakeSystem::default()
+        },
+        validator_system: ValidatorSystem {
+            total_validator_score: 1_000_000,
+            total_active_balance: 7_000_000_000_000_000,
+            ..ValidatorSystem::default()
+        },
+        liq_pool: LiqPool {
+            lp_mint: Pubkey::new_unique(),
+            msol_leg: Pubkey::new_unique(),
+            lp_liquidity_target: 10_000_000_000_000,
+            lp_max_fee: Fee { basis_points: 300 },
+            lp_min_fee: Fee { basis_points: 30 },
+            treasury_cut: Fee { basis_points: 2_500 },
+            ..LiqPool::default()
+        },
+        available_reserve_balance: 400_000_000_000_000,
+        msol_supply: 6_000_000_000_000_000,
+        msol_price: 5_368_709_120,
+        circulating_ticket_count: 100,
+        circulating_ticket_balance: 200_000_000_000_000,
+        lent_from_reserve: 0,
+        min_deposit: 1,
+        min_withdraw: 1,
+        staking_sol_cap: u64::MAX,
+        emergency_cooling_down: 0,
+        pause_authority: Pubkey::new_unique(),
+        paused: false,
+        delayed_unstake_fee: FeeCents { bp_cents: 0 },
+        withdraw_stake_account_fee: FeeCents { bp_cents: 0 },
+        withdraw_stake_account_enabled: true,
+        last_stake_move_epoch: 600,
+        stake_moved: 0,
+        max_stake_moved_per_epoch: Fee { basis_points: 100 },
+    }
+}
+
+/// every numeric field set to a different value, so a wrong offset reads the wrong number
+pub fn distinct_state() -> MarinadeState {
+    let mut state = sample_state();
+    let mut next = 0u64;
+    let mut n = || {
+        next += 1;
+        next * 1_000_003
+    };
+    state.rent_exempt_for_token_acc = n();
+    state.stake_system.delayed_unstake_cooling_down = n();
+    state.stake_system.slots_for_stake_delta = n();
+    state.stake_system.last_stake_delta_epoch = n();
+    state.stake_system.min_stake = n();
+    state.validator_system.total_active_balance = n();
+    state.liq_pool.lp_liquidity_target = n();
+    state.liq_pool.lp_supply = n();
+    state.liq_pool.lent_from_sol_leg = n();
+    state.liq_pool.liquidity_sol_cap = n();
+    state.available_reserve_balance = n();
+    state.msol_supply = n();
+    state.msol_price = n();
+    state.circulating_ticket_count = n();
+    state.circulating_ticket_balance = n();
+    state.lent_from_reserve = n();
+    state.min_deposit = n();
+    state.min_withdraw = n();
+    state.staking_sol_cap = n();
+    state.emergency_cooling_down = n();
+    state.last_stake_move_epoch = n();
+    state.stake_moved = n();
+    state
+}
+
+/// the state snapshots parser tests run against
+pub fn fixture_states() -> Vec<MarinadeState> {
+    vec![sample_state(), distinct_state(), MarinadeState::default()]
+}
+
+/// a stake pool with 7.7M SOL behind 7M pool tokens, i.e. 1.1 SOL per token
+pub fn sample_stake_pool(pool_mint: Pubkey) -> StakePool {
+    StakePool {
+        account_type: AccountType::StakePool,
+        manager: Pubkey::new_unique(),
+        staker: Pubkey::new_unique(),
+        stake_deposit_authority: Pubkey::new_unique(),
+        stake_withdraw_bump_seed: 255,
+        validator_list: Pubkey::new_unique(),
+        reserve_stake: Pubkey::new_unique(),
+        pool_mint,
+        manager_fee_account: Pubkey::new_unique(),
+        token_program_id: TOKEN_PROGRAM_ID,
+        total_lamports: 7_700_000_000_000_000,
+        pool_token_supply: 7_000_000_000_000_000,
+        last_update_epoch: 578,
+        epoch_fee: PoolFee { denominator: 100, numerator: 4 },
+        sol_deposit_fee: PoolFee { denominator: 1000, numerator: 1 },
+        preferred_deposit_validator_vote_address: Some(Pubkey::new_unique()),
+        last_epoch_pool_token_supply: 6_990_000_000_000_000,
+        last_epoch_total_lamports: 7_680_000_000_000_000,
+        ..StakePool::default()
+    }
+}
+
+/// a lido state with 10.5M SOL behind 9M stSOL
+pub fn sample_lido_state() -> LidoState {
+    LidoState {
+        account_type: LIDO_ACCOUNT_TYPE,
+        lido_version: 2,
+        manager: Pubkey::new_from_array([7; 32]),
+        st_sol_mint: STSOL_MINT_PUBKEY,
+        exchange_rate: ExchangeRate {
+            computed_in_epoch: 578,
+            st_sol_supply: 9_000_000_000_000_000,
+            sol_balance: 10_500_000_000_000_000,
+        },
+    }
+}
+
+/// the borsh layout of a state, as `parse_marinade_state` reads it
+pub fn state_data(state: &MarinadeState) -> Vec<u8> {
+    state.try_to_vec().expect("fixture state serializes")
+}
+
+/// the data of a state account as the node serves it, the layout behind `STATE_DISCRIMINATOR`
+pub fn state_account_data(state: &MarinadeState) -> Vec<u8> {
+    [&STATE_DISCRIMINATOR[..], &state_data(state)].concat()
+}
+
+/// the bytes of a lido state account; the bytes after the exchange rate are left zeroed
+pub fn lido_data(state: &LidoState) -> Vec<u8> {
+    let mut data = state.try_to_vec().expect("fixture lido state serializes");
+    data.resize(LIDO_ACCOUNT_LEN, 0);
+    data
+}
+
+// room for the fields after the exchange rate, which the parser skips
+const LIDO_ACCOUNT_LEN: usize = 512;
+
+
+/// the bytes of a stake pool account, padded like on-chain ones are
+pub fn stake_pool_data(pool: &StakePool) -> Vec<u8> {
+    let mut data = pool.try_to_vec().expect("fixture pool serializes");
+    data.resize(data.len() + 64, 0);
+    data
+}
+
+const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// byte strings for the adversarial parser tests, the same every run so a failure reproduces:
+/// `corpus` as it is, then `count` inputs cut short, bit-flipped, overwritten, spliced into one
+/// another and run long from it, then noise of every length up to `max_noise`
+pub fn adversarial_inputs(corpus: &[Vec<u8>], count: usize, max_noise: usize) -> Vec<Vec<u8>> {
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let mut inputs = corpus.to_vec();
+    for _ in 0..count {
+        let mut input = corpus[rng.below(corpus.len())].clone();
+        for _ in 0..=rng.below(3) {
+            match rng.below(5) {
+                0 => input.truncate(rng.below(input.len() + 1)),
+                1 if !input.is_empty() => {
+                    let i = rng.below(input.len());
+                    input[i] ^= 1 << rng.below(8);
+                }
+                // the values a length, a bool or an enum tag goes wrong with
+                2 if !input.is_empty() => {
+                    let i = rng.below(input.len());
+                    input[i] = [0, 1, 2, 0x7f, 0x80, 0xff][rng.below(6)];
+                }
+                3 => {
+                    let other = &corpus[rng.below(corpus.len())];
+                    let at = rng.below(input.len() + 1).min(other.len());
+                    input.truncate(at);
+                    input.extend_from_slice(&other[at..]);
+                }
+                _ => {
+                    let len = rng.below(64);
+                    input.extend((0..len).map(|_| rng.next() as u8));
+                }
+            }
+        }
+        inputs.push(input);
+    }
+    inputs.extend((0..=max_noise).map(|len| (0..len).map(|_| rng.next() as u8).collect()));
+    inputs
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
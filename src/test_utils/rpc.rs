@@ -0,0 +1,911 @@
+//! the account and transaction fixtures, and a scriptable in-memory `RpcFetcher`
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::AnchorSerialize;
+use anchor_spl::token::spl_token;
+use anchor_spl::token::spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use solana_account_decoder::parse_token::UiTokenAmount;
+use solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding, UiDataSliceConfig};
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig};
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP, JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    JSON_RPC_SERVER_ERROR_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION,
+};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_client::rpc_response::{Response, RpcConfirmedTransactionStatusWithSignature, RpcResponseContext, RpcResult};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::epoch_info::EpochInfo;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::{v0, Message, VersionedMessage};
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{Transaction, TransactionVersion, VersionedTransaction};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, EncodedTransactionWithStatusMeta,
+    TransactionBinaryEncoding, TransactionStatus, UiCompiledInstruction, UiConfirmedBlock, UiInnerInstructions,
+    UiInstruction, UiLoadedAddresses, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+use super::{lido_data, stake_pool_data, state_account_data, FIXTURE_IX_AMOUNT, FIXTURE_SLOT};
+use crate::accounts::instructions::MarinadeFinanceInstruction;
+use crate::accounts::marinade::MarinadeState;
+use crate::accounts::spl_stake_pool::StakePool;
+use crate::accounts::stake::STAKE_ACCOUNT_LEN;
+use crate::accounts::lido::LidoState;
+use crate::constants::{LIDO_PROGRAM_ID, SPL_STAKE_POOL_PROGRAM_ID};
+use crate::deployment::DeploymentConfig;
+use crate::rpc::RpcFetcher;
+use crate::valuation::ValuationAddresses;
+
+pub fn state_account(state: &MarinadeState) -> Account {
+    let data = state_account_data(state);
+    Account { lamports: 1_000_000_000, data, owner: Pubkey::new_unique(), executable: false, rent_epoch: 0 }
+}
+
+pub fn lido_account(state: &LidoState) -> Account {
+    Account { lamports: 1_000_000_000, data: lido_data(state), owner: LIDO_PROGRAM_ID, executable: false, rent_epoch: 0 }
+}
+
+/// a delegated stake account; `deactivation_epoch` is `u64::MAX` for stake that isn't deactivating
+pub fn stake_account(staker: &Pubkey, withdrawer: &Pubkey, stake: u64, activation_epoch: u64, deactivation_epoch: u64) -> Account {
+    use solana_sdk::stake::state::{Authorized, Delegation, Meta, Stake, StakeState};
+    let rent_exempt_reserve = 2_282_880;
+    let state = StakeState::Stake(
+        Meta { rent_exempt_reserve, authorized: Authorized { staker: *staker, withdrawer: *withdrawer }, ..Meta::default() },
+        Stake {
+            delegation: Delegation { voter_pubkey: Pubkey::new_unique(), stake, activation_epoch, deactivation_epoch, ..Delegation::default() },
+            credits_observed: 0,
+        },
+    );
+    let mut data = state.try_to_vec().expect("fixture stake serializes");
+    data.resize(STAKE_ACCOUNT_LEN, 0);
+    Account { lamports: stake + rent_exempt_reserve, data, owner: solana_sdk::stake::program::id(), executable: false, rent_epoch: 0 }
+}
+
+/// an empty stake history: every delegation is treated as fully warmed up/cooled down
+/// one epoch after it started
+pub fn stake_history_account() -> Account {
+    solana_sdk::account::create_account_for_test(&solana_sdk::stake_history::StakeHistory::default())
+}
+
+/// a stake pool account, padded like on-chain ones are
+pub fn stake_pool_account(pool: &StakePool) -> Account {
+    let data = stake_pool_data(pool);
+    Account { lamports: 1_000_000_000, data, owner: SPL_STAKE_POOL_PROGRAM_ID, executable: false, rent_epoch: 0 }
+}
+
+fn packed_account<T: Pack>(value: T, owner: Pubkey) -> Account {
+    let mut data = vec![0u8; T::LEN];
+    value.pack_into_slice(&mut data);
+    Account { lamports: 2_039_280, data, owner, executable: false, rent_epoch: 0 }
+}
+
+/// an initialized 9-decimal spl mint
+pub fn mint_account(supply: u64) -> Account {
+    let mint = Mint {
+        mint_authority: COption::Some(Pubkey::new_unique()),
+        supply,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    packed_account(mint, spl_token::ID)
+}
+
+/// the deployment's mSOL mint as `verify_msol_mint` expects it
+pub fn msol_mint_account(deployment: &crate::deployment::DeploymentConfig, supply: u64) -> Account {
+    let mint = Mint {
+        mint_authority: COption::Some(deployment.msol_mint_authority()),
+        supply,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    };
+    packed_account(mint, spl_token::ID)
+}
+
+pub fn token_account(mint: Pubkey, amount: u64) -> Account {
+    let account = TokenAccount {
+        mint,
+        owner: Pubkey::new_unique(),
+        amount,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    };
+    packed_account(account, spl_token::ID)
+}
+
+/// a transaction wrapper with an empty message; enough for code paths that only look at slot/block_time
+pub fn sample_transaction(slot: Slot, block_time: Option<UnixTimestamp>) -> EncodedConfirmedTransactionWithStatusMeta {
+    EncodedConfirmedTransactionWithStatusMeta {
+        slot,
+        transaction: EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Binary(String::new(), TransactionBinaryEncoding::Base64),
+            meta: None,
+            version: None,
+        },
+        block_time,
+    }
+}
+
+/// a base64-encoded transaction calling the mainnet marinade program once per entry of `ixs`,
+/// each with a single u64 argument
+pub fn marinade_transaction(
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+    ixs: &[MarinadeFinanceInstruction],
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    marinade_transaction_for(&DeploymentConfig::MAINNET, slot, block_time, ixs)
+}
+
+/// same as `marinade_transaction`, against a given deployment
+pub fn marinade_transaction_for(
+    deployment: &DeploymentConfig,
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+    ixs: &[MarinadeFinanceInstruction],
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    transaction_with(slot, block_time, &fixture_instructions(deployment, ixs))
+}
+
+/// `marinade_transaction` as a v0 message, its state account loaded from a lookup table rather
+/// than among the static keys
+pub fn v0_marinade_transaction(
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+    ixs: &[MarinadeFinanceInstruction],
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    let deployment = DeploymentConfig::MAINNET;
+    let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![deployment.state] };
+    let instructions = fixture_instructions(&deployment, ixs);
+    let message = v0::Message::try_compile(&Pubkey::new_unique(), &instructions, &[table], Hash::default())
+        .expect("fixture message compiles");
+    let tx = VersionedTransaction { signatures: vec![Signature::default()], message: VersionedMessage::V0(message) };
+
+    let mut encoded = sample_transaction(slot, block_time);
+    encoded.transaction.transaction =
+        EncodedTransaction::Binary(base64::encode(bincode::serialize(&tx).unwrap()), TransactionBinaryEncoding::Base64);
+    let mut encoded = with_inner_instructions(encoded, &[]);
+    encoded.transaction.version = Some(TransactionVersion::Number(0));
+    let meta = encoded.transaction.meta.as_mut().unwrap();
+    let loaded = UiLoadedAddresses { writable: vec![deployment.state.to_string()], readonly: Vec::new() };
+    meta.loaded_addresses = OptionSerializer::Some(loaded);
+    encoded
+}
+
+/// one call per entry of `ixs`, each passing the deployment's state
+fn fixture_instructions(deployment: &DeploymentConfig, ixs: &[MarinadeFinanceInstruction]) -> Vec<Instruction> {
+    ixs.iter()
+        .map(|ix| {
+            let mut data = ix.discriminator().to_vec();
+            data.extend_from_slice(&FIXTURE_IX_AMOUNT.to_le_bytes());
+            Instruction::new_with_bytes(deployment.program_id, &data, vec![AccountMeta::new(deployment.state, false)])
+        })
+        .collect()
+}
+
+/// a marinade instruction with `args` after the discriminator, passing the `named` accounts
+/// for their roles (see `MarinadeFinanceInstruction::account_roles`) and fresh keys for the rest
+pub fn marinade_call(
+    instruction: MarinadeFinanceInstruction,
+    args: impl AnchorSerialize,
+    named: &[(&str, Pubkey)],
+) -> Instruction {
+    let mut data = instruction.discriminator().to_vec();
+    data.extend(args.try_to_vec().expect("fixture args serialize"));
+    let accounts = instruction
+        .account_roles()
+        .iter()
+        .map(|role| match named.iter().find(|(name, _)| name == role) {
+            Some((_, pubkey)) => AccountMeta::new(*pubkey, false),
+            None => AccountMeta::new_readonly(Pubkey::new_unique(), false),
+        })
+        .collect();
+    Instruction { program_id: DeploymentConfig::MAINNET.program_id, accounts, data }
+}
+
+/// an unsigned tx carrying `instructions`, paid for by a fresh key
+pub fn transaction_with(
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+    instructions: &[Instruction],
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    let payer = Pubkey::new_unique();
+    let tx = VersionedTransaction::from(Transaction::new_unsigned(Message::new(instructions, Some(&payer))));
+
+    let mut encoded = sample_transaction(slot, block_time);
+    encoded.transaction.transaction =
+        EncodedTransaction::Binary(base64::encode(bincode::serialize(&tx).unwrap()), TransactionBinaryEncoding::Base64);
+    encoded
+}
+
+/// record `inner` as cpis of the first top-level instruction. their program ids and accounts
+/// must already be among the tx's account keys
+pub fn with_inner_instructions(
+    mut tx: EncodedConfirmedTransactionWithStatusMeta,
+    inner: &[Instruction],
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    let keys = crate::transaction::transaction_account_keys(&tx).expect("fixture transaction decodes");
+    let index_of = |key: &Pubkey| keys.iter().position(|k| k == key).expect("inner instruction key is in the message") as u8;
+    let instructions = inner
+        .iter()
+        .map(|ix| {
+            UiInstruction::Compiled(UiCompiledInstruction {
+                program_id_index: index_of(&ix.program_id),
+                accounts: ix.accounts.iter().map(|meta| index_of(&meta.pubkey)).collect(),
+                data: bs58::encode(&ix.data).into_string(),
+                stack_height: Some(2),
+            })
+        })
+        .collect();
+    tx.transaction.meta = Some(UiTransactionStatusMeta {
+        err: None,
+        status: Ok(()),
+        fee: 5_000,
+        pre_balances: Vec::new(),
+        post_balances: Vec::new(),
+        inner_instructions: OptionSerializer::Some(vec![UiInnerInstructions { index: 0, instructions }]),
+        log_messages: OptionSerializer::None,
+        pre_token_balances: OptionSerializer::None,
+        post_token_balances: OptionSerializer::None,
+        rewards: OptionSerializer::None,
+        loaded_addresses: OptionSerializer::Skip,
+        return_data: OptionSerializer::Skip,
+        compute_units_consumed: OptionSerializer::Skip,
+    });
+    tx
+}
+
+/// record the `pre`/`post` token balances of `account`, which must already be among the tx's
+/// account keys. keeps whatever else the meta holds, other accounts' token balances included
+pub fn with_token_balance(
+    mut tx: EncodedConfirmedTransactionWithStatusMeta,
+    account: &Pubkey,
+    mint: &Pubkey,
+    pre: u64,
+    post: u64,
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    let keys = crate::transaction::transaction_account_keys(&tx).expect("fixture transaction decodes");
+    let account_index = keys.iter().position(|key| key == account).expect("token account is in the message") as u8;
+    let balance = |amount: u64| UiTransactionTokenBalance {
+        account_index,
+        mint: mint.to_string(),
+        ui_token_amount: UiTokenAmount {
+            ui_amount: Some(amount as f64 / 1e9),
+            decimals: 9,
+            amount: amount.to_string(),
+            ui_amount_string: (amount as f64 / 1e9).to_string(),
+        },
+        owner: OptionSerializer::None,
+        program_id: OptionSerializer::Some(spl_token::ID.to_string()),
+    };
+    let meta = tx.transaction.meta.get_or_insert_with(empty_meta);
+    for (balances, amount) in [(&mut meta.pre_token_balances, pre), (&mut meta.post_token_balances, post)] {
+        let mut kept: Vec<_> = match std::mem::replace(balances, OptionSerializer::None) {
+            OptionSerializer::Some(kept) => kept.into_iter().filter(|b| b.account_index != account_index).collect(),
+            _ => Vec::new(),
+        };
+        kept.push(balance(amount));
+        *balances = OptionSerializer::Some(kept);
+    }
+    tx
+}
+
+/// record the `pre`/`post` lamports of `account`, which must already be among the tx's account
+/// keys. every other key gets 0, unless an earlier call set it
+pub fn with_balance(
+    mut tx: EncodedConfirmedTransactionWithStatusMeta,
+    account: &Pubkey,
+    pre: u64,
+    post: u64,
+) -> EncodedConfirmedTransactionWithStatusMeta {
+    let keys = crate::transaction::transaction_account_keys(&tx).expect("fixture transaction decodes");
+    let index = keys.iter().position(|key| key == account).expect("account is in the message");
+    let meta = tx.transaction.meta.get_or_insert_with(empty_meta);
+    for (balances, lamports) in [(&mut meta.pre_balances, pre), (&mut meta.post_balances, post)] {
+        balances.resize(keys.len(), 0);
+        balances[index] = lamports;
+    }
+    tx
+}
+
+/// a successful tx's meta with nothing in it but the fee
+fn empty_meta() -> UiTransactionStatusMeta {
+    UiTransactionStatusMeta {
+        err: None,
+        status: Ok(()),
+        fee: 5_000,
+        pre_balances: Vec::new(),
+        post_balances: Vec::new(),
+        inner_instructions: OptionSerializer::None,
+        log_messages: OptionSerializer::None,
+        pre_token_balances: OptionSerializer::None,
+        post_token_balances: OptionSerializer::None,
+        rewards: OptionSerializer::None,
+        loaded_addresses: OptionSerializer::Skip,
+        return_data: OptionSerializer::Skip,
+        compute_units_consumed: OptionSerializer::Skip,
+    }
+}
+
+/// a block as `getBlock` returns it with full details, holding `transactions` as they are
+pub fn block_of(transactions: &[EncodedConfirmedTransactionWithStatusMeta]) -> UiConfirmedBlock {
+    UiConfirmedBlock {
+        previous_blockhash: String::new(),
+        blockhash: String::new(),
+        parent_slot: transactions.first().map_or(FIXTURE_SLOT, |tx| tx.slot) - 1,
+        transactions: Some(transactions.iter().map(|tx| tx.transaction.clone()).collect()),
+        signatures: None,
+        rewards: None,
+        block_time: transactions.first().and_then(|tx| tx.block_time),
+        block_height: None,
+    }
+}
+
+/// a stake or validator list account holding `items`, each padded to `item_size`
+pub fn list_account<T: AnchorSerialize>(items: &[T], item_size: usize) -> Account {
+    let mut data = vec![0u8; crate::accounts::lists::LIST_HEADER_LEN];
+    for item in items {
+        let mut bytes = item.try_to_vec().expect("fixture list item serializes");
+        bytes.resize(item_size, 0);
+        data.extend(bytes);
+    }
+    Account { lamports: 1_000_000_000, data, owner: Pubkey::new_unique(), executable: false, rent_epoch: 0 }
+}
+
+/// what a `TraceRecorder` saw: a span or an event, its name/message and its fields
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub kind: &'static str,
+    pub level: tracing::Level,
+    pub name: String,
+    pub fields: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct FieldVisitor(HashMap<String, String>);
+
+impl tracing::field::Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+}
+
+/// tracing-subscriber layer that records spans and events for assertions
+#[derive(Default, Clone)]
+pub struct TraceRecorder(std::sync::Arc<Mutex<Vec<Recorded>>>);
+
+impl TraceRecorder {
+    pub fn records(&self) -> Vec<Recorded> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn span(&self, name: &str) -> Option<Recorded> {
+        self.records().into_iter().find(|r| r.kind == "span" && r.name == name)
+    }
+
+    pub fn event(&self, message: &str) -> Option<Recorded> {
+        self.records().into_iter().find(|r| r.kind == "event" && r.name == message)
+    }
+
+    /// run `f` with this recorder as the thread's subscriber
+    pub fn capture<T>(&self, f: impl FnOnce() -> T) -> T {
+        use tracing_subscriber::layer::SubscriberExt;
+        let subscriber = tracing_subscriber::registry().with(self.clone());
+        tracing::subscriber::with_default(subscriber, f)
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for TraceRecorder {
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        _id: &tracing::span::Id,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        self.0.lock().unwrap().push(Recorded {
+            kind: "span",
+            level: *attrs.metadata().level(),
+            name: attrs.metadata().name().to_string(),
+            fields: visitor.0,
+        });
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let name = visitor.0.remove("message").unwrap_or_default();
+        self.0.lock().unwrap().push(Recorded { kind: "event", level: *event.metadata().level(), name, fields: visitor.0 });
+    }
+}
+
+/// the bytes a `data_slice` asks for, like the node cuts them
+fn slice(mut account: Account, data_slice: Option<UiDataSliceConfig>) -> Account {
+    if let Some(UiDataSliceConfig { offset, length }) = data_slice {
+        let start = offset.min(account.data.len());
+        account.data = account.data[start..(start + length).min(account.data.len())].to_vec();
+    }
+    account
+}
+
+/// the account as `RpcClient` decodes it from the encoding asked for, None for data that doesn't
+/// decompress like with `RpcClient`
+fn over_the_wire(pubkey: &Pubkey, account: Account, config: &RpcAccountInfoConfig, zstd: MockZstd) -> Option<Account> {
+    let encoding = config.encoding.unwrap_or(UiAccountEncoding::Base64);
+    let mut ui_account = UiAccount::encode(pubkey, &account, encoding, None, None);
+    if zstd == MockZstd::Corrupt && encoding == UiAccountEncoding::Base64Zstd {
+        ui_account.data = UiAccountData::Binary(base64::encode(b"not zstd"), encoding);
+    }
+    ui_account.decode()
+}
+
+fn mock_error(msg: &str) -> ClientError {
+    ClientError::from(ClientErrorKind::Custom(msg.to_string()))
+}
+
+fn response_error(code: i64, message: String) -> ClientError {
+    ClientError::from(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+        code,
+        message,
+        data: RpcResponseErrorData::Empty,
+    }))
+}
+
+/// what the node answers for a slot it hasn't reached
+fn min_context_slot_not_reached(context_slot: Slot) -> ClientError {
+    let message = format!("Minimum context slot has not been reached, context slot {context_slot}");
+    response_error(JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, message)
+}
+
+#[derive(Default)]
+struct MockInner {
+    accounts: HashMap<Pubkey, Account>,
+    // the ui transaction type isn't Clone, so keep the json form and rebuild per call
+    transactions: HashMap<Signature, serde_json::Value>,
+    block_times: HashMap<Slot, UnixTimestamp>,
+    // slots without a block, and the first the node still keeps, see `with_skipped_slots`
+    skipped_slots: HashSet<Slot>,
+    first_available_block: Slot,
+    epoch_info: Option<EpochInfo>,
+    // per address, newest first like the node returns them
+    signatures: HashMap<Pubkey, Vec<RpcConfirmedTransactionStatusWithSignature>>,
+    // what `getSignatureStatuses` answers, None for signatures not in here
+    statuses: HashMap<Signature, TransactionStatus>,
+    // calls of a method still to fail, see `fail_next`
+    failures: HashMap<&'static str, usize>,
+    calls: Vec<&'static str>,
+    // the commitment each call that takes one asked for, in call order
+    commitments: Vec<(&'static str, Option<CommitmentConfig>)>,
+    // the slots the node reports, one `getSlot` at a time, see `with_node_slots`
+    node_slots: VecDeque<Slot>,
+    // how account reads answer for base64+zstd, and the encoding each asked for
+    zstd: MockZstd,
+    encodings: Vec<Option<UiAccountEncoding>>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum MockZstd {
+    #[default]
+    Supported,
+    Refused,
+    Corrupt,
+}
+
+impl MockInner {
+    /// record a call to `method`, failing it if `fail_next` asked for that
+    #[allow(clippy::result_large_err)]
+    fn call(&mut self, method: &'static str) -> ClientResult<()> {
+        self.calls.push(method);
+        match self.failures.get_mut(method) {
+            Some(left) if *left > 0 => {
+                *left -= 1;
+                Err(mock_error("injected failure"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// in-memory fetcher; every call is recorded so tests can assert on rpc usage
+#[derive(Default)]
+pub struct MockFetcher {
+    inner: Mutex<MockInner>,
+    latency: Duration,
+    // the most a call sleeps on top of `latency`, see `with_jitter`
+    jitter: Duration,
+    calls_started: AtomicU64,
+    // `getTransaction` of these panics, see `with_panic_on`
+    panics: HashSet<Signature>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+/// counts a call as in flight from the start of the mock's latency until dropped
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl MockFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_account(self, pubkey: Pubkey, account: Account) -> Self {
+        self.inner.lock().unwrap().accounts.insert(pubkey, account);
+        self
+    }
+
+    /// the five accounts a valuation reads at `addresses`: `state`, a sol leg of
+    /// `sol_leg_lamports` rent included, 3 mSOL in the msol leg, 7 LP tokens and the state's mSOL
+    /// supply in a mint `verify_msol_mint` passes. `with_account` afterwards swaps any of them
+    pub fn with_valuation_accounts(
+        self,
+        addresses: &ValuationAddresses,
+        state: &MarinadeState,
+        sol_leg_lamports: u64,
+    ) -> Self {
+        let sol_leg = Account { lamports: sol_leg_lamports, ..Account::default() };
+        self.with_account(addresses.state, state_account(state))
+            .with_account(addresses.liq_pool_sol_leg, sol_leg)
+            .with_account(addresses.liq_pool_msol_leg, token_account(addresses.msol_mint, 3_000_000_000))
+            .with_account(addresses.lp_mint, mint_account(7_000_000_000))
+            .with_account(addresses.msol_mint, msol_mint_account(&DeploymentConfig::MAINNET, state.msol_supply))
+    }
+
+    pub fn with_transaction(self, signature: Signature, tx: EncodedConfirmedTransactionWithStatusMeta) -> Self {
+        let json = serde_json::to_value(tx).expect("fixture transaction serializes");
+        self.inner.lock().unwrap().transactions.insert(signature, json);
+        self
+    }
+
+    pub fn with_block_time(self, slot: Slot, block_time: UnixTimestamp) -> Self {
+        self.inner.lock().unwrap().block_times.insert(slot, block_time);
+        self
+    }
+
+    /// `getBlockTime` of these slots fails like for a slot the leader skipped
+    pub fn with_skipped_slots(self, slots: impl IntoIterator<Item = Slot>) -> Self {
+        self.inner.lock().unwrap().skipped_slots.extend(slots);
+        self
+    }
+
+    /// `getBlockTime` of slots before this one fails like for a block the node has cleaned up
+    pub fn with_first_available_block(self, slot: Slot) -> Self {
+        self.inner.lock().unwrap().first_available_block = slot;
+        self
+    }
+
+    pub fn with_epoch_info(self, epoch_info: EpochInfo) -> Self {
+        self.inner.lock().unwrap().epoch_info = Some(epoch_info);
+        self
+    }
+
+    /// a node at `slots[0]` that moves to the next slot on each `getSlot`, staying at the last.
+    /// account reads answer at the node's slot, and those with a `min_context_slot` beyond it
+    /// fail like on a lagging node
+    pub fn with_node_slots(self, slots: impl IntoIterator<Item = Slot>) -> Self {
+        self.inner.lock().unwrap().node_slots = slots.into_iter().collect();
+        self
+    }
+
+    /// `signatures` in any order; they are served newest (highest slot) first
+    pub fn with_signatures(
+        self,
+        address: Pubkey,
+        mut signatures: Vec<RpcConfirmedTransactionStatusWithSignature>,
+    ) -> Self {
+        signatures.sort_by_key(|status| std::cmp::Reverse(status.slot));
+        self.inner.lock().unwrap().signatures.insert(address, signatures);
+        self
+    }
+
+    /// every call sleeps this long before answering, without holding the mock's lock
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// every call also sleeps a pseudo-random time below `max`, different from call to call but
+    /// the same from run to run
+    pub fn with_jitter(mut self, max: Duration) -> Self {
+        self.jitter = max;
+        self
+    }
+
+    /// `getTransaction` of `signature` panics, like a bug somewhere below the crate would
+    pub fn with_panic_on(mut self, signature: Signature) -> Self {
+        self.panics.insert(signature);
+        self
+    }
+
+    /// fail the next `count` calls of `method`, named like in `calls`
+    pub fn fail_next(&self, method: &'static str, count: usize) {
+        self.inner.lock().unwrap().failures.insert(method, count);
+    }
+
+    /// the most calls that were ever running at the same time
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    fn in_flight(&self) -> InFlight<'_> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        let mut jitter = Duration::ZERO;
+        if !self.jitter.is_zero() {
+            // splitmix64 of the call's number
+            let mut x = self.calls_started.fetch_add(1, Ordering::SeqCst).wrapping_add(0x9e37_79b9_7f4a_7c15);
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            jitter = self.jitter.mul_f64((x ^ (x >> 31)) as f64 / u64::MAX as f64);
+        }
+        std::thread::sleep(self.latency + jitter);
+        InFlight(&self.in_flight)
+    }
+
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.inner.lock().unwrap().calls.clone()
+    }
+
+    pub fn call_count(&self, method: &str) -> usize {
+        self.inner.lock().unwrap().calls.iter().filter(|c| **c == method).count()
+    }
+
+    /// `getSignatureStatuses` answers `status` for `signature`
+    pub fn with_signature_status(self, signature: Signature, status: TransactionStatus) -> Self {
+        self.inner.lock().unwrap().statuses.insert(signature, status);
+        self
+    }
+
+    /// `getAccountInfo` and `getMultipleAccounts` with base64+zstd fail like on a node that
+    /// doesn't support it
+    pub fn without_zstd(self) -> Self {
+        self.inner.lock().unwrap().zstd = MockZstd::Refused;
+        self
+    }
+
+    /// `getAccountInfo` and `getMultipleAccounts` with base64+zstd answer with data that doesn't
+    /// decompress
+    pub fn with_corrupt_zstd(self) -> Self {
+        self.inner.lock().unwrap().zstd = MockZstd::Corrupt;
+        self
+    }
+
+    /// the encoding every `getAccountInfo` and `getMultipleAccounts` asked for
+    pub fn encodings(&self) -> Vec<Option<UiAccountEncoding>> {
+        self.inner.lock().unwrap().encodings.clone()
+    }
+
+    /// the commitment of every call that carries one, by method
+    pub fn commitments(&self) -> Vec<(&'static str, Option<CommitmentConfig>)> {
+        self.inner.lock().unwrap().commitments.clone()
+    }
+}
+
+impl RpcFetcher for MockFetcher {
+    fn get_account_with_config(&self, pubkey: &Pubkey, config: RpcAccountInfoConfig) -> RpcResult<Option<Account>> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getAccountInfo")?;
+        inner.commitments.push(("getAccountInfo", config.commitment));
+        inner.encodings.push(config.encoding);
+        if config.encoding == Some(UiAccountEncoding::Base64Zstd) && inner.zstd == MockZstd::Refused {
+            // json-rpc's invalid params
+            return Err(response_error(-32602, "unsupported encoding: base64+zstd".to_string()));
+        }
+        let node_slot = inner.node_slots.front().copied();
+        if let Some(context_slot) = config.min_context_slot.filter(|slot| node_slot.is_some_and(|node| *slot > node)) {
+            return Err(min_context_slot_not_reached(context_slot));
+        }
+        Ok(Response {
+            context: RpcResponseContext {
+                slot: node_slot.or(config.min_context_slot).unwrap_or(FIXTURE_SLOT),
+                api_version: None,
+            },
+            value: inner
+                .accounts
+                .get(pubkey)
+                .cloned()
+                .and_then(|account| over_the_wire(pubkey, slice(account, config.data_slice), &config, inner.zstd)),
+        })
+    }
+
+    fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[Pubkey],
+        config: RpcAccountInfoConfig,
+    ) -> RpcResult<Vec<Option<Account>>> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getMultipleAccounts")?;
+        inner.commitments.push(("getMultipleAccounts", config.commitment));
+        inner.encodings.push(config.encoding);
+        if config.encoding == Some(UiAccountEncoding::Base64Zstd) && inner.zstd == MockZstd::Refused {
+            return Err(response_error(-32602, "unsupported encoding: base64+zstd".to_string()));
+        }
+        let node_slot = inner.node_slots.front().copied();
+        if let Some(context_slot) = config.min_context_slot.filter(|slot| node_slot.is_some_and(|node| *slot > node)) {
+            return Err(min_context_slot_not_reached(context_slot));
+        }
+        Ok(Response {
+            context: RpcResponseContext {
+                slot: node_slot.or(config.min_context_slot).unwrap_or(FIXTURE_SLOT),
+                api_version: None,
+            },
+            value: pubkeys
+                .iter()
+                .map(|pubkey| {
+                    let account = inner.accounts.get(pubkey).cloned()?;
+                    over_the_wire(pubkey, slice(account, config.data_slice), &config, inner.zstd)
+                })
+                .collect(),
+        })
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let _in_flight = self.in_flight();
+        assert!(!self.panics.contains(signature), "injected panic fetching {}", signature);
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getTransaction")?;
+        inner.commitments.push(("getTransaction", config.commitment));
+        match inner.transactions.get(signature).cloned() {
+            Some(json) => {
+                let tx: EncodedConfirmedTransactionWithStatusMeta =
+                    serde_json::from_value(json).expect("fixture transaction deserializes");
+                // as the node refuses a transaction newer than the client asked for
+                match (&tx.transaction.version, config.max_supported_transaction_version) {
+                    (Some(TransactionVersion::Number(version)), max) if max.is_none_or(|max| *version > max) => {
+                        let message = format!(
+                            "Transaction version ({version}) is not supported by the requesting client. Please try \
+                             the request again with the following configuration parameter: \
+                             \"maxSupportedTransactionVersion\": {version}"
+                        );
+                        Err(response_error(JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION, message))
+                    }
+                    _ => Ok(tx),
+                }
+            }
+            // `RpcClient` fails to deserialize the node's `null` for a transaction it doesn't have
+            None => Err(serde_json::from_value::<EncodedConfirmedTransactionWithStatusMeta>(serde_json::Value::Null)
+                .unwrap_err()
+                .into()),
+        }
+    }
+
+    fn get_block_time(&self, slot: Slot) -> ClientResult<UnixTimestamp> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getBlockTime")?;
+        if slot < inner.first_available_block {
+            let first = inner.first_available_block;
+            let message = format!("Block {slot} cleaned up, does not exist on node. First available block: {first}");
+            return Err(response_error(JSON_RPC_SERVER_ERROR_BLOCK_CLEANED_UP, message));
+        }
+        if inner.skipped_slots.contains(&slot) {
+            let message = format!("Slot {slot} was skipped, or missing due to ledger jump to recent snapshot");
+            return Err(response_error(JSON_RPC_SERVER_ERROR_SLOT_SKIPPED, message));
+        }
+        inner.block_times.get(&slot).copied().ok_or_else(|| mock_error("block not available for slot"))
+    }
+
+    /// owner and `filters` are applied like the rpc node would; results are sorted by pubkey
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getProgramAccounts")?;
+        inner.commitments.push(("getProgramAccounts", config.account_config.commitment));
+        let filters = config.filters.unwrap_or_default();
+        let mut found: Vec<(Pubkey, Account)> = inner
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| {
+                let shared = AccountSharedData::from((*account).clone());
+                filters.iter().all(|filter| filter.allows(&shared))
+            })
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect();
+        found.sort_by_key(|(pubkey, _)| *pubkey);
+        Ok(found)
+    }
+
+    /// the epoch set with `with_epoch_info`, else the one containing `FIXTURE_SLOT`
+    fn get_epoch_info(&self) -> ClientResult<EpochInfo> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getEpochInfo")?;
+        Ok(inner.epoch_info.clone().unwrap_or_else(|| {
+            let schedule = EpochSchedule::without_warmup();
+            let (epoch, slot_index) = schedule.get_epoch_and_slot_index(FIXTURE_SLOT);
+            EpochInfo {
+                epoch,
+                slot_index,
+                slots_in_epoch: schedule.slots_per_epoch,
+                absolute_slot: FIXTURE_SLOT,
+                block_height: FIXTURE_SLOT,
+                transaction_count: None,
+            }
+        }))
+    }
+
+    /// the next of `with_node_slots`, or `FIXTURE_SLOT`
+    fn get_slot_with_commitment(&self, commitment: CommitmentConfig) -> ClientResult<Slot> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getSlot")?;
+        inner.commitments.push(("getSlot", Some(commitment)));
+        if inner.node_slots.len() > 1 {
+            inner.node_slots.pop_front();
+        }
+        Ok(inner.node_slots.front().copied().unwrap_or(FIXTURE_SLOT))
+    }
+
+    /// pages like the node: after `before`, up to `until`, both exclusive, at most `limit`
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getSignaturesForAddress")?;
+        inner.commitments.push(("getSignaturesForAddress", config.commitment));
+        let all = inner.signatures.get(address).cloned().unwrap_or_default();
+        let position = |signature: Signature| all.iter().position(|status| status.signature == signature.to_string());
+        let start = match config.before {
+            Some(before) => position(before).ok_or_else(|| mock_error("before signature not found"))? + 1,
+            None => 0,
+        };
+        let end = config.until.and_then(position).unwrap_or(all.len()).max(start);
+        Ok(all[start..end].iter().take(config.limit.unwrap_or(1000)).cloned().collect())
+    }
+
+    fn get_signature_statuses_with_history(
+        &self,
+        signatures: &[Signature],
+    ) -> RpcResult<Vec<Option<TransactionStatus>>> {
+        let _in_flight = self.in_flight();
+        let mut inner = self.inner.lock().unwrap();
+        inner.call("getSignatureStatuses")?;
+        if signatures.len() > 256 {
+            return Err(response_error(-32602, "Too many inputs provided; max 256".to_string()));
+        }
+        let slot = inner.node_slots.front().copied().unwrap_or(FIXTURE_SLOT);
+        Ok(Response {
+            context: RpcResponseContext { slot, api_version: None },
+            value: signatures.iter().map(|signature| inner.statuses.get(signature).cloned()).collect(),
+        })
+    }
+}
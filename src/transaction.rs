@@ -0,0 +1,763 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION;
+use solana_client::rpc_request::RpcError;
+use solana_sdk::clock::{Slot, UnixTimestamp};
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{TransactionVersion, VersionedTransaction};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, EncodedTransactionWithStatusMeta,
+    TransactionBinaryEncoding, TransactionStatusMeta, TransactionTokenBalance, UiInstruction,
+    UiTransactionStatusMeta, UiTransactionTokenBalance, VersionedConfirmedTransactionWithStatusMeta,
+};
+
+use crate::accounts::instructions::MarinadeFinanceInstruction;
+use crate::error::{Error, ErrorKind, Result};
+use crate::verbosity::debug;
+
+/// the account keys an instruction's indices resolve against: a legacy message's own keys, or a
+/// v0 one's static keys then the addresses it loaded from lookup tables (writable before
+/// readonly, as the runtime orders them)
+pub(crate) fn account_keys<'a>(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    message: &'a VersionedMessage,
+) -> Cow<'a, [Pubkey]> {
+    match message {
+        // lookup tables came with v0, whatever the meta says
+        VersionedMessage::Legacy(message) => Cow::Borrowed(&message.account_keys),
+        VersionedMessage::V0(message) => with_loaded_addresses(tx, &message.account_keys),
+    }
+}
+
+fn with_loaded_addresses<'a>(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    static_keys: &'a [Pubkey],
+) -> Cow<'a, [Pubkey]> {
+    if let Some(meta) = &tx.transaction.meta {
+        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+            if loaded.writable.is_empty() && loaded.readonly.is_empty() {
+                return Cow::Borrowed(static_keys);
+            }
+            let mut keys = static_keys.to_vec();
+            keys.extend(
+                loaded
+                    .writable
+                    .iter()
+                    .chain(loaded.readonly.iter())
+                    .filter_map(|key| Pubkey::from_str(key).ok()),
+            );
+            return Cow::Owned(keys);
+        }
+    }
+    Cow::Borrowed(static_keys)
+}
+
+/// every account a tx references, lookup table addresses included. None when the
+/// transaction payload can't be decoded
+pub fn transaction_account_keys(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<Vec<Pubkey>> {
+    let versioned = tx.transaction.transaction.decode()?;
+    Some(account_keys(tx, &versioned.message).into_owned())
+}
+
+/// the version of a tx's message, None when the payload can't be decoded
+pub fn transaction_version(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<TransactionVersion> {
+    Some(message_version(&tx.transaction.transaction.decode()?.message))
+}
+
+fn message_version(message: &VersionedMessage) -> TransactionVersion {
+    match message {
+        VersionedMessage::Legacy(_) => TransactionVersion::LEGACY,
+        VersionedMessage::V0(_) => TransactionVersion::Number(0),
+    }
+}
+
+/// the `max_supported_transaction_version` that asks the node for transactions up to `max`
+pub(crate) fn max_supported_version(max: &TransactionVersion) -> Option<u8> {
+    match max {
+        TransactionVersion::Legacy(_) => None,
+        TransactionVersion::Number(max) => Some(*max),
+    }
+}
+
+/// `ErrorKind::UnsupportedTransactionVersion` for a tx newer than `max`. legacy is always read
+pub(crate) fn check_version(version: &TransactionVersion, max: &TransactionVersion) -> Result<()> {
+    let TransactionVersion::Number(version) = *version else {
+        return Ok(());
+    };
+    match max_supported_version(max) {
+        Some(max_supported) if version <= max_supported => Ok(()),
+        _ => Err(Error::new(ErrorKind::UnsupportedTransactionVersion { version, max: max.clone() })),
+    }
+}
+
+/// the node's refusal of a tx newer than the `max_supported_transaction_version` asked for, as
+/// `ErrorKind::UnsupportedTransactionVersion`
+pub(crate) fn unsupported_version(e: ClientError, max: &TransactionVersion) -> Error {
+    let version = match e.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code: JSON_RPC_SERVER_ERROR_UNSUPPORTED_TRANSACTION_VERSION,
+            message,
+            ..
+        }) => {
+            // "Transaction version (1) is not supported by the requesting client. ..."
+            let rest = message.strip_prefix("Transaction version (");
+            rest.and_then(|rest| rest.split_once(')')).and_then(|(version, _)| version.parse().ok())
+        }
+        _ => None,
+    };
+    match version {
+        Some(version) => Error::new(ErrorKind::UnsupportedTransactionVersion { version, max: max.clone() }),
+        None => Error::from(e),
+    }
+}
+
+/// what `fetch_transaction` and `analyze_signature` take: a `Signature`, or its base58 string,
+/// which fails with `ErrorKind::InvalidSignature` when it doesn't parse
+pub trait IntoSignature {
+    fn into_signature(self) -> Result<Signature>;
+}
+
+impl IntoSignature for Signature {
+    fn into_signature(self) -> Result<Signature> {
+        Ok(self)
+    }
+}
+
+impl IntoSignature for &Signature {
+    fn into_signature(self) -> Result<Signature> {
+        Ok(*self)
+    }
+}
+
+impl IntoSignature for &str {
+    fn into_signature(self) -> Result<Signature> {
+        Signature::from_str(self)
+            .map_err(|e| Error::new(ErrorKind::InvalidSignature { input: self.to_string(), reason: e.to_string() }))
+    }
+}
+
+impl IntoSignature for &String {
+    fn into_signature(self) -> Result<Signature> {
+        self.as_str().into_signature()
+    }
+}
+
+impl IntoSignature for String {
+    fn into_signature(self) -> Result<Signature> {
+        self.as_str().into_signature()
+    }
+}
+
+/// a transaction to analyze, either as `getTransaction` returns it or in the native types a
+/// geyser plugin or ledger replay hands out, which are read as they are rather than re-encoded
+#[derive(Debug, Clone, Copy)]
+pub enum TransactionInput<'a> {
+    Encoded(&'a EncodedConfirmedTransactionWithStatusMeta),
+    Native {
+        slot: Slot,
+        block_time: Option<UnixTimestamp>,
+        transaction: &'a VersionedTransaction,
+        /// inner instructions, loaded addresses and token balances come from here
+        meta: Option<&'a TransactionStatusMeta>,
+    },
+}
+
+impl<'a> From<&'a EncodedConfirmedTransactionWithStatusMeta> for TransactionInput<'a> {
+    fn from(tx: &'a EncodedConfirmedTransactionWithStatusMeta) -> Self {
+        Self::Encoded(tx)
+    }
+}
+
+impl<'a> From<&'a VersionedConfirmedTransactionWithStatusMeta> for TransactionInput<'a> {
+    fn from(tx: &'a VersionedConfirmedTransactionWithStatusMeta) -> Self {
+        Self::native(tx.slot, tx.block_time, &tx.tx_with_meta.transaction, Some(&tx.tx_with_meta.meta))
+    }
+}
+
+impl<'a> TransactionInput<'a> {
+    pub fn native(
+        slot: Slot,
+        block_time: Option<UnixTimestamp>,
+        transaction: &'a VersionedTransaction,
+        meta: Option<&'a TransactionStatusMeta>,
+    ) -> Self {
+        Self::Native { slot, block_time, transaction, meta }
+    }
+
+    pub fn slot(&self) -> Slot {
+        match self {
+            Self::Encoded(tx) => tx.slot,
+            Self::Native { slot, .. } => *slot,
+        }
+    }
+
+    pub fn block_time(&self) -> Option<UnixTimestamp> {
+        match self {
+            Self::Encoded(tx) => tx.block_time,
+            Self::Native { block_time, .. } => *block_time,
+        }
+    }
+
+    /// see `transaction_signature`
+    pub fn signature(&self) -> Option<Signature> {
+        match self {
+            Self::Encoded(tx) => transaction_signature(tx),
+            Self::Native { transaction, .. } => transaction.signatures.first().copied(),
+        }
+    }
+
+    /// see `transaction_version`
+    pub fn version(&self) -> Option<TransactionVersion> {
+        match self {
+            Self::Encoded(tx) => transaction_version(tx),
+            Self::Native { transaction, .. } => Some(message_version(&transaction.message)),
+        }
+    }
+
+    /// see `transaction_account_keys`
+    pub fn account_keys(&self) -> Option<Vec<Pubkey>> {
+        match self {
+            Self::Encoded(tx) => transaction_account_keys(tx),
+            Self::Native { transaction, meta, .. } => Some(native_account_keys(transaction, *meta)),
+        }
+    }
+
+    /// see `marinade_instructions`
+    pub fn marinade_instructions(&self, program_id: &Pubkey) -> Option<Vec<MarinadeFinanceInstruction>> {
+        match self {
+            Self::Encoded(tx) => marinade_instructions(tx, program_id),
+            Self::Native { transaction, meta, .. } => Some(native_marinade_instructions(transaction, *meta, program_id)),
+        }
+    }
+
+    /// see `marinade_calls`
+    pub fn marinade_calls(&self, program_id: &Pubkey) -> Option<Vec<MarinadeCall>> {
+        match self {
+            Self::Encoded(tx) => marinade_calls(tx, program_id),
+            Self::Native { transaction, meta, .. } => Some(native_marinade_calls(transaction, *meta, program_id)),
+        }
+    }
+
+    /// every instruction, top level then inner. one with undecodable data or an index past
+    /// `account_keys` is left out. None when the transaction payload can't be decoded
+    pub(crate) fn instructions(&self) -> Option<Vec<ResolvedInstruction>> {
+        let keys = self.account_keys()?;
+        let (top, inner): (Vec<CompiledInstruction>, Vec<CompiledInstruction>) = match self {
+            Self::Encoded(tx) => {
+                let top = tx.transaction.transaction.decode()?.message.instructions().to_vec();
+                let inner = match tx.transaction.meta.as_ref().map(|meta| &meta.inner_instructions) {
+                    Some(OptionSerializer::Some(inner)) => {
+                        inner.iter().flat_map(|inner| &inner.instructions).filter_map(compiled_instruction).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                (top, inner)
+            }
+            Self::Native { transaction, meta, .. } => {
+                let inner = meta.and_then(|meta| meta.inner_instructions.as_ref()).into_iter().flatten();
+                let inner = inner.flat_map(|inner| inner.instructions.iter().map(|ix| ix.instruction.clone()));
+                (transaction.message.instructions().to_vec(), inner.collect())
+            }
+        };
+        let key = |index: &u8| keys.get(*index as usize).copied();
+        let resolve = |ix: CompiledInstruction| {
+            let accounts = ix.accounts.iter().map(key).collect::<Option<Vec<Pubkey>>>()?;
+            Some(ResolvedInstruction { program_id: key(&ix.program_id_index)?, accounts, data: ix.data })
+        };
+        Some(top.into_iter().chain(inner).filter_map(resolve).collect())
+    }
+
+    /// whether the meta records an error. a transaction without meta is taken to have landed
+    pub fn failed(&self) -> bool {
+        match self {
+            Self::Encoded(tx) => tx.transaction.meta.as_ref().is_some_and(|meta| meta.err.is_some()),
+            Self::Native { meta, .. } => meta.is_some_and(|meta| meta.status.is_err()),
+        }
+    }
+
+    /// lamports of each account key before the transaction, empty when there's no meta
+    pub fn pre_balances(&self) -> Vec<u64> {
+        match self {
+            Self::Encoded(tx) => tx.transaction.meta.as_ref().map(|meta| meta.pre_balances.clone()).unwrap_or_default(),
+            Self::Native { meta, .. } => meta.map(|meta| meta.pre_balances.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// lamports of each account key after the transaction, empty when there's no meta
+    pub fn post_balances(&self) -> Vec<u64> {
+        match self {
+            Self::Encoded(tx) => tx.transaction.meta.as_ref().map(|meta| meta.post_balances.clone()).unwrap_or_default(),
+            Self::Native { meta, .. } => meta.map(|meta| meta.post_balances.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// token balances before the transaction, empty when the meta has none
+    pub fn pre_token_balances(&self) -> Vec<TransactionTokenBalance> {
+        match self {
+            Self::Encoded(tx) => ui_token_balances(tx.transaction.meta.as_ref().map(|meta| &meta.pre_token_balances)),
+            Self::Native { meta, .. } => meta.and_then(|meta| meta.pre_token_balances.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// token balances after the transaction, empty when the meta has none
+    pub fn post_token_balances(&self) -> Vec<TransactionTokenBalance> {
+        match self {
+            Self::Encoded(tx) => ui_token_balances(tx.transaction.meta.as_ref().map(|meta| &meta.post_token_balances)),
+            Self::Native { meta, .. } => meta.and_then(|meta| meta.post_token_balances.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// an instruction with its program and accounts resolved against the tx's keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ResolvedInstruction {
+    pub(crate) program_id: Pubkey,
+    pub(crate) accounts: Vec<Pubkey>,
+    pub(crate) data: Vec<u8>,
+}
+
+/// an inner instruction as `getTransaction` encodes it, None for a parsed one or undecodable data
+fn compiled_instruction(ix: &UiInstruction) -> Option<CompiledInstruction> {
+    let UiInstruction::Compiled(compiled) = ix else {
+        return None;
+    };
+    match bs58::decode(&compiled.data).into_vec() {
+        Ok(data) => {
+            let accounts = compiled.accounts.clone();
+            Some(CompiledInstruction { program_id_index: compiled.program_id_index, accounts, data })
+        }
+        Err(e) => {
+            debug!(error = %e, "skipping inner instruction with undecodable data");
+            None
+        }
+    }
+}
+
+/// the same keys as `account_keys`
+fn native_account_keys(transaction: &VersionedTransaction, meta: Option<&TransactionStatusMeta>) -> Vec<Pubkey> {
+    match &transaction.message {
+        VersionedMessage::Legacy(message) => message.account_keys.clone(),
+        VersionedMessage::V0(message) => {
+            let mut keys = message.account_keys.clone();
+            if let Some(meta) = meta {
+                keys.extend(meta.loaded_addresses.writable.iter().chain(meta.loaded_addresses.readonly.iter()));
+            }
+            keys
+        }
+    }
+}
+
+fn native_marinade_instructions(
+    transaction: &VersionedTransaction,
+    meta: Option<&TransactionStatusMeta>,
+    program_id: &Pubkey,
+) -> Vec<MarinadeFinanceInstruction> {
+    let keys = native_account_keys(transaction, meta);
+    let is_marinade = |program_id_index: u8| keys.get(program_id_index as usize) == Some(program_id);
+    let inner = meta
+        .and_then(|meta| meta.inner_instructions.as_ref())
+        .into_iter()
+        .flatten()
+        .flat_map(|inner| inner.instructions.iter().map(|ix| &ix.instruction));
+    let found: Vec<MarinadeFinanceInstruction> = transaction
+        .message
+        .instructions()
+        .iter()
+        .chain(inner)
+        .filter(|ix| is_marinade(ix.program_id_index))
+        .filter_map(|ix| MarinadeFinanceInstruction::try_from_data(&ix.data))
+        .collect();
+    debug!(instructions = ?found, "decoded marinade instructions");
+    found
+}
+
+fn native_marinade_calls(
+    transaction: &VersionedTransaction,
+    meta: Option<&TransactionStatusMeta>,
+    program_id: &Pubkey,
+) -> Vec<MarinadeCall> {
+    let keys = native_account_keys(transaction, meta);
+    let inner = meta
+        .and_then(|meta| meta.inner_instructions.as_ref())
+        .into_iter()
+        .flatten()
+        .flat_map(|inner| inner.instructions.iter().map(|ix| &ix.instruction));
+    transaction
+        .message
+        .instructions()
+        .iter()
+        .chain(inner)
+        .filter(|ix| keys.get(ix.program_id_index as usize) == Some(program_id))
+        .filter_map(|ix| MarinadeCall::resolve(&keys, &ix.accounts, ix.data.clone()))
+        .collect()
+}
+
+fn ui_token_balances(balances: Option<&OptionSerializer<Vec<UiTransactionTokenBalance>>>) -> Vec<TransactionTokenBalance> {
+    let Some(OptionSerializer::Some(balances)) = balances else {
+        return Vec::new();
+    };
+    let or_empty = |value: &OptionSerializer<String>| match value {
+        OptionSerializer::Some(value) => value.clone(),
+        _ => String::new(),
+    };
+    balances
+        .iter()
+        .map(|balance| TransactionTokenBalance {
+            account_index: balance.account_index,
+            mint: balance.mint.clone(),
+            ui_token_amount: balance.ui_token_amount.clone(),
+            owner: or_empty(&balance.owner),
+            program_id: or_empty(&balance.program_id),
+        })
+        .collect()
+}
+
+/// a transaction as delivered raw, e.g. by a webhook: the base64 wire bytes and, optionally,
+/// the status meta in the json shape `getTransaction` returns it. the slot and block time
+/// aren't part of either, so the caller passes them along
+pub fn decode_transaction(
+    payload: &str,
+    meta: Option<&str>,
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let bytes = base64::decode(payload.trim()).map_err(|e| invalid_transaction(format!("payload isn't base64: {}", e)))?;
+    decode_transaction_bytes(&bytes, meta, slot, block_time)
+}
+
+/// same as `decode_transaction` for the wire bytes themselves
+pub fn decode_transaction_bytes(
+    bytes: &[u8],
+    meta: Option<&str>,
+    slot: Slot,
+    block_time: Option<UnixTimestamp>,
+) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    if bytes.is_empty() || bytes.len() > PACKET_DATA_SIZE {
+        return Err(invalid_transaction(format!("{} bytes, a transaction is 1 to {}", bytes.len(), PACKET_DATA_SIZE)));
+    }
+    let transaction = EncodedTransaction::Binary(base64::encode(bytes), TransactionBinaryEncoding::Base64);
+    let versioned = transaction
+        .decode()
+        .ok_or_else(|| invalid_transaction("bytes don't deserialize as a versioned transaction".to_string()))?;
+    versioned.sanitize(true).map_err(|e| invalid_transaction(format!("transaction doesn't sanitize: {}", e)))?;
+    let meta = meta
+        .map(serde_json::from_str::<UiTransactionStatusMeta>)
+        .transpose()
+        .map_err(|e| invalid_transaction(format!("meta isn't a transaction status meta: {}", e)))?;
+    let version = Some(versioned.version());
+    Ok(EncodedConfirmedTransactionWithStatusMeta {
+        slot,
+        transaction: EncodedTransactionWithStatusMeta { transaction, meta, version },
+        block_time,
+    })
+}
+
+fn invalid_transaction(reason: String) -> Error {
+    Error::new(ErrorKind::InvalidTransaction { reason })
+}
+
+/// the first signature of a tx, which is its id
+pub fn transaction_signature(tx: &EncodedConfirmedTransactionWithStatusMeta) -> Option<Signature> {
+    tx.transaction.transaction.decode()?.signatures.first().copied()
+}
+
+/// every marinade instruction in a tx, top level first then inner (cpi) instructions.
+/// returns None when the transaction payload can't be decoded at all
+pub fn marinade_instructions(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    program_id: &Pubkey,
+) -> Option<Vec<MarinadeFinanceInstruction>> {
+    let versioned = tx.transaction.transaction.decode()?;
+    let keys = account_keys(tx, &versioned.message);
+
+    let is_marinade = |program_id_index: u8| keys.get(program_id_index as usize) == Some(program_id);
+
+    let mut found: Vec<MarinadeFinanceInstruction> = versioned
+        .message
+        .instructions()
+        .iter()
+        .filter(|ix| is_marinade(ix.program_id_index))
+        .filter_map(|ix| MarinadeFinanceInstruction::try_from_data(&ix.data))
+        .collect();
+
+    if let Some(meta) = &tx.transaction.meta {
+        if let OptionSerializer::Some(inner) = &meta.inner_instructions {
+            for ix in inner.iter().flat_map(|inner| inner.instructions.iter()) {
+                if let UiInstruction::Compiled(compiled) = ix {
+                    if !is_marinade(compiled.program_id_index) {
+                        continue;
+                    }
+                    match bs58::decode(&compiled.data).into_vec() {
+                        Ok(data) => found.extend(MarinadeFinanceInstruction::try_from_data(&data)),
+                        Err(e) => debug!(error = %e, "skipping inner instruction with undecodable data"),
+                    }
+                }
+            }
+        }
+    }
+
+    debug!(instructions = ?found, "decoded marinade instructions");
+    Some(found)
+}
+
+/// a marinade instruction along with what its handler is called with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarinadeCall {
+    pub instruction: MarinadeFinanceInstruction,
+    /// the whole instruction data, discriminator included
+    pub data: Vec<u8>,
+    /// the instruction's accounts, in the order the handler takes them
+    pub accounts: Vec<Pubkey>,
+}
+
+impl MarinadeCall {
+    /// None for data that isn't a marinade instruction or an account index past `keys`
+    fn resolve(keys: &[Pubkey], accounts: &[u8], data: Vec<u8>) -> Option<Self> {
+        let instruction = MarinadeFinanceInstruction::try_from_data(&data)?;
+        let Some(accounts) = accounts.iter().map(|index| keys.get(*index as usize).copied()).collect() else {
+            debug!(?instruction, "skipping instruction with an account index past the keys");
+            return None;
+        };
+        Some(Self { instruction, data, accounts })
+    }
+
+    /// the account passed as `role`, one of `MarinadeFinanceInstruction::account_roles`
+    pub fn account(&self, role: &str) -> Option<Pubkey> {
+        let index = self.instruction.account_roles().iter().position(|name| *name == role)?;
+        self.accounts.get(index).copied()
+    }
+}
+
+/// like `marinade_instructions`, with each instruction's data and accounts
+pub fn marinade_calls(tx: &EncodedConfirmedTransactionWithStatusMeta, program_id: &Pubkey) -> Option<Vec<MarinadeCall>> {
+    let versioned = tx.transaction.transaction.decode()?;
+    let keys = account_keys(tx, &versioned.message);
+    let is_marinade = |program_id_index: u8| keys.get(program_id_index as usize) == Some(program_id);
+
+    let mut found: Vec<MarinadeCall> = versioned
+        .message
+        .instructions()
+        .iter()
+        .filter(|ix| is_marinade(ix.program_id_index))
+        .filter_map(|ix| MarinadeCall::resolve(&keys, &ix.accounts, ix.data.clone()))
+        .collect();
+
+    if let Some(meta) = &tx.transaction.meta {
+        if let OptionSerializer::Some(inner) = &meta.inner_instructions {
+            for ix in inner.iter().flat_map(|inner| inner.instructions.iter()) {
+                if let UiInstruction::Compiled(compiled) = ix {
+                    if !is_marinade(compiled.program_id_index) {
+                        continue;
+                    }
+                    match bs58::decode(&compiled.data).into_vec() {
+                        Ok(data) => found.extend(MarinadeCall::resolve(&keys, &compiled.accounts, data)),
+                        Err(e) => debug!(error = %e, "skipping inner instruction with undecodable data"),
+                    }
+                }
+            }
+        }
+    }
+    Some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction::{Deposit, LiquidUnstake};
+    use crate::client::MarinadeClient;
+    use crate::constants::{MARINADE_PROGRAM_ID, MARINADE_STATE_PUBKEY};
+    use crate::test_utils::{
+        marinade_transaction, sample_state, state_account, v0_marinade_transaction, MockFetcher, FIXTURE_BLOCK_TIME,
+        FIXTURE_SLOT,
+    };
+    use crate::deployment::DeploymentConfig;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_sdk::message::v0::{self, LoadedAddresses, MessageAddressTableLookup};
+    use std::sync::Arc;
+    use solana_transaction_status::{
+        ConfirmedTransactionWithStatusMeta, InnerInstruction, InnerInstructions, TransactionWithStatusMeta,
+        UiTransactionEncoding, VersionedTransactionWithStatusMeta,
+    };
+
+    // as a webhook delivers it
+    const META: &str = r#"{"err":null,"status":{"Ok":null},"fee":5000,"preBalances":[10000,0],"postBalances":[5000,0]}"#;
+
+    #[test]
+    fn test_raw_payload_matches_the_fetched_transaction() {
+        let mut fetched = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[Deposit, LiquidUnstake]);
+        fetched.transaction.meta = Some(serde_json::from_str(META).unwrap());
+        let EncodedTransaction::Binary(payload, _) = fetched.transaction.transaction.clone() else {
+            panic!("fixture is base64");
+        };
+        let decoded = decode_transaction(&payload, Some(META), FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME)).unwrap();
+        let bytes = base64::decode(&payload).unwrap();
+        let from_bytes = decode_transaction_bytes(&bytes, Some(META), FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME)).unwrap();
+
+        let state = sample_state();
+        let signature = Signature::new_unique();
+        let rpc =
+            MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state)).with_transaction(signature, fetched);
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let fetched = client.fetch_transaction(signature).unwrap();
+        for tx in [&decoded, &from_bytes] {
+            let program_id = &MARINADE_PROGRAM_ID;
+            assert_eq!(marinade_instructions(tx, program_id), marinade_instructions(&fetched, program_id));
+            assert_eq!(transaction_account_keys(tx), transaction_account_keys(&fetched));
+            assert_eq!(tx.transaction.meta.as_ref().map(|meta| meta.fee), Some(5000));
+            let offline = crate::analyze_transaction_with_state(tx, &state.minimal()).unwrap();
+            let online = client.analyze_transaction(&fetched).unwrap();
+            // only the fetch knows the slot the state was read at
+            assert_eq!(online.state_slot, Some(FIXTURE_SLOT));
+            let online = crate::MintUnderlying { state_slot: None, ..online };
+            assert_eq!(serde_json::to_value(offline).unwrap(), serde_json::to_value(online).unwrap());
+        }
+
+        let reason = |result: Result<EncodedConfirmedTransactionWithStatusMeta>| match result.unwrap_err().kind() {
+            ErrorKind::InvalidTransaction { reason } => reason.clone(),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert!(reason(decode_transaction("not base64!", None, FIXTURE_SLOT, None)).contains("base64"));
+        let truncated = &bytes[..bytes.len() / 2];
+        assert!(reason(decode_transaction_bytes(truncated, None, FIXTURE_SLOT, None)).contains("deserialize"));
+        assert!(reason(decode_transaction_bytes(&[], None, FIXTURE_SLOT, None)).contains("0 bytes"));
+        assert!(reason(decode_transaction(&payload, Some("{\"fee\":1}"), FIXTURE_SLOT, None)).contains("meta"));
+    }
+
+    #[test]
+    fn test_native_shape_reads_like_the_encoded_one() {
+        let fixture = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[Deposit]);
+        let mut transaction = fixture.transaction.transaction.decode().unwrap();
+        let keys = transaction.message.static_account_keys().to_vec();
+        // as a v0 message, which the loaded address below needs
+        let VersionedMessage::Legacy(legacy) = transaction.message else { panic!("fixture is legacy") };
+        let lookup = MessageAddressTableLookup {
+            account_key: Pubkey::new_unique(),
+            writable_indexes: vec![0],
+            readonly_indexes: vec![],
+        };
+        transaction.message = VersionedMessage::V0(v0::Message {
+            header: legacy.header,
+            account_keys: legacy.account_keys,
+            recent_blockhash: legacy.recent_blockhash,
+            instructions: legacy.instructions,
+            address_table_lookups: vec![lookup],
+        });
+        let program_id_index = keys.iter().position(|key| *key == MARINADE_PROGRAM_ID).unwrap() as u8;
+        let mut data = LiquidUnstake.discriminator().to_vec();
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes());
+        let cpi = CompiledInstruction { program_id_index, accounts: vec![1], data };
+        let balance = |amount: &str| TransactionTokenBalance {
+            account_index: 1,
+            mint: DeploymentConfig::MAINNET.msol_mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 9,
+                amount: amount.to_string(),
+                ui_amount_string: String::new(),
+            },
+            owner: Pubkey::new_unique().to_string(),
+            program_id: String::new(),
+        };
+        let meta = TransactionStatusMeta {
+            inner_instructions: Some(vec![InnerInstructions {
+                index: 0,
+                instructions: vec![InnerInstruction { instruction: cpi, stack_height: Some(2) }],
+            }]),
+            pre_token_balances: Some(vec![balance("0")]),
+            post_token_balances: Some(vec![balance("1000000000")]),
+            loaded_addresses: LoadedAddresses { writable: vec![Pubkey::new_unique()], readonly: vec![] },
+            ..TransactionStatusMeta::default()
+        };
+        let native = VersionedConfirmedTransactionWithStatusMeta {
+            slot: FIXTURE_SLOT,
+            tx_with_meta: VersionedTransactionWithStatusMeta { transaction, meta },
+            block_time: Some(FIXTURE_BLOCK_TIME),
+        };
+        let encoded = ConfirmedTransactionWithStatusMeta {
+            slot: native.slot,
+            tx_with_meta: TransactionWithStatusMeta::Complete(native.tx_with_meta.clone()),
+            block_time: native.block_time,
+        }
+        .encode(UiTransactionEncoding::Base64, Some(0))
+        .unwrap();
+
+        let (from_native, from_encoded) = (TransactionInput::from(&native), TransactionInput::from(&encoded));
+        assert_eq!((from_native.slot(), from_native.block_time()), (FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME)));
+        assert_eq!(from_native.signature(), from_encoded.signature());
+        assert_eq!(from_native.account_keys(), from_encoded.account_keys());
+        assert_eq!(from_native.account_keys().unwrap().len(), keys.len() + 1);
+        let instructions = from_native.marinade_instructions(&MARINADE_PROGRAM_ID);
+        assert_eq!(instructions, Some(vec![Deposit, LiquidUnstake]));
+        assert_eq!(instructions, from_encoded.marinade_instructions(&MARINADE_PROGRAM_ID));
+        assert_eq!(from_native.pre_token_balances(), from_encoded.pre_token_balances());
+        assert_eq!(from_native.post_token_balances(), from_encoded.post_token_balances());
+        assert_eq!(from_native.post_token_balances()[0].ui_token_amount.amount, "1000000000");
+        let calls = from_native.marinade_calls(&MARINADE_PROGRAM_ID).unwrap();
+        assert_eq!(calls, from_encoded.marinade_calls(&MARINADE_PROGRAM_ID).unwrap());
+        assert_eq!((calls[1].instruction, calls[1].accounts.as_slice()), (LiquidUnstake, &keys[1..2]));
+        assert_eq!(calls[1].data[8..], 1_000_000_000u64.to_le_bytes());
+
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let client = MarinadeClient::builder().rpc_client(rpc).build();
+        let value = |result: Result<crate::MintUnderlying>| serde_json::to_value(result.unwrap()).unwrap();
+        assert_eq!(value(client.analyze_transaction(&native)), value(client.analyze_transaction(&encoded)));
+        // both instructions are price neutral, so the analyzer reuses the state for the second
+        let mut analyzer = client.analyzer();
+        analyzer.analyze(&native).unwrap();
+        assert!(analyzer.analyze(&native).unwrap().state_reused);
+    }
+
+    #[test]
+    fn test_legacy_and_v0_read_alike() {
+        // from before v0: no version in the response, and a meta that says nothing was loaded
+        let mut legacy = marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[Deposit]);
+        legacy.transaction.meta = Some(serde_json::from_str(META).unwrap());
+        let v0 = v0_marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[Deposit]);
+        assert_eq!(transaction_version(&legacy), Some(TransactionVersion::LEGACY));
+        assert_eq!(transaction_version(&v0), Some(TransactionVersion::Number(0)));
+        // the v0 state account only resolves through the lookup table
+        let static_keys = v0.transaction.transaction.decode().unwrap().message.static_account_keys().to_vec();
+        assert!(!static_keys.contains(&MARINADE_STATE_PUBKEY));
+        for tx in [&legacy, &v0] {
+            assert!(transaction_account_keys(tx).unwrap().contains(&MARINADE_STATE_PUBKEY));
+            let calls = marinade_calls(tx, &MARINADE_PROGRAM_ID).unwrap();
+            assert_eq!((calls.len(), calls[0].accounts.as_slice()), (1, [MARINADE_STATE_PUBKEY].as_slice()));
+        }
+
+        let (legacy_signature, v0_signature) = (Signature::new_unique(), Signature::new_unique());
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&sample_state()));
+        let rpc = rpc.with_transaction(legacy_signature, legacy);
+        let v0_fetched = v0_marinade_transaction(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[Deposit]);
+        let rpc = rpc.with_transaction(v0_signature, v0_fetched);
+        let rpc = Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let legacy_value = client.analyze_signature(legacy_signature).unwrap();
+        let v0_value = client.analyze_signature(v0_signature).unwrap();
+        assert_eq!(serde_json::to_value(legacy_value).unwrap(), serde_json::to_value(v0_value).unwrap());
+
+        // a client limited to legacy refuses v0, whether the node turns it away or it's handed over
+        let legacy_only = MarinadeClient::builder().rpc_client(rpc.clone());
+        let legacy_only = legacy_only.max_transaction_version(TransactionVersion::LEGACY).build();
+        assert!(legacy_only.analyze_signature(legacy_signature).is_ok());
+        let reads = rpc.call_count("getAccountInfo");
+        let refused = legacy_only.fetch_transaction(v0_signature).unwrap_err();
+        let unsupported = |e: &Error| match e.kind() {
+            ErrorKind::UnsupportedTransactionVersion { version, max } => (*version, max.clone()),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(unsupported(&refused), (0, TransactionVersion::LEGACY));
+        assert_eq!(refused.signature(), Some(&v0_signature));
+        let handed = legacy_only.analyze_transaction(&v0).unwrap_err();
+        assert_eq!(unsupported(&handed), (0, TransactionVersion::LEGACY));
+        assert_eq!(rpc.call_count("getAccountInfo"), reads);
+        let message = "transaction version 0 is newer than legacy, the max configured";
+        assert_eq!(handed.to_string().split(" (").next(), Some(message));
+    }
+}
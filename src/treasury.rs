@@ -0,0 +1,306 @@
+//! protocol revenue from the reward fee. each `update_active` / `update_deactivated` crank
+//! mints `reward_fee` of the rewards it extracts, as mSOL, into the state's
+//! `treasury_msol_account`. `TreasuryAnalyzer` reads that mint off a crank's token balances
+//! and values it at the post-crank price; `TreasuryRevenue` sums accruals over a range.
+//! user instructions pay it fees too, which `ProtocolRevenue` has per transaction
+
+use serde::Serialize;
+use solana_sdk::epoch_schedule::{Epoch, EpochSchedule};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::TransactionTokenBalance;
+
+use crate::accounts::instructions::{decode_args, MarinadeFinanceInstruction, WithdrawStakeAccountArgs};
+use crate::accounts::marinade::MarinadeState;
+use crate::deployment::DeploymentConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::rpc::RpcFetcher;
+use crate::transaction::TransactionInput;
+use crate::unstake::simulate_liquid_unstake;
+use crate::{fetch_full_state, AnalyzeOptions};
+use crate::verbosity::debug;
+
+/// the reward fee one crank paid the treasury
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreasuryAccrual {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub epoch: Epoch,
+    /// the treasury account's mSOL balance change over the crank
+    pub msol_minted: u64,
+    /// lamports per mSOL after the crank
+    pub msol_price_lamports: u64,
+    /// `msol_minted` at `msol_price_lamports`
+    pub lamports_value: u64,
+    pub reward_fee_bps: u32,
+    /// the rewards that would take `lamports_value` at `reward_fee_bps`. None without a fee
+    pub implied_rewards_lamports: Option<u64>,
+}
+
+/// accruals summed, e.g. over a month of cranks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TreasuryRevenue {
+    pub cranks: usize,
+    pub msol_minted: u64,
+    pub lamports_value: u64,
+    /// first and last epoch of the accruals, None when there were none
+    pub epochs: Option<(Epoch, Epoch)>,
+}
+
+impl TreasuryRevenue {
+    pub fn from_accruals(accruals: &[TreasuryAccrual]) -> Self {
+        let epochs = accruals.iter().map(|accrual| accrual.epoch);
+        Self {
+            cranks: accruals.len(),
+            msol_minted: accruals.iter().map(|accrual| accrual.msol_minted).sum(),
+            lamports_value: accruals.iter().map(|accrual| accrual.lamports_value).sum(),
+            epochs: epochs.clone().min().zip(epochs.max()),
+        }
+    }
+}
+
+/// the fees one transaction paid the treasury, see `MarinadeFinanceInstruction::pays_treasury`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ProtocolRevenue {
+    pub msol_amount: u64,
+    /// `msol_amount` at the state's price
+    pub lamports_value: u64,
+}
+
+impl std::iter::Sum for ProtocolRevenue {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, revenue| Self {
+            msol_amount: total.msol_amount + revenue.msol_amount,
+            lamports_value: total.lamports_value + revenue.lamports_value,
+        })
+    }
+}
+
+/// the fees `tx` paid under `state`'s fee parameters and price, nothing for a failed one. a
+/// liquid unstake's fee follows the SOL leg it found, read off the meta; None when it has no
+/// balance for the leg
+pub fn transaction_revenue<'t>(
+    tx: impl Into<TransactionInput<'t>>,
+    state: &MarinadeState,
+    program_id: &Pubkey,
+) -> Option<ProtocolRevenue> {
+    let tx = tx.into();
+    if tx.failed() {
+        return Some(ProtocolRevenue::default());
+    }
+    let (keys, pre) = (tx.account_keys().unwrap_or_default(), tx.pre_balances());
+    let mut msol_amount = 0;
+    for call in tx.marinade_calls(program_id).unwrap_or_default() {
+        msol_amount += match call.instruction {
+            MarinadeFinanceInstruction::LiquidUnstake => {
+                let Some(unstaked) = decode_args::<u64>(&call.data) else { continue };
+                let sol_leg = call.account("liq_pool_sol_leg_pda");
+                let index = keys.iter().position(|key| Some(*key) == sol_leg)?;
+                let sol_leg_lamports = pre.get(index)?.saturating_sub(state.rent_exempt_for_token_acc);
+                simulate_liquid_unstake(state, sol_leg_lamports, 0, unstaked).ok()?.treasury_msol_cut
+            }
+            MarinadeFinanceInstruction::WithdrawStakeAccount => {
+                let Some(args) = decode_args::<WithdrawStakeAccountArgs>(&call.data) else { continue };
+                state.withdraw_stake_account_fee.apply(args.msol_amount)
+            }
+            _ => continue,
+        };
+    }
+    Some(ProtocolRevenue { msol_amount, lamports_value: state.minimal().msol_to_sol(msol_amount) })
+}
+
+/// values the treasury mint of crank transactions, skipping everything else
+pub struct TreasuryAnalyzer<'a> {
+    rpc_client: &'a dyn RpcFetcher,
+    options: AnalyzeOptions,
+    epoch_schedule: EpochSchedule,
+    deployment: DeploymentConfig,
+}
+
+impl<'a> TreasuryAnalyzer<'a> {
+    pub fn new(rpc_client: &'a dyn RpcFetcher, options: AnalyzeOptions) -> Self {
+        Self {
+            rpc_client,
+            options,
+            epoch_schedule: EpochSchedule::without_warmup(),
+            deployment: DeploymentConfig::MAINNET,
+        }
+    }
+
+    pub fn with_epoch_schedule(mut self, epoch_schedule: EpochSchedule) -> Self {
+        self.epoch_schedule = epoch_schedule;
+        self
+    }
+
+    pub fn with_deployment(mut self, deployment: DeploymentConfig) -> Self {
+        self.deployment = deployment;
+        self
+    }
+
+    /// None for a transaction without a crank. fails when the crank's meta has no token
+    /// balance for the treasury account, since the mint can't be read without one
+    pub fn analyze<'t>(&self, tx: impl Into<TransactionInput<'t>>) -> Result<Option<TreasuryAccrual>> {
+        let tx = tx.into();
+        self.analyze_inner(tx).map_err(|e| e.with_signature(tx.signature()))
+    }
+
+    /// `transaction_revenue` at the state as of the transaction's slot, which is only fetched
+    /// when one of its instructions pays the treasury
+    pub fn revenue<'t>(&self, tx: impl Into<TransactionInput<'t>>) -> Result<Option<ProtocolRevenue>> {
+        let tx = tx.into();
+        let program_id = &self.deployment.program_id;
+        let ixs = tx.marinade_instructions(program_id).unwrap_or_default();
+        if tx.failed() || !ixs.iter().any(|ix| ix.pays_treasury()) {
+            return Ok(Some(ProtocolRevenue::default()));
+        }
+        let (_, state) = fetch_full_state(self.rpc_client, &self.deployment.state, Some(tx.slot()), &self.options)
+            .map_err(|e| e.with_signature(tx.signature()))?;
+        let revenue = transaction_revenue(tx, &state, program_id);
+        debug!(?revenue, "protocol revenue");
+        Ok(revenue)
+    }
+
+    fn analyze_inner(&self, tx: TransactionInput<'_>) -> Result<Option<TreasuryAccrual>> {
+        let ixs = tx.marinade_instructions(&self.deployment.program_id).unwrap_or_default();
+        if !ixs.iter().any(|ix| ix.is_crank()) {
+            return Ok(None);
+        }
+        let (_, state) = fetch_full_state(self.rpc_client, &self.deployment.state, Some(tx.slot()), &self.options)?;
+        let treasury = state.treasury_msol_account;
+        let Some(index) = tx.account_keys().and_then(|keys| keys.iter().position(|key| *key == treasury)) else {
+            debug!(%treasury, "crank without the treasury account");
+            return Ok(None);
+        };
+        let balance = |balances: Vec<TransactionTokenBalance>| {
+            balances
+                .into_iter()
+                .find(|balance| balance.account_index as usize == index)
+                .and_then(|balance| balance.ui_token_amount.amount.parse::<u64>().ok())
+        };
+        let (Some(pre), Some(post)) = (balance(tx.pre_token_balances()), balance(tx.post_token_balances())) else {
+            return Err(Error::new(ErrorKind::InvalidTransaction {
+                reason: "no token balances for the treasury msol account".to_string(),
+            })
+            .with_pubkey(treasury)
+            .with_slot(Some(tx.slot())));
+        };
+
+        let msol_minted = post.saturating_sub(pre);
+        let lamports_value = state.minimal().msol_to_sol(msol_minted);
+        let reward_fee_bps = state.reward_fee.basis_points;
+        Ok(Some(TreasuryAccrual {
+            slot: tx.slot(),
+            block_time: tx.block_time(),
+            epoch: self.epoch_schedule.get_epoch(tx.slot()),
+            msol_minted,
+            msol_price_lamports: state.msol_price_lamports(),
+            lamports_value,
+            reward_fee_bps,
+            implied_rewards_lamports: (reward_fee_bps > 0)
+                .then(|| (lamports_value as u128 * 10_000 / reward_fee_bps as u128) as u64),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::instructions::MarinadeFinanceInstruction;
+    use crate::accounts::marinade::MarinadeState;
+    use crate::constants::{MARINADE_PROGRAM_ID, MARINADE_STATE_PUBKEY};
+    use crate::test_utils::{
+        marinade_call, marinade_transaction, sample_state, state_account, transaction_with, with_balance,
+        with_token_balance, MockFetcher, FIXTURE_BLOCK_TIME, FIXTURE_SLOT,
+    };
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+    /// an `update_active` moving the treasury's mSOL balance from `pre` to `post`
+    fn crank(state: &MarinadeState, slot: u64, pre: u64, post: u64) -> EncodedConfirmedTransactionWithStatusMeta {
+        let data = MarinadeFinanceInstruction::UpdateActive.discriminator().to_vec();
+        let accounts =
+            vec![AccountMeta::new(MARINADE_STATE_PUBKEY, false), AccountMeta::new(state.treasury_msol_account, false)];
+        let tx = transaction_with(
+            slot,
+            Some(FIXTURE_BLOCK_TIME),
+            &[Instruction::new_with_bytes(MARINADE_PROGRAM_ID, &data, accounts)],
+        );
+        with_token_balance(tx, &state.treasury_msol_account, &state.msol_mint, pre, post)
+    }
+
+    #[test]
+    fn test_crank_accrual_matches_the_reward_fee() {
+        // a crank extracting 10_000 SOL of rewards at a 600 bp fee mints 600 SOL worth of mSOL
+        // at the post-crank price
+        let state = sample_state();
+        let minted = state.minimal().sol_to_msol(600_000_000_000);
+        assert_eq!(minted, 499_930_565_199);
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let analyzer = TreasuryAnalyzer::new(&rpc, AnalyzeOptions::default());
+
+        let accrual = analyzer.analyze(&crank(&state, FIXTURE_SLOT, 7_000, 7_000 + minted)).unwrap().unwrap();
+        assert_eq!((accrual.msol_minted, accrual.lamports_value), (minted, 599_999_999_999));
+        assert_eq!((accrual.reward_fee_bps, accrual.msol_price_lamports), (600, 1_200_166_666));
+        assert_eq!(accrual.implied_rewards_lamports, Some(9_999_999_999_983));
+        assert_eq!(accrual.epoch, FIXTURE_SLOT / 432_000);
+
+        // deposits don't accrue anything, and aren't fetched for
+        let deposit = marinade_transaction(FIXTURE_SLOT, None, &[MarinadeFinanceInstruction::Deposit]);
+        assert_eq!(analyzer.analyze(&deposit).unwrap(), None);
+        assert_eq!(rpc.calls().len(), 1);
+
+        let revenue = TreasuryRevenue::from_accruals(&[accrual.clone(), accrual]);
+        assert_eq!((revenue.cranks, revenue.msol_minted), (2, 2 * minted));
+        assert_eq!((revenue.lamports_value, revenue.epochs), (1_199_999_999_998, Some((578, 578))));
+    }
+
+    #[test]
+    fn test_crank_without_token_balances_is_an_error() {
+        let state = sample_state();
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let mut tx = crank(&state, FIXTURE_SLOT, 0, 1);
+        tx.transaction.meta = None;
+
+        let err = TreasuryAnalyzer::new(&rpc, AnalyzeOptions::default()).analyze(&tx).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidTransaction { .. }));
+        assert_eq!(err.pubkey(), Some(&state.treasury_msol_account));
+    }
+
+    #[test]
+    fn test_revenue_of_fee_bearing_and_fee_free_transactions() {
+        const SOL: u64 = 1_000_000_000;
+        // at exactly 1 SOL per mSOL, with a 0.5% withdraw_stake_account fee
+        let mut state = sample_state();
+        state.msol_supply = state.total_virtual_staked_lamports();
+        state.withdraw_stake_account_fee.bp_cents = 5_000;
+        let rpc = MockFetcher::new().with_account(MARINADE_STATE_PUBKEY, state_account(&state));
+        let analyzer = TreasuryAnalyzer::new(&rpc, AnalyzeOptions::default());
+
+        let sol_leg = Pubkey::new_unique();
+        let roles = [("liq_pool_sol_leg_pda", sol_leg)];
+        let unstake = marinade_call(MarinadeFinanceInstruction::LiquidUnstake, 1_000 * SOL, &roles);
+        let args = WithdrawStakeAccountArgs {
+            stake_index: 0,
+            validator_index: 0,
+            msol_amount: 100 * SOL,
+            beneficiary: Pubkey::new_unique(),
+        };
+        let withdraw = marinade_call(MarinadeFinanceInstruction::WithdrawStakeAccount, args, &[]);
+        let tx = transaction_with(FIXTURE_SLOT, Some(FIXTURE_BLOCK_TIME), &[unstake, withdraw]);
+        // a leg above the liquidity target, so the min fee of 30 bp, of which the treasury cuts a quarter
+        let pre = 20_000 * SOL + state.rent_exempt_for_token_acc;
+        let tx = with_balance(tx, &sol_leg, pre, pre - 997 * SOL);
+        let revenue = analyzer.revenue(&tx).unwrap().unwrap();
+        assert_eq!(revenue, ProtocolRevenue { msol_amount: 750_000_000 + 500_000_000, lamports_value: 1_250_000_000 });
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+
+        // a deposited stake account pays nothing, and isn't fetched for
+        let deposit = marinade_transaction(FIXTURE_SLOT, None, &[MarinadeFinanceInstruction::DepositStakeAccount]);
+        assert_eq!(analyzer.revenue(&deposit).unwrap(), Some(ProtocolRevenue::default()));
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+        // and without the leg's balance the liquid unstake's fee can't be told
+        let mut tx = tx;
+        tx.transaction.meta = None;
+        assert_eq!(analyzer.revenue(&tx).unwrap(), None);
+        assert_eq!([revenue, revenue].into_iter().sum::<ProtocolRevenue>().msol_amount, 2_500_000_000);
+    }
+}
@@ -0,0 +1,140 @@
+//! TVL sampled over a slot or epoch range, for charting next to the mSOL price. every point
+//! reads the same accounts `get_tvl` does, as of at least the point's slot. a node only serves
+//! its current accounts, so a point it's already well past has nothing of its own to read and
+//! is left missing instead of showing the present as the past
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::{Epoch, Slot, UnixTimestamp};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use tracing::instrument;
+
+use crate::client::MarinadeClient;
+use crate::error::{Error, ErrorKind};
+use crate::valuation::{fetch_valuation_accounts, Tvl, ValuationAccounts};
+use crate::verbosity::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesRange {
+    /// every `step` slots from `start`, up to `end` inclusive
+    Slots { start: Slot, end: Slot },
+    /// the first slot of every `step`th epoch from `start` through `end`, by mainnet's schedule
+    Epochs { start: Epoch, end: Epoch },
+}
+
+impl SeriesRange {
+    /// each point's slot with the slot the next one starts at, ascending. a `step` of 0 is 1
+    pub fn windows(&self, step: u64) -> Vec<(Slot, Slot)> {
+        let step = step.max(1);
+        let (start, end) = match *self {
+            Self::Slots { start, end } | Self::Epochs { start, end } => (start, end),
+        };
+        let slot = |at: u64| match self {
+            Self::Slots { .. } => at,
+            Self::Epochs { .. } => EpochSchedule::without_warmup().get_first_slot_in_epoch(at),
+        };
+        (start..=end).step_by(step as usize).map(|at| (slot(at), slot(at.saturating_add(step)))).collect()
+    }
+}
+
+/// one point of a TVL series
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TvlPoint {
+    /// the slot sampled; the accounts were read at `tvl.slot`, before the next point's
+    pub slot: Slot,
+    /// when `tvl.slot` was produced, if the node could say; not looked up offline
+    pub block_time: Option<UnixTimestamp>,
+    /// None when the point couldn't be read, with why in `missing`
+    pub tvl: Option<Tvl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing: Option<String>,
+}
+
+/// the TVL of `client`'s deployment across `range`, sorted by slot. a point that can't be read
+/// is kept, missing, instead of failing the series. offline every point is missing: the
+/// snapshot cache only holds states, not the liq pool's accounts
+#[instrument(level = "debug", skip(client))]
+pub fn build_tvl_series(client: &MarinadeClient, range: SeriesRange, step: u64) -> Vec<TvlPoint> {
+    let _verbosity = client.options().verbosity.enter();
+    let addresses = client.deployment().valuation_addresses();
+    let offline = client.options().offline;
+    // the last read, which serves the points up to its slot without asking the node again
+    let mut last: Option<ValuationAccounts> = None;
+    let mut series = Vec::new();
+    for (slot, next) in range.windows(step) {
+        let read = match last.take().filter(|accounts| accounts.slot >= slot) {
+            Some(accounts) => Ok(accounts),
+            None if offline => Err(Error::new(ErrorKind::OfflineMiss { slot: Some(slot) })),
+            None => fetch_valuation_accounts(client.rpc_client(), &addresses, Some(slot), client.options()),
+        };
+        let tvl = read.map_err(|e| e.to_string()).and_then(|accounts| {
+            let tvl = Tvl::from_accounts(&accounts);
+            last = Some(accounts);
+            match tvl.slot < next {
+                true => Ok(tvl),
+                false => Err(format!("read at slot {}, past the next point at {}", tvl.slot, next)),
+            }
+        });
+        series.push(match tvl {
+            Ok(tvl) => TvlPoint {
+                slot,
+                block_time: if offline { None } else { client.rpc_client().get_block_time(tvl.slot).ok() },
+                tvl: Some(tvl),
+                missing: None,
+            },
+            Err(reason) => {
+                debug!(slot, %reason, "tvl point missing");
+                TvlPoint { slot, block_time: None, tvl: None, missing: Some(reason) }
+            }
+        });
+    }
+    debug!(points = series.len(), read = series.iter().filter(|point| point.tvl.is_some()).count(), "tvl series built");
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deployment::DeploymentConfig;
+    use crate::test_utils::{sample_state, MockFetcher, FIXTURE_SLOT};
+
+    #[test]
+    fn test_series_keeps_only_points_the_node_can_read() {
+        let state = sample_state();
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let rpc = MockFetcher::new()
+            .with_valuation_accounts(&addresses, &state, 5_000_000_000 + state.rent_exempt_for_token_acc)
+            .with_node_slots([FIXTURE_SLOT + 50])
+            .with_block_time(FIXTURE_SLOT + 50, 1_700_000_000);
+        let rpc = std::sync::Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+
+        let range = SeriesRange::Slots { start: FIXTURE_SLOT - 200, end: FIXTURE_SLOT + 100 };
+        let series = build_tvl_series(&client, range, 100);
+        let slots: Vec<_> = series.iter().map(|point| point.slot).collect();
+        assert_eq!(slots, vec![FIXTURE_SLOT - 200, FIXTURE_SLOT - 100, FIXTURE_SLOT, FIXTURE_SLOT + 100]);
+        // the node only has its current accounts, which belong to the point before them
+        let read: Vec<_> = series.iter().map(|point| point.tvl.map(|tvl| tvl.slot)).collect();
+        assert_eq!(read, vec![None, None, Some(FIXTURE_SLOT + 50), None]);
+        let tvl = series[2].tvl.unwrap();
+        assert_eq!(tvl.total_lamports, state.total_lamports_under_control() + 5_000_000_000);
+        assert_eq!((series[2].block_time, series[2].missing.as_deref()), (Some(1_700_000_000), None));
+        let past = format!("read at slot {}, past the next point at {}", FIXTURE_SLOT + 50, FIXTURE_SLOT - 100);
+        assert_eq!(series[0].missing.as_deref(), Some(past.as_str()));
+        assert!(series[3].missing.as_deref().unwrap().contains("Minimum context slot has not been reached"));
+        // the past points share one read, and only the point beyond the node asks again
+        assert_eq!((rpc.call_count("getMultipleAccounts"), rpc.call_count("getBlockTime")), (2, 1));
+
+        let json = serde_json::to_value(&series).unwrap();
+        assert_eq!(json[2].get("missing"), None);
+        assert_eq!(serde_json::from_value::<Vec<TvlPoint>>(json).unwrap(), series);
+    }
+
+    #[test]
+    fn test_epoch_windows() {
+        let first = |epoch: u64| epoch * 432_000;
+        let windows = SeriesRange::Epochs { start: 600, end: 604 }.windows(2);
+        assert_eq!(windows, vec![(first(600), first(602)), (first(602), first(604)), (first(604), first(606))]);
+        assert_eq!(SeriesRange::Slots { start: 10, end: 12 }.windows(0), vec![(10, 11), (11, 12), (12, 13)]);
+    }
+}
@@ -0,0 +1,162 @@
+//! smoothing a price series for display: a time-weighted average over a trailing window, and
+//! simple moving averages over the last few observations. prices are in whatever unit they come
+//! in, usually lamports per mSOL, and averages round down
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use solana_program::clock::UnixTimestamp;
+
+/// an observation older than the latest one in the accumulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfOrder {
+    pub latest: UnixTimestamp,
+    pub got: UnixTimestamp,
+}
+
+impl fmt::Display for OutOfOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "observation at {} is older than the latest one, at {}", self.got, self.latest)
+    }
+}
+
+impl std::error::Error for OutOfOrder {}
+
+/// `(block_time, price)` observations in block time order. each price holds from its
+/// observation until the next one, so irregular spacing weights it by how long it held
+#[derive(Debug, Clone, Default)]
+pub struct Twap {
+    observations: VecDeque<(UnixTimestamp, u64)>,
+}
+
+impl Twap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a second observation at the same block time replaces the first
+    pub fn push(&mut self, block_time: UnixTimestamp, price: u64) -> Result<(), OutOfOrder> {
+        match self.observations.back_mut() {
+            Some((latest, _)) if block_time < *latest => Err(OutOfOrder { latest: *latest, got: block_time }),
+            Some((latest, last_price)) if block_time == *latest => {
+                *last_price = price;
+                Ok(())
+            }
+            _ => {
+                self.observations.push_back((block_time, price));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.observations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observations.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<(UnixTimestamp, u64)> {
+        self.observations.back().copied()
+    }
+
+    /// drops observations that no window ending at or after `block_time` can reach, keeping
+    /// the one whose price still holds at `block_time`
+    pub fn prune_before(&mut self, block_time: UnixTimestamp) {
+        while self.observations.get(1).is_some_and(|&(time, _)| time <= block_time) {
+            self.observations.pop_front();
+        }
+    }
+
+    /// the time-weighted average over the `window` before the latest observation, see `twap_until`
+    pub fn twap(&self, window: Duration) -> Option<u64> {
+        self.twap_until(self.latest()?.0, window)
+    }
+
+    /// the time-weighted average over `[end - window, end]`. a window reaching back past the
+    /// first observation starts at it, and a window with no length is the price holding at
+    /// `end`. None before the first observation
+    pub fn twap_until(&self, end: UnixTimestamp, window: Duration) -> Option<u64> {
+        let start = end.saturating_sub(window.as_secs().min(i64::MAX as u64) as i64);
+        let mut holding = None;
+        let (mut weighted, mut total) = (0u128, 0u128);
+        for (i, &(time, price)) in self.observations.iter().enumerate() {
+            if time > end {
+                break;
+            }
+            holding = Some(price);
+            let until = self.observations.get(i + 1).map_or(end, |&(next, _)| next.min(end));
+            let held = (until - time.max(start)).max(0) as u128;
+            weighted += price as u128 * held;
+            total += held;
+        }
+        match total {
+            0 => holding,
+            total => Some((weighted / total) as u64),
+        }
+    }
+
+    /// the mean of the last `count` prices, or of all of them when there are fewer. None for
+    /// a `count` of 0 or no observations
+    pub fn sma(&self, count: usize) -> Option<u64> {
+        let count = count.min(self.observations.len());
+        if count == 0 {
+            return None;
+        }
+        let sum: u128 = self.observations.iter().rev().take(count).map(|&(_, price)| price as u128).sum();
+        Some((sum / count as u128) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twap(observations: &[(UnixTimestamp, u64)]) -> Twap {
+        let mut twap = Twap::new();
+        for &(time, price) in observations {
+            twap.push(time, price).unwrap();
+        }
+        twap
+    }
+
+    #[test]
+    fn test_time_weighted_over_irregular_spacing() {
+        let series = twap(&[(0, 100), (10, 200), (15, 110), (45, 130)]);
+        let secs = Duration::from_secs;
+
+        // 100 for 10s, 200 for 5s, 110 for 30s: 5300 / 45
+        assert_eq!(series.twap(secs(45)), Some(117));
+        // from t=5: 500 + 1000 + 3300 over 40s
+        assert_eq!(series.twap(secs(40)), Some(120));
+        // clipped to the first observation
+        assert_eq!(series.twap(secs(1_000)), Some(117));
+        // from t=40 to t=60: 110 for 5s, then 130 for 15s
+        assert_eq!(series.twap_until(60, secs(20)), Some(125));
+        // inside one price's span, and no window at all
+        assert_eq!(series.twap_until(14, secs(3)), Some(200));
+        assert_eq!(series.twap(Duration::ZERO), Some(130));
+        assert_eq!(series.twap_until(-1, secs(10)), None);
+        assert_eq!(Twap::new().twap(secs(10)), None);
+
+        let mut pruned = series.clone();
+        pruned.prune_before(12);
+        assert_eq!((pruned.len(), pruned.twap(secs(35))), (3, Some(122)));
+    }
+
+    #[test]
+    fn test_moving_average_and_ordering() {
+        let mut series = twap(&[(0, 100), (10, 200), (15, 110), (45, 130)]);
+        assert_eq!(series.sma(2), Some(120));
+        // 440 / 3
+        assert_eq!(series.sma(3), Some(146));
+        assert_eq!(series.sma(10), Some(135));
+        assert_eq!(series.sma(0), None);
+
+        assert_eq!(series.push(44, 1), Err(OutOfOrder { latest: 45, got: 44 }));
+        series.push(45, 150).unwrap();
+        assert_eq!((series.len(), series.latest(), series.sma(2)), (4, Some((45, 150)), Some(130)));
+    }
+}
@@ -0,0 +1,265 @@
+//! `MintUnderlying::builder`, for records put together from something other than the chain,
+//! e.g. an export being ingested. `build` refuses the records `MintUnderlying::new` can't rule
+//! out: no underlyings, a block time at or before the epoch, a zero supply, or a price the
+//! supply and its SOL don't come to
+
+use solana_program::pubkey::Pubkey;
+
+use crate::accounts::marinade::LAMPORTS_PER_MSOL;
+use crate::constants::SOL_MINT_PUBKEY;
+use crate::{BlockTimeSource, MintUnderlying, UnderlyingsMismatch};
+
+/// the default of `MintUnderlyingBuilder::price_tolerance_bps`
+pub const DEFAULT_PRICE_TOLERANCE_BPS: u32 = 1;
+
+/// from `MintUnderlyingBuilder::build`: the record it was given can't be right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidMintUnderlying {
+    /// a field without a default wasn't set
+    Missing(&'static str),
+    Unpaired(UnderlyingsMismatch),
+    NoUnderlyings,
+    /// unix seconds, 0 or earlier
+    BlockTime(i64),
+    ZeroSupply,
+    /// `msol_value` is further than `tolerance_bps` from the `implied` price: the SOL behind the
+    /// supply, per whole token
+    PriceMismatch { msol_value: u64, implied: u64, tolerance_bps: u32 },
+}
+
+impl std::fmt::Display for InvalidMintUnderlying {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(field) => write!(f, "no {} given", field),
+            Self::Unpaired(mismatch) => mismatch.fmt(f),
+            Self::NoUnderlyings => write!(f, "no underlying mints"),
+            Self::BlockTime(block_time) => write!(f, "block time {} isn't after the unix epoch", block_time),
+            Self::ZeroSupply => write!(f, "zero supply"),
+            Self::PriceMismatch { msol_value, implied, tolerance_bps } => write!(
+                f,
+                "price of {} lamports is more than {} bps from the {} the supply and underlying imply",
+                msol_value, tolerance_bps, implied
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidMintUnderlying {}
+
+#[derive(Debug, Clone)]
+pub struct MintUnderlyingBuilder {
+    block_time: Option<(i64, BlockTimeSource)>,
+    msol_value: Option<u64>,
+    mint: Option<Pubkey>,
+    platform_program: Option<Pubkey>,
+    mints: Vec<Pubkey>,
+    amounts: Vec<u64>,
+    supply: Option<u64>,
+    price_tolerance_bps: u32,
+    state_reused: bool,
+    crossed_epoch_boundary: bool,
+    state_slot: Option<u64>,
+    msol_supply_change: (u64, u64, bool),
+}
+
+impl MintUnderlying {
+    /// see `underlying`
+    pub fn builder() -> MintUnderlyingBuilder {
+        MintUnderlyingBuilder {
+            block_time: None,
+            msol_value: None,
+            mint: None,
+            platform_program: None,
+            mints: Vec::new(),
+            amounts: Vec::new(),
+            supply: None,
+            price_tolerance_bps: DEFAULT_PRICE_TOLERANCE_BPS,
+            state_reused: false,
+            crossed_epoch_boundary: false,
+            state_slot: None,
+            msol_supply_change: (0, 0, false),
+        }
+    }
+}
+
+impl MintUnderlyingBuilder {
+    /// required, and after the unix epoch
+    pub fn block_time(mut self, block_time: i64, source: BlockTimeSource) -> Self {
+        self.block_time = Some((block_time, source));
+        self
+    }
+
+    /// lamports per whole token, required
+    pub fn msol_value(mut self, msol_value: u64) -> Self {
+        self.msol_value = Some(msol_value);
+        self
+    }
+
+    /// required
+    pub fn mint(mut self, mint: Pubkey) -> Self {
+        self.mint = Some(mint);
+        self
+    }
+
+    /// required
+    pub fn platform_program(mut self, platform_program: Pubkey) -> Self {
+        self.platform_program = Some(platform_program);
+        self
+    }
+
+    /// adds `mint` with the amount of it behind the whole supply. at least one is required
+    pub fn underlying(mut self, mint: Pubkey, amount: u64) -> Self {
+        self.mints.push(mint);
+        self.amounts.push(amount);
+        self
+    }
+
+    /// replaces the underlyings, each mint paired with the amount at its index
+    pub fn underlyings(mut self, mints: &[Pubkey], amounts: Vec<u64>) -> Self {
+        self.mints = mints.to_vec();
+        self.amounts = amounts;
+        self
+    }
+
+    /// token supply, in base units of a 9 decimal mint. optional, and not 0 when given. with
+    /// SOL the only underlying, `msol_value` is checked against the price the two imply
+    pub fn supply(mut self, supply: u64) -> Self {
+        self.supply = Some(supply);
+        self
+    }
+
+    /// how far `msol_value` may be from the price `supply` implies, `DEFAULT_PRICE_TOLERANCE_BPS`
+    /// by default
+    pub fn price_tolerance_bps(mut self, bps: u32) -> Self {
+        self.price_tolerance_bps = bps;
+        self
+    }
+
+    /// see `MintUnderlying::state_reused`
+    pub fn state_reused(mut self, state_reused: bool) -> Self {
+        self.state_reused = state_reused;
+        self
+    }
+
+    /// see `MintUnderlying::crossed_epoch_boundary`
+    pub fn crossed_epoch_boundary(mut self, crossed_epoch_boundary: bool) -> Self {
+        self.crossed_epoch_boundary = crossed_epoch_boundary;
+        self
+    }
+
+    /// see `MintUnderlying::state_slot`
+    pub fn state_slot(mut self, state_slot: u64) -> Self {
+        self.state_slot = Some(state_slot);
+        self
+    }
+
+    /// the mSOL minted and burned, and whether they differ from what the instructions account
+    /// for, see `MintUnderlying::msol_minted`
+    pub fn msol_supply_change(mut self, minted: u64, burned: u64, discrepancy: bool) -> Self {
+        self.msol_supply_change = (minted, burned, discrepancy);
+        self
+    }
+
+    pub fn build(self) -> Result<MintUnderlying, InvalidMintUnderlying> {
+        let (block_time, source) = self.block_time.ok_or(InvalidMintUnderlying::Missing("block_time"))?;
+        let msol_value = self.msol_value.ok_or(InvalidMintUnderlying::Missing("msol_value"))?;
+        let mint = self.mint.ok_or(InvalidMintUnderlying::Missing("mint"))?;
+        let platform_program = self.platform_program.ok_or(InvalidMintUnderlying::Missing("platform_program"))?;
+        if block_time <= 0 {
+            return Err(InvalidMintUnderlying::BlockTime(block_time));
+        }
+        if self.mints.is_empty() && self.amounts.is_empty() {
+            return Err(InvalidMintUnderlying::NoUnderlyings);
+        }
+        if self.supply == Some(0) {
+            return Err(InvalidMintUnderlying::ZeroSupply);
+        }
+        if let (Some(supply), [SOL_MINT_PUBKEY], [lamports]) = (self.supply, &self.mints[..], &self.amounts[..]) {
+            let implied = (*lamports as u128 * LAMPORTS_PER_MSOL as u128 / supply as u128).min(u64::MAX as u128);
+            let tolerance_bps = self.price_tolerance_bps;
+            if (msol_value as u128).abs_diff(implied) * 10_000 > implied * tolerance_bps as u128 {
+                let implied = implied as u64;
+                return Err(InvalidMintUnderlying::PriceMismatch { msol_value, implied, tolerance_bps });
+            }
+        }
+
+        let (mints, amounts) = (&self.mints, self.amounts);
+        let mu = MintUnderlying::new(block_time, source, msol_value, &mint, &platform_program, mints, amounts)
+            .map_err(InvalidMintUnderlying::Unpaired)?;
+        let (msol_minted, msol_burned, msol_discrepancy) = self.msol_supply_change;
+        Ok(MintUnderlying {
+            state_reused: self.state_reused,
+            crossed_epoch_boundary: self.crossed_epoch_boundary,
+            state_slot: self.state_slot,
+            msol_minted,
+            msol_burned,
+            msol_discrepancy,
+            ..mu
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{MARINADE_STATE_PUBKEY, MSOL_MINT_PUBKEY};
+
+    const SOL: u64 = 1_000_000_000;
+
+    /// 7.2M SOL behind 6M mSOL, 1.2 SOL each
+    fn builder() -> MintUnderlyingBuilder {
+        MintUnderlying::builder()
+            .block_time(1_700_000_000, BlockTimeSource::Snapshot)
+            .msol_value(1_200_000_000)
+            .mint(MSOL_MINT_PUBKEY)
+            .platform_program(MARINADE_STATE_PUBKEY)
+            .underlying(SOL_MINT_PUBKEY, 7_200_000 * SOL)
+            .supply(6_000_000 * SOL)
+    }
+
+    #[test]
+    fn test_builds_a_consistent_record() {
+        let mu = builder().state_slot(7).msol_supply_change(3, 1, false).build().unwrap();
+        assert_eq!(mu.underlyings().unwrap(), [(SOL_MINT_PUBKEY, 7_200_000 * SOL)]);
+        assert_eq!((mu.block_time, mu.msol_value), (1_700_000_000, 1_200_000_000));
+        assert_eq!((mu.state_slot, mu.msol_minted, mu.msol_burned), (Some(7), 3, 1));
+        // within the tolerance, and anything goes without a supply to check against
+        assert!(builder().msol_value(1_200_100_000).build().is_ok());
+        let unchecked = MintUnderlying::builder().block_time(1, BlockTimeSource::Rpc).msol_value(5);
+        let unchecked = unchecked.mint(MSOL_MINT_PUBKEY).platform_program(MARINADE_STATE_PUBKEY);
+        assert!(unchecked.underlying(SOL_MINT_PUBKEY, 1).build().is_ok());
+    }
+
+    #[test]
+    fn test_each_invariant_is_enforced() {
+        let missing = MintUnderlying::builder().msol_value(1).mint(MSOL_MINT_PUBKEY).build().unwrap_err();
+        assert_eq!(missing, InvalidMintUnderlying::Missing("block_time"));
+        assert_eq!(missing.to_string(), "no block_time given");
+
+        let unpaired = builder().underlyings(&[SOL_MINT_PUBKEY, MSOL_MINT_PUBKEY], vec![1]).build().unwrap_err();
+        let mismatch = UnderlyingsMismatch { mints: 2, amounts: 1 };
+        assert_eq!((unpaired, unpaired.to_string()), (InvalidMintUnderlying::Unpaired(mismatch), mismatch.to_string()));
+
+        let empty = builder().underlyings(&[], vec![]).build().unwrap_err();
+        assert_eq!((empty, empty.to_string()), (InvalidMintUnderlying::NoUnderlyings, "no underlying mints".into()));
+
+        for block_time in [0, -5] {
+            let e = builder().block_time(block_time, BlockTimeSource::Transaction).build().unwrap_err();
+            assert_eq!(e, InvalidMintUnderlying::BlockTime(block_time));
+        }
+        assert_eq!(builder().supply(0).build().unwrap_err(), InvalidMintUnderlying::ZeroSupply);
+
+        let mispriced = builder().msol_value(1_201_000_000).build().unwrap_err();
+        let expected = InvalidMintUnderlying::PriceMismatch {
+            msol_value: 1_201_000_000,
+            implied: 1_200_000_000,
+            tolerance_bps: DEFAULT_PRICE_TOLERANCE_BPS,
+        };
+        assert_eq!(mispriced, expected);
+        assert_eq!(
+            mispriced.to_string(),
+            "price of 1201000000 lamports is more than 1 bps from the 1200000000 the supply and underlying imply"
+        );
+        assert!(builder().msol_value(1_201_000_000).price_tolerance_bps(10).build().is_ok());
+    }
+}
@@ -0,0 +1,402 @@
+//! quoting a liquid unstake the way the program's `liquid_unstake` instruction would pay it
+//! out: a fee on the mSOL taken off the curve between `lp_min_fee` and `lp_max_fee` for how
+//! much the trade leaves in the sol leg, the rest paid in lamports from the sol leg. no rpc
+//! here; `MarinadeClient::simulate_liquid_unstake` fetches what it needs
+//!
+//! `simulate_delayed_unstake` quotes the ticket `order_unstake` would create instead, and when
+//! it can be claimed, and `simulate_withdraw_stake_account` the stake account split off for
+//! `withdraw_stake_account`
+
+use std::fmt;
+
+use serde::Serialize;
+use solana_program::clock::Epoch;
+#[cfg(feature = "rpc")]
+use solana_program::clock::DEFAULT_MS_PER_SLOT;
+#[cfg(feature = "rpc")]
+use solana_sdk::epoch_info::EpochInfo;
+
+use crate::accounts::marinade::{AmountError, MarinadeState, LAMPORTS_PER_MSOL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiquidUnstakeError {
+    Paused,
+    /// the sol leg, above its rent-exempt reserve, can't pay out the `lamports` owed
+    InsufficientLiquidity { lamports: u64, sol_leg_lamports: u64 },
+}
+
+impl fmt::Display for LiquidUnstakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Paused => write!(f, "the program is paused"),
+            Self::InsufficientLiquidity { lamports, sol_leg_lamports } => write!(
+                f,
+                "the liq pool holds {} lamports, not enough to pay out {}",
+                sol_leg_lamports, lamports
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LiquidUnstakeError {}
+
+/// what a liquid unstake of `msol_amount` pays out, and the pool it leaves behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LiquidUnstakeQuote {
+    pub msol_amount: u64,
+    /// lamports the seller receives
+    pub lamports: u64,
+    pub fee_bps: u32,
+    /// mSOL withheld, of which `treasury_msol_cut` goes to the treasury and the rest stays in
+    /// the pool for the LPs
+    pub fee_msol: u64,
+    pub treasury_msol_cut: u64,
+    /// the fee at the state's price: what `msol_amount` is worth less `lamports`
+    pub fee_lamports: u64,
+    /// the sol leg above its rent-exempt reserve once the trade settled
+    pub sol_leg_lamports_after: u64,
+    pub msol_leg_amount_after: u64,
+    /// context slot of the state and pool quoted against, when they were fetched
+    pub slot: Option<u64>,
+}
+
+/// quote unstaking `msol_amount` through the liq pool, whose sol leg holds `sol_leg_lamports`
+/// above its rent-exempt reserve and whose mSOL leg holds `msol_leg_amount`
+pub fn simulate_liquid_unstake(
+    state: &MarinadeState,
+    sol_leg_lamports: u64,
+    msol_leg_amount: u64,
+    msol_amount: u64,
+) -> Result<LiquidUnstakeQuote, LiquidUnstakeError> {
+    if state.paused {
+        return Err(LiquidUnstakeError::Paused);
+    }
+    let liq_pool = &state.liq_pool;
+    let minimal = state.minimal();
+    let value = minimal.msol_to_sol(msol_amount);
+    // priced on what the trade would leave in the leg, the max fee if it would drain it
+    let fee = if value >= sol_leg_lamports {
+        liq_pool.lp_max_fee.clone()
+    } else {
+        liq_pool.linear_fee(sol_leg_lamports - value)
+    };
+    let fee_msol = fee.apply(msol_amount);
+    let lamports = minimal.msol_to_sol(msol_amount - fee_msol);
+    if lamports > sol_leg_lamports {
+        return Err(LiquidUnstakeError::InsufficientLiquidity { lamports, sol_leg_lamports });
+    }
+    let treasury_msol_cut = liq_pool.treasury_cut.apply(fee_msol);
+    Ok(LiquidUnstakeQuote {
+        msol_amount,
+        lamports,
+        fee_bps: fee.basis_points,
+        fee_msol,
+        treasury_msol_cut,
+        fee_lamports: value - lamports,
+        sol_leg_lamports_after: sol_leg_lamports - lamports,
+        msol_leg_amount_after: msol_leg_amount.saturating_add(msol_amount - treasury_msol_cut),
+        slot: None,
+    })
+}
+
+/// how long into the epoch after a ticket's the program makes its holder wait, for the
+/// stake deactivated at the boundary to be withdrawn into the reserve
+pub const TICKET_EXTRA_WAIT_SECONDS: u64 = 30 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayedUnstakeError {
+    Paused,
+    /// the ticket, after the fee, would be below `min_withdraw`
+    Amount(AmountError),
+}
+
+impl From<AmountError> for DelayedUnstakeError {
+    fn from(e: AmountError) -> Self {
+        Self::Amount(e)
+    }
+}
+
+impl fmt::Display for DelayedUnstakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Paused => write!(f, "the program is paused"),
+            Self::Amount(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DelayedUnstakeError {}
+
+/// the ticket a delayed unstake of `msol_amount` creates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DelayedUnstakeQuote {
+    pub msol_amount: u64,
+    /// lamports the ticket is for
+    pub lamports: u64,
+    /// mSOL withheld for `delayed_unstake_fee`
+    pub fee_msol: u64,
+    pub created_epoch: Epoch,
+    pub claimable_epoch: Epoch,
+    /// estimated from the slots left in the epoch at `DEFAULT_MS_PER_SLOT`, plus
+    /// `TICKET_EXTRA_WAIT_SECONDS`
+    pub claimable_in_secs: u64,
+}
+
+/// quote ordering an unstake of `msol_amount` during the epoch described by `epoch_info`. a
+/// ticket ordered in the last slots of an epoch may land in the next one, and then wait a
+/// whole epoch longer than quoted
+#[cfg(feature = "rpc")]
+pub fn simulate_delayed_unstake(
+    state: &MarinadeState,
+    msol_amount: u64,
+    epoch_info: &EpochInfo,
+) -> Result<DelayedUnstakeQuote, DelayedUnstakeError> {
+    if state.paused {
+        return Err(DelayedUnstakeError::Paused);
+    }
+    let fee_msol = state.delayed_unstake_fee.apply(msol_amount);
+    state.validate_unstake(msol_amount - fee_msol)?;
+    let lamports = state.minimal().msol_to_sol(msol_amount - fee_msol);
+    let slots_left = epoch_info.slots_in_epoch.saturating_sub(epoch_info.slot_index);
+    Ok(DelayedUnstakeQuote {
+        msol_amount,
+        lamports,
+        fee_msol,
+        created_epoch: epoch_info.epoch,
+        claimable_epoch: epoch_info.epoch + 1,
+        claimable_in_secs: slots_left * DEFAULT_MS_PER_SLOT / 1_000 + TICKET_EXTRA_WAIT_SECONDS,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WithdrawStakeAccountError {
+    Paused,
+    /// `withdraw_stake_account_enabled` is off
+    NotEnabled,
+    /// the split would be delegating less than `min_stake`
+    BelowMinStake { lamports: u64, min_stake: u64 },
+    /// the stake account is delegating less than the split
+    NotEnoughFunds { lamports: u64, delegated_lamports: u64 },
+    /// what the split leaves delegated in the stake account would be below `min_stake`
+    RemainderBelowMinStake { remainder_lamports: u64, min_stake: u64 },
+}
+
+impl fmt::Display for WithdrawStakeAccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Paused => write!(f, "the program is paused"),
+            Self::NotEnabled => write!(f, "withdrawing stake accounts isn't enabled"),
+            Self::BelowMinStake { lamports, min_stake } => {
+                write!(f, "a split of {} lamports is below the minimum stake of {}", lamports, min_stake)
+            }
+            Self::NotEnoughFunds { lamports, delegated_lamports } => write!(
+                f,
+                "the stake account delegates {} lamports, not enough to split off {}",
+                delegated_lamports, lamports
+            ),
+            Self::RemainderBelowMinStake { remainder_lamports, min_stake } => write!(
+                f,
+                "the split would leave {} lamports delegated, below the minimum stake of {}",
+                remainder_lamports, min_stake
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WithdrawStakeAccountError {}
+
+/// what a `withdraw_stake_account` burns, and the stake it delivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct WithdrawStakeAccountSplit {
+    pub msol_amount: u64,
+    /// mSOL sent to the treasury for `withdraw_stake_account_fee`
+    pub fee_msol: u64,
+    pub msol_burned: u64,
+    /// lamports delegated in the split stake account, its rent reserve aside
+    pub lamports: u64,
+    /// lamports delivered per whole mSOL given up, fee included
+    pub effective_rate_lamports: u64,
+}
+
+impl WithdrawStakeAccountSplit {
+    /// the split for `msol_amount` at the state's fee and price, like the program computes it
+    pub fn new(state: &MarinadeState, msol_amount: u64) -> Self {
+        let fee_msol = state.withdraw_stake_account_fee.apply(msol_amount);
+        let msol_burned = msol_amount - fee_msol;
+        let lamports = state.minimal().msol_to_sol(msol_burned);
+        let effective_rate_lamports = match msol_amount {
+            0 => 0,
+            _ => (lamports as u128 * LAMPORTS_PER_MSOL as u128 / msol_amount as u128) as u64,
+        };
+        Self { msol_amount, fee_msol, msol_burned, lamports, effective_rate_lamports }
+    }
+}
+
+/// the split a `withdraw_stake_account` of `msol_amount` from a stake account delegating
+/// `delegated_lamports` would make, checked the way the program checks it
+pub fn simulate_withdraw_stake_account(
+    state: &MarinadeState,
+    delegated_lamports: u64,
+    msol_amount: u64,
+) -> Result<WithdrawStakeAccountSplit, WithdrawStakeAccountError> {
+    if state.paused {
+        return Err(WithdrawStakeAccountError::Paused);
+    }
+    if !state.withdraw_stake_account_enabled {
+        return Err(WithdrawStakeAccountError::NotEnabled);
+    }
+    let split = WithdrawStakeAccountSplit::new(state, msol_amount);
+    let min_stake = state.stake_system.min_stake;
+    if split.lamports < min_stake {
+        return Err(WithdrawStakeAccountError::BelowMinStake { lamports: split.lamports, min_stake });
+    }
+    let Some(remainder_lamports) = delegated_lamports.checked_sub(split.lamports) else {
+        return Err(WithdrawStakeAccountError::NotEnoughFunds { lamports: split.lamports, delegated_lamports });
+    };
+    if remainder_lamports < min_stake {
+        return Err(WithdrawStakeAccountError::RemainderBelowMinStake { remainder_lamports, min_stake });
+    }
+    Ok(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sample_state;
+
+    const SOL: u64 = 1_000_000_000;
+
+    /// the sample state at exactly 1 SOL per mSOL, so fees read straight off the curve
+    fn par_state() -> MarinadeState {
+        let mut state = sample_state();
+        state.msol_supply = state.total_virtual_staked_lamports();
+        state
+    }
+
+    #[test]
+    fn test_fee_follows_the_curve() {
+        // 10_000 SOL target, 30 bp at or above it rising to 300 bp at an empty leg
+        let state = par_state();
+        let quote = |sol_leg_lamports| simulate_liquid_unstake(&state, sol_leg_lamports, 0, 1_000 * SOL);
+
+        // above the target after the trade
+        let above = quote(20_000 * SOL).unwrap();
+        assert_eq!((above.fee_bps, above.fee_msol, above.lamports), (30, 3 * SOL, 997 * SOL));
+        assert_eq!((above.fee_lamports, above.treasury_msol_cut), (3 * SOL, 750_000_000));
+        assert_eq!(above.sol_leg_lamports_after, 20_000 * SOL - 997 * SOL);
+        assert_eq!(above.msol_leg_amount_after, 1_000 * SOL - 750_000_000);
+        // landing exactly on the target is still the min fee
+        assert_eq!(quote(11_000 * SOL).unwrap().fee_bps, 30);
+        // half the target left: halfway up the curve
+        assert_eq!(quote(6_000 * SOL).unwrap().fee_bps, 165);
+        // a trade worth the whole leg pays the max fee, which is what lets it fit
+        let drained = quote(1_000 * SOL).unwrap();
+        assert_eq!((drained.fee_bps, drained.lamports, drained.sol_leg_lamports_after), (300, 970 * SOL, 30 * SOL));
+
+        assert_eq!(
+            quote(900 * SOL),
+            Err(LiquidUnstakeError::InsufficientLiquidity { lamports: 970 * SOL, sol_leg_lamports: 900 * SOL })
+        );
+        let mut paused = par_state();
+        paused.paused = true;
+        assert_eq!(simulate_liquid_unstake(&paused, u64::MAX, 0, SOL), Err(LiquidUnstakeError::Paused));
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_client_quotes_against_the_fetched_pool() {
+        use crate::client::MarinadeClient;
+        use crate::deployment::DeploymentConfig;
+        use crate::error::ErrorKind;
+        use crate::test_utils::{token_account, MockFetcher, FIXTURE_SLOT};
+
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let state = par_state();
+        let rpc = |sol_leg_lamports| {
+            MockFetcher::new()
+                .with_valuation_accounts(&addresses, &state, sol_leg_lamports + state.rent_exempt_for_token_acc)
+                .with_account(addresses.liq_pool_msol_leg, token_account(addresses.msol_mint, 0))
+        };
+
+        let client = MarinadeClient::builder().rpc_client(rpc(6_000 * SOL)).build();
+        let quote = client.simulate_liquid_unstake(1_000 * SOL).unwrap();
+        assert_eq!((quote.fee_bps, quote.slot), (165, Some(FIXTURE_SLOT)));
+
+        let client = MarinadeClient::builder().rpc_client(rpc(900 * SOL)).build();
+        let err = client.simulate_liquid_unstake(1_000 * SOL).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::LiquidUnstake(LiquidUnstakeError::InsufficientLiquidity { .. })));
+        assert_eq!(err.slot(), Some(FIXTURE_SLOT));
+    }
+
+    #[cfg(feature = "rpc")]
+    fn epoch(slot_index: u64) -> EpochInfo {
+        EpochInfo {
+            epoch: 600,
+            slot_index,
+            slots_in_epoch: 432_000,
+            absolute_slot: 600 * 432_000 + slot_index,
+            block_height: 0,
+            transaction_count: None,
+        }
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_delayed_unstake_ticket_and_timing() {
+        use crate::accounts::marinade::FeeCents;
+
+        let mut state = par_state();
+        state.delayed_unstake_fee = FeeCents { bp_cents: 150 };
+
+        // halfway through the epoch: 216_000 slots of 400ms left, then the extra wait
+        let quote = simulate_delayed_unstake(&state, 1_000 * SOL, &epoch(216_000)).unwrap();
+        assert_eq!((quote.fee_msol, quote.lamports), (150_000_000, 1_000 * SOL - 150_000_000));
+        assert_eq!((quote.created_epoch, quote.claimable_epoch), (600, 601));
+        assert_eq!(quote.claimable_in_secs, 86_400 + TICKET_EXTRA_WAIT_SECONDS);
+
+        // the last slot of the epoch is still claimable next epoch, after little more than the wait
+        let quote = simulate_delayed_unstake(&state, 1_000 * SOL, &epoch(431_999)).unwrap();
+        assert_eq!((quote.claimable_epoch, quote.claimable_in_secs), (601, TICKET_EXTRA_WAIT_SECONDS));
+
+        state.min_withdraw = SOL;
+        assert_eq!(
+            simulate_delayed_unstake(&state, SOL, &epoch(0)),
+            Err(DelayedUnstakeError::Amount(AmountError::BelowMinimum { min: SOL, got: SOL - 150_000 }))
+        );
+        state.paused = true;
+        assert_eq!(simulate_delayed_unstake(&state, SOL, &epoch(0)), Err(DelayedUnstakeError::Paused));
+    }
+
+    #[test]
+    fn test_withdraw_stake_account_split_and_remainder() {
+        use crate::accounts::marinade::FeeCents;
+
+        let mut state = par_state();
+        state.withdraw_stake_account_fee = FeeCents { bp_cents: 2_000 };
+        state.withdraw_stake_account_enabled = true;
+        state.stake_system.min_stake = SOL;
+
+        // 0.2% of 100 mSOL to the treasury, the rest burned for as many lamports of stake
+        let split = simulate_withdraw_stake_account(&state, 1_000 * SOL, 100 * SOL).unwrap();
+        assert_eq!((split.fee_msol, split.msol_burned, split.lamports), (200_000_000, 99_800_000_000, 99_800_000_000));
+        assert_eq!(split.effective_rate_lamports, 998_000_000);
+
+        // a split leaving exactly the minimum behind goes through, a lamport more doesn't
+        assert!(simulate_withdraw_stake_account(&state, SOL + split.lamports, 100 * SOL).is_ok());
+        assert_eq!(
+            simulate_withdraw_stake_account(&state, SOL + split.lamports - 1, 100 * SOL),
+            Err(WithdrawStakeAccountError::RemainderBelowMinStake { remainder_lamports: SOL - 1, min_stake: SOL })
+        );
+        assert_eq!(
+            simulate_withdraw_stake_account(&state, 50 * SOL, 100 * SOL),
+            Err(WithdrawStakeAccountError::NotEnoughFunds { lamports: 99_800_000_000, delegated_lamports: 50 * SOL })
+        );
+        assert_eq!(
+            simulate_withdraw_stake_account(&state, 1_000 * SOL, SOL),
+            Err(WithdrawStakeAccountError::BelowMinStake { lamports: 998_000_000, min_stake: SOL })
+        );
+        state.withdraw_stake_account_enabled = false;
+        let disabled = simulate_withdraw_stake_account(&state, 1_000 * SOL, SOL);
+        assert_eq!(disabled, Err(WithdrawStakeAccountError::NotEnabled));
+    }
+}
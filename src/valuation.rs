@@ -0,0 +1,577 @@
+//! valuations that need more than the state account: the liq pool legs, the LP mint and
+//! the mSOL mint. everything comes from a single `getMultipleAccounts` round trip
+
+use anchor_lang::solana_program::program_pack::{IsInitialized, Pack};
+use anchor_spl::token::spl_token::{self, state::{Account as TokenAccount, Mint}};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_client::rpc_response::Response;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::accounts::marinade::{MarinadeState, LAMPORTS_PER_MSOL};
+use crate::analysis::{account_info_config, parse_state};
+use crate::client::MarinadeClient;
+use crate::constants::{MSOL_DECIMALS, SPL_TOKEN_2022_PROGRAM_ID};
+use crate::deployment::{DeploymentConfig, LIQ_POOL_SOL_LEG_SEED};
+use crate::error::{Error, ErrorKind, Result};
+use crate::rpc::{wait_for_min_context_slot, RpcFetcher};
+use crate::verbosity::debug;
+use crate::AnalyzeOptions;
+
+/// fetch several accounts in one rpc call. every requested account must exist; `roles`
+/// names each entry of `pubkeys` for errors. read the way `fetch_account_data` reads one: at
+/// `options.commitment`, waiting up to `options.wait_for_slot` for a node that isn't at `slot`
+/// yet, and with `options.prefer_zstd` compressed first, again as base64 when the node refuses
+/// that or leaves any account out
+#[instrument(level = "debug", skip_all, fields(count = pubkeys.len(), slot = ?slot))]
+pub(crate) fn fetch_multiple_accounts(
+    rpc_client: &dyn RpcFetcher,
+    pubkeys: &[Pubkey],
+    roles: &[&'static str],
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<(u64, Vec<Account>)> {
+    let _verbosity = options.verbosity.enter();
+    let config = account_info_config(slot, options, None);
+    let mut read = None;
+    if options.prefer_zstd {
+        let zstd = RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::Base64Zstd), ..config.clone() };
+        match read_accounts(rpc_client, pubkeys, slot, options, zstd) {
+            Ok(response) if response.value.iter().all(Option::is_some) => {
+                read = Some((response, UiAccountEncoding::Base64Zstd))
+            }
+            Ok(_) => debug!("accounts missing from the zstd read, reading them as base64"),
+            Err(e) if matches!(e.kind(), ErrorKind::Rpc(_)) => {
+                debug!(error = %e, "zstd read failed, reading the accounts as base64")
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    let (response, encoding) = match read {
+        Some(read) => read,
+        None => (read_accounts(rpc_client, pubkeys, slot, options, config)?, UiAccountEncoding::Base64),
+    };
+    for _ in response.value.iter().flatten() {
+        options.observe(|o| o.on_account_read(encoding));
+    }
+
+    if response.value.len() != pubkeys.len() {
+        let kind = ErrorKind::ShortResponse { requested: pubkeys.len(), returned: response.value.len() };
+        return Err(Error::new(kind).with_slot(slot));
+    }
+
+    let accounts = response
+        .value
+        .into_iter()
+        .zip(pubkeys.iter().zip(roles.iter()))
+        .map(|(account, (pubkey, role))| {
+            account.ok_or_else(|| Error::new(ErrorKind::AccountNotFound { role }).with_pubkey(*pubkey).with_slot(slot))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((response.context.slot, accounts))
+}
+
+// the closure hands `RpcClient`'s error through as it is
+#[allow(clippy::result_large_err)]
+fn read_accounts(
+    rpc_client: &dyn RpcFetcher,
+    pubkeys: &[Pubkey],
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+    config: RpcAccountInfoConfig,
+) -> Result<Response<Vec<Option<Account>>>> {
+    wait_for_min_context_slot(rpc_client, slot, options.commitment, options.wait_for_slot, || {
+        rpc_client.get_multiple_accounts_with_config(pubkeys, config.clone())
+    })
+    .map_err(|e| Error::from(e).with_slot(slot))
+}
+
+/// the accounts a full valuation reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuationAddresses {
+    pub state: Pubkey,
+    pub liq_pool_sol_leg: Pubkey,
+    pub liq_pool_msol_leg: Pubkey,
+    pub lp_mint: Pubkey,
+    pub msol_mint: Pubkey,
+}
+
+impl ValuationAddresses {
+    /// derive the remaining addresses from a state parsed earlier; they only change through
+    /// admin instructions, so a cached state is good enough to locate them
+    pub fn from_state(state_address: Pubkey, program_id: &Pubkey, state: &MarinadeState) -> Self {
+        let (liq_pool_sol_leg, _) =
+            Pubkey::find_program_address(&[state_address.as_ref(), LIQ_POOL_SOL_LEG_SEED], program_id);
+        Self {
+            state: state_address,
+            liq_pool_sol_leg,
+            liq_pool_msol_leg: state.liq_pool.msol_leg,
+            lp_mint: state.liq_pool.lp_mint,
+            msol_mint: state.msol_mint,
+        }
+    }
+}
+
+/// balances of the SOL/mSOL liquidity pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiqPoolBalances {
+    /// lamports in the sol leg above its rent-exempt reserve
+    pub sol_leg_lamports: u64,
+    pub msol_leg_amount: u64,
+    pub lp_supply: u64,
+}
+
+/// everything fetched for a valuation, all from the same response
+#[derive(Debug, Clone)]
+pub struct ValuationAccounts {
+    /// context slot of the response
+    pub slot: u64,
+    pub state: MarinadeState,
+    pub liq_pool: LiqPoolBalances,
+    pub msol_mint_supply: u64,
+}
+
+impl ValuationAccounts {
+    /// lamports per mSOL
+    pub fn msol_price_lamports(&self) -> u64 {
+        self.state.msol_price_lamports()
+    }
+
+    /// lamports per whole LP token: both legs, the mSOL one at the state's price, over the LP
+    /// supply. None while there's no supply
+    pub fn lp_price_lamports(&self) -> Option<u64> {
+        let pool = &self.liq_pool;
+        let value = pool.sol_leg_lamports as u128 + self.state.minimal().msol_to_sol(pool.msol_leg_amount) as u128;
+        (pool.lp_supply > 0).then(|| (value * LAMPORTS_PER_MSOL as u128 / pool.lp_supply as u128) as u64)
+    }
+}
+
+/// what the composite valuations (`Tvl`, `ValuationAccounts::lp_price_lamports`,
+/// `PointValuation::from_snapshot`) take, so their numbers agree with each other: every account
+/// from one `getMultipleAccounts`, at the one slot of its response
+pub type ConsistentSnapshot = ValuationAccounts;
+
+/// the `ConsistentSnapshot` of `client`'s deployment now, read with the client's options. any
+/// account missing fails the lot
+pub fn fetch_consistent_snapshot(client: &MarinadeClient) -> Result<ConsistentSnapshot> {
+    let _verbosity = client.options().verbosity.enter();
+    let addresses = client.deployment().valuation_addresses();
+    fetch_valuation_accounts(client.rpc_client(), &addresses, None, client.options())
+}
+
+fn invalid_account(role: &'static str, pubkey: Pubkey, slot: Option<u64>, source: std::io::Error) -> Error {
+    Error::new(ErrorKind::InvalidAccountData { role, source }).with_pubkey(pubkey).with_slot(slot)
+}
+
+fn unpack<T: Pack + IsInitialized>(account: &Account, role: &'static str, pubkey: Pubkey, slot: Option<u64>) -> Result<T> {
+    T::unpack(&account.data).map_err(|e| {
+        invalid_account(role, pubkey, slot, std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    })
+}
+
+/// every account of `addresses` in one `getMultipleAccounts`, read at `options.commitment` and
+/// the rest as `fetch_account_data` reads
+pub fn fetch_valuation_accounts(
+    rpc_client: &dyn RpcFetcher,
+    addresses: &ValuationAddresses,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<ValuationAccounts> {
+    let pubkeys = [
+        addresses.state,
+        addresses.liq_pool_sol_leg,
+        addresses.liq_pool_msol_leg,
+        addresses.lp_mint,
+        addresses.msol_mint,
+    ];
+    let roles = ["marinade state", "liq pool sol leg", "liq pool msol leg", "lp mint", "msol mint"];
+    let (context_slot, accounts) = fetch_multiple_accounts(rpc_client, &pubkeys, &roles, slot, options)?;
+
+    let state = parse_state(&accounts[0].data, options).map_err(|e| invalid_account(roles[0], pubkeys[0], slot, e))?;
+    let msol_leg: TokenAccount = unpack(&accounts[2], roles[2], pubkeys[2], slot)?;
+    let lp_mint: Mint = unpack(&accounts[3], roles[3], pubkeys[3], slot)?;
+    let msol_mint: Mint = unpack(&accounts[4], roles[4], pubkeys[4], slot)?;
+
+    let liq_pool = LiqPoolBalances {
+        sol_leg_lamports: accounts[1].lamports.saturating_sub(state.rent_exempt_for_token_acc),
+        msol_leg_amount: msol_leg.amount,
+        lp_supply: lp_mint.supply,
+    };
+    debug!(context_slot, ?liq_pool, "liq pool balances fetched");
+
+    Ok(ValuationAccounts { slot: context_slot, state, liq_pool, msol_mint_supply: msol_mint.supply })
+}
+
+/// lamports per mSOL
+pub fn fetch_msol_price(
+    rpc_client: &dyn RpcFetcher,
+    addresses: &ValuationAddresses,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<u64> {
+    Ok(fetch_valuation_accounts(rpc_client, addresses, slot, options)?.msol_price_lamports())
+}
+
+/// marinade's total value locked: the SOL backing mSOL plus the liq pool's SOL leg, which the
+/// mSOL price leaves out since it's the LPs', not the holders'. the pool's mSOL leg is already
+/// backed by `staked_lamports` and the rest, so it isn't counted again. lamports owed to ticket
+/// holders are still locked, so unlike `total_virtual_staked_lamports` this doesn't take them off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tvl {
+    /// delegated to validators and active
+    pub staked_lamports: u64,
+    /// in the reserve, waiting for the next stake-delta crank or paying out claims
+    pub reserve_lamports: u64,
+    /// deactivating for delayed and emergency unstakes
+    pub cooling_down_lamports: u64,
+    /// in the liq pool's SOL leg, above its rent-exempt reserve
+    pub liq_pool_sol_lamports: u64,
+    /// saturating at `u64::MAX`, which only corrupt or crafted accounts reach
+    pub total_lamports: u64,
+    /// context slot of the accounts
+    pub slot: u64,
+}
+
+impl Tvl {
+    pub fn from_accounts(accounts: &ValuationAccounts) -> Self {
+        let state = accounts.state.minimal();
+        let (staked_lamports, reserve_lamports) = (state.total_active_balance, state.available_reserve_balance);
+        let cooling_down_lamports = state.total_cooling_down();
+        let liq_pool_sol_lamports = accounts.liq_pool.sol_leg_lamports;
+        let parts = [staked_lamports, reserve_lamports, cooling_down_lamports, liq_pool_sol_lamports];
+        Self {
+            staked_lamports,
+            reserve_lamports,
+            cooling_down_lamports,
+            liq_pool_sol_lamports,
+            total_lamports: parts.into_iter().fold(0, u64::saturating_add),
+            slot: accounts.slot,
+        }
+    }
+}
+
+/// the TVL of `client`'s deployment now, from one `getMultipleAccounts` read with the client's
+/// options
+pub fn get_tvl(client: &MarinadeClient) -> Result<Tvl> {
+    Ok(Tvl::from_accounts(&fetch_consistent_snapshot(client)?))
+}
+
+pub fn fetch_liq_pool_balances(
+    rpc_client: &dyn RpcFetcher,
+    addresses: &ValuationAddresses,
+    slot: Option<u64>,
+    options: &AnalyzeOptions,
+) -> Result<LiqPoolBalances> {
+    Ok(fetch_valuation_accounts(rpc_client, addresses, slot, options)?.liq_pool)
+}
+
+/// the token program owning a mint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenProgram {
+    SplToken,
+    /// token-2022, whose extensions can change transfer amounts: worth flagging to anything that
+    /// assumes plain spl token balances
+    Token2022,
+}
+
+/// what `verify_msol_mint` found, every check having passed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsolMintReport {
+    /// context slot of the response
+    pub slot: u64,
+    pub mint: Pubkey,
+    pub token_program: TokenProgram,
+    pub decimals: u8,
+    /// the deployment's mSOL mint authority pda
+    pub mint_authority: Pubkey,
+    pub supply: u64,
+}
+
+/// why the mSOL mint can't be trusted for token balance math
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MintCheckError {
+    /// owned by neither token program
+    NotATokenMint { owner: Pubkey },
+    Decimals { expected: u8, found: u8 },
+    /// none when the mint has no authority left
+    MintAuthority { expected: Pubkey, found: Option<Pubkey> },
+}
+
+impl std::fmt::Display for MintCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotATokenMint { owner } => write!(f, "owned by {}, not a token program", owner),
+            Self::Decimals { expected, found } => write!(f, "{} decimals, expected {}", found, expected),
+            Self::MintAuthority { expected, found: Some(found) } => {
+                write!(f, "mint authority {}, expected {}", found, expected)
+            }
+            Self::MintAuthority { expected, found: None } => write!(f, "no mint authority, expected {}", expected),
+        }
+    }
+}
+
+impl std::error::Error for MintCheckError {}
+
+/// checks the deployment's mSOL mint is a token mint with 9 decimals that only the program can
+/// mint. a token-2022 mint passes, flagged in `token_program`
+pub fn verify_msol_mint(
+    rpc_client: &dyn RpcFetcher,
+    deployment: &DeploymentConfig,
+    options: &AnalyzeOptions,
+) -> Result<MsolMintReport> {
+    let (role, pubkey) = ("msol mint", deployment.msol_mint);
+    let (slot, accounts) = fetch_multiple_accounts(rpc_client, &[pubkey], &[role], None, options)?;
+    let account = &accounts[0];
+    let fail = |problem| Error::new(ErrorKind::InvalidMint(problem)).with_pubkey(pubkey).with_slot(Some(slot));
+
+    let token_program = match account.owner {
+        owner if owner == spl_token::ID => TokenProgram::SplToken,
+        owner if owner == SPL_TOKEN_2022_PROGRAM_ID => TokenProgram::Token2022,
+        owner => return Err(fail(MintCheckError::NotATokenMint { owner })),
+    };
+    // token-2022 mints share spl token's layout, with any extensions after it
+    let base = Account { data: account.data.get(..Mint::LEN).unwrap_or(&account.data).to_vec(), ..account.clone() };
+    let mint: Mint = unpack(&base, role, pubkey, Some(slot))?;
+    if mint.decimals != MSOL_DECIMALS {
+        return Err(fail(MintCheckError::Decimals { expected: MSOL_DECIMALS, found: mint.decimals }));
+    }
+    let expected = deployment.msol_mint_authority();
+    let found = Option::from(mint.mint_authority);
+    if found != Some(expected) {
+        return Err(fail(MintCheckError::MintAuthority { expected, found }));
+    }
+    debug!(slot, ?token_program, "msol mint verified");
+    let (decimals, supply) = (mint.decimals, mint.supply);
+    Ok(MsolMintReport { slot, mint: pubkey, token_program, decimals, mint_authority: expected, supply })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_option::COption;
+    use crate::test_utils::{mint_account, msol_mint_account, sample_state, state_account, token_account, MockFetcher};
+
+    fn addresses() -> (MarinadeState, ValuationAddresses) {
+        let state = sample_state();
+        let addresses = ValuationAddresses::from_state(
+            crate::constants::MARINADE_STATE_PUBKEY,
+            &crate::constants::MARINADE_PROGRAM_ID,
+            &state,
+        );
+        (state, addresses)
+    }
+
+    fn sol_leg(lamports: u64) -> Account {
+        Account { lamports, data: vec![], owner: Pubkey::default(), executable: false, rent_epoch: 0 }
+    }
+
+    #[test]
+    fn test_valuation_uses_a_single_rpc_call() {
+        let (state, addresses) = addresses();
+        let sol_leg_lamports = 5_000_000_000 + state.rent_exempt_for_token_acc;
+        let rpc = MockFetcher::new().with_valuation_accounts(&addresses, &state, sol_leg_lamports);
+
+        let valuation = fetch_valuation_accounts(&rpc, &addresses, Some(200), &AnalyzeOptions::default()).unwrap();
+        assert_eq!(rpc.calls(), vec!["getMultipleAccounts"]);
+        assert_eq!(valuation.slot, 200);
+        assert_eq!(valuation.state, state);
+        assert_eq!(
+            valuation.liq_pool,
+            LiqPoolBalances { sol_leg_lamports: 5_000_000_000, msol_leg_amount: 3_000_000_000, lp_supply: 7_000_000_000 }
+        );
+        assert_eq!(valuation.msol_mint_supply, state.msol_supply);
+        assert_eq!(valuation.msol_price_lamports(), state.msol_price_lamports());
+    }
+
+    #[test]
+    fn test_accounts_are_read_with_the_options() {
+        use solana_sdk::commitment_config::CommitmentConfig;
+        use std::time::Duration;
+        use UiAccountEncoding::{Base64, Base64Zstd};
+
+        let (state, addresses) = addresses();
+        let mock = |rpc: MockFetcher| rpc.with_valuation_accounts(&addresses, &state, 5_000_000_000);
+        let options = AnalyzeOptions {
+            commitment: CommitmentConfig::finalized(),
+            wait_for_slot: Some(Duration::from_secs(5)),
+            prefer_zstd: true,
+            ..AnalyzeOptions::default()
+        };
+
+        let rpc = mock(MockFetcher::new());
+        assert_eq!(fetch_valuation_accounts(&rpc, &addresses, None, &options).unwrap().state, state);
+        assert_eq!(rpc.encodings(), [Some(Base64Zstd)]);
+        assert_eq!(rpc.commitments(), [("getMultipleAccounts", Some(CommitmentConfig::finalized()))]);
+
+        // refused, then read as base64 once the node has caught up with the slot
+        let rpc = mock(MockFetcher::new().without_zstd().with_node_slots([150, 250]));
+        assert_eq!(fetch_valuation_accounts(&rpc, &addresses, Some(200), &options).unwrap().slot, 250);
+        assert_eq!(rpc.calls(), ["getMultipleAccounts", "getMultipleAccounts", "getSlot", "getMultipleAccounts"]);
+        assert_eq!(rpc.encodings(), [Some(Base64Zstd), Some(Base64), Some(Base64)]);
+        assert!(rpc.commitments().iter().all(|(_, commitment)| *commitment == Some(CommitmentConfig::finalized())));
+
+        // data that doesn't decompress reads as missing accounts
+        let rpc = mock(MockFetcher::new().with_corrupt_zstd());
+        assert_eq!(fetch_valuation_accounts(&rpc, &addresses, None, &options).unwrap().state, state);
+        assert_eq!(rpc.encodings(), [Some(Base64Zstd), Some(Base64)]);
+    }
+
+    #[test]
+    fn test_tvl_counts_the_liq_pool_sol_leg() {
+        use crate::deployment::DeploymentConfig;
+        use solana_sdk::commitment_config::CommitmentConfig;
+
+        let state = sample_state();
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let sol_leg_lamports = 5_000_000_000 + state.rent_exempt_for_token_acc;
+        let rpc = MockFetcher::new().with_valuation_accounts(&addresses, &state, sol_leg_lamports);
+        let rpc = std::sync::Arc::new(rpc);
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+        let client = client.with_commitment(CommitmentConfig::finalized());
+
+        let tvl = get_tvl(&client).unwrap();
+        assert_eq!(rpc.commitments(), [("getMultipleAccounts", Some(CommitmentConfig::finalized()))]);
+        let minimal = state.minimal();
+        assert_eq!(tvl.staked_lamports, minimal.total_active_balance);
+        assert_eq!(tvl.reserve_lamports, minimal.available_reserve_balance);
+        assert_eq!(tvl.cooling_down_lamports, state.total_cooling_down());
+        assert_eq!(tvl.liq_pool_sol_lamports, 5_000_000_000);
+        assert_eq!(tvl.total_lamports, state.total_lamports_under_control() + 5_000_000_000);
+        // more than what backs mSOL, by the SOL leg and the tickets
+        let backing = state.total_virtual_staked_lamports();
+        assert_eq!(tvl.total_lamports - backing, 5_000_000_000 + minimal.circulating_ticket_balance);
+        assert_eq!(tvl.slot, crate::test_utils::FIXTURE_SLOT);
+        let json = serde_json::to_value(tvl).unwrap();
+        assert_eq!(serde_json::from_value::<Tvl>(json).unwrap(), tvl);
+
+        // balances no real deployment holds add up to the top of the range, not past it
+        let mut state = state.clone();
+        state.validator_system.total_active_balance = u64::MAX;
+        let liq_pool = LiqPoolBalances { sol_leg_lamports: u64::MAX, msol_leg_amount: 0, lp_supply: 0 };
+        let crafted = ValuationAccounts { slot: 1, state, liq_pool, msol_mint_supply: 0 };
+        assert_eq!(Tvl::from_accounts(&crafted).total_lamports, u64::MAX);
+    }
+
+    #[test]
+    fn test_consistent_snapshot_feeds_every_valuation() {
+        use crate::deployment::DeploymentConfig;
+        use crate::portfolio::PointValuation;
+
+        let state = sample_state();
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let rpc = std::sync::Arc::new(
+            MockFetcher::new()
+                .with_valuation_accounts(&addresses, &state, 5_000_000_000 + state.rent_exempt_for_token_acc)
+                .with_node_slots([crate::test_utils::FIXTURE_SLOT + 7]),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).build();
+
+        let snapshot = fetch_consistent_snapshot(&client).unwrap();
+        assert_eq!(rpc.calls(), vec!["getMultipleAccounts"]);
+        let slot = crate::test_utils::FIXTURE_SLOT + 7;
+        let tvl = Tvl::from_accounts(&snapshot);
+        let point = PointValuation::from_snapshot(&snapshot, 2_000_000_000);
+        assert_eq!((snapshot.slot, tvl.slot, point.price_slot), (slot, slot, slot));
+        // 5 SOL and 3 mSOL at 1.2 SOL behind 7 LP tokens
+        assert_eq!(snapshot.lp_price_lamports(), Some(1_228_642_857));
+        assert_eq!((point.price_lamports, point.value_lamports), (1_200_166_666, 2_400_333_333));
+        let empty = ConsistentSnapshot { liq_pool: LiqPoolBalances { lp_supply: 0, ..snapshot.liq_pool }, ..snapshot };
+        assert_eq!(empty.lp_price_lamports(), None);
+    }
+
+    #[test]
+    fn test_snapshot_is_read_with_the_client_options() {
+        use crate::deployment::DeploymentConfig;
+        use solana_sdk::commitment_config::CommitmentConfig;
+        use UiAccountEncoding::{Base64, Base64Zstd};
+
+        let state = sample_state();
+        let addresses = DeploymentConfig::MAINNET.valuation_addresses();
+        let rpc = std::sync::Arc::new(
+            MockFetcher::new().with_valuation_accounts(&addresses, &state, 5_000_000_000).without_zstd(),
+        );
+        let client = MarinadeClient::builder().rpc_client(rpc.clone()).prefer_zstd(true).build();
+        let client = client.with_commitment(CommitmentConfig::finalized());
+
+        assert_eq!(fetch_consistent_snapshot(&client).unwrap().state, state);
+        assert_eq!(rpc.encodings(), [Some(Base64Zstd), Some(Base64)]);
+        assert!(rpc.commitments().iter().all(|(_, commitment)| *commitment == Some(CommitmentConfig::finalized())));
+    }
+
+    #[test]
+    fn test_missing_and_invalid_accounts_are_named() {
+        let (state, addresses) = addresses();
+        let rpc = MockFetcher::new()
+            .with_account(addresses.state, state_account(&state))
+            .with_account(addresses.liq_pool_sol_leg, sol_leg(1))
+            .with_account(addresses.liq_pool_msol_leg, token_account(addresses.msol_mint, 1))
+            .with_account(addresses.msol_mint, mint_account(1));
+
+        let err = fetch_liq_pool_balances(&rpc, &addresses, Some(200), &AnalyzeOptions::default()).unwrap_err();
+        match err.kind() {
+            ErrorKind::AccountNotFound { role } => assert_eq!(*role, "lp mint"),
+            other => panic!("expected a missing lp mint, got {:?}", other),
+        }
+        assert_eq!(err.pubkey(), Some(&addresses.lp_mint));
+        assert_eq!(err.slot(), Some(200));
+
+        // an lp "mint" that is really a token account
+        let rpc = rpc.with_account(addresses.lp_mint, token_account(addresses.msol_mint, 1));
+        let err = fetch_msol_price(&rpc, &addresses, None, &AnalyzeOptions::default()).unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidAccountData { role, .. } => assert_eq!(*role, "lp mint"),
+            other => panic!("expected an invalid lp mint, got {:?}", other),
+        }
+        assert_eq!(err.pubkey(), Some(&addresses.lp_mint));
+        assert!(err.parse_error().is_some());
+
+        // a state without its discriminator only parses leniently
+        let bare = Account { data: crate::test_utils::state_data(&state), ..state_account(&state) };
+        let rpc = rpc.with_account(addresses.lp_mint, mint_account(1)).with_account(addresses.state, bare);
+        let err = fetch_msol_price(&rpc, &addresses, None, &AnalyzeOptions::default()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidAccountData { role: "marinade state", .. }), "{}", err);
+        let lenient = AnalyzeOptions { parse_mode: crate::accounts::marinade::ParseMode::Lenient, ..AnalyzeOptions::default() };
+        assert_eq!(fetch_msol_price(&rpc, &addresses, None, &lenient).unwrap(), state.msol_price_lamports());
+    }
+    #[test]
+    fn test_msol_mint_verification() {
+        let deployment = DeploymentConfig::MAINNET;
+        let verify = |account| {
+            let rpc = MockFetcher::new().with_account(deployment.msol_mint, account);
+            verify_msol_mint(&rpc, &deployment, &AnalyzeOptions::default())
+        };
+        let altered = |change: &dyn Fn(&mut Mint)| {
+            let mut account = msol_mint_account(&deployment, 10);
+            let mut mint = Mint::unpack(&account.data).unwrap();
+            change(&mut mint);
+            mint.pack_into_slice(&mut account.data);
+            account
+        };
+        let problem = |account| match verify(account).unwrap_err().kind() {
+            ErrorKind::InvalidMint(problem) => problem.clone(),
+            other => panic!("expected a failed mint check, got {:?}", other),
+        };
+
+        let report = verify(msol_mint_account(&deployment, 10)).unwrap();
+        assert_eq!((report.token_program, report.decimals, report.supply), (TokenProgram::SplToken, 9, 10));
+        assert_eq!(report.mint_authority, deployment.msol_mint_authority());
+        // token-2022, with an extension after the base layout
+        let mut extended = msol_mint_account(&deployment, 10);
+        extended.owner = SPL_TOKEN_2022_PROGRAM_ID;
+        extended.data.extend_from_slice(&[0; 83]);
+        assert_eq!(verify(extended).unwrap().token_program, TokenProgram::Token2022);
+
+        let owner = Pubkey::new_unique();
+        let foreign = Account { owner, ..msol_mint_account(&deployment, 10) };
+        assert_eq!(problem(foreign), MintCheckError::NotATokenMint { owner });
+        assert_eq!(problem(altered(&|mint| mint.decimals = 6)), MintCheckError::Decimals { expected: 9, found: 6 });
+        let (expected, other) = (deployment.msol_mint_authority(), Pubkey::new_unique());
+        let stolen = altered(&|mint| mint.mint_authority = COption::Some(other));
+        assert_eq!(problem(stolen), MintCheckError::MintAuthority { expected, found: Some(other) });
+        let frozen = altered(&|mint| mint.mint_authority = COption::None);
+        assert_eq!(problem(frozen), MintCheckError::MintAuthority { expected, found: None });
+    }
+}
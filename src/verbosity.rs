@@ -0,0 +1,107 @@
+//! how much the crate itself logs, per client rather than per process. the host's subscriber
+//! still filters what's left; this only holds back events the crate would otherwise emit, e.g.
+//! a service's global `debug` filter with one client kept to errors. spans aren't affected
+//!
+//! the crate's modules log through the macros here instead of tracing's, which check the
+//! verbosity the current call runs at: whatever `Verbosity::enter` set for the thread, `Normal`
+//! outside of any
+
+use tracing::Level;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verbosity {
+    /// nothing at all, not even failed analyses with `AnalyzeOptions::verbose`
+    Off,
+    /// errors and warnings, e.g. a lenient parse or a failed cache write
+    Errors,
+    /// up to debug: fetches, parses and outcomes, with the pubkeys and slots involved
+    #[default]
+    Normal,
+    /// also the raw bytes, e.g. the prefix of every account read
+    Trace,
+}
+
+impl Verbosity {
+    /// the most verbose level let through, None for `Off`
+    pub fn max_level(&self) -> Option<Level> {
+        match self {
+            Self::Off => None,
+            Self::Errors => Some(Level::WARN),
+            Self::Normal => Some(Level::DEBUG),
+            Self::Trace => Some(Level::TRACE),
+        }
+    }
+
+    pub fn allows(&self, level: Level) -> bool {
+        self.max_level().is_some_and(|max| level <= max)
+    }
+}
+
+#[cfg(feature = "rpc")]
+pub(crate) use scope::*;
+
+#[cfg(feature = "rpc")]
+mod scope {
+    use std::cell::Cell;
+
+    use super::Verbosity;
+
+    thread_local! {
+        static CURRENT: Cell<Verbosity> = const { Cell::new(Verbosity::Normal) };
+    }
+
+    /// the verbosity set back when dropped
+    pub(crate) struct VerbosityGuard(Verbosity);
+
+    impl Drop for VerbosityGuard {
+        fn drop(&mut self) {
+            CURRENT.with(|current| current.set(self.0));
+        }
+    }
+
+    impl Verbosity {
+        /// run the thread at this verbosity until the guard drops, for a call's duration
+        pub(crate) fn enter(self) -> VerbosityGuard {
+            VerbosityGuard(CURRENT.with(|current| current.replace(self)))
+        }
+    }
+
+    pub(crate) fn enabled(level: tracing::Level) -> bool {
+        CURRENT.with(|current| current.get().allows(level))
+    }
+
+    macro_rules! trace {
+        ($($arg:tt)+) => {
+            if $crate::verbosity::enabled(::tracing::Level::TRACE) {
+                ::tracing::trace!($($arg)+)
+            }
+        };
+    }
+
+    macro_rules! debug {
+        ($($arg:tt)+) => {
+            if $crate::verbosity::enabled(::tracing::Level::DEBUG) {
+                ::tracing::debug!($($arg)+)
+            }
+        };
+    }
+
+    // named apart from the builtin `#[warn]` attribute, which a bare `warn` would be ambiguous with
+    macro_rules! warn_event {
+        ($($arg:tt)+) => {
+            if $crate::verbosity::enabled(::tracing::Level::WARN) {
+                ::tracing::warn!($($arg)+)
+            }
+        };
+    }
+
+    macro_rules! error {
+        ($($arg:tt)+) => {
+            if $crate::verbosity::enabled(::tracing::Level::ERROR) {
+                ::tracing::error!($($arg)+)
+            }
+        };
+    }
+
+    pub(crate) use {debug, error, trace, warn_event as warn};
+}
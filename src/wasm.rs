@@ -0,0 +1,82 @@
+//! wasm-bindgen exports of the parsing entry points, for valuing mSOL in a browser from
+//! account data the page fetched itself. build with
+//! `cargo build --target wasm32-unknown-unknown --no-default-features --features wasm`
+
+use wasm_bindgen::prelude::*;
+
+use crate::accounts::instructions::MarinadeFinanceInstruction;
+use crate::accounts::marinade::{parse_marinade_state_with, MinimalState, ParseMode};
+
+/// the price components of a marinade state account. u64s cross as `BigInt`
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarinadePrice {
+    /// lamports per whole mSOL
+    pub msol_price_lamports: u64,
+    /// the SOL backing the whole supply
+    pub total_virtual_staked_lamports: u64,
+    pub msol_supply: u64,
+}
+
+impl MarinadePrice {
+    /// None when the balances overflow the price math, which only crafted data makes them do
+    fn checked(state: &MinimalState) -> Option<Self> {
+        Some(Self {
+            msol_price_lamports: state.checked_msol_price_lamports()?,
+            total_virtual_staked_lamports: state.checked_total_virtual_staked_lamports()?,
+            msol_supply: state.msol_supply,
+        })
+    }
+}
+
+/// the price components of raw state account data, the discriminator included
+#[wasm_bindgen(js_name = parseMarinadeState)]
+pub fn parse_marinade_state(account_data: &[u8]) -> Result<MarinadePrice, JsError> {
+    let parsed = parse_marinade_state_with(account_data, ParseMode::Strict).map_err(|e| JsError::new(&e.to_string()))?;
+    price_of(&parsed.state.minimal()).map_err(JsError::new)
+}
+
+/// the checked price of `state`, the error still a string: a `JsError` needs a js host
+fn price_of(state: &MinimalState) -> Result<MarinadePrice, &'static str> {
+    MarinadePrice::checked(state).ok_or("state balances overflow the price math")
+}
+
+/// lamports per whole mSOL in raw state account data
+#[wasm_bindgen(js_name = computeMsolPrice)]
+pub fn compute_msol_price(account_data: &[u8]) -> Result<u64, JsError> {
+    parse_marinade_state(account_data).map(|price| price.msol_price_lamports)
+}
+
+/// the handler name of a marinade instruction's data, e.g. "deposit", if it is one
+#[wasm_bindgen(js_name = decodeInstruction)]
+pub fn decode_instruction(data: &[u8]) -> Option<String> {
+    MarinadeFinanceInstruction::try_from_data(data).map(|ix| ix.name().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sample_state, state_account_data};
+
+    // only the success paths: building a `JsError` needs a js host
+    #[test]
+    fn test_exports_match_the_rust_api() {
+        let state = sample_state();
+        let data = state_account_data(&state);
+        assert_eq!(parse_marinade_state(&data).ok(), MarinadePrice::checked(&state.minimal()));
+        assert_eq!(compute_msol_price(&data).ok(), Some(state.msol_price_lamports()));
+
+        let deposit = MarinadeFinanceInstruction::Deposit.discriminator();
+        assert_eq!(decode_instruction(&deposit).as_deref(), Some("deposit"));
+        assert_eq!(decode_instruction(&[0; 8]), None);
+    }
+
+    #[test]
+    fn test_overflowing_balances_are_refused() {
+        let state = sample_state().minimal();
+        assert_eq!(price_of(&state).unwrap().msol_price_lamports, state.msol_price_lamports());
+        let crafted = MinimalState { total_active_balance: u64::MAX, available_reserve_balance: 1, ..state };
+        assert!(price_of(&crafted).is_err());
+        assert!(price_of(&MinimalState { msol_supply: 1, ..state }).is_err());
+    }
+}
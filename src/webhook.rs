@@ -0,0 +1,250 @@
+//! a `PriceSink` that POSTs each snapshot as json to a url, and an `EventSink` doing the same
+//! with events. the client is blocking, so publish from a plain thread rather than from inside
+//! an async runtime
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::events::{EventSink, MarinadeEvent};
+use crate::parsers::PriceSnapshot;
+use crate::sink::PriceSink;
+use crate::verbosity::{debug, error, warn};
+
+/// carries `signature(secret, body)` when the sink has a secret
+pub const SIGNATURE_HEADER: &str = "X-Marinade-Signature";
+
+/// attempts are spaced by a backoff that doubles from `initial_backoff` up to `max_backoff`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 5, initial_backoff: Duration::from_millis(500), max_backoff: Duration::from_secs(30) }
+    }
+}
+
+impl RetryPolicy {
+    /// the wait before attempt `attempt + 1`, counting from 1
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(1 << (attempt - 1).min(16)).min(self.max_backoff)
+    }
+}
+
+pub struct WebhookSink {
+    client: Client,
+    url: String,
+    secret: Option<Vec<u8>>,
+    retry: RetryPolicy,
+    dead_letter: Option<PathBuf>,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build().expect("http client builds");
+        Self { client, url: url.into(), secret: None, retry: RetryPolicy::default(), dead_letter: None }
+    }
+
+    /// sign every payload, see `SIGNATURE_HEADER`
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// append payloads that exhausted their retries to this file, one json object per line
+    pub fn with_dead_letter(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dead_letter = Some(path.into());
+        self
+    }
+
+    /// POST `snapshot`, retrying until the endpoint answers 2xx or the attempts run out
+    pub fn deliver(&self, snapshot: &PriceSnapshot) -> Result<()> {
+        self.deliver_body(&serde_json::to_vec(snapshot).expect("snapshots serialize"), snapshot.slot)
+    }
+
+    /// POST `event` as `MarinadeEvent` serializes it, with its `kind`, retrying like `deliver`
+    pub fn deliver_event(&self, event: &MarinadeEvent) -> Result<()> {
+        self.deliver_body(&serde_json::to_vec(event).expect("events serialize"), event.context().slot)
+    }
+
+    fn deliver_body(&self, body: &[u8], slot: u64) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let reason = match self.post(body) {
+                Ok(()) => {
+                    debug!(url = %self.url, slot, attempt, "webhook delivered");
+                    return Ok(());
+                }
+                Err(reason) => reason,
+            };
+            if attempt >= self.retry.max_attempts {
+                return Err(Error::new(ErrorKind::Delivery { attempts: attempt, reason }).with_slot(Some(slot)));
+            }
+            let backoff = self.retry.backoff(attempt);
+            warn!(url = %self.url, attempt, reason, ?backoff, "webhook delivery failed, retrying");
+            std::thread::sleep(backoff);
+            attempt += 1;
+        }
+    }
+
+    fn post(&self, body: &[u8]) -> std::result::Result<(), String> {
+        let mut request = self.client.post(&self.url).header(CONTENT_TYPE, "application/json").body(body.to_vec());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, body));
+        }
+        let response = request.send().map_err(|e| e.to_string())?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(format!("endpoint answered {}", status)),
+        }
+    }
+
+    fn write_dead_letter(&self, payload: &impl Serialize, e: &Error) {
+        let Some(path) = &self.dead_letter else {
+            return;
+        };
+        let line = serde_json::json!({ "url": self.url, "error": e.to_string(), "payload": payload });
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(write_error) = written {
+            error!(path = %path.display(), error = %write_error, "failed to write webhook dead letter");
+        }
+    }
+}
+
+impl PriceSink for WebhookSink {
+    fn publish(&self, snapshot: &PriceSnapshot) {
+        if let Err(e) = self.deliver(snapshot) {
+            error!(url = %self.url, error = %e, "webhook payload dead-lettered");
+            self.write_dead_letter(snapshot, &e);
+        }
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn publish_event(&self, event: &MarinadeEvent) {
+        if let Err(e) = self.deliver_event(event) {
+            error!(url = %self.url, error = %e, "webhook event dead-lettered");
+            self.write_dead_letter(event, &e);
+        }
+    }
+}
+
+/// `sha256=` and the hex hmac-sha256 of `body` under `secret`
+pub fn signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac takes keys of any length");
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MSOL_MINT_PUBKEY;
+    use crate::parsers::Protocol;
+    use crate::test_utils::FIXTURE_SLOT;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// lowercased header lines and the body
+    type Received = (Vec<String>, Vec<u8>);
+
+    /// answers one connection per status in `statuses`, sending each request back
+    fn endpoint(statuses: Vec<u16>) -> (String, mpsc::Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    headers.push(line.trim().to_lowercase());
+                }
+                let len = headers.iter().find_map(|h| h.strip_prefix("content-length: ")).unwrap().parse().unwrap();
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                write!(reader.get_mut(), "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                requests.send((headers, body)).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn snapshot() -> PriceSnapshot {
+        PriceSnapshot::new(Protocol::Marinade, MSOL_MINT_PUBKEY, 1_200_166_666, 7_201, 6_000, FIXTURE_SLOT)
+    }
+
+    fn fast_retry(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, initial_backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(2) }
+    }
+
+    #[test]
+    fn test_signed_delivery_after_a_retry() {
+        let (url, received) = endpoint(vec![503, 200]);
+        let sink = WebhookSink::new(url).with_secret("hook secret").with_retry(fast_retry(3));
+        sink.deliver(&snapshot()).unwrap();
+
+        let (_, first) = received.recv().unwrap();
+        let (headers, body) = received.recv().unwrap();
+        assert_eq!(first, body);
+        assert_eq!(body, serde_json::to_vec(&snapshot()).unwrap());
+        let expected = format!("{}: {}", SIGNATURE_HEADER.to_lowercase(), signature(b"hook secret", &body));
+        assert!(headers.contains(&expected));
+        // the usual hmac-sha256 example vector
+        assert_eq!(
+            signature(b"key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert_eq!(fast_retry(10).backoff(5), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn test_exhausted_payload_is_dead_lettered() {
+        let (url, received) = endpoint(vec![500, 500, 500, 500]);
+        let sink = WebhookSink::new(url).with_retry(fast_retry(2));
+        let err = sink.deliver(&snapshot()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Delivery { attempts: 2, .. }));
+        assert_eq!(err.slot(), Some(FIXTURE_SLOT));
+
+        let path = std::env::temp_dir().join(format!("webhook-dead-letter-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        sink.with_dead_letter(&path).publish(&snapshot());
+        assert_eq!(received.iter().take(4).count(), 4);
+        let lines = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let letter: serde_json::Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+        assert_eq!(lines.lines().count(), 1);
+        assert_eq!(letter["payload"]["slot"], FIXTURE_SLOT);
+        assert!(letter["error"].as_str().unwrap().contains("500"));
+    }
+}
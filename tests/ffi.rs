@@ -0,0 +1,59 @@
+//! compiles `tests/ffi/ffi_test.c` against the header and the cdylib of this build, then checks
+//! what it prints against the rust api. needs a c compiler, `cc` or whatever `CC` names
+
+use std::path::Path;
+use std::process::Command;
+
+use anchor_lang::AnchorSerialize;
+use parser_test::accounts::marinade::{
+    MarinadeState, StakeSystem, ValidatorSystem, LAMPORTS_PER_MSOL, STATE_DISCRIMINATOR,
+};
+
+#[test]
+fn test_c_program_against_the_cdylib() {
+    // 7.201M SOL behind 6M mSOL
+    let state = MarinadeState {
+        stake_system: StakeSystem { delayed_unstake_cooling_down: 1_000_000_000_000, ..StakeSystem::default() },
+        validator_system: ValidatorSystem { total_active_balance: 7_000_000_000_000_000, ..ValidatorSystem::default() },
+        available_reserve_balance: 400_000_000_000_000,
+        circulating_ticket_balance: 200_000_000_000_000,
+        msol_supply: 6_000_000_000_000_000,
+        ..MarinadeState::default()
+    };
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let state_path = tmp.join("ffi_state.bin");
+    // as the node serves the account, the discriminator first
+    std::fs::write(&state_path, [&STATE_DISCRIMINATOR[..], &state.try_to_vec().unwrap()].concat()).unwrap();
+
+    // integration tests run from target/<profile>/deps, next to the cdylib
+    let lib_dir = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let program = tmp.join("ffi_test");
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir.join("tests/ffi/ffi_test.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lparser_test", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .status()
+        .expect("c compiler runs");
+    assert!(compiled.success());
+
+    // cargo points LD_LIBRARY_PATH at target/<profile>, where a libparser_test.so built without
+    // the ffi feature may sit and shadow the rpath
+    let output = Command::new(&program).arg(&state_path).env_remove("LD_LIBRARY_PATH").output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let minimal = state.minimal();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!(
+            "{} {} {}",
+            minimal.msol_price_lamports(),
+            minimal.sol_to_msol(LAMPORTS_PER_MSOL),
+            minimal.msol_to_sol(LAMPORTS_PER_MSOL)
+        )
+    );
+}
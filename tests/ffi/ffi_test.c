@@ -0,0 +1,47 @@
+/* drives the ffi the way a c/c++ caller would. prints the price of the state file given as
+ * the only argument, then what 1 SOL and 1 mSOL convert to, for tests/ffi.rs to compare
+ * against the rust api */
+#include <inttypes.h>
+#include <stdio.h>
+
+#include "marinade_ffi.h"
+
+#define CHECK(cond)                                              \
+  do {                                                           \
+    if (!(cond)) {                                               \
+      fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                  \
+    }                                                            \
+  } while (0)
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+  FILE *file = fopen(argv[1], "rb");
+  CHECK(file != NULL);
+  uint8_t data[1024];
+  size_t len = fread(data, 1, sizeof(data), file);
+  fclose(file);
+
+  MarinadeStatus status = MARINADE_STATUS_OK;
+  CHECK(marinade_parse_state(data, 16, &status) == NULL);
+  CHECK(status == MARINADE_STATUS_SHORT_BUFFER);
+  CHECK(marinade_parse_state(data + 8, len - 8, &status) == NULL);
+  CHECK(status == MARINADE_STATUS_INVALID_DATA);
+  CHECK(marinade_parse_state(NULL, len, &status) == NULL);
+  CHECK(status == MARINADE_STATUS_NULL_POINTER);
+
+  MarinadeStatePrice *price = marinade_parse_state(data, len, &status);
+  CHECK(price != NULL);
+  CHECK(status == MARINADE_STATUS_OK);
+
+  uint64_t msol = 0, lamports = 0;
+  CHECK(marinade_sol_to_msol(price, 1000000000, &msol) == MARINADE_STATUS_OK);
+  CHECK(marinade_msol_to_sol(price, 1000000000, &lamports) == MARINADE_STATUS_OK);
+  CHECK(lamports == price->msol_price_lamports);
+  CHECK(marinade_sol_to_msol(price, 1, NULL) == MARINADE_STATUS_NULL_POINTER);
+  printf("%" PRIu64 " %" PRIu64 " %" PRIu64 "\n", price->msol_price_lamports, msol, lamports);
+
+  marinade_price_free(price);
+  marinade_price_free(NULL);
+  return 0;
+}
@@ -0,0 +1,156 @@
+//! the price math against `tests/golden/*.json`: state inputs paired with what marinade's
+//! typescript sdk computes from them, see `scripts/golden-vectors.mjs` for how they're made and
+//! regenerated. agreement is to the lamport, and a failure names the vector and the component
+//! that moved, intermediates first, so a formula change reads off the first line. a vector with
+//! an `overflow` adds up past a u64, which the crate has to refuse rather than value
+
+use std::path::Path;
+
+use parser_test::accounts::marinade::MinimalState;
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Vector {
+    #[serde(rename = "source")]
+    _source: String,
+    state: Inputs,
+    /// exact, so past `u64::MAX` for the vectors with an `overflow`
+    total_lamports_under_control: u128,
+    total_virtual_staked_lamports: u128,
+    msol_price_lamports: u128,
+    /// the first of the three above that doesn't fit a u64
+    #[serde(default)]
+    overflow: Option<String>,
+    /// (lamports, mSOL)
+    sol_to_msol: Vec<(u64, u64)>,
+    /// (mSOL, lamports)
+    msol_to_sol: Vec<(u64, u64)>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Inputs {
+    total_active_balance: u64,
+    delayed_unstake_cooling_down: u64,
+    emergency_cooling_down: u64,
+    available_reserve_balance: u64,
+    circulating_ticket_balance: u64,
+    msol_supply: u64,
+}
+
+impl Inputs {
+    fn state(&self) -> MinimalState {
+        MinimalState {
+            msol_mint: Pubkey::default(),
+            total_active_balance: self.total_active_balance,
+            delayed_unstake_cooling_down: self.delayed_unstake_cooling_down,
+            emergency_cooling_down: self.emergency_cooling_down,
+            available_reserve_balance: self.available_reserve_balance,
+            circulating_ticket_balance: self.circulating_ticket_balance,
+            msol_supply: self.msol_supply,
+        }
+    }
+}
+
+/// every way `vector` disagrees with the crate, one line each
+fn mismatches(name: &str, vector: &Vector) -> Vec<String> {
+    let state = vector.state.state();
+    let mut found = Vec::new();
+    if let Some(overflow) = &vector.overflow {
+        let totals = [
+            ("total_lamports_under_control", vector.total_lamports_under_control),
+            ("total_virtual_staked_lamports", vector.total_virtual_staked_lamports),
+            ("msol_price_lamports", vector.msol_price_lamports),
+        ];
+        let first = totals.iter().find(|(_, total)| *total > u64::MAX as u128).map(|(component, _)| *component);
+        if first != Some(overflow.as_str()) {
+            found.push(format!("{}: overflow is {} but the first total past u64::MAX is {:?}", name, overflow, first));
+        }
+        let checked = [
+            ("total_lamports_under_control", state.checked_total_lamports_under_control()),
+            ("total_virtual_staked_lamports", state.checked_total_virtual_staked_lamports()),
+            ("msol_price_lamports", state.checked_msol_price_lamports()),
+        ];
+        for (component, got) in checked.into_iter().filter(|(_, got)| got.is_some()) {
+            found.push(format!("{}: checked {} is {:?}, not refused", name, component, got));
+        }
+        if state.total_lamports_under_control() != u64::MAX {
+            found.push(format!("{}: total_lamports_under_control doesn't saturate", name));
+        }
+        return found;
+    }
+    let mut check = |component: String, expected: u128, got: u64| {
+        if expected != got as u128 {
+            found.push(format!("{}: {} moved from {} to {}", name, component, expected, got));
+        }
+    };
+    let under_control = state.total_lamports_under_control();
+    check("total_lamports_under_control".into(), vector.total_lamports_under_control, under_control);
+    let virtual_staked = state.total_virtual_staked_lamports();
+    check("total_virtual_staked_lamports".into(), vector.total_virtual_staked_lamports, virtual_staked);
+    check("msol_price_lamports".into(), vector.msol_price_lamports, state.msol_price_lamports());
+    for (lamports, msol) in &vector.sol_to_msol {
+        check(format!("sol_to_msol({})", lamports), *msol as u128, state.sol_to_msol(*lamports));
+    }
+    for (msol, lamports) in &vector.msol_to_sol {
+        check(format!("msol_to_sol({})", msol), *lamports as u128, state.msol_to_sol(*msol));
+    }
+    found
+}
+
+#[test]
+fn test_math_matches_the_sdk_vectors() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+    paths.sort();
+    assert!(paths.len() >= 5, "only {} vectors in {}", paths.len(), dir.display());
+
+    let mut found = Vec::new();
+    let mut overflows = 0;
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let vector: Vector = serde_json::from_slice(&std::fs::read(path).unwrap())
+            .unwrap_or_else(|e| panic!("{} isn't a vector: {}", path.display(), e));
+        let priced = !vector.sol_to_msol.is_empty() && !vector.msol_to_sol.is_empty();
+        assert_eq!(priced, vector.overflow.is_none(), "{} has amounts only if it doesn't overflow", name);
+        overflows += vector.overflow.is_some() as usize;
+        found.extend(mismatches(&name, &vector));
+    }
+    assert!(found.is_empty(), "{} golden values moved:\n{}", found.len(), found.join("\n"));
+    assert!(overflows >= 1, "no vector past u64::MAX in {}", dir.display());
+}
+
+#[test]
+fn test_a_moved_component_is_named() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/fixture.json");
+    let mut vector: Vector = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    vector.total_virtual_staked_lamports += 1;
+    vector.sol_to_msol[3].1 -= 1;
+    let found = mismatches("fixture", &vector);
+    assert_eq!(found.len(), 2, "{:?}", found);
+    assert!(found[0].starts_with("fixture: total_virtual_staked_lamports moved from"), "{}", found[0]);
+    assert_eq!(found[1], "fixture: sol_to_msol(1000000000) moved from 833217607 to 833217608");
+}
+
+#[test]
+fn test_an_accepted_overflow_is_named() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/past_u64_max.json");
+    let mut vector: Vector = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    assert_eq!(vector.overflow.as_deref(), Some("total_lamports_under_control"));
+    assert_eq!(vector.total_lamports_under_control, u64::MAX as u128 + 1);
+    assert!(mismatches("past_u64_max", &vector).is_empty());
+
+    // one lamport less in the reserve fits, and the crate values it instead of refusing
+    vector.state.available_reserve_balance -= 1;
+    let found = mismatches("past_u64_max", &vector);
+    assert_eq!(found.len(), 3, "{:?}", found);
+    let refused = "past_u64_max: checked total_lamports_under_control is Some(18446744073709551615), not refused";
+    assert_eq!(found[0], refused);
+
+    vector.total_lamports_under_control -= 1;
+    let found = mismatches("past_u64_max", &vector);
+    assert!(found[0].starts_with("past_u64_max: overflow is total_lamports_under_control but"), "{}", found[0]);
+}